@@ -0,0 +1,45 @@
+//! Extracts a single file from a VPK of any supported format.
+//!
+//! ```sh
+//! cargo run --example extract_one -- path/to/pak01_dir.vpk materials/test.vmt test.vmt
+//! ```
+
+use std::error::Error;
+use std::path::Path;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [dir_vpk_path, file_path, output_path] = args.as_slice() else {
+        eprintln!(
+            "Usage: extract_one <path to the directory file> <file path in the VPK> <output path>"
+        );
+        return ExitCode::from(2);
+    };
+
+    match extract_one(Path::new(dir_vpk_path), file_path, output_path) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+
+            // Display messages leave out the error that caused them
+            let mut source = e.source();
+            while let Some(cause) = source {
+                eprintln!("  caused by: {cause}");
+                source = cause.source();
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Extracts the file at `file_path` in the VPK to `output_path`.
+pub fn extract_one(
+    dir_vpk_path: &Path,
+    file_path: &str,
+    output_path: &str,
+) -> Result<(), Box<dyn Error>> {
+    let vpk = vpk_plumber::open(dir_vpk_path)?;
+    vpk.extract_file(file_path, output_path)?;
+    Ok(())
+}
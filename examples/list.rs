@@ -0,0 +1,48 @@
+//! Lists the files stored in a VPK of any supported format along with their sizes.
+//!
+//! ```sh
+//! cargo run --example list -- path/to/pak01_dir.vpk
+//! ```
+
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let Some(dir_vpk_path) = std::env::args().nth(1) else {
+        eprintln!("Usage: list <path to the directory file>");
+        return ExitCode::from(2);
+    };
+
+    match list(Path::new(&dir_vpk_path), &mut io::stdout().lock()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+
+            // Display messages leave out the error that caused them
+            let mut source = e.source();
+            while let Some(cause) = source {
+                eprintln!("  caused by: {cause}");
+                source = cause.source();
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Writes the format of the VPK and a line with the path and size of every file in it, sorted by path.
+pub fn list(dir_vpk_path: &Path, out: &mut impl Write) -> Result<(), Box<dyn Error>> {
+    let vpk = vpk_plumber::open(dir_vpk_path)?;
+    writeln!(out, "{} ({})", dir_vpk_path.display(), vpk.format())?;
+
+    let mut file_paths = vpk.file_paths();
+    file_paths.sort_unstable();
+
+    for file_path in file_paths {
+        let size = vpk.file_size(file_path).unwrap_or_default();
+        writeln!(out, "{file_path}\t{size}")?;
+    }
+
+    Ok(())
+}
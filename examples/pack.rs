@@ -0,0 +1,60 @@
+//! Packs the contents of a directory into a VPK version 1 directory file and its archives.
+//!
+//! ```sh
+//! cargo run --example pack -- path/to/source output pak01
+//! ```
+
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+use vpk_plumber::pak::{Dedup, PakBuilder};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [source_dir, output_dir, vpk_name] = args.as_slice() else {
+        eprintln!("Usage: pack <source directory> <output directory> <VPK name>");
+        return ExitCode::from(2);
+    };
+
+    match pack(
+        Path::new(source_dir),
+        output_dir,
+        vpk_name,
+        &mut io::stdout().lock(),
+    ) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+
+            // Display messages leave out the error that caused them
+            let mut source = e.source();
+            while let Some(cause) = source {
+                eprintln!("  caused by: {cause}");
+                source = cause.source();
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Packs every file in `source_dir` into `{vpk_name}_dir.vpk` and its archives in `output_dir`, writing a summary to `out`.
+pub fn pack(
+    source_dir: &Path,
+    output_dir: &str,
+    vpk_name: &str,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let mut builder = PakBuilder::new().dedup(Dedup::On);
+    let file_count = builder.add_dir(source_dir)?;
+    let report = builder.build(output_dir, vpk_name)?;
+
+    writeln!(
+        out,
+        "Packed {file_count} files into {} archives, {} bytes written and {} bytes deduplicated",
+        report.archive_count, report.bytes_written, report.bytes_deduplicated
+    )?;
+
+    Ok(())
+}
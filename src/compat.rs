@@ -0,0 +1,117 @@
+//! Compatibility with the module layout of sourcepak 0.x, which this crate was forked from.
+//!
+//! Code written against sourcepak only needs its imports changed from `sourcepak::` to `vpk_plumber::compat::`.
+//! In sourcepak, `PakReader` also provided `new` and `from_file`, which now live in [`PakWorker`].
+//! The methods of the compatibility [`PakReader`] are deprecated, new code should use [`pak`](crate::pak) and [`PakWorker`] directly.
+
+use crate::pak::{PakWorker, Result};
+use std::fs::File;
+
+pub mod common {
+    //! The `sourcepak::common` module.
+
+    pub mod format {
+        //! The `sourcepak::common::format` module, containing the shared traits and directory entry types.
+
+        pub use super::super::PakReader;
+        pub use crate::pak::{
+            DirEntry, PakWriter, VPK_ENTRY_TERMINATOR, VPKDirectoryEntry, VPKTree,
+        };
+    }
+}
+
+pub mod pak {
+    //! The `sourcepak::pak` module, containing one submodule per format.
+
+    pub mod v1 {
+        //! The `sourcepak::pak::v1` module.
+
+        pub mod format {
+            //! The `sourcepak::pak::v1::format` module.
+
+            pub use crate::pak::v1::*;
+        }
+    }
+
+    pub mod v2 {
+        //! The `sourcepak::pak::v2` module.
+
+        pub mod format {
+            //! The `sourcepak::pak::v2::format` module.
+
+            pub use crate::pak::v2::*;
+        }
+    }
+
+    #[cfg(feature = "revpk")]
+    pub mod revpk {
+        //! The `sourcepak::pak::revpk` module.
+
+        pub mod format {
+            //! The `sourcepak::pak::revpk::format` module.
+
+            pub use crate::pak::revpk::*;
+        }
+    }
+}
+
+/// The reading trait of sourcepak, which also constructed VPKs.
+/// Implemented for every [`PakWorker`], forwarding to [`PakWorker`] and [`pak::PakReader`](crate::pak::PakReader).
+pub trait PakReader {
+    /// Create an empty VPK, see [`PakWorker::new`].
+    #[deprecated(since = "0.5.0", note = "use `vpk_plumber::pak::PakWorker::new`")]
+    fn new() -> Self
+    where
+        Self: Sized;
+
+    /// Create a VPK from a directory file, see [`PakWorker::from_file`].
+    /// # Errors
+    /// - When the file is in an invalid format
+    #[deprecated(since = "0.5.0", note = "use `vpk_plumber::pak::PakWorker::from_file`")]
+    fn from_file(file: &mut File) -> Result<Self>
+    where
+        Self: Sized;
+
+    /// Read the contents of a file, see [`pak::PakReader::read_file`](crate::pak::PakReader::read_file).
+    #[deprecated(since = "0.5.0", note = "use `vpk_plumber::pak::PakReader::read_file`")]
+    fn read_file(&self, archive_path: &str, vpk_name: &str, file_path: &str) -> Option<Vec<u8>>;
+
+    /// Extract a file, see [`pak::PakReader::extract_file`](crate::pak::PakReader::extract_file).
+    /// # Errors
+    /// - When the file cannot be read or written
+    #[deprecated(
+        since = "0.5.0",
+        note = "use `vpk_plumber::pak::PakReader::extract_file`"
+    )]
+    fn extract_file(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        output_path: &str,
+    ) -> Result<()>;
+}
+
+impl<W: PakWorker> PakReader for W {
+    fn new() -> Self {
+        <W as PakWorker>::new()
+    }
+
+    fn from_file(file: &mut File) -> Result<Self> {
+        <W as PakWorker>::from_file(file)
+    }
+
+    fn read_file(&self, archive_path: &str, vpk_name: &str, file_path: &str) -> Option<Vec<u8>> {
+        crate::pak::PakReader::read_file(self, archive_path, vpk_name, file_path)
+    }
+
+    fn extract_file(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        output_path: &str,
+    ) -> Result<()> {
+        crate::pak::PakReader::extract_file(self, archive_path, vpk_name, file_path, output_path)
+    }
+}
@@ -7,13 +7,29 @@ pub type Result<T> = core::result::Result<T, Error>;
 #[derive(Debug)]
 pub enum Error {
     UnknownFormat,
+    UnknownVersion { signature: u32, version: u32 },
     Pak(pak::Error),
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", &self)
+        match self {
+            Error::UnknownFormat => write!(f, "unknown VPK format"),
+            Error::UnknownVersion { signature, version } => write!(
+                f,
+                "unknown VPK version {version} with signature {signature:#010X}"
+            ),
+            Error::Pak(e) => fmt::Display::fmt(e, f),
+        }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            // The wrapped error is displayed in place of this one, so its source is passed through
+            Error::Pak(e) => e.source(),
+            Error::UnknownFormat | Error::UnknownVersion { .. } => None,
+        }
+    }
+}
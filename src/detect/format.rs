@@ -1,11 +1,22 @@
+use crate::pak::{
+    FormatCaps,
+    v1::{VPK_CAPS_V1, VPK_SIGNATURE_V1, VPK_VERSION_V1},
+    v2::{VPK_CAPS_V2, VPK_SIGNATURE_V2, VPK_VERSION_V2},
+};
 use std::fmt;
 
+#[cfg(feature = "revpk")]
+use crate::pak::revpk::{VPK_CAPS_REVPK, VPK_SIGNATURE_REVPK, VPK_VERSION_REVPK};
+
 /// Lists the different formats of VPK files.
 #[derive(PartialEq, Eq, Debug)]
 pub enum PakFormat {
     /// Unknown format.
     Unknown,
 
+    /// A VPK signature with a version this crate doesn't know, e.g. from a format derived from one of the supported ones.
+    UnknownVersioned { signature: u32, version: u32 },
+
     /// VPK version 1.
     VPKVersion1,
 
@@ -16,10 +27,53 @@ pub enum PakFormat {
     VPKRespawn,
 }
 
+impl PakFormat {
+    /// Determines the format from the signature and version at the start of a directory file.
+    ///
+    /// VPK version 2 and Respawn VPKs share the signature `0x55AA1234`, so only the full 32-bit version tells them apart:
+    /// `2` for version 2 and `0x0003_0002` (`196610`) for Respawn VPKs, which store their version as a 16-bit major
+    /// version of 2 followed by a 16-bit minor version of 3. Comparing only the low 16 bits would mistake Respawn VPKs
+    /// for version 2, any other combination is reported as [`PakFormat::UnknownVersioned`].
+    ///
+    /// *Returns [`PakFormat::UnknownVersioned`] for Respawn VPKs if the `revpk` feature is not enabled.*
+    #[must_use]
+    pub fn from_header(signature: u32, version: u32) -> Self {
+        match (signature, version) {
+            (VPK_SIGNATURE_V1, VPK_VERSION_V1) => PakFormat::VPKVersion1,
+            (VPK_SIGNATURE_V2, VPK_VERSION_V2) => PakFormat::VPKVersion2,
+            #[cfg(feature = "revpk")]
+            (VPK_SIGNATURE_REVPK, VPK_VERSION_REVPK) => PakFormat::VPKRespawn,
+            _ if signature == VPK_SIGNATURE_V1 || signature == VPK_SIGNATURE_V2 => {
+                PakFormat::UnknownVersioned { signature, version }
+            }
+            _ => PakFormat::Unknown,
+        }
+    }
+
+    /// The operations supported for the format.
+    ///
+    /// Nothing is supported for unknown formats, or for [`PakFormat::VPKRespawn`] when the `revpk` feature is not enabled.
+    #[must_use]
+    pub fn capabilities(&self) -> FormatCaps {
+        match self {
+            PakFormat::Unknown | PakFormat::UnknownVersioned { .. } => FormatCaps::default(),
+            PakFormat::VPKVersion1 => VPK_CAPS_V1,
+            PakFormat::VPKVersion2 => VPK_CAPS_V2,
+            #[cfg(feature = "revpk")]
+            PakFormat::VPKRespawn => VPK_CAPS_REVPK,
+            #[cfg(not(feature = "revpk"))]
+            PakFormat::VPKRespawn => FormatCaps::default(),
+        }
+    }
+}
+
 impl fmt::Display for PakFormat {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let str = match self {
             PakFormat::Unknown => "Unknown",
+            PakFormat::UnknownVersioned { signature, version } => {
+                return write!(f, "Unknown (signature {signature:#X}, version {version})");
+            }
             PakFormat::VPKVersion1 => "VPK Version 1",
             PakFormat::VPKVersion2 => "VPK Version 2",
             PakFormat::VPKRespawn => "VPK Respawn",
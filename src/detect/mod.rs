@@ -1,67 +1,154 @@
 //! This module contains functionality for detecting VPK formats
 
 use crate::pak::PakWorker;
-use crate::pak::{
-    v1::{VPKHeaderV1, VPKVersion1},
-    v2::{VPKHeaderV2, VPKVersion2},
-};
+use crate::pak::{v1::VPKVersion1, v2::VPKVersion2};
+use crate::util::file::VPKFileReader;
 use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
 
 #[cfg(feature = "revpk")]
-use crate::pak::revpk::{VPKHeaderRespawn, VPKRespawn};
+use crate::pak::revpk::VPKRespawn;
 
 pub use error::{Error, Result};
 pub use format::PakFormat;
+pub use open::{OpenedPak, open};
 
 mod error;
 mod format;
+mod open;
 
 /// Detects the format of a VPK file by reading its header.
 /// Leaves the file cursor in the position it was at when the function was called.
 ///
-/// This calls the `is_format` method of each VPK header format until it finds a match.
+/// The signature and version are read once and matched with [`PakFormat::from_header`].
+/// Files starting with a VPK signature but an unknown version are reported as [`PakFormat::UnknownVersioned`].
 ///
 /// *Will not test for the Respawn VPK format if the `revpk` feature is not enabled.*
-pub fn detect_pak_format(file: &mut File) -> PakFormat {
-    if VPKHeaderV1::is_format(file) {
-        return PakFormat::VPKVersion1;
+pub fn detect_pak_format<R: Read + Seek>(file: &mut R) -> PakFormat {
+    let Ok(pos) = file.stream_position() else {
+        return PakFormat::Unknown;
+    };
+
+    let signature = file.read_u32();
+    let version = file.read_u32();
+
+    let _ = file.seek(SeekFrom::Start(pos));
+
+    match (signature, version) {
+        (Ok(signature), Ok(version)) => PakFormat::from_header(signature, version),
+        _ => PakFormat::Unknown,
     }
+}
+
+/// Detects the format of a VPK file held in memory.
+///
+/// Behaves like [`detect_pak_format`], without requiring the data to be written to a file first.
+#[must_use]
+pub fn detect_pak_format_bytes(data: &[u8]) -> PakFormat {
+    detect_pak_format(&mut Cursor::new(data))
+}
 
-    if VPKHeaderV2::is_format(file) {
-        return PakFormat::VPKVersion2;
+/// Detects the format of a VPK file from its path, reading its header only when the file name is ambiguous.
+///
+/// The file name is only a hint: archives such as `pak01_000.vpk` are reported as [`PakFormat::Unknown`] and
+/// Respawn directory files such as `englishclient_mp_colony.bsp.pak000_dir.vpk` as [`PakFormat::VPKRespawn`]
+/// without opening them. Every other file is checked with [`detect_pak_format`].
+/// Use [`detect_pak_format`] when a wrongly named file must not be misreported, the header is the canonical check.
+///
+/// *Will not guess the Respawn VPK format from the file name if the `revpk` feature is not enabled.*
+#[must_use]
+pub fn detect_pak_format_from_path(path: &Path) -> PakFormat {
+    if let Some(format) = path
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .and_then(|file_name| format_from_file_name(&file_name.to_ascii_lowercase()))
+    {
+        return format;
     }
 
+    let Ok(mut file) = File::open(path) else {
+        return PakFormat::Unknown;
+    };
+
+    detect_pak_format(&mut file)
+}
+
+/// Guesses the format of a VPK file from Valve's and Respawn's naming conventions,
+/// returning `None` when the name could belong to several formats.
+fn format_from_file_name(file_name: &str) -> Option<PakFormat> {
+    let is_index = |index: &str| index.len() == 3 && index.bytes().all(|b| b.is_ascii_digit());
+    let stem = file_name.strip_suffix(".vpk")?;
+
+    // Archives have no header to detect
+    if stem
+        .rsplit_once('_')
+        .is_some_and(|(_, index)| is_index(index))
+    {
+        return Some(PakFormat::Unknown);
+    }
+
+    // Respawn directory files are named after their map, e.g. `englishclient_mp_colony.bsp.pak000_dir.vpk`
     #[cfg(feature = "revpk")]
-    if VPKHeaderRespawn::is_format(file) {
-        return PakFormat::VPKRespawn;
+    if stem
+        .strip_suffix("_dir")
+        .and_then(|vpk_name| vpk_name.rsplit_once(".bsp.pak"))
+        .is_some_and(|(_, index)| is_index(index))
+    {
+        return Some(PakFormat::VPKRespawn);
     }
 
-    PakFormat::Unknown
+    None
 }
 
 /// Detects the correct VPK format to use and returns
 /// the appropriate `PakWorker` to work with the format.
 /// # Errors
-/// - When the format is unknown
+/// - When the format is unknown, with the signature and version found if the file starts with a VPK signature
 /// - When the file data is invalid
-pub fn find_pak_worker(file: &mut File) -> Result<Box<dyn PakWorker>> {
-    match detect_pak_format(file) {
+pub fn find_pak_worker<R: Read + Seek>(file: &mut R) -> Result<Box<dyn PakWorker>> {
+    let format = detect_pak_format(file);
+    pak_worker_for_format(file, &format)
+}
+
+/// Parses the directory file read by `file` with the `PakWorker` of `format`, which was already detected with [`detect_pak_format`].
+/// # Errors
+/// - When the format is unknown, with the signature and version found if the file starts with a VPK signature
+/// - When the file data is invalid
+pub(crate) fn pak_worker_for_format<R: Read + Seek>(
+    file: &mut R,
+    format: &PakFormat,
+) -> Result<Box<dyn PakWorker>> {
+    match format {
         PakFormat::VPKVersion1 => {
-            let packager = VPKVersion1::from_file(file).map_err(Error::Pak)?;
+            let packager = VPKVersion1::from_reader(file).map_err(Error::Pak)?;
             Ok(Box::new(packager))
         }
 
         PakFormat::VPKVersion2 => {
-            let packager = VPKVersion2::from_file(file).map_err(Error::Pak)?;
+            let packager = VPKVersion2::from_reader(file).map_err(Error::Pak)?;
             Ok(Box::new(packager))
         }
 
         #[cfg(feature = "revpk")]
         PakFormat::VPKRespawn => {
-            let packager = VPKRespawn::from_file(file).map_err(Error::Pak)?;
+            let packager = VPKRespawn::from_reader(file).map_err(Error::Pak)?;
             Ok(Box::new(packager))
         }
 
+        &PakFormat::UnknownVersioned { signature, version } => {
+            Err(Error::UnknownVersion { signature, version })
+        }
+
         _ => Err(Error::UnknownFormat), // Handle other cases
     }
 }
+
+/// Detects the correct VPK format for a directory file held in memory and returns
+/// the appropriate `PakWorker` to work with the format.
+/// # Errors
+/// - When the format is unknown
+/// - When the data is invalid
+pub fn find_pak_worker_bytes(data: &[u8]) -> Result<Box<dyn PakWorker>> {
+    find_pak_worker(&mut Cursor::new(data))
+}
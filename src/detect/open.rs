@@ -0,0 +1,150 @@
+use std::{fs::File, path::Path};
+
+use super::{Error, PakFormat, Result, detect_pak_format, pak_worker_for_format};
+use crate::pak::{self, ExtractSummary, FileLocation, Manifest, PakWorker};
+use crate::util::path::split_pak_path;
+
+#[cfg(feature = "revpk")]
+use crate::pak::revpk::strip_language_prefix;
+
+/// A VPK opened from the path of its directory file.
+///
+/// Stores the directory containing the archives and the VPK name derived from the path,
+/// so files can be read without passing them along on every call.
+pub struct OpenedPak {
+    worker: Box<dyn PakWorker>,
+    format: PakFormat,
+    archive_path: String,
+    vpk_name: String,
+}
+
+impl OpenedPak {
+    /// The worker for the detected format.
+    #[must_use]
+    pub fn worker(&self) -> &dyn PakWorker {
+        self.worker.as_ref()
+    }
+
+    /// The mutable worker for the detected format, e.g. to recover the concrete type with
+    /// [`downcast_mut`](crate::pak::PakWorker#method.downcast_mut).
+    #[must_use]
+    pub fn worker_mut(&mut self) -> &mut dyn PakWorker {
+        self.worker.as_mut()
+    }
+
+    /// The detected format of the VPK.
+    #[must_use]
+    pub fn format(&self) -> &PakFormat {
+        &self.format
+    }
+
+    /// The directory containing the archives of the VPK.
+    #[must_use]
+    pub fn archive_path(&self) -> &str {
+        &self.archive_path
+    }
+
+    /// The name of the VPK used to locate its archives, e.g. `pak01` for `pak01_dir.vpk`.
+    #[must_use]
+    pub fn vpk_name(&self) -> &str {
+        &self.vpk_name
+    }
+
+    /// Lists the paths of all files stored in the VPK.
+    #[must_use]
+    pub fn file_paths(&self) -> Vec<&str> {
+        self.worker.file_paths()
+    }
+
+    /// Returns the number of bytes extracting a file stored in the VPK produces, or `None` if the file is not in the VPK.
+    /// No archives are opened, see [`PakReader::file_size`](crate::pak::PakReader::file_size).
+    #[must_use]
+    pub fn file_size(&self, file_path: &str) -> Option<u64> {
+        self.worker.file_size(file_path)
+    }
+
+    /// Returns the CRC of the contents of a file stored in the VPK, or `None` if the file is not in the VPK.
+    /// No archives are opened, see [`PakReader::file_crc`](crate::pak::PakReader::file_crc).
+    #[must_use]
+    pub fn file_crc(&self, file_path: &str) -> Option<u32> {
+        self.worker.file_crc(file_path)
+    }
+
+    /// Returns where the data of a file stored in the VPK is located, or `None` if the file is not in the VPK.
+    /// No archives are opened, see [`PakReader::file_location`](crate::pak::PakReader::file_location).
+    #[must_use]
+    pub fn file_location(&self, file_path: &str) -> Option<FileLocation> {
+        self.worker.file_location(file_path)
+    }
+
+    /// Lists every file stored in the VPK with its CRC, size and archives, sorted by path.
+    /// No archives are opened, see [`PakReader::manifest`](crate::pak::PakReader::manifest).
+    #[must_use]
+    pub fn manifest(&self) -> Manifest {
+        self.worker.manifest()
+    }
+
+    /// Read the contents of a file stored in the VPK into memory.
+    /// # Errors
+    /// - When the file is not in the VPK
+    /// - When the file data could not be read
+    pub fn read_file(&self, file_path: &str) -> Result<Vec<u8>> {
+        self.worker
+            .try_read_file(&self.archive_path, &self.vpk_name, file_path)
+            .map_err(Error::Pak)
+    }
+
+    /// Extract the contents of a file stored in the VPK to a file system location.
+    /// # Errors
+    /// - When the file is not in the VPK
+    /// - When the output file could not be written
+    /// - When the file data could not be read
+    pub fn extract_file(&self, file_path: &str, output_path: &str) -> Result<()> {
+        self.worker
+            .extract_file(&self.archive_path, &self.vpk_name, file_path, output_path)
+            .map_err(Error::Pak)
+    }
+
+    /// Extract every file stored in the VPK into `output_dir`, see [`PakReader::extract_all`](crate::pak::PakReader::extract_all).
+    ///
+    /// Files that fail to extract are reported in the returned summary.
+    /// # Errors
+    /// - When the output directory cannot be created
+    pub fn extract_all(&self, output_dir: &str) -> Result<ExtractSummary> {
+        self.worker
+            .extract_all(&self.archive_path, &self.vpk_name, output_dir, None)
+            .map_err(Error::Pak)
+    }
+}
+
+/// Opens a VPK from the path of its directory file, detecting its format.
+///
+/// The VPK name is the file name without its extension and trailing `_dir`,
+/// so `pak01_dir.vpk` and `pak01.vpk` both look for archives named `pak01_000.vpk` next to the file.
+/// For Respawn VPKs the language prefix is also stripped, as the archives are shared between languages.
+/// # Errors
+/// - When the file could not be opened
+/// - When the format is unknown
+/// - When the file data is invalid
+pub fn open(dir_vpk_path: &Path) -> Result<OpenedPak> {
+    let (archive_path, vpk_name) = split_pak_path(dir_vpk_path)
+        .ok_or_else(|| Error::Pak(pak::Error::FileNotFound(dir_vpk_path.display().to_string())))?;
+
+    let mut file = File::open(dir_vpk_path).map_err(|e| Error::Pak(pak::Error::Io(e)))?;
+    let format = detect_pak_format(&mut file);
+    let worker = pak_worker_for_format(&mut file, &format)?;
+
+    #[cfg(feature = "revpk")]
+    let vpk_name = if format == PakFormat::VPKRespawn {
+        strip_language_prefix(vpk_name)
+    } else {
+        vpk_name
+    };
+
+    Ok(OpenedPak {
+        worker,
+        format,
+        archive_path: archive_path.to_string(),
+        vpk_name: vpk_name.to_string(),
+    })
+}
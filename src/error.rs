@@ -0,0 +1,62 @@
+use std::fmt;
+
+#[cfg(feature = "detect")]
+use crate::detect;
+use crate::{pak, util};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Any error returned by this crate.
+///
+/// Wraps the error types of the individual modules, which remain the precise error types of their functions.
+/// The display and source chain of the wrapped error are passed through unchanged.
+#[derive(Debug)]
+pub enum Error {
+    #[cfg(feature = "detect")]
+    Detect(detect::Error),
+    Pak(pak::Error),
+    Util(util::Error),
+}
+
+impl Error {
+    /// Returns the wrapped module error.
+    fn inner(&self) -> &(dyn std::error::Error + 'static) {
+        match self {
+            #[cfg(feature = "detect")]
+            Error::Detect(e) => e,
+            Error::Pak(e) => e,
+            Error::Util(e) => e,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.inner(), f)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.inner().source()
+    }
+}
+
+#[cfg(feature = "detect")]
+impl From<detect::Error> for Error {
+    fn from(e: detect::Error) -> Self {
+        Error::Detect(e)
+    }
+}
+
+impl From<pak::Error> for Error {
+    fn from(e: pak::Error) -> Self {
+        Error::Pak(e)
+    }
+}
+
+impl From<util::Error> for Error {
+    fn from(e: util::Error) -> Self {
+        Error::Util(e)
+    }
+}
@@ -5,26 +5,70 @@
 //! | Format      | Support     | Game(s)                                                      |
 //! | ----------- | ----------- | ------------------------------------------------------------ |
 //! | VPK v1      | &#x1F7E2;   | Alien Swarm, Dota 2, L4D, L4D2, Portal 2, SFM                |
-//! | VPK v2      | &#x1F7E1; * | CS:GO, CS:S, DoD:S, HL:S, HL2, HL2:DM, Portal, TF2, Source 2 |
+//! | VPK v2      | &#x1F7E2;   | CS:GO, CS:S, DoD:S, HL:S, HL2, HL2:DM, Portal, TF2, Source 2 |
 //! | Respawn VPK | &#x1F7E2;   | Titanfall                                                    |
 //!
-//! * sourcepak doesn't currently support reading archive contents or writing directory files for VPK v2.
-//!
 //! # Features
+//! - `detect` (default): Add the [`detect`] module, detecting the format of VPK files and opening them without knowing it up front.
+//! - `md5` (default): Compute the MD5 checksums of VPK v2 files, needed by `VPKVersion2::verify_archives` and to write VPK v2 directory files.
 //! - `revpk`: Add support for Respawn VPK files.
 //! - `mem-map`: Use memory mapping to read VPK files. This can be faster and use less memory, but is not supported on all platforms.
+//! - `serde`: Derive `Serialize` and `Deserialize` for VPK trees, entries and headers.
+//! - `parallel`: Add `PakReader::extract_files_parallel` and `PakReader::extract_all_parallel`, extracting files on several threads with `rayon`.
+//! - `compat`: Add the [`compat`] module, mirroring the module layout of sourcepak 0.x for callers migrating from it.
+//! - `signature-verify`: Add `VPKVersion2::verify_signature` to verify the RSA signatures of VPK v2 directory files.
+//! - `ordered-tree`: Store the files of a `VPKTree` in an `IndexMap`, iterating in the order of the directory file instead of an arbitrary order.
+//! - `tokio`: Add `pak::async_reader`, reading files from VPKs without blocking the threads of a tokio runtime.
+//!
+//! **Note:** Enabling the `md5` feature requires additional dependencies (`md5`).
 //!
 //! **Note:** Enabling the `revpk` feature requires additional dependencies (`lzham-alpha-sys`).
 //!
+//! **Note:** Enabling the `serde` feature requires additional dependencies (`serde`).
+//!
 //! **Note:** Enabling the `mem-map` feature requires additional dependencies (`filebuffer`).
+//!
+//! **Note:** Enabling the `signature-verify` feature requires additional dependencies (`rsa`, `sha2`).
+//!
+//! **Note:** Enabling the `ordered-tree` feature requires additional dependencies (`indexmap`).
+//!
+//! **Note:** The `ordered-tree` feature is not additive. It changes the type of `VPKTree::files` and `VPKTree::preload`
+//! from a `HashMap` to an `IndexMap`, for every crate in the build that uses this crate. Code that names `HashMap` for them,
+//! or calls methods only one of the two has, breaks when another dependency enables the feature.
+//! Refer to the maps through the `pak::TreeMap` alias and stick to the methods both types share.
+//!
+//! **Note:** Enabling the `tokio` feature requires additional dependencies (`tokio`).
+//!
+//! # Errors
+//! Every module has its own precise error type. They all convert into the crate-level [`Error`],
+//! so functions mixing operations from several modules can use `?` with [`Result`].
 
 #![cfg_attr(docsrs, feature(doc_auto_cfg))]
 
+pub use error::{Error, Result};
+
 #[cfg(feature = "detect")]
 pub mod detect;
 pub mod pak;
 
 pub(crate) mod util;
 
+#[cfg(feature = "compat")]
+pub mod compat;
+
+mod error;
+
+/// Opens a VPK directory file of any supported format, see [`detect::open`].
+///
+/// Returns the crate-level [`Error`], for callers that don't need the precise [`detect::Error`].
+/// # Errors
+/// - When the file could not be opened
+/// - When the format is unknown
+/// - When the file data is invalid
+#[cfg(feature = "detect")]
+pub fn open(dir_vpk_path: &std::path::Path) -> Result<detect::OpenedPak> {
+    Ok(detect::open(dir_vpk_path)?)
+}
+
 #[cfg(test)]
 mod tests;
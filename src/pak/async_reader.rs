@@ -0,0 +1,110 @@
+//! Reading files from VPKs in async code, see [`AsyncPakReader`].
+
+use super::{Error, PakReader, Result};
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+
+/// Reads files from a VPK without blocking the threads of a tokio runtime.
+///
+/// Implemented for any [`PakReader`] shared through an [`Arc`]. The reads currently run the synchronous
+/// [`PakReader`] functions on tokio's blocking thread pool with [`tokio::task::spawn_blocking`],
+/// reading archives with async IO is a possible future improvement.
+pub trait AsyncPakReader {
+    /// Read the contents of a file stored in the VPK into memory, see [`PakReader::read_file`].
+    ///
+    /// Returns `None` on any error, use [`Self::try_read_file`] to find out what went wrong.
+    fn read_file(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+    ) -> impl Future<Output = Option<Vec<u8>>> + Send;
+
+    /// Read the contents of a file stored in the VPK into memory, see [`PakReader::try_read_file`].
+    /// # Errors
+    /// - When reading the file fails, see [`PakReader::try_read_file`]
+    /// - When the blocking task panics or is cancelled
+    fn try_read_file(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+    ) -> impl Future<Output = Result<Vec<u8>>> + Send;
+
+    /// Extract a file stored in the VPK to `output_path`, see [`PakReader::extract_file`].
+    /// # Errors
+    /// - When extracting the file fails, see [`PakReader::extract_file`]
+    /// - When the blocking task panics or is cancelled
+    fn extract_file(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        output_path: &str,
+    ) -> impl Future<Output = Result<()>> + Send;
+}
+
+impl<T: PakReader + Send + Sync + 'static> AsyncPakReader for Arc<T> {
+    async fn read_file(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+    ) -> Option<Vec<u8>> {
+        self.try_read_file(archive_path, vpk_name, file_path)
+            .await
+            .ok()
+    }
+
+    async fn try_read_file(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+    ) -> Result<Vec<u8>> {
+        let vpk = Arc::clone(self);
+        let (archive_path, vpk_name, file_path) = (
+            archive_path.to_string(),
+            vpk_name.to_string(),
+            file_path.to_string(),
+        );
+
+        spawn_blocking(move || {
+            vpk.as_ref()
+                .try_read_file(&archive_path, &vpk_name, &file_path)
+        })
+        .await
+    }
+
+    async fn extract_file(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        output_path: &str,
+    ) -> Result<()> {
+        let vpk = Arc::clone(self);
+        let (archive_path, vpk_name, file_path, output_path) = (
+            archive_path.to_string(),
+            vpk_name.to_string(),
+            file_path.to_string(),
+            output_path.to_string(),
+        );
+
+        spawn_blocking(move || {
+            vpk.as_ref()
+                .extract_file(&archive_path, &vpk_name, &file_path, &output_path)
+        })
+        .await
+    }
+}
+
+/// Runs `f` on the blocking thread pool, turning a panicked or cancelled task into an error.
+async fn spawn_blocking<R: Send + 'static>(
+    f: impl FnOnce() -> Result<R> + Send + 'static,
+) -> Result<R> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Error::Io(io::Error::other(e)))?
+}
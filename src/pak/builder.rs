@@ -0,0 +1,458 @@
+//! Building VPK version 1 files and their archives from file contents.
+
+use super::v1::VPKVersion1;
+use super::{Error, PakWorker, PakWriter, Result, VPKDirectoryEntry, split_file_path};
+use crate::util::path::normalize_vpk_path;
+use crc::{CRC_32_ISO_HDLC, Crc};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// The default maximum size of a single archive written by a [`PakBuilder`], matching Valve's packer.
+pub const DEFAULT_MAX_ARCHIVE_SIZE: u64 = 200 * 1024 * 1024;
+
+/// Compares two file paths for [`PackOrdering::Custom`].
+pub type PackComparator = Box<dyn Fn(&str, &str) -> Ordering>;
+
+/// The order in which the data of files is laid out in the archives built by a [`PakBuilder`].
+///
+/// This only affects the byte layout within the archives, not the order of the directory tree.
+/// Files the ordering considers equal are laid out alphabetically.
+#[derive(Default)]
+pub enum PackOrdering {
+    /// Lays out files alphabetically by path.
+    #[default]
+    Alphabetical,
+    /// Lays out files with the given extensions first, in the given order, followed by all other files.
+    /// Extensions are given without a leading dot and compared case-insensitively.
+    ByExtensionPriority(Vec<String>),
+    /// Lays out files using a custom comparison of their paths.
+    Custom(PackComparator),
+}
+
+impl PackOrdering {
+    /// Sorts `file_paths` into the order their data is laid out in.
+    fn sort(&self, file_paths: &mut [&str]) {
+        file_paths.sort_unstable();
+
+        match self {
+            PackOrdering::Alphabetical => {}
+            PackOrdering::ByExtensionPriority(extensions) => {
+                file_paths.sort_by_key(|file_path| {
+                    split_file_path(file_path)
+                        .1
+                        .rsplit_once('.')
+                        .and_then(|(_, ext)| {
+                            extensions
+                                .iter()
+                                .position(|priority| priority.eq_ignore_ascii_case(ext))
+                        })
+                        .unwrap_or(extensions.len())
+                });
+            }
+            PackOrdering::Custom(compare) => file_paths.sort_by(|a, b| compare(a, b)),
+        }
+    }
+}
+
+/// Whether a [`PakBuilder`] writes the contents of identical files only once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dedup {
+    /// Files with the same contents as a file written earlier point at the data of that file instead of storing it again,
+    /// like Valve's packer does. Contents are matched by their CRC and size and then compared byte for byte,
+    /// so files added from paths are read once more before they are written.
+    On,
+    /// The contents of every file are written to the archives.
+    #[default]
+    Off,
+}
+
+/// Where the contents of a file added to a [`PakBuilder`] come from.
+enum FileSource {
+    Data(Vec<u8>),
+    Path(PathBuf),
+}
+
+impl FileSource {
+    /// The size of the contents of the file.
+    fn len(&self) -> Result<u64> {
+        match self {
+            FileSource::Data(data) => Ok(data.len() as u64),
+            FileSource::Path(path) => std::fs::metadata(path)
+                .map(|metadata| metadata.len())
+                .map_err(Error::Io),
+        }
+    }
+
+    /// Opens the contents of the file for reading.
+    fn open(&self) -> Result<Box<dyn Read + '_>> {
+        match self {
+            FileSource::Data(data) => Ok(Box::new(data.as_slice())),
+            FileSource::Path(path) => Ok(Box::new(File::open(path).map_err(Error::Io)?)),
+        }
+    }
+
+    /// The CRC of the contents of the file.
+    fn checksum(&self, crc: &Crc<u32>) -> Result<u32> {
+        match self {
+            FileSource::Data(data) => Ok(crc.checksum(data)),
+            FileSource::Path(_) => {
+                let mut source = self.open()?;
+                let mut digest = crc.digest();
+                let mut buffer = vec![0; 1024 * 1024];
+
+                loop {
+                    let read = source.read(&mut buffer).map_err(Error::Io)?;
+                    if read == 0 {
+                        return Ok(digest.finalize());
+                    }
+
+                    digest.update(&buffer[..read]);
+                }
+            }
+        }
+    }
+
+    /// Whether the first `len` bytes of the contents of both files are identical.
+    fn same_contents(&self, other: &FileSource, len: u32) -> Result<bool> {
+        if let (FileSource::Data(a), FileSource::Data(b)) = (self, other) {
+            return Ok(a == b);
+        }
+
+        let (mut a, mut b) = (self.open()?, other.open()?);
+        let (mut buffer_a, mut buffer_b) = (vec![0; 64 * 1024], vec![0; 64 * 1024]);
+        let mut remaining = len as usize;
+
+        while remaining > 0 {
+            let chunk = remaining.min(buffer_a.len());
+            a.read_exact(&mut buffer_a[..chunk]).map_err(Error::Io)?;
+            b.read_exact(&mut buffer_b[..chunk]).map_err(Error::Io)?;
+
+            if buffer_a[..chunk] != buffer_b[..chunk] {
+                return Ok(false);
+            }
+            remaining -= chunk;
+        }
+
+        Ok(true)
+    }
+}
+
+/// The data of a file written to an archive, which identical files can point at.
+struct WrittenData<'a> {
+    file_path: &'a str,
+    archive_index: u16,
+    entry_offset: u32,
+}
+
+/// A summary of the files written by [`PakBuilder::build`].
+#[derive(Debug, Default)]
+pub struct BuildReport {
+    /// The paths of all files, in the order their data was written to the archives.
+    /// Files pointing at the data of an identical file are listed where their data would have been written.
+    pub order: Vec<String>,
+    /// The number of archives written.
+    pub archive_count: u16,
+    /// The total number of bytes of file data written to the archives.
+    pub bytes_written: u64,
+    /// The number of bytes not written because the files had the same contents as a file written earlier, see [`Dedup`].
+    pub bytes_deduplicated: u64,
+}
+
+/// Builds a VPK version 1 directory file and its archives from file contents.
+///
+/// The output is deterministic: building the same files with the same settings always produces byte-identical files.
+/// The order files were added in does not matter, archive data is laid out according to the [`PackOrdering`],
+/// archives are split at the same points and no timestamps are written.
+pub struct PakBuilder {
+    files: BTreeMap<String, FileSource>,
+    ordering: PackOrdering,
+    max_archive_size: u64,
+    dedup: Dedup,
+}
+
+impl Default for PakBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PakBuilder {
+    /// Creates an empty builder with alphabetical ordering and the default maximum archive size.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            files: BTreeMap::new(),
+            ordering: PackOrdering::default(),
+            max_archive_size: DEFAULT_MAX_ARCHIVE_SIZE,
+            dedup: Dedup::default(),
+        }
+    }
+
+    /// Sets the order in which file data is laid out in the archives.
+    #[must_use]
+    pub fn ordering(mut self, ordering: PackOrdering) -> Self {
+        self.ordering = ordering;
+        self
+    }
+
+    /// Sets the size after which a new archive is started. Files larger than this are written to an archive of their own.
+    #[must_use]
+    pub fn max_archive_size(mut self, max_archive_size: u64) -> Self {
+        self.max_archive_size = max_archive_size;
+        self
+    }
+
+    /// Sets whether identical file contents are only written once, see [`Dedup`]. Deduplication is off by default.
+    #[must_use]
+    pub fn dedup(mut self, dedup: Dedup) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Adds a file to the VPK, replacing any file previously added at the same path.
+    /// Backslashes in `file_path` are treated as directory separators.
+    pub fn add_file(&mut self, file_path: &str, data: Vec<u8>) {
+        self.files.insert(
+            normalize_vpk_path(file_path, false).into_owned(),
+            FileSource::Data(data),
+        );
+    }
+
+    /// Adds a file whose contents are read from `source_path` when building, replacing any file previously added at the same path.
+    /// Backslashes in `file_path` are treated as directory separators.
+    pub fn add_file_from_path(&mut self, file_path: &str, source_path: &Path) {
+        self.files.insert(
+            normalize_vpk_path(file_path, false).into_owned(),
+            FileSource::Path(source_path.to_path_buf()),
+        );
+    }
+
+    /// Adds every file in `source_dir` and its subdirectories, stored under their paths relative to `source_dir`.
+    /// Their contents are read when building, see [`Self::add_file_from_path`]. Returns the number of files added.
+    /// # Errors
+    /// - When a directory cannot be read
+    /// - When a path is not valid UTF-8
+    pub fn add_dir(&mut self, source_dir: &Path) -> Result<usize> {
+        let mut added = 0;
+        let mut dirs = vec![source_dir.to_path_buf()];
+
+        while let Some(dir) = dirs.pop() {
+            for dir_entry in std::fs::read_dir(&dir).map_err(Error::Io)? {
+                let path = dir_entry.map_err(Error::Io)?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+
+                let file_path = path
+                    .strip_prefix(source_dir)
+                    .ok()
+                    .and_then(Path::to_str)
+                    .ok_or(Error::BadData(format!(
+                        "Invalid file path: {}",
+                        path.display()
+                    )))?
+                    .to_string();
+
+                self.add_file_from_path(&file_path, &path);
+                added += 1;
+            }
+        }
+
+        Ok(added)
+    }
+
+    /// Writes the directory file `{vpk_name}_dir.vpk` and the archives `{vpk_name}_000.vpk`, ... into `output_dir`.
+    ///
+    /// The sizes of all files are checked before anything is written.
+    /// # Errors
+    /// - When a file is larger than the maximum entry size of 4 GiB ([`Error::EntryTooLarge`])
+    /// - When too many archives would be needed
+    /// - When an IO operation fails
+    pub fn build(&self, output_dir: &str, vpk_name: &str) -> Result<BuildReport> {
+        let output_dir = Path::new(output_dir);
+        std::fs::create_dir_all(output_dir).map_err(Error::Io)?;
+
+        let mut file_paths: Vec<&str> = self.files.keys().map(String::as_str).collect();
+        self.ordering.sort(&mut file_paths);
+
+        // An entry length is a u32, larger files would be recorded with a truncated length
+        let mut entry_lengths = BTreeMap::new();
+        for (file_path, source) in &self.files {
+            let size = source.len()?;
+            let entry_length = u32::try_from(size).map_err(|_| Error::EntryTooLarge {
+                path: file_path.clone(),
+                size,
+            })?;
+            entry_lengths.insert(file_path.as_str(), entry_length);
+        }
+
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+        let mut vpk = VPKVersion1::new();
+        let mut report = BuildReport::default();
+
+        let mut archive: Option<BufWriter<File>> = None;
+        let mut archive_index: u16 = 0;
+        let mut archive_size: u64 = 0;
+
+        // Written files by their CRC and size, to find identical files when deduplicating
+        let mut written_data: HashMap<(u32, u32), Vec<WrittenData>> = HashMap::new();
+
+        for file_path in file_paths {
+            let entry_length = entry_lengths[file_path];
+            let source = &self.files[file_path];
+
+            let checksum = if self.dedup == Dedup::On && entry_length > 0 {
+                Some(source.checksum(&crc)?)
+            } else {
+                None
+            };
+
+            if let Some(checksum) = checksum {
+                let mut original = None;
+                for written in written_data
+                    .get(&(checksum, entry_length))
+                    .into_iter()
+                    .flatten()
+                {
+                    if source.same_contents(&self.files[written.file_path], entry_length)? {
+                        original = Some(written);
+                        break;
+                    }
+                }
+
+                if let Some(original) = original {
+                    vpk.tree.files.insert(
+                        file_path.to_string(),
+                        VPKDirectoryEntry {
+                            crc: checksum,
+                            preload_length: 0,
+                            archive_index: original.archive_index,
+                            entry_offset: original.entry_offset,
+                            entry_length,
+                            ..Default::default()
+                        },
+                    );
+
+                    report.bytes_deduplicated += u64::from(entry_length);
+                    report.order.push(file_path.to_string());
+                    continue;
+                }
+            }
+
+            if archive_size > 0 && archive_size + u64::from(entry_length) > self.max_archive_size {
+                archive_index = archive_index.checked_add(1).ok_or(Error::DataTooLarge)?;
+                if let Some(mut full_archive) = archive.take() {
+                    full_archive.flush().map_err(Error::Io)?;
+                }
+                archive_size = 0;
+            }
+
+            let archive_file = match &mut archive {
+                Some(archive_file) => archive_file,
+                None => {
+                    report.archive_count += 1;
+                    archive.insert(BufWriter::new(
+                        File::create(
+                            output_dir.join(format!("{vpk_name}_{archive_index:0>3}.vpk")),
+                        )
+                        .map_err(Error::Io)?,
+                    ))
+                }
+            };
+
+            let checksum = match source {
+                FileSource::Data(data) => {
+                    archive_file.write_all(data).map_err(Error::Io)?;
+                    crc.checksum(data)
+                }
+                FileSource::Path(source_path) => {
+                    copy_file(&crc, source_path, archive_file, entry_length, file_path)?
+                }
+            };
+
+            let entry_offset = u32::try_from(archive_size).map_err(|_| Error::DataTooLarge)?;
+            vpk.tree.files.insert(
+                file_path.to_string(),
+                VPKDirectoryEntry {
+                    crc: checksum,
+                    preload_length: 0,
+                    archive_index,
+                    entry_offset,
+                    entry_length,
+                    ..Default::default()
+                },
+            );
+
+            if self.dedup == Dedup::On && entry_length > 0 {
+                written_data
+                    .entry((checksum, entry_length))
+                    .or_default()
+                    .push(WrittenData {
+                        file_path,
+                        archive_index,
+                        entry_offset,
+                    });
+            }
+
+            archive_size += u64::from(entry_length);
+            report.bytes_written += u64::from(entry_length);
+            report.order.push(file_path.to_string());
+        }
+
+        if let Some(mut archive) = archive {
+            archive.flush().map_err(Error::Io)?;
+        }
+
+        vpk.write_dir(
+            output_dir
+                .join(format!("{vpk_name}_dir.vpk"))
+                .to_str()
+                .ok_or(Error::BadData(format!(
+                    "Invalid output directory: {}",
+                    output_dir.display()
+                )))?,
+        )?;
+
+        Ok(report)
+    }
+}
+
+/// Copies `entry_length` bytes from the file at `source_path` into `out`, returning their CRC.
+/// # Errors
+/// - When the file cannot be read
+/// - When the size of the file changed since it was checked
+fn copy_file(
+    crc: &Crc<u32>,
+    source_path: &Path,
+    out: &mut impl Write,
+    entry_length: u32,
+    file_path: &str,
+) -> Result<u32> {
+    let mut source = File::open(source_path).map_err(Error::Io)?;
+    let mut digest = crc.digest();
+    let mut buffer = vec![0; 1024 * 1024];
+    let mut copied: u64 = 0;
+
+    loop {
+        let read = source.read(&mut buffer).map_err(Error::Io)?;
+        if read == 0 {
+            break;
+        }
+
+        out.write_all(&buffer[..read]).map_err(Error::Io)?;
+        digest.update(&buffer[..read]);
+        copied += read as u64;
+    }
+
+    if copied != u64::from(entry_length) {
+        return Err(Error::BadData(format!(
+            "Size of {file_path} changed while building, expected {entry_length} bytes but read {copied}"
+        )));
+    }
+
+    Ok(digest.finalize())
+}
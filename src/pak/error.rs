@@ -9,23 +9,127 @@ pub enum Error {
     InvalidEntryTerminator(String),
     InvalidSignature(String),
     TreeNotFound(std::io::Error),
+    InvalidTreeSize(String),
     BadVersion(String),
     Io(std::io::Error),
     FileNotFound(String),
+    ArchiveOpen {
+        path: String,
+        source: std::io::Error,
+    },
     Util {
         source: util::Error,
         context: String,
     },
     BadData(String),
+    CrcMismatch {
+        expected: u32,
+        found: u32,
+        file: String,
+    },
     DataNotFound(String),
+    UnexpectedEof {
+        file: String,
+        missing: u64,
+    },
+    #[cfg(feature = "revpk")]
+    Decompress {
+        file: String,
+        source: util::lzham::LzhamError,
+    },
+    TruncatedPart {
+        path: String,
+        part_index: usize,
+        expected: u64,
+        got: u64,
+    },
+    OutOfSpace {
+        file: String,
+        written: u64,
+    },
     MemoryMappedFileNotFound(u16),
     DataTooLarge,
+    EntryTooLarge {
+        path: String,
+        size: u64,
+    },
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", &self)
+        match self {
+            Error::InvalidEntryTerminator(msg) => write!(f, "invalid entry terminator: {msg}"),
+            Error::InvalidSignature(msg) => write!(f, "invalid VPK signature: {msg}"),
+            Error::TreeNotFound(_) => write!(f, "directory tree not found"),
+            Error::InvalidTreeSize(msg) => write!(f, "invalid tree size: {msg}"),
+            Error::BadVersion(msg) => write!(f, "unsupported VPK version: {msg}"),
+            Error::Io(_) => write!(f, "IO error"),
+            Error::FileNotFound(path) => write!(f, "file not found in VPK: {path}"),
+            Error::ArchiveOpen { path, .. } => write!(f, "failed to open archive {path}"),
+            Error::Util { context, .. } => write!(f, "{context}"),
+            Error::BadData(msg) => write!(f, "bad data: {msg}"),
+            Error::CrcMismatch {
+                expected,
+                found,
+                file,
+            } => write!(
+                f,
+                "CRC mismatch in {file}: expected {expected:#010X}, found {found:#010X}"
+            ),
+            Error::DataNotFound(msg) => write!(f, "data not found: {msg}"),
+            Error::UnexpectedEof { file, missing } => {
+                write!(
+                    f,
+                    "unexpected end of data in {file}, {missing} bytes missing"
+                )
+            }
+            #[cfg(feature = "revpk")]
+            Error::Decompress { file, .. } => write!(f, "failed to decompress {file}"),
+            Error::TruncatedPart {
+                path,
+                part_index,
+                expected,
+                got,
+            } => write!(
+                f,
+                "file part {part_index} of {path} is truncated, expected {expected} bytes but got {got}"
+            ),
+            Error::OutOfSpace { file, written } => {
+                write!(f, "out of space while writing {file} after {written} bytes")
+            }
+            Error::MemoryMappedFileNotFound(archive_index) => write!(
+                f,
+                "no memory-mapped file provided for archive {archive_index}"
+            ),
+            Error::DataTooLarge => write!(f, "data too large for the VPK format"),
+            Error::EntryTooLarge { path, size } => {
+                write!(f, "{path} is {size} bytes, too large for a single entry")
+            }
+        }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::TreeNotFound(e) | Error::Io(e) | Error::ArchiveOpen { source: e, .. } => Some(e),
+            Error::Util { source, .. } => Some(source),
+            #[cfg(feature = "revpk")]
+            Error::Decompress { source, .. } => Some(source),
+            Error::InvalidEntryTerminator(_)
+            | Error::InvalidSignature(_)
+            | Error::InvalidTreeSize(_)
+            | Error::BadVersion(_)
+            | Error::FileNotFound(_)
+            | Error::BadData(_)
+            | Error::CrcMismatch { .. }
+            | Error::DataNotFound(_)
+            | Error::UnexpectedEof { .. }
+            | Error::TruncatedPart { .. }
+            | Error::OutOfSpace { .. }
+            | Error::MemoryMappedFileNotFound(_)
+            | Error::DataTooLarge
+            | Error::EntryTooLarge { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Filters to select files stored in a VPK.
+
+use super::split_file_path;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+/// Selects files by their extension, compared case-insensitively.
+///
+/// Selecting files from an [`ExtensionIndex`] only looks up every extension of the filter once,
+/// build the index once with [`PakReader::extension_index`](super::PakReader::extension_index) to select files repeatedly.
+#[derive(Debug, Clone, Default)]
+pub struct ExtensionFilter {
+    extensions: HashSet<String>,
+}
+
+impl ExtensionFilter {
+    /// Creates a filter matching the given extensions, e.g. `&["vtf", "vmt"]`. A leading dot is ignored.
+    #[must_use]
+    pub fn new(extensions: &[&str]) -> Self {
+        Self {
+            extensions: extensions
+                .iter()
+                .map(|ext| ext.strip_prefix('.').unwrap_or(ext).to_ascii_lowercase())
+                .collect(),
+        }
+    }
+
+    /// Whether the file at `file_path` has one of the extensions of the filter.
+    #[must_use]
+    pub fn matches(&self, file_path: &str) -> bool {
+        extension(file_path).is_some_and(|ext| self.extensions.contains(lowercase(ext).as_ref()))
+    }
+
+    /// Returns the paths of `file_paths` with one of the extensions of the filter, sorted by path.
+    ///
+    /// Groups `file_paths` by extension first, use [`Self::select_from`] to reuse the grouping.
+    pub fn select<'a, I>(&self, file_paths: I) -> Vec<&'a str>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        self.select_from(&ExtensionIndex::new(file_paths))
+    }
+
+    /// Returns the paths in `index` with one of the extensions of the filter, sorted by path.
+    #[must_use]
+    pub fn select_from<'a>(&self, index: &ExtensionIndex<'a>) -> Vec<&'a str> {
+        let mut selected: Vec<&str> = self
+            .extensions
+            .iter()
+            .flat_map(|ext| index.get(ext))
+            .copied()
+            .collect();
+        selected.sort_unstable();
+        selected
+    }
+}
+
+/// The paths of the files stored in a VPK grouped by their extension, compared case-insensitively.
+#[derive(Debug, Clone, Default)]
+pub struct ExtensionIndex<'a> {
+    groups: HashMap<String, Vec<&'a str>>,
+}
+
+impl<'a> ExtensionIndex<'a> {
+    /// Groups `file_paths` by their extension. Paths without an extension are left out.
+    pub fn new<I>(file_paths: I) -> Self
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut groups: HashMap<String, Vec<&str>> = HashMap::new();
+        for file_path in file_paths {
+            if let Some(ext) = extension(file_path) {
+                let ext = lowercase(ext);
+                match groups.get_mut(ext.as_ref()) {
+                    Some(group) => group.push(file_path),
+                    None => {
+                        groups.insert(ext.into_owned(), vec![file_path]);
+                    }
+                }
+            }
+        }
+
+        Self { groups }
+    }
+
+    /// Returns the paths of the files with the extension `ext`, given without a leading dot.
+    #[must_use]
+    pub fn get(&self, ext: &str) -> &[&'a str] {
+        self.groups
+            .get(lowercase(ext).as_ref())
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns every extension in lowercase along with the number of files with that extension, in no particular order.
+    pub fn counts(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.groups
+            .iter()
+            .map(|(ext, file_paths)| (ext.as_str(), file_paths.len()))
+    }
+}
+
+/// The extension of a VPK file path, as stored in the directory tree.
+fn extension(file_path: &str) -> Option<&str> {
+    split_file_path(file_path)
+        .1
+        .rsplit_once('.')
+        .map(|(_, ext)| ext)
+}
+
+/// Lowercases an extension, only allocating when it contains uppercase characters.
+fn lowercase(ext: &str) -> Cow<'_, str> {
+    if ext.bytes().any(|b| b.is_ascii_uppercase()) {
+        Cow::Owned(ext.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(ext)
+    }
+}
@@ -0,0 +1,44 @@
+//! Where the data of a file is stored, see [`PakReader::file_location`](super::PakReader::file_location).
+
+/// Where the data of a file following its preload data is stored.
+///
+/// Preload data is always stored in the directory tree, see [`VPKTree::preload`](super::VPKTree::preload).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileLocation {
+    /// The file is stored entirely as preload data.
+    Preload,
+    /// The data follows the directory tree in the directory file.
+    InDirectory {
+        /// The offset of the data from the start of the directory file.
+        offset: u64,
+        /// The number of bytes of data.
+        length: u64,
+    },
+    /// The data is stored in a single archive.
+    Archive {
+        /// The index of the archive, e.g. `2` for `pak01_002.vpk`.
+        index: u16,
+        /// The offset of the data from the start of the archive.
+        offset: u64,
+        /// The number of bytes of data.
+        length: u64,
+    },
+    /// The data is split into parts, which may be compressed, as in Respawn VPKs.
+    MultiPart {
+        /// The parts in the order their data makes up the file.
+        parts: Vec<FilePartLocation>,
+    },
+}
+
+/// Where a single part of a file split into parts is stored, see [`FileLocation::MultiPart`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilePartLocation {
+    /// The index of the archive, e.g. `2` for `pak01_002.vpk`.
+    pub index: u16,
+    /// The offset of the data from the start of the archive.
+    pub offset: u64,
+    /// The number of bytes stored in the archive.
+    pub length: u64,
+    /// The number of bytes after decompressing, equal to `length` for uncompressed parts.
+    pub uncompressed_length: u64,
+}
@@ -0,0 +1,213 @@
+//! Listings of the files in a loaded VPK for verifying installs, see [`PakReader::manifest`](super::PakReader::manifest).
+
+use super::{Error, FileLocation, Result};
+use std::collections::BTreeMap;
+use std::io::{BufWriter, Write};
+
+/// A single file listed in a [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ManifestEntry {
+    /// The path of the file in the VPK.
+    pub path: String,
+    /// The CRC of the contents of the file as recorded in the directory tree.
+    pub crc: u32,
+    /// The number of bytes extracting the file produces.
+    pub size: u64,
+    /// The indices of the archives the data of the file is stored in, in the order the data is stored.
+    /// Empty for files stored in preload data or in the directory file.
+    pub archives: Vec<u16>,
+}
+
+impl ManifestEntry {
+    /// Creates an entry, taking the archive indices from where the data of the file is located.
+    #[must_use]
+    pub fn new(path: &str, crc: u32, size: u64, location: Option<&FileLocation>) -> Self {
+        let archives = match location {
+            Some(FileLocation::Archive { index, .. }) => vec![*index],
+            Some(FileLocation::MultiPart { parts }) => {
+                let mut archives: Vec<u16> = parts.iter().map(|part| part.index).collect();
+                archives.dedup();
+                archives
+            }
+            Some(FileLocation::Preload | FileLocation::InDirectory { .. }) | None => Vec::new(),
+        };
+
+        Self {
+            path: path.to_string(),
+            crc,
+            size,
+            archives,
+        }
+    }
+}
+
+/// The files of a VPK along with their CRCs and sizes, sorted by path.
+///
+/// Write it with [`Self::write_csv`] or [`Self::write_json`] and compare two manifests with [`Self::diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Manifest {
+    /// The listed files, sorted by path.
+    pub files: Vec<ManifestEntry>,
+}
+
+impl FromIterator<ManifestEntry> for Manifest {
+    fn from_iter<I: IntoIterator<Item = ManifestEntry>>(iter: I) -> Self {
+        let mut files: Vec<ManifestEntry> = iter.into_iter().collect();
+        files.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+
+        Self { files }
+    }
+}
+
+/// The differences between two manifests, see [`Manifest::diff`]. All paths are sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ManifestDiff {
+    /// Paths only listed in the other manifest.
+    pub added: Vec<String>,
+    /// Paths only listed in this manifest.
+    pub removed: Vec<String>,
+    /// Paths listed in both manifests with a different CRC or size.
+    pub changed: Vec<String>,
+}
+
+impl ManifestDiff {
+    /// Whether the manifests list the same files with the same CRCs and sizes.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl Manifest {
+    /// Looks up the entry of a file.
+    #[must_use]
+    pub fn get(&self, path: &str) -> Option<&ManifestEntry> {
+        self.files
+            .binary_search_by(|entry| entry.path.as_str().cmp(path))
+            .ok()
+            .map(|index| &self.files[index])
+    }
+
+    /// Lists the files added, removed and changed in `other` compared to this manifest.
+    ///
+    /// Files count as changed when their CRC or size differs, files moved to another archive do not.
+    #[must_use]
+    pub fn diff(&self, other: &Manifest) -> ManifestDiff {
+        let ours: BTreeMap<&str, &ManifestEntry> = self
+            .files
+            .iter()
+            .map(|entry| (entry.path.as_str(), entry))
+            .collect();
+        let theirs: BTreeMap<&str, &ManifestEntry> = other
+            .files
+            .iter()
+            .map(|entry| (entry.path.as_str(), entry))
+            .collect();
+
+        let mut diff = ManifestDiff::default();
+        for (path, entry) in &ours {
+            match theirs.get(path) {
+                None => diff.removed.push((*path).to_string()),
+                Some(other_entry)
+                    if other_entry.crc != entry.crc || other_entry.size != entry.size =>
+                {
+                    diff.changed.push((*path).to_string());
+                }
+                Some(_) => {}
+            }
+        }
+
+        diff.added = theirs
+            .keys()
+            .filter(|path| !ours.contains_key(*path))
+            .map(|path| (*path).to_string())
+            .collect();
+
+        diff
+    }
+
+    /// Writes the manifest as CSV with the columns `path`, `crc32`, `size` and `archives`.
+    ///
+    /// CRCs are written as 8 hexadecimal digits, archive indices are separated by `;`.
+    /// Paths containing commas, quotes or line breaks are quoted.
+    /// # Errors
+    /// - When writing fails
+    pub fn write_csv(&self, w: impl Write) -> Result<()> {
+        let mut w = BufWriter::new(w);
+        writeln!(w, "path,crc32,size,archives").map_err(Error::Io)?;
+
+        for entry in &self.files {
+            let path = if entry.path.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", entry.path.replace('"', "\"\""))
+            } else {
+                entry.path.clone()
+            };
+            let archives: Vec<String> = entry.archives.iter().map(u16::to_string).collect();
+
+            writeln!(
+                w,
+                "{path},{:08X},{},{}",
+                entry.crc,
+                entry.size,
+                archives.join(";")
+            )
+            .map_err(Error::Io)?;
+        }
+
+        w.flush().map_err(Error::Io)
+    }
+
+    /// Writes the manifest as JSON, an object with a `files` array of objects with the fields of [`ManifestEntry`].
+    ///
+    /// The output matches serializing the manifest with the `serde` feature, but does not require it.
+    /// # Errors
+    /// - When writing fails
+    pub fn write_json(&self, w: impl Write) -> Result<()> {
+        let mut w = BufWriter::new(w);
+        write!(w, "{{\"files\":[").map_err(Error::Io)?;
+
+        for (i, entry) in self.files.iter().enumerate() {
+            if i > 0 {
+                write!(w, ",").map_err(Error::Io)?;
+            }
+
+            let archives: Vec<String> = entry.archives.iter().map(u16::to_string).collect();
+            write!(
+                w,
+                "{{\"path\":{},\"crc\":{},\"size\":{},\"archives\":[{}]}}",
+                json_string(&entry.path),
+                entry.crc,
+                entry.size,
+                archives.join(",")
+            )
+            .map_err(Error::Io)?;
+        }
+
+        write!(w, "]}}").map_err(Error::Io)?;
+        w.flush().map_err(Error::Io)
+    }
+}
+
+/// Quotes a string for JSON, escaping quotes, backslashes and control characters.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
@@ -1,24 +1,67 @@
 //! Support for various VPK formats with traits to allow for extension.
 
-use crate::util::file::{VPKFileReader, VPKFileWriter};
-use std::collections::HashMap;
+use crate::util::glob::Pattern;
+use crate::util::path::{join_sanitized, normalize_vpk_path};
+use crate::util::{
+    self,
+    file::{VPKFileReader, VPKFileWriter},
+};
+use crc::{CRC_32_ISO_HDLC, Crc};
+use parser::EMPTY_TREE_COMPONENT;
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::OsStr;
 use std::fs::File;
-use std::io::{Seek, SeekFrom};
-use std::path::Path;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 #[cfg(feature = "mem-map")]
 use filebuffer::FileBuffer;
 
+pub use builder::{
+    BuildReport, DEFAULT_MAX_ARCHIVE_SIZE, Dedup, PackComparator, PackOrdering, PakBuilder,
+};
 pub use error::{Error, Result};
+pub use filter::{ExtensionFilter, ExtensionIndex};
+pub use location::{FileLocation, FilePartLocation};
+pub use manifest::{Manifest, ManifestDiff, ManifestEntry};
+pub use naming::{ArchiveNaming, ValveArchiveNaming};
+pub use observer::IoObserver;
+pub use options::{
+    CaseSensitivity, DEFAULT_CHUNK_SIZE, DuplicatePolicy, ExtractOptions, FileDataMode,
+    ParseOptions, WriteOptions,
+};
+pub use parser::{
+    IncrementalTreeParser, ParseWarning, ParseWarningKind, ParsedEntry, StepResult, TreeEntries,
+    VPKTreeIter,
+};
+pub use stream::CrcValidatingReader;
+pub use validate::{ValidationIssue, ValidationIssueKind};
+
+pub(crate) use naming::Naming;
+pub(crate) use observer::{ArchiveFile, Observer};
 
 pub mod v1;
 pub mod v2;
 
+#[cfg(feature = "tokio")]
+pub mod async_reader;
+
 #[cfg(feature = "revpk")]
 pub mod revpk;
 
+mod builder;
 mod error;
+mod filter;
+mod location;
+mod manifest;
+mod naming;
+mod observer;
+mod options;
+mod parser;
+mod stream;
+mod validate;
 
 /// The terminator sequence (2 bytes) for a [`VPKDirectoryEntry`].
 pub const VPK_ENTRY_TERMINATOR: u16 = 0xFFFF;
@@ -29,32 +72,99 @@ pub trait DirEntry {
     /// # Errors
     /// - When an IO operation fails
     /// - When the file contains invalid data
-    fn from(file: &mut File) -> Result<Self>
+    fn from<R: Read + Seek>(file: &mut R) -> Result<Self>
     where
         Self: Sized;
 
+    /// Reads a directory entry from a file using custom parse options, see [`ParseOptions::allow_bad_terminators`].
+    /// # Errors
+    /// - When an IO operation fails
+    /// - When the file contains invalid data
+    fn from_with_options<R: Read + Seek>(file: &mut R, options: &ParseOptions) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let _ = options;
+        Self::from(file)
+    }
+
+    /// Whether the entry ends with a valid terminator, always `true` for formats without one.
+    fn has_valid_terminator(&self) -> bool {
+        true
+    }
+
     /// Write the directory entry to a file.
     /// # Errors
     /// When an IO operation fails
     /// When the data is invalid
-    fn write(&self, file: &mut File) -> Result<()>;
+    fn write<W: Write>(&self, file: &mut W) -> Result<()>;
 
     /// Returns the number of bytes of preload data for an entry, this is 0 if all the data is stored in archives.
     fn get_preload_length(&self) -> usize;
+
+    /// Sets the number of bytes of preload data for an entry.
+    ///
+    /// Does nothing by default, for entries that can't store preload data.
+    /// [`VPKTree::insert_file_with_preload`] rejects preload data for those entries.
+    fn set_preload_length(&mut self, preload_length: u16) {
+        let _ = preload_length;
+    }
+}
+
+#[cfg(feature = "ordered-tree")]
+use indexmap::IndexMap as TreeMapImpl;
+#[cfg(not(feature = "ordered-tree"))]
+use std::collections::HashMap as TreeMapImpl;
+
+/// The map type of [`VPKTree::files`] and [`VPKTree::preload`].
+///
+/// A [`HashMap`] by default. With the `ordered-tree` feature it is an [`IndexMap`](indexmap::IndexMap),
+/// which iterates in insertion order, so a parsed tree iterates in the order of the directory file.
+///
+/// The feature is not additive, as it changes this type for every crate in the build.
+/// Name the maps through this alias and only use the methods both map types share, so code keeps compiling either way.
+pub type TreeMap<K, V> = TreeMapImpl<K, V>;
+
+/// Removes `key` from a [`TreeMap`], keeping the order of the remaining keys.
+pub(crate) fn tree_map_remove<V>(map: &mut TreeMap<String, V>, key: &str) -> Option<V> {
+    #[cfg(not(feature = "ordered-tree"))]
+    return map.remove(key);
+    #[cfg(feature = "ordered-tree")]
+    return map.shift_remove(key);
 }
 
 /// The file tree parsed from a VPK directory files.
-#[derive(PartialEq, Eq)]
+///
+/// Paths in the tree are always `/`-delimited. The lookup helpers [`Self::get`] and [`Self::get_preload`]
+/// accept `\\` as a separator as well, see [`normalize_vpk_path`](crate::util::path::normalize_vpk_path).
+///
+/// Trees are compared by their files and preload data, recorded entry spans don't take part in comparisons.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VPKTree<DirectoryEntry>
 where
     DirectoryEntry: DirEntry,
 {
     /// A map pointing every file described in the directory tree to its corresponding entry.
-    pub files: HashMap<String, DirectoryEntry>,
+    pub files: TreeMap<String, DirectoryEntry>,
     /// A map pointing every file with preload data to its preload data. A path will only be a valid key if the file at that path has a non-zero amount of preload data.
-    pub preload: HashMap<String, Vec<u8>>,
+    pub preload: TreeMap<String, Vec<u8>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    entry_spans: HashMap<String, Range<u64>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    warnings: Vec<ParseWarning>,
 }
 
+impl<DirectoryEntry> PartialEq for VPKTree<DirectoryEntry>
+where
+    DirectoryEntry: DirEntry + PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.files == other.files && self.preload == other.preload
+    }
+}
+
+impl<DirectoryEntry> Eq for VPKTree<DirectoryEntry> where DirectoryEntry: DirEntry + Eq {}
+
 impl<DirectoryEntry> Default for VPKTree<DirectoryEntry>
 where
     DirectoryEntry: DirEntry,
@@ -71,117 +181,393 @@ where
     #[must_use]
     pub fn new() -> Self {
         Self {
-            files: HashMap::new(),
-            preload: HashMap::new(),
+            files: TreeMap::new(),
+            preload: TreeMap::new(),
+            entry_spans: HashMap::new(),
+            warnings: Vec::new(),
         }
     }
 
     /// Reads from a file
+    ///
+    /// To scan the files of a tree without building it, see [`VPKTreeIter`].
     /// # Errors
     /// - When the data is invalid
     /// - When IO operations fail
-    pub fn from(file: &mut File, start: u64, size: u64) -> Result<Self> {
-        file.seek(SeekFrom::Start(start))
-            .map_err(Error::TreeNotFound)?;
+    pub fn from<R: Read + Seek>(file: &mut R, start: u64, size: u64) -> Result<Self> {
+        Self::from_with_options(file, start, size, &ParseOptions::default())
+    }
 
+    /// Reads from a file using custom parse options.
+    ///
+    /// The tree size is used as given, [`ParseOptions::allow_truncated_tree`] and [`ParseOptions::file_data`] do not apply here.
+    /// # Errors
+    /// - When the data is invalid
+    /// - When IO operations fail
+    /// - When a path appears more than once and duplicates are rejected
+    pub fn from_with_options<R: Read + Seek>(
+        file: &mut R,
+        start: u64,
+        size: u64,
+        options: &ParseOptions,
+    ) -> Result<Self> {
         let mut tree = Self::new();
+        let mut entries = TreeEntries::with_options(file, start, size, options)?;
 
-        while file.stream_position().map_err(Error::Io)? < start + size {
-            let extension = file.read_string().map_err(|e| Error::Util {
-                source: e,
-                context: "Failed to read extension".to_string(),
-            })?;
+        for parsed in entries.by_ref() {
+            let parsed = parsed?;
 
-            if extension.is_empty() {
-                break;
-            }
+            if tree.files.contains_key(&parsed.file_path) {
+                if options.duplicates == DuplicatePolicy::Reject {
+                    return Err(Error::BadData(format!(
+                        "{} appears more than once in the tree",
+                        parsed.file_path
+                    )));
+                }
 
-            loop {
-                let path = file.read_string().map_err(|e| Error::Util {
-                    source: e,
-                    context: "Failed to path".to_string(),
-                })?;
+                if options.collect_warnings {
+                    tree.warnings.push(ParseWarning {
+                        kind: ParseWarningKind::DuplicatePath,
+                        offset: parsed.span.start,
+                        path: Some(parsed.file_path.clone()),
+                        message: format!(
+                            "path appears more than once, {:?} applies",
+                            options.duplicates
+                        ),
+                    });
+                }
 
-                if path.is_empty() || file.stream_position().map_err(Error::Io)? > start + size {
-                    break;
+                if options.duplicates == DuplicatePolicy::FirstWins {
+                    continue;
                 }
 
-                loop {
-                    let file_name = file.read_string().map_err(|e| Error::Util {
-                        source: e,
-                        context: "Failed to read file name".to_string(),
-                    })?;
+                // The preload data of the replaced entry must not outlive it
+                tree_map_remove(&mut tree.preload, &parsed.file_path);
+            }
 
-                    if file_name.is_empty()
-                        || file.stream_position().map_err(Error::Io)? > start + size
-                    {
-                        break;
-                    }
+            if options.record_entry_spans {
+                tree.entry_spans
+                    .insert(parsed.file_path.clone(), parsed.span.clone());
+            }
 
-                    let file_path = format!("{path}/{file_name}.{extension}");
+            tree.insert_parsed(parsed);
+        }
 
-                    let entry = DirectoryEntry::from(file)?;
+        tree.warnings.extend(entries.take_warnings());
+        tree.warnings.sort_by_key(|warning| warning.offset);
 
-                    if entry.get_preload_length() > 0 {
-                        tree.preload.insert(
-                            file_path.clone(),
-                            file.read_bytes(entry.get_preload_length()).map_err(|e| {
-                                Error::Util {
-                                    source: e,
-                                    context: "Failed to read preload data".to_string(),
-                                }
-                            })?,
-                        );
-                    }
+        Ok(tree)
+    }
 
-                    tree.files.insert(file_path, entry);
-                }
+    /// Returns the recoverable problems found while parsing the tree, ordered by offset.
+    ///
+    /// Warnings are only recorded when parsing with [`ParseOptions::collect_warnings`] and are not updated when the tree is edited.
+    #[must_use]
+    pub fn parse_warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    /// Returns the byte range the directory entry of a file occupies in the directory file the tree was parsed from,
+    /// excluding its path and preload data. Use it with [`patch_entry_in_place`] to change a single entry without rewriting the tree.
+    ///
+    /// Spans are only recorded when parsing with [`ParseOptions::record_entry_spans`].
+    /// They describe the parsed file and are not updated when the tree is edited, the span of a removed or renamed file is forgotten.
+    #[must_use]
+    pub fn entry_span(&self, path: &str) -> Option<Range<u64>> {
+        self.entry_spans
+            .get(normalize_vpk_path(path, false).as_ref())
+            .cloned()
+    }
+
+    /// Looks up the entry of a file. Backslashes in `path` are treated as directory separators.
+    #[must_use]
+    pub fn get(&self, path: &str) -> Option<&DirectoryEntry> {
+        self.files.get(normalize_vpk_path(path, false).as_ref())
+    }
+
+    /// Whether the tree contains a file. Backslashes in `path` are treated as directory separators.
+    #[must_use]
+    pub fn contains(&self, path: &str) -> bool {
+        self.files
+            .contains_key(normalize_vpk_path(path, false).as_ref())
+    }
+
+    /// The number of files in the tree.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Whether the tree contains no files.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Looks up the entry of a file regardless of case, see [`Self::find`].
+    #[must_use]
+    pub fn get_ci(&self, path: &str) -> Option<&DirectoryEntry> {
+        self.find(path, CaseSensitivity::Insensitive)
+            .map(|(_, entry)| entry)
+    }
+
+    /// Looks up a file, returning the path it is stored under along with its entry.
+    ///
+    /// `path` is normalized with [`normalize_vpk_path`] first. With [`CaseSensitivity::Insensitive`],
+    /// a path that doesn't match exactly is compared against every path in the tree regardless of case,
+    /// if several paths match the alphabetically first one is returned.
+    #[must_use]
+    pub fn find(
+        &self,
+        path: &str,
+        case_sensitivity: CaseSensitivity,
+    ) -> Option<(&str, &DirectoryEntry)> {
+        let path = normalize_vpk_path(path, false);
+        if let Some((stored_path, entry)) = self.files.get_key_value(path.as_ref()) {
+            return Some((stored_path, entry));
+        }
+
+        if case_sensitivity == CaseSensitivity::Sensitive {
+            return None;
+        }
+
+        let path = path.to_lowercase();
+        self.files
+            .iter()
+            .filter(|(stored_path, _)| {
+                stored_path
+                    .chars()
+                    .flat_map(char::to_lowercase)
+                    .eq(path.chars())
+            })
+            .min_by_key(|(stored_path, _)| *stored_path)
+            .map(|(stored_path, entry)| (stored_path.as_str(), entry))
+    }
+
+    /// Looks up the preload data of a file. Backslashes in `path` are treated as directory separators.
+    #[must_use]
+    pub fn get_preload(&self, path: &str) -> Option<&Vec<u8>> {
+        self.preload.get(normalize_vpk_path(path, false).as_ref())
+    }
+
+    /// Inserts an entry read by the tree parser.
+    pub(crate) fn insert_parsed(&mut self, parsed: ParsedEntry<DirectoryEntry>) {
+        if let Some(preload) = parsed.preload {
+            self.preload.insert(parsed.file_path.clone(), preload);
+        }
+
+        self.files.insert(parsed.file_path, parsed.entry);
+    }
+
+    /// Inserts a file into the tree, storing `preload_bytes` as its preload data.
+    /// The preload length of the entry is updated to match, an empty `preload_bytes` removes any existing preload data.
+    /// Backslashes in `path` are treated as directory separators.
+    /// # Errors
+    /// - When `preload_bytes` is larger than the maximum preload length
+    /// - When the entry can't store preload data, see [`DirEntry::set_preload_length`]
+    pub fn insert_file_with_preload(
+        &mut self,
+        path: &str,
+        mut entry: DirectoryEntry,
+        preload_bytes: Vec<u8>,
+    ) -> Result<()> {
+        let path = normalize_vpk_path(path, false);
+        let preload_length = u16::try_from(preload_bytes.len()).map_err(|_| Error::DataTooLarge)?;
+        entry.set_preload_length(preload_length);
+        if entry.get_preload_length() != preload_bytes.len() {
+            return Err(Error::BadData(format!(
+                "The entry of {path} can't store {preload_length} bytes of preload data"
+            )));
+        }
+
+        if preload_bytes.is_empty() {
+            tree_map_remove(&mut self.preload, &path);
+        } else {
+            self.preload.insert(path.to_string(), preload_bytes);
+        }
+
+        self.files.insert(path.to_string(), entry);
+        Ok(())
+    }
+
+    /// Removes a file and its preload data from the tree, returning its entry if it existed.
+    /// Backslashes in `path` are treated as directory separators.
+    pub fn remove_file(&mut self, path: &str) -> Option<DirectoryEntry> {
+        let path = normalize_vpk_path(path, false);
+        tree_map_remove(&mut self.preload, &path);
+        self.entry_spans.remove(path.as_ref());
+        tree_map_remove(&mut self.files, &path)
+    }
+
+    /// Moves a file and its preload data to a new path.
+    /// Backslashes in `from` and `to` are treated as directory separators.
+    /// # Errors
+    /// - When there is no file at `from`
+    /// - When there already is a file at `to`
+    pub fn rename_file(&mut self, from: &str, to: &str) -> Result<()> {
+        let from = normalize_vpk_path(from, false);
+        let to = normalize_vpk_path(to, false);
+        let (from, to) = (from.as_ref(), to.as_ref());
+
+        if from == to {
+            return if self.files.contains_key(from) {
+                Ok(())
+            } else {
+                Err(Error::FileNotFound(from.to_string()))
+            };
+        }
+
+        if self.files.contains_key(to) {
+            return Err(Error::BadData(format!("File already exists: {to}")));
+        }
+
+        let entry =
+            tree_map_remove(&mut self.files, from).ok_or(Error::FileNotFound(from.to_string()))?;
+        self.files.insert(to.to_string(), entry);
+        self.entry_spans.remove(from);
+
+        if let Some(preload) = tree_map_remove(&mut self.preload, from) {
+            self.preload.insert(to.to_string(), preload);
+        }
+
+        Ok(())
+    }
+
+    /// Returns an iterator over the files with the given extension, compared case-insensitively.
+    /// The extension is given without a leading dot, e.g. `"vmt"`.
+    pub fn filter_by_extension<'a>(
+        &'a self,
+        ext: &'a str,
+    ) -> impl Iterator<Item = (&'a str, &'a DirectoryEntry)> + 'a {
+        self.files
+            .iter()
+            .filter(move |(path, _)| {
+                split_file_path(path)
+                    .1
+                    .rsplit_once('.')
+                    .is_some_and(|(_, file_ext)| file_ext.eq_ignore_ascii_case(ext))
+            })
+            .map(|(path, entry)| (path.as_str(), entry))
+    }
+
+    /// Returns an iterator over the files directly inside the given directory, not including subdirectories.
+    /// A trailing `/` in `dir` is ignored.
+    pub fn filter_by_directory<'a>(
+        &'a self,
+        dir: &'a str,
+    ) -> impl Iterator<Item = (&'a str, &'a DirectoryEntry)> + 'a {
+        let dir = dir.strip_suffix('/').unwrap_or(dir);
+
+        self.files
+            .iter()
+            .filter(move |(path, _)| split_file_path(path).0 == dir)
+            .map(|(path, entry)| (path.as_str(), entry))
+    }
+
+    /// Returns an iterator over the files whose path starts with `prefix`.
+    pub fn filter_by_path_prefix<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = (&'a str, &'a DirectoryEntry)> + 'a {
+        self.files
+            .iter()
+            .filter(move |(path, _)| path.starts_with(prefix))
+            .map(|(path, entry)| (path.as_str(), entry))
+    }
+
+    /// Returns an iterator over the files whose full path matches a glob pattern, see [`Pattern`].
+    /// Invalid patterns match no files, use [`VPKTree::try_glob`] to detect them.
+    pub fn glob<'a>(
+        &'a self,
+        pattern: &str,
+    ) -> impl Iterator<Item = (&'a str, &'a DirectoryEntry)> + 'a {
+        self.try_glob(pattern).into_iter().flatten()
+    }
+
+    /// Returns an iterator over the files whose full path matches a glob pattern, see [`Pattern`].
+    /// # Errors
+    /// - When the pattern is invalid
+    pub fn try_glob<'a>(
+        &'a self,
+        pattern: &str,
+    ) -> Result<impl Iterator<Item = (&'a str, &'a DirectoryEntry)> + 'a> {
+        let pattern = Pattern::new(pattern).map_err(|e| Error::Util {
+            source: e,
+            context: "Failed to parse glob pattern".to_string(),
+        })?;
+
+        Ok(self
+            .files
+            .iter()
+            .filter(move |(path, _)| pattern.matches(path))
+            .map(|(path, entry)| (path.as_str(), entry)))
+    }
+
+    /// Checks that the preload data of every entry matches its preload length.
+    /// # Errors
+    /// - When an entry has a preload length but no or differently sized preload data
+    pub fn validate_preload(&self) -> Result<()> {
+        for (path, entry) in &self.files {
+            let preload_length = self.preload.get(path).map_or(0, Vec::len);
+
+            if preload_length != entry.get_preload_length() {
+                return Err(Error::BadData(format!(
+                    "Entry {path} has a preload length of {} but {preload_length} bytes of preload data",
+                    entry.get_preload_length(),
+                )));
             }
         }
 
-        Ok(tree)
+        Ok(())
+    }
+
+    /// Returns the paths of all files in the order [`Self::write`] writes them.
+    ///
+    /// Paths are sorted by extension, then by directory, then by path, independent of the order files were inserted in.
+    #[must_use]
+    pub fn sorted_paths(&self) -> Vec<&str> {
+        let mut file_paths: Vec<(String, String, &str)> = self
+            .files
+            .keys()
+            .map(|path_str| {
+                let (extension, dir, _) = tree_components(path_str);
+                (extension, dir, path_str.as_str())
+            })
+            .collect();
+        file_paths.sort_unstable();
+
+        file_paths
+            .into_iter()
+            .map(|(_, _, path_str)| path_str)
+            .collect()
     }
 
     /// Write a file
+    ///
+    /// Extensions, directories and files are written in sorted order, see [`Self::sorted_paths`],
+    /// so the same tree always results in the same bytes.
     /// # Panics
     /// - Should never panic, if it does, contact the crate author
     /// # Errors
+    /// - When the preload data of an entry does not match its preload length
     /// - When an IO operation fails
-    pub fn write(&self, file: &mut File) -> Result<()> {
+    pub fn write<W: Write>(&self, file: &mut W) -> Result<()> {
+        self.validate_preload()?;
+
         #[allow(clippy::type_complexity)]
-        let mut treeified: HashMap<
+        let mut treeified: BTreeMap<
             String,
-            HashMap<String, Vec<(String, &DirectoryEntry, Option<&Vec<u8>>)>>,
-        > = HashMap::new();
-
-        for (path_str, entry) in &self.files {
-            let path = Path::new(&path_str);
+            BTreeMap<String, Vec<(String, &DirectoryEntry, Option<&Vec<u8>>)>>,
+        > = BTreeMap::new();
 
-            let extension = path
-                .extension()
-                .unwrap_or(OsStr::new(""))
-                .to_str()
-                .unwrap_or("")
-                .to_owned();
+        for path_str in self.sorted_paths() {
+            let entry = &self.files[path_str];
+            let (extension, dir, file_name) = tree_components(path_str);
 
             if !treeified.contains_key(&extension) {
-                treeified.insert(extension.clone(), HashMap::new());
+                treeified.insert(extension.clone(), BTreeMap::new());
             }
 
-            let dir = path
-                .parent()
-                .unwrap_or(Path::new(""))
-                .to_str()
-                .unwrap_or("/")
-                .to_owned();
-
-            let file_name = path
-                .file_stem()
-                .unwrap_or(OsStr::new(""))
-                .to_str()
-                .unwrap_or("")
-                .to_owned();
-
             let dir_map = treeified
                 .get_mut(&extension)
                 .ok_or(Error::DataNotFound(format!(
@@ -241,12 +627,127 @@ where
             })?;
         }
 
+        // An empty extension marks the end of the tree, empty trees are written without one
+        if !self.files.is_empty() {
+            file.write_u8(0).map_err(|e| Error::Util {
+                source: e,
+                context: "Error writing tree terminator".to_string(),
+            })?;
+        }
+
         Ok(())
     }
+
+    /// Serializes the tree into memory, returning the data along with its size for the header.
+    /// # Errors
+    /// - When the preload data does not match the entries
+    /// - When the tree is too large for the header
+    pub(crate) fn serialize(&self) -> Result<(Vec<u8>, u32)> {
+        let mut tree = Vec::new();
+        self.write(&mut tree)?;
+
+        let tree_size = u32::try_from(tree.len()).map_err(|_| Error::DataTooLarge)?;
+        Ok((tree, tree_size))
+    }
+}
+
+impl VPKTree<VPKDirectoryEntry> {
+    /// Inserts a file, computing its CRC and storing up to `max_preload_bytes` of `data` as preload data.
+    ///
+    /// The preload data is capped at [`u16::MAX`] bytes. The remainder of `data` is described as stored at `entry_offset`
+    /// in the archive `archive_index`, writing it to the archive is left to the caller.
+    /// Backslashes in `path` are treated as directory separators.
+    /// # Errors
+    /// - When `path` is already in the tree and `overwrite` is not set
+    /// - When the remainder of `data` is too large to be described by an entry
+    pub fn insert_file(
+        &mut self,
+        path: &str,
+        data: &[u8],
+        archive_index: u16,
+        entry_offset: u32,
+        max_preload_bytes: usize,
+        overwrite: bool,
+    ) -> Result<()> {
+        let path = normalize_vpk_path(path, false);
+        if !overwrite && self.files.contains_key(path.as_ref()) {
+            return Err(Error::BadData(format!(
+                "File {path} is already in the tree"
+            )));
+        }
+
+        let (preload, archive_data) =
+            data.split_at(max_preload_bytes.min(data.len()).min(u16::MAX.into()));
+
+        let entry = VPKDirectoryEntry {
+            crc: Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(data),
+            archive_index,
+            entry_offset,
+            entry_length: u32::try_from(archive_data.len()).map_err(|_| Error::EntryTooLarge {
+                path: path.to_string(),
+                size: archive_data.len() as u64,
+            })?,
+            ..Default::default()
+        };
+
+        self.insert_file_with_preload(&path, entry, preload.to_vec())
+    }
+
+    /// Returns the indices of all archives the tree needs data from, e.g. `0` and `1` for `pak01_000.vpk` and `pak01_001.vpk`.
+    ///
+    /// Entries stored entirely in preload data and entries stored in the directory file itself
+    /// (see [`v1::VPK_DIR_ARCHIVE_INDEX`] and [`v2::VPK_DIR_ARCHIVE_INDEX_V2`]) are not counted.
+    #[must_use]
+    pub fn archive_indices(&self) -> HashSet<u16> {
+        self.files
+            .values()
+            .filter(|entry| entry.entry_length > 0)
+            .map(|entry| entry.archive_index)
+            .filter(|&archive_index| !is_dir_archive_index(archive_index))
+            .collect()
+    }
+
+    /// Returns the total size of all files in the tree, the sum of their entry lengths and preload bytes.
+    /// No archives are opened.
+    #[must_use]
+    pub fn total_entry_size(&self) -> u64 {
+        self.files
+            .values()
+            .map(|entry| u64::from(entry.entry_length) + u64::from(entry.preload_length))
+            .sum()
+    }
+}
+
+/// Checks whether an archive index refers to data stored in the directory file itself rather than in an archive.
+pub(crate) fn is_dir_archive_index(archive_index: u16) -> bool {
+    archive_index == v1::VPK_DIR_ARCHIVE_INDEX
+        || u32::from(archive_index) == v2::VPK_DIR_ARCHIVE_INDEX_V2
+}
+
+/// Splits a path into the extension, directory and file name it is stored under in a directory tree.
+/// Empty components are stored as [`EMPTY_TREE_COMPONENT`], e.g. the directory of a file in the root directory.
+fn tree_components(path_str: &str) -> (String, String, String) {
+    let path = Path::new(path_str);
+    let component = |s: Option<&str>| match s {
+        Some(s) if !s.is_empty() => s.to_owned(),
+        _ => EMPTY_TREE_COMPONENT.to_owned(),
+    };
+
+    let extension = component(path.extension().and_then(OsStr::to_str));
+    let dir = component(path.parent().and_then(Path::to_str));
+    let file_name = component(path.file_stem().and_then(OsStr::to_str));
+
+    (extension, dir, file_name)
+}
+
+/// Splits a file path from a [`VPKTree`] into its directory and file name.
+fn split_file_path(path: &str) -> (&str, &str) {
+    path.rsplit_once('/').unwrap_or(("", path))
 }
 
 /// The entry format used by VPK version 1 and VPK version 2. For the format used by Respawn VPKs see [`VPKDirectoryRespawn`](crate::pak::revpk::format::VPKDirectoryEntryRespawn).
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VPKDirectoryEntry {
     /// A 32bit CRC of the file's data. Uses the CRC32 ISO HDLC algorithm.
     pub crc: u32,
@@ -292,7 +793,11 @@ impl VPKDirectoryEntry {
 }
 
 impl DirEntry for VPKDirectoryEntry {
-    fn from(file: &mut File) -> Result<Self> {
+    fn from<R: Read + Seek>(file: &mut R) -> Result<Self> {
+        Self::from_with_options(file, &ParseOptions::default())
+    }
+
+    fn from_with_options<R: Read + Seek>(file: &mut R, options: &ParseOptions) -> Result<Self> {
         let crc = file.read_u32().map_err(|e| Error::Util {
             source: e,
             context: "Failed to read CRC".to_string(),
@@ -323,7 +828,7 @@ impl DirEntry for VPKDirectoryEntry {
             context: "Failed to read terminator".to_string(),
         })?;
 
-        if terminator != VPK_ENTRY_TERMINATOR {
+        if terminator != VPK_ENTRY_TERMINATOR && !options.allow_bad_terminators {
             return Err(Error::InvalidEntryTerminator(format!(
                 "Should be 0xFFFF but found {terminator:X}"
             )));
@@ -339,7 +844,11 @@ impl DirEntry for VPKDirectoryEntry {
         })
     }
 
-    fn write(&self, file: &mut File) -> Result<()> {
+    fn has_valid_terminator(&self) -> bool {
+        self.terminator == VPK_ENTRY_TERMINATOR
+    }
+
+    fn write<W: Write>(&self, file: &mut W) -> Result<()> {
         if self.terminator != VPK_ENTRY_TERMINATOR {
             return Err(Error::InvalidEntryTerminator(format!(
                 "Should be 0xFFFF but found {:X}",
@@ -385,12 +894,493 @@ impl DirEntry for VPKDirectoryEntry {
     fn get_preload_length(&self) -> usize {
         self.preload_length.into()
     }
+
+    fn set_preload_length(&mut self, preload_length: u16) {
+        self.preload_length = preload_length;
+    }
+}
+
+/// The operations supported for a VPK format, see [`PakFormat::capabilities`](crate::detect::PakFormat::capabilities).
+///
+/// Every format module defines its capabilities next to its implementation,
+/// e.g. [`v1::VPK_CAPS_V1`], so they are updated together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FormatCaps {
+    /// Whether files can be read and extracted.
+    pub read: bool,
+    /// Whether the directory file can be written with [`PakWriter::write_dir`].
+    pub write_dir: bool,
+    /// Whether archives containing file data can be written.
+    pub write_archives: bool,
+    /// Whether compressed file data can be read and written.
+    pub compression: bool,
+    /// Whether signatures can be verified.
+    pub signatures: bool,
+}
+
+/// Callback used to report progress while extracting many files.
+/// Receives the path of the file that was just processed, the number of files processed so far and the total number of files.
+pub type ExtractProgress<'a> = &'a mut dyn FnMut(&str, usize, usize);
+
+/// The outcome of extracting many files from a VPK at once.
+#[derive(Debug, Default)]
+pub struct ExtractSummary {
+    /// The number of files that were extracted successfully.
+    pub files_extracted: usize,
+    /// The number of files that were not extracted because they already existed, see [`v1::VPKVersion1::extract_to_dir`].
+    pub files_skipped: usize,
+    /// The total number of bytes written for the successfully extracted files.
+    pub bytes_written: u64,
+    /// The files that could not be extracted, along with the reason why.
+    pub errors: Vec<(String, Error)>,
+    /// The files that were not extracted because the extraction stopped early when the file system ran out of space,
+    /// including the file that could not be written. Pass them to [`PakReader::extract_files`] to resume the extraction.
+    pub not_attempted: Vec<String>,
+}
+
+impl ExtractSummary {
+    /// Adds the result of extracting a single file to the summary.
+    /// Returns `false` if the extraction should stop, as the file system ran out of space.
+    pub(crate) fn record(&mut self, file_path: &str, result: Result<u64>) -> bool {
+        match result {
+            Ok(bytes_written) => {
+                self.files_extracted += 1;
+                self.bytes_written += bytes_written;
+                true
+            }
+            Err(e) => {
+                let out_of_space = matches!(e, Error::OutOfSpace { .. });
+                self.errors.push((file_path.to_string(), e));
+                !out_of_space
+            }
+        }
+    }
+}
+
+/// The outcome of checking whether an archive matches the loaded directory tree, see [`PakReader::check_archive_consistency`].
+#[derive(Debug)]
+pub enum ArchiveConsistency {
+    /// Every sampled entry of the archive matched its checksum.
+    Consistent(u16),
+    /// A sampled entry did not match its checksum or the archive ended before its data,
+    /// the archive most likely belongs to a different version of the directory file.
+    ArchiveStale(u16),
+    /// The archive could not be opened or read, along with the error that occurred.
+    Unreadable(u16, Error),
+}
+
+impl ArchiveConsistency {
+    /// Classifies the result of checking the sampled entries of an archive.
+    ///
+    /// Only data that does not match the tree makes the archive stale, any other error is kept.
+    pub(crate) fn from_result(archive_index: u16, result: Result<()>) -> Self {
+        match result {
+            Ok(()) => Self::Consistent(archive_index),
+            Err(
+                Error::CrcMismatch { .. }
+                | Error::UnexpectedEof { .. }
+                | Error::TruncatedPart { .. },
+            ) => Self::ArchiveStale(archive_index),
+            Err(e) => Self::Unreadable(archive_index, e),
+        }
+    }
+
+    /// The index of the archive that was checked.
+    #[must_use]
+    pub fn archive_index(&self) -> u16 {
+        match self {
+            Self::Consistent(archive_index)
+            | Self::ArchiveStale(archive_index)
+            | Self::Unreadable(archive_index, _) => *archive_index,
+        }
+    }
+}
+
+/// Picks at most `samples` items spread evenly over `items`, always including the first and last item.
+pub(crate) fn sample_evenly<T>(items: &[T], samples: usize) -> impl Iterator<Item = &T> {
+    let count = samples.min(items.len());
+
+    (0..count).map(move |i| {
+        if count == 1 {
+            &items[0]
+        } else {
+            &items[i * (items.len() - 1) / (count - 1)]
+        }
+    })
+}
+
+/// An output file that is only created, along with its parent directories, once it is written to.
+/// Used so a failed extraction doesn't leave an empty file behind.
+struct LazyFile<'a> {
+    path: &'a Path,
+    file: Option<File>,
+    written: u64,
+}
+
+impl<'a> LazyFile<'a> {
+    fn new(path: &'a Path) -> Self {
+        Self {
+            path,
+            file: None,
+            written: 0,
+        }
+    }
+
+    /// Returns the file, creating it if needed.
+    fn file(&mut self) -> io::Result<&mut File> {
+        if self.file.is_none() {
+            if let Some(prefix) = self.path.parent() {
+                std::fs::create_dir_all(prefix)?;
+            }
+
+            self.file = Some(File::create(self.path)?);
+        }
+
+        Ok(self.file.as_mut().expect("File was just created"))
+    }
+}
+
+impl Write for LazyFile<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.file()?.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.as_mut().map_or(Ok(()), Write::flush)
+    }
+}
+
+/// Lists the files at `file_paths` with their CRC, size and archives, see [`PakReader::manifest`].
+fn manifest_of<P: PakReader + ?Sized>(pak: &P, file_paths: Vec<&str>) -> Manifest {
+    file_paths
+        .into_iter()
+        .filter_map(|file_path| {
+            Some(ManifestEntry::new(
+                file_path,
+                pak.file_crc(file_path)?,
+                pak.file_size(file_path)?,
+                pak.file_location(file_path).as_ref(),
+            ))
+        })
+        .collect()
+}
+
+/// Returns the output path for a VPK file path inside of `output_dir`.
+/// # Errors
+/// - When the file path would escape `output_dir`
+pub(crate) fn output_path(output_dir: &str, file_path: &str) -> Result<PathBuf> {
+    join_sanitized(Path::new(output_dir), file_path).ok_or(Error::BadData(format!(
+        "File path escapes the output directory: {file_path}"
+    )))
+}
+
+/// Writes the extracted data of the file at `file_path` to `out_path` with `write`, returning the number of bytes written.
+/// The output file and its parent directories are only created once data is written, or after `write` succeeds for empty files.
+///
+/// When the file system runs out of space or the data does not match its CRC, the partial output file is removed,
+/// or renamed with a `.partial` suffix if `keep_partial` is set.
+/// # Errors
+/// - When the file system runs out of space ([`Error::OutOfSpace`] with the number of bytes written)
+/// - When `write` fails
+pub(crate) fn write_output_file(
+    out_path: &Path,
+    file_path: &str,
+    keep_partial: bool,
+    write: impl FnOnce(&mut dyn Write) -> Result<u64>,
+) -> Result<u64> {
+    let mut out_file = LazyFile::new(out_path);
+
+    let result = write(&mut out_file).and_then(|written| {
+        // Empty files never write any data
+        out_file.file().map_err(Error::Io)?;
+        Ok(written)
+    });
+
+    match result {
+        Err(Error::Io(e)) if e.kind() == io::ErrorKind::StorageFull => {
+            discard_partial(&mut out_file, keep_partial);
+
+            Err(Error::OutOfSpace {
+                file: file_path.to_string(),
+                written: out_file.written,
+            })
+        }
+        Err(e @ Error::CrcMismatch { .. }) => {
+            discard_partial(&mut out_file, keep_partial);
+            Err(e)
+        }
+        result => result,
+    }
+}
+
+/// Removes the output file of a failed extraction, or renames it with a `.partial` suffix if `keep_partial` is set.
+fn discard_partial(out_file: &mut LazyFile, keep_partial: bool) {
+    if out_file.file.take().is_some() {
+        // The extraction failed either way, a partial file that cannot be cleaned up is left behind
+        let _ = if keep_partial {
+            let mut partial_path = out_file.path.as_os_str().to_owned();
+            partial_path.push(".partial");
+            std::fs::rename(out_file.path, partial_path)
+        } else {
+            std::fs::remove_file(out_file.path)
+        };
+    }
+}
+
+/// Reads `count` bytes of the archive data of the file at `file_path`.
+/// `remaining` is the number of bytes of its archive data left to read, including this read.
+/// # Errors
+/// - When the archive ends early ([`Error::UnexpectedEof`] with the number of bytes missing from the file)
+/// - When an IO operation fails
+pub(crate) fn read_archive_chunk<R: Read>(
+    archive_file: &mut R,
+    count: usize,
+    remaining: u64,
+    file_path: &str,
+) -> Result<Vec<u8>> {
+    archive_file.read_bytes(count).map_err(|e| match e {
+        util::Error::UnexpectedEof { available, .. } => Error::UnexpectedEof {
+            file: file_path.to_string(),
+            missing: remaining - available as u64,
+        },
+        e => Error::Util {
+            source: e,
+            context: "Failed to read from archive file".to_string(),
+        },
+    })
+}
+
+/// Extracts every file of `pak` into `output_dir` with [`PakReader::extract_files`].
+/// Files that already exist are skipped and counted in [`ExtractSummary::files_skipped`] unless `overwrite` is set.
+///
+/// This returns an [`ExtractSummary`] like [`PakReader::extract_files`] rather than the result of every file,
+/// so the extraction stops when the file system runs out of space and the files not attempted are listed.
+/// # Errors
+/// - When the output directory cannot be created
+pub(crate) fn extract_to_dir<P: PakReader + ?Sized>(
+    pak: &P,
+    archive_path: &str,
+    vpk_name: &str,
+    output_dir: &str,
+    overwrite: bool,
+) -> Result<ExtractSummary> {
+    let (skipped, file_paths): (Vec<&str>, Vec<&str>) =
+        pak.file_paths().into_iter().partition(|file_path| {
+            !overwrite
+                && join_sanitized(Path::new(output_dir), file_path)
+                    .is_some_and(|out_path| out_path.exists())
+        });
+
+    let mut summary = pak.extract_files(archive_path, vpk_name, &file_paths, output_dir, None)?;
+    summary.files_skipped = skipped.len();
+
+    Ok(summary)
+}
+
+/// Overwrites the directory entry occupying `span` in `dir_file` with `entry`, leaving the rest of the file untouched.
+///
+/// Get the span from [`VPKTree::entry_span`]. The path and preload data of the file cannot be changed this way,
+/// so neither can the preload length of the entry.
+/// # Errors
+/// - When `entry` doesn't serialize to exactly the length of `span`, nothing is written then
+/// - When IO operations fail
+pub fn patch_entry_in_place<E: DirEntry>(
+    dir_file: &mut File,
+    span: Range<u64>,
+    entry: &E,
+) -> Result<()> {
+    let mut bytes = Vec::new();
+    entry.write(&mut bytes)?;
+
+    if bytes.len() as u64 != span.end.saturating_sub(span.start) {
+        return Err(Error::BadData(format!(
+            "Entry is {} bytes but the span {span:?} is {} bytes",
+            bytes.len(),
+            span.end.saturating_sub(span.start)
+        )));
+    }
+
+    dir_file
+        .seek(SeekFrom::Start(span.start))
+        .map_err(Error::Io)?;
+    dir_file.write_all(&bytes).map_err(Error::Io)
+}
+
+/// Checks the tree size from a header against the length of the data in `reader`, returning the tree size to parse.
+/// The position of `reader` is left unchanged.
+/// # Errors
+/// - When the tree extends past the end of the data, unless [`ParseOptions::allow_truncated_tree`] is set
+/// - When the tree is empty while data follows the header, unless [`ParseOptions::allow_truncated_tree`] is set
+/// - When IO operations fail
+pub(crate) fn checked_tree_size<R: Seek>(
+    reader: &mut R,
+    tree_start: u64,
+    tree_size: u64,
+    options: &ParseOptions,
+) -> Result<u64> {
+    let position = reader.stream_position().map_err(Error::Io)?;
+    let len = reader.seek(SeekFrom::End(0)).map_err(Error::Io)?;
+    reader.seek(SeekFrom::Start(position)).map_err(Error::Io)?;
+
+    let available = len.saturating_sub(tree_start);
+
+    if options.allow_truncated_tree {
+        return Ok(tree_size.min(available));
+    }
+
+    if tree_size > available {
+        return Err(Error::InvalidTreeSize(format!(
+            "Tree size of {tree_size} bytes exceeds the {available} bytes following the header"
+        )));
+    }
+
+    if tree_size == 0 && available > 0 {
+        return Err(Error::InvalidTreeSize(format!(
+            "Tree size is 0 but {available} bytes follow the header"
+        )));
+    }
+
+    Ok(tree_size)
 }
 
 /// Trait for reading VPK files.
+///
+/// Reading never creates or modifies files next to the VPK: the directory file, its archives and CAM files are only opened for reading,
+/// so VPKs can be read and extracted from read-only media. Only the output paths given to the extract functions are written to.
 pub trait PakReader {
+    /// Returns the paths of all files stored in the VPK.
+    fn file_paths(&self) -> Vec<&str>;
+
+    /// Returns the number of bytes extracting a file stored in the VPK produces, or `None` if the file is not in the VPK.
+    ///
+    /// Only the directory tree is used, no archives are opened. Use it to check for disk space or
+    /// to decide whether a file fits in memory before reading it.
+    fn file_size(&self, file_path: &str) -> Option<u64>;
+
+    /// Returns the CRC of the contents of a file stored in the VPK as recorded in the directory tree,
+    /// or `None` if the file is not in the VPK. No archives are opened.
+    fn file_crc(&self, file_path: &str) -> Option<u32>;
+
+    /// Returns where the data of a file stored in the VPK is located, or `None` if the file is not in the VPK.
+    /// No archives are opened.
+    fn file_location(&self, file_path: &str) -> Option<FileLocation>;
+
+    /// Lists every file stored in the VPK with its CRC, size and archives, sorted by path. No archives are opened.
+    ///
+    /// Files whose size cannot be determined, e.g. Respawn WAV files without a CAM entry, are left out.
+    fn manifest(&self) -> Manifest {
+        manifest_of(self, self.file_paths())
+    }
+
+    /// Lists every file matching `filter` like [`Self::manifest`]. No archives are opened.
+    fn manifest_filtered(&self, filter: &ExtensionFilter) -> Manifest {
+        manifest_of(self, filter.select_from(&self.extension_index()))
+    }
+
+    /// Groups the paths of all files stored in the VPK by extension, to select files with an [`ExtensionFilter`].
+    ///
+    /// Build the index once and use [`ExtensionFilter::select_from`] when selecting files repeatedly.
+    fn extension_index(&self) -> ExtensionIndex<'_> {
+        ExtensionIndex::new(self.file_paths())
+    }
+
+    /// Read the contents of a file stored in the VPK into memory.
+    ///
+    /// Returns `None` on any error, use [`Self::try_read_file`] to find out what went wrong.
+    fn read_file(&self, archive_path: &str, vpk_name: &str, file_path: &str) -> Option<Vec<u8>> {
+        self.read_file_with(
+            archive_path,
+            vpk_name,
+            file_path,
+            &ExtractOptions::default(),
+        )
+    }
+
+    /// Read the contents of a file stored in the VPK into memory, matching `file_path` regardless of case like the Source engine.
+    ///
+    /// Shorthand for [`Self::read_file_with`] using [`CaseSensitivity::Insensitive`].
+    fn read_file_ci(&self, archive_path: &str, vpk_name: &str, file_path: &str) -> Option<Vec<u8>> {
+        self.read_file_with(
+            archive_path,
+            vpk_name,
+            file_path,
+            &ExtractOptions {
+                case_sensitivity: CaseSensitivity::Insensitive,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Read the contents of a file stored in the VPK into memory using custom extract options,
+    /// e.g. to return the data of files with stale CRCs by disabling [`ExtractOptions::verify_crc`].
+    ///
+    /// Returns `None` on any error, use [`Self::try_read_file_with`] to find out what went wrong.
+    fn read_file_with(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        options: &ExtractOptions,
+    ) -> Option<Vec<u8>> {
+        self.try_read_file_with(archive_path, vpk_name, file_path, options)
+            .ok()
+    }
+
     /// Read the contents of a file stored in the VPK into memory.
-    fn read_file(&self, archive_path: &str, vpk_name: &str, file_path: &str) -> Option<Vec<u8>>;
+    /// # Errors
+    /// - When the file does not exist in the VPK ([`Error::FileNotFound`])
+    /// - When an archive file cannot be opened ([`Error::ArchiveOpen`])
+    /// - When the preload data of the file is missing ([`Error::DataNotFound`])
+    /// - When an archive ends before the data of the file ([`Error::UnexpectedEof`])
+    /// - When compressed data of a Respawn VPK cannot be decompressed ([`Error::Decompress`])
+    /// - When an archive ends in the middle of a compressed part of a Respawn VPK ([`Error::TruncatedPart`])
+    /// - When the data does not match its CRC ([`Error::CrcMismatch`])
+    /// - When the data is invalid or an IO operation fails
+    fn try_read_file(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+    ) -> Result<Vec<u8>> {
+        self.try_read_file_with(
+            archive_path,
+            vpk_name,
+            file_path,
+            &ExtractOptions::default(),
+        )
+    }
+
+    /// Read the contents of a file stored in the VPK into memory using custom extract options, see [`Self::try_read_file`].
+    /// # Errors
+    /// - When the file does not exist in the VPK ([`Error::FileNotFound`])
+    /// - When an archive file cannot be opened ([`Error::ArchiveOpen`])
+    /// - When the preload data of the file is missing ([`Error::DataNotFound`])
+    /// - When an archive ends before the data of the file ([`Error::UnexpectedEof`])
+    /// - When compressed data of a Respawn VPK cannot be decompressed ([`Error::Decompress`])
+    /// - When an archive ends in the middle of a compressed part of a Respawn VPK ([`Error::TruncatedPart`])
+    /// - When the data does not match its CRC and [`ExtractOptions::verify_crc`] is set ([`Error::CrcMismatch`])
+    /// - When the data is invalid or an IO operation fails
+    fn try_read_file_with(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        options: &ExtractOptions,
+    ) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.read_file_to_with(
+            archive_path,
+            vpk_name,
+            file_path,
+            &mut buf,
+            options,
+            &mut |_, _| {},
+        )?;
+
+        Ok(buf)
+    }
 
     /// Extract the contents of a file stored in the VPK to a file system location.
     fn extract_file(
@@ -399,10 +1389,345 @@ pub trait PakReader {
         vpk_name: &str,
         file_path: &str,
         output_path: &str,
-    ) -> Result<()>;
+    ) -> Result<()> {
+        self.extract_file_with(
+            archive_path,
+            vpk_name,
+            file_path,
+            output_path,
+            &ExtractOptions::default(),
+            &mut |_, _| {},
+        )
+    }
+
+    /// Extract the contents of a file stored in the VPK to a file system location, reporting progress along the way.
+    ///
+    /// `progress` is called after every chunk written with the number of bytes written so far and the expected size of the file.
+    fn extract_file_with_progress(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        output_path: &str,
+        progress: &mut dyn FnMut(u64, u64),
+    ) -> Result<()> {
+        self.extract_file_with(
+            archive_path,
+            vpk_name,
+            file_path,
+            output_path,
+            &ExtractOptions::default(),
+            progress,
+        )
+    }
+
+    /// Extract the contents of a file stored in the VPK to a file system location using custom extract options.
+    ///
+    /// `progress` is called after every chunk written with the number of bytes written so far and the expected size of the file.
+    /// The output file is only created once the file is found, see [`Self::read_file_to_with`] for how the data is streamed.
+    ///
+    /// When the file system runs out of space or the data does not match its CRC,
+    /// the partial output file is removed unless [`ExtractOptions::keep_partial`] is set.
+    /// # Errors
+    /// - When the file system runs out of space ([`Error::OutOfSpace`] with the number of bytes written)
+    /// - See [`Self::try_read_file_with`]
+    fn extract_file_with(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        output_path: &str,
+        options: &ExtractOptions,
+        progress: &mut dyn FnMut(u64, u64),
+    ) -> Result<()> {
+        write_output_file(
+            Path::new(output_path),
+            file_path,
+            options.keep_partial,
+            |out_file| {
+                self.read_file_to_with(
+                    archive_path,
+                    vpk_name,
+                    file_path,
+                    out_file,
+                    options,
+                    progress,
+                )
+            },
+        )?;
+
+        Ok(())
+    }
+
+    /// Open a file stored in the VPK for reading, without reading its data into memory.
+    ///
+    /// Archives are only read as the returned reader is consumed, file parts of Respawn VPKs are stitched together transparently.
+    /// The CRC of the data is verified once the reader is fully consumed, see [`CrcValidatingReader`].
+    ///
+    /// By default the whole file is read into memory with [`PakReader::try_read_file`], verifying its CRC up front.
+    /// The formats of this crate stream the data instead.
+    /// # Errors
+    /// - When the file does not exist in the VPK
+    /// - When an archive cannot be opened
+    fn read_file_streaming(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+    ) -> Result<Box<dyn Read + '_>> {
+        let data = self.try_read_file(archive_path, vpk_name, file_path)?;
+        Ok(Box::new(io::Cursor::new(data)))
+    }
+
+    /// Stream the contents of a file stored in the VPK into `out`, returning the number of bytes written.
+    /// # Errors
+    /// - When the file does not exist in the VPK
+    /// - When an archive cannot be opened or read
+    /// - When writing to `out` fails
+    /// - When the CRC of the data does not match
+    fn read_file_to(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        out: &mut dyn Write,
+    ) -> Result<u64> {
+        self.read_file_to_with(
+            archive_path,
+            vpk_name,
+            file_path,
+            out,
+            &ExtractOptions::default(),
+            &mut |_, _| {},
+        )
+    }
+
+    /// Stream the contents of a file stored in the VPK into `out` using custom extract options, returning the number of bytes written.
+    ///
+    /// `progress` is called after every chunk written with the number of bytes written so far and the expected size of the file.
+    ///
+    /// Regardless of the size of the file at most `chunk_size` bytes of file data are held in memory at once,
+    /// or a single compressed file part and its decompressed contents for Respawn VPKs.
+    /// # Errors
+    /// - When the file does not exist in the VPK
+    /// - When an archive cannot be opened or read
+    /// - When writing to `out` fails
+    /// - When the CRC of the data does not match and [`ExtractOptions::verify_crc`] is set
+    fn read_file_to_with(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        out: &mut dyn Write,
+        options: &ExtractOptions,
+        progress: &mut dyn FnMut(u64, u64),
+    ) -> Result<u64>;
+
+    /// Extract every file stored in the VPK into `output_dir`, mirroring the directory structure of the VPK.
+    ///
+    /// Files that fail to extract (e.g. because of a CRC mismatch) are reported in the returned summary
+    /// instead of aborting the extraction. Paths that would escape `output_dir` are rejected.
+    /// When the file system runs out of space the extraction stops, the partial file is removed and the remaining files
+    /// are listed in [`ExtractSummary::not_attempted`].
+    ///
+    /// If provided, `progress` is called after every file, see [`ExtractProgress`].
+    /// # Errors
+    /// - When the output directory cannot be created
+    fn extract_all(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        output_dir: &str,
+        progress: Option<ExtractProgress>,
+    ) -> Result<ExtractSummary> {
+        self.extract_files(
+            archive_path,
+            vpk_name,
+            &self.file_paths(),
+            output_dir,
+            progress,
+        )
+    }
+
+    /// Extract every file matching `filter` into `output_dir`, see [`PakReader::extract_all`].
+    /// # Errors
+    /// - When the output directory cannot be created
+    fn extract_filtered(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        output_dir: &str,
+        filter: &ExtensionFilter,
+        progress: Option<ExtractProgress>,
+    ) -> Result<ExtractSummary> {
+        self.extract_files(
+            archive_path,
+            vpk_name,
+            &filter.select_from(&self.extension_index()),
+            output_dir,
+            progress,
+        )
+    }
+
+    /// Extract the given files into `output_dir`, see [`PakReader::extract_all`].
+    /// Files not stored in the VPK are reported in the returned summary.
+    /// # Errors
+    /// - When the output directory cannot be created
+    fn extract_files(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_paths: &[&str],
+        output_dir: &str,
+        mut progress: Option<ExtractProgress>,
+    ) -> Result<ExtractSummary> {
+        std::fs::create_dir_all(output_dir).map_err(Error::Io)?;
+
+        let mut file_paths = file_paths.to_vec();
+        file_paths.sort_unstable();
+
+        let mut summary = ExtractSummary::default();
+        let total = file_paths.len();
+
+        for (i, &file_path) in file_paths.iter().enumerate() {
+            let result = join_sanitized(Path::new(output_dir), file_path)
+                .and_then(|out_path| out_path.to_str().map(str::to_string))
+                .ok_or(Error::BadData(format!(
+                    "File path escapes the output directory: {file_path}"
+                )))
+                .and_then(|out_path| {
+                    self.extract_file(archive_path, vpk_name, file_path, &out_path)?;
+                    std::fs::metadata(out_path)
+                        .map(|metadata| metadata.len())
+                        .map_err(Error::Io)
+                });
+
+            let proceed = summary.record(file_path, result);
+
+            if let Some(progress) = progress.as_mut() {
+                progress(file_path, i + 1, total);
+            }
+
+            if !proceed {
+                summary
+                    .not_attempted
+                    .extend(file_paths[i..].iter().map(ToString::to_string));
+                break;
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Extract every file stored in the VPK into `output_dir` on the threads of the [`rayon`] thread pool,
+    /// see [`PakReader::extract_files_parallel`].
+    #[cfg(feature = "parallel")]
+    fn extract_all_parallel(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        output_dir: &str,
+    ) -> Vec<(String, Result<()>)>
+    where
+        Self: Sized + Sync,
+    {
+        self.extract_files_parallel(archive_path, vpk_name, &self.file_paths(), output_dir)
+    }
+
+    /// Extract the given files into `output_dir` on the threads of the [`rayon`] thread pool,
+    /// returning the result of every file ordered by path.
+    ///
+    /// Every file is extracted with [`PakReader::extract_file`], which opens its own handle to the archive,
+    /// so archive files and decompression state are never shared between threads.
+    /// The directories of all files are created up front, before any thread starts extracting.
+    /// Once a file fails with [`Error::OutOfSpace`], files not started yet are not attempted and fail with [`Error::OutOfSpace`] as well.
+    #[cfg(feature = "parallel")]
+    fn extract_files_parallel(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_paths: &[&str],
+        output_dir: &str,
+    ) -> Vec<(String, Result<()>)>
+    where
+        Self: Sized + Sync,
+    {
+        use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let mut file_paths = file_paths.to_vec();
+        file_paths.sort_unstable();
+
+        // Failures are reported for the files themselves when they are extracted
+        let _ = std::fs::create_dir_all(output_dir);
+        let dirs: std::collections::BTreeSet<PathBuf> = file_paths
+            .iter()
+            .filter_map(|file_path| join_sanitized(Path::new(output_dir), file_path))
+            .filter_map(|out_path| out_path.parent().map(Path::to_path_buf))
+            .collect();
+        for dir in dirs {
+            let _ = std::fs::create_dir_all(dir);
+        }
+
+        let out_of_space = AtomicBool::new(false);
+
+        file_paths
+            .par_iter()
+            .map(|&file_path| {
+                let result = if out_of_space.load(Ordering::Relaxed) {
+                    Err(Error::OutOfSpace {
+                        file: file_path.to_string(),
+                        written: 0,
+                    })
+                } else {
+                    join_sanitized(Path::new(output_dir), file_path)
+                        .and_then(|out_path| out_path.to_str().map(str::to_string))
+                        .ok_or(Error::BadData(format!(
+                            "File path escapes the output directory: {file_path}"
+                        )))
+                        .and_then(|out_path| {
+                            self.extract_file(archive_path, vpk_name, file_path, &out_path)
+                        })
+                };
+
+                if matches!(result, Err(Error::OutOfSpace { .. })) {
+                    out_of_space.store(true, Ordering::Relaxed);
+                }
+
+                (file_path.to_string(), result)
+            })
+            .collect()
+    }
+
+    /// Checks whether the archives in `archive_path` belong to the loaded directory tree,
+    /// e.g. to detect old archives left next to an updated directory file.
+    ///
+    /// Reads up to `samples_per_archive` entries spread over each archive and verifies their checksums,
+    /// returning the outcome for every archive referenced by the tree, ordered by archive index.
+    fn check_archive_consistency(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        samples_per_archive: usize,
+    ) -> Vec<ArchiveConsistency>;
+
+    /// Checks the internal consistency of the loaded VPK without touching any archives,
+    /// e.g. before shipping a directory file produced by [`PakWriter::write_dir`].
+    ///
+    /// Reports the issues found by [`VPKTree::validate`] along with problems specific to the format,
+    /// such as bad entry terminators or header section sizes that do not match the parsed sections.
+    fn validate(&self) -> Vec<ValidationIssue>;
 
     /// Extract the contents of a file stored in the VPK to a file system location using memory-mapped files.
     /// Memory mapped files for every archive used in the extraction must be provided.
+    /// Data stored in the directory file itself is looked up under the format's directory index (see [`v1::VPK_DIR_ARCHIVE_INDEX`]).
+    ///
+    /// Like [`Self::extract_file`], the output file is only created once data is written,
+    /// and is removed again when the file system runs out of space or the data does not match its CRC.
+    /// # Errors
+    /// - When the file system runs out of space ([`Error::OutOfSpace`] with the number of bytes written)
+    /// - When a memory-mapped archive is missing ([`Error::MemoryMappedFileNotFound`])
+    /// - When the data does not match its CRC ([`Error::CrcMismatch`])
     #[cfg(feature = "mem-map")]
     fn extract_file_mem_map(
         &self,
@@ -411,13 +1736,69 @@ pub trait PakReader {
         vpk_name: &str,
         file_path: &str,
         output_path: &str,
+    ) -> Result<()> {
+        self.extract_file_mem_map_with(
+            archive_path,
+            archive_mmaps,
+            vpk_name,
+            file_path,
+            output_path,
+            &ExtractOptions::default(),
+        )
+    }
+
+    /// Extract the contents of a file stored in the VPK to a file system location using memory-mapped files and custom extract options,
+    /// see [`Self::extract_file_mem_map`] and [`Self::extract_file_with`].
+    /// # Errors
+    /// - See [`Self::extract_file_mem_map`]
+    #[cfg(feature = "mem-map")]
+    fn extract_file_mem_map_with(
+        &self,
+        archive_path: &str,
+        archive_mmaps: &HashMap<u16, FileBuffer>,
+        vpk_name: &str,
+        file_path: &str,
+        output_path: &str,
+        options: &ExtractOptions,
     ) -> Result<()>;
 }
 
+/// Opens a VPK version 1 directory file, see [`PakWorker::from_file`].
+/// # Errors
+/// - When the file cannot be opened
+/// - When the file is in an invalid format
+pub fn open_v1(path: &Path) -> Result<v1::VPKVersion1> {
+    open_worker(path)
+}
+
+/// Opens a VPK version 2 directory file, see [`PakWorker::from_file`].
+/// # Errors
+/// - When the file cannot be opened
+/// - When the file is in an invalid format
+pub fn open_v2(path: &Path) -> Result<v2::VPKVersion2> {
+    open_worker(path)
+}
+
+/// Opens a Respawn VPK directory file, see [`PakWorker::from_file`].
+/// CAM files are not read, see [`revpk::VPKRespawn::read_all_cams`].
+/// # Errors
+/// - When the file cannot be opened
+/// - When the file is in an invalid format
+#[cfg(feature = "revpk")]
+pub fn open_respawn(path: &Path) -> Result<revpk::VPKRespawn> {
+    open_worker(path)
+}
+
+/// Opens a directory file and parses it with the given format.
+fn open_worker<W: PakWorker>(path: &Path) -> Result<W> {
+    W::from_file(&mut File::open(path).map_err(Error::Io)?)
+}
+
 /// Trait for writing VPK files.
 pub trait PakWriter {
     /// Write the dir.vpk file for this VPK to disk with a given path.
     /// Does not modify or create archives if the any [`VPKDirectoryEntry`] has changed.
+    /// The tree size written in the header always matches the written tree.
     fn write_dir(&self, output_path: &str) -> Result<()>;
 }
 
@@ -428,9 +1809,87 @@ pub trait PakWorker: PakReader + PakWriter {
         Self: Sized;
 
     /// Create a readable VPK from a directory file.
+    ///
+    /// The file is read through a [`BufReader`](io::BufReader), so its position afterwards may be past the directory tree.
     /// # Errors
     /// - When the file is in an invalid format
     fn from_file(file: &mut File) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::from_file_with_options(file, &ParseOptions::default())
+    }
+
+    /// Create a readable VPK from a directory file using custom parse options, see [`Self::from_file`].
+    /// # Errors
+    /// - When the file is in an invalid format
+    fn from_file_with_options(file: &mut File, options: &ParseOptions) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::from_reader_with_options(&mut io::BufReader::new(file), options)
+    }
+
+    /// Create a readable VPK from any seekable source containing a directory file, such as an in-memory [`Cursor`](std::io::Cursor).
+    /// # Errors
+    /// - When the data is in an invalid format
+    fn from_reader<R: Read + Seek>(reader: &mut R) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::from_reader_with_options(reader, &ParseOptions::default())
+    }
+
+    /// Create a readable VPK from the contents of a directory file held in memory, e.g. a downloaded VPK.
+    /// # Errors
+    /// - When the data is in an invalid format
+    fn from_bytes(data: &[u8]) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::from_reader(&mut io::Cursor::new(data))
+    }
+
+    /// Create a readable VPK from any seekable source containing a directory file using custom parse options.
+    /// # Errors
+    /// - When the data is in an invalid format
+    /// - When the tree size in the header does not fit the data, see [`ParseOptions`]
+    fn from_reader_with_options<R: Read + Seek>(
+        reader: &mut R,
+        options: &ParseOptions,
+    ) -> Result<Self>
     where
         Self: Sized;
+
+    /// The format of the VPK, to branch on before recovering the concrete type with [`Self::as_any`].
+    #[cfg(feature = "detect")]
+    fn format(&self) -> crate::detect::PakFormat;
+
+    /// Returns the VPK as [`Any`], to recover its concrete type when only a `dyn PakWorker` is at hand,
+    /// such as the worker returned by [`find_pak_worker`](crate::detect::find_pak_worker).
+    /// See [`downcast_ref`](#method.downcast_ref) for a shorthand.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Returns the VPK as a mutable [`Any`], see [`Self::as_any`].
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl dyn PakWorker + '_ {
+    /// Returns the VPK as its concrete type `T`, or `None` if it is of another type.
+    /// ```
+    /// # use vpk_plumber::pak::{PakWorker, v1::VPKVersion1, v2::VPKVersion2};
+    /// let worker: Box<dyn PakWorker> = Box::new(VPKVersion1::new());
+    /// assert!(worker.downcast_ref::<VPKVersion1>().is_some());
+    /// assert!(worker.downcast_ref::<VPKVersion2>().is_none());
+    /// ```
+    #[must_use]
+    pub fn downcast_ref<T: PakWorker + 'static>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+
+    /// Returns the VPK as its mutable concrete type `T`, or `None` if it is of another type.
+    #[must_use]
+    pub fn downcast_mut<T: PakWorker + 'static>(&mut self) -> Option<&mut T> {
+        self.as_any_mut().downcast_mut()
+    }
 }
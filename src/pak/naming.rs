@@ -0,0 +1,94 @@
+//! Locating the archive files of a VPK, see [`ArchiveNaming`].
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Maps the archive indices of a VPK to the paths of its archive files,
+/// e.g. for pak layouts that don't follow Valve's `{vpk_name}_{archive_index:0>3}.vpk` naming.
+///
+/// Set it on a reader with `set_archive_naming`, for example [`VPKVersion1::set_archive_naming`](super::v1::VPKVersion1::set_archive_naming).
+/// Closures taking the archive index implement this trait as well, resolving only the archive files:
+/// ```
+/// # use std::path::PathBuf;
+/// # use std::sync::Arc;
+/// # use vpk_plumber::pak::{PakWorker, v1::VPKVersion1};
+/// let mut vpk = VPKVersion1::new();
+/// vpk.set_archive_naming(Some(Arc::new(|archive_index: u16| {
+///     PathBuf::from(format!("paks/pak01_{archive_index}.vpk"))
+/// })));
+/// ```
+pub trait ArchiveNaming: Send + Sync {
+    /// Returns the path of the archive file with the index `archive_index` of the VPK `vpk_name` in `archive_path`.
+    fn archive_path(&self, archive_path: &Path, vpk_name: &str, archive_index: u16) -> PathBuf;
+
+    /// Returns the path of the directory file of the VPK `vpk_name` in `archive_path`,
+    /// which is read for data stored in the directory file.
+    fn dir_path(&self, archive_path: &Path, vpk_name: &str) -> PathBuf {
+        ValveArchiveNaming.dir_path(archive_path, vpk_name)
+    }
+}
+
+/// Valve's archive naming, `{vpk_name}_dir.vpk` for the directory file and `{vpk_name}_{archive_index:0>3}.vpk` for the archives.
+///
+/// This is the naming used when no other is set.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValveArchiveNaming;
+
+impl ArchiveNaming for ValveArchiveNaming {
+    fn archive_path(&self, archive_path: &Path, vpk_name: &str, archive_index: u16) -> PathBuf {
+        archive_path.join(format!("{vpk_name}_{archive_index:0>3}.vpk"))
+    }
+
+    fn dir_path(&self, archive_path: &Path, vpk_name: &str) -> PathBuf {
+        archive_path.join(format!("{vpk_name}_dir.vpk"))
+    }
+}
+
+impl<F: Fn(u16) -> PathBuf + Send + Sync> ArchiveNaming for F {
+    fn archive_path(&self, _archive_path: &Path, _vpk_name: &str, archive_index: u16) -> PathBuf {
+        self(archive_index)
+    }
+}
+
+/// The archive naming set on a reader, if any.
+///
+/// Namings don't take part in comparisons, like observers.
+#[derive(Clone, Default)]
+pub(crate) struct Naming(Option<Arc<dyn ArchiveNaming>>);
+
+impl Naming {
+    pub(crate) fn new(naming: Option<Arc<dyn ArchiveNaming>>) -> Self {
+        Self(naming)
+    }
+
+    fn get(&self) -> &dyn ArchiveNaming {
+        match &self.0 {
+            Some(naming) => naming.as_ref(),
+            None => &ValveArchiveNaming,
+        }
+    }
+
+    /// Returns the path of the archive file with the index `archive_index`.
+    pub(crate) fn archive_path(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        archive_index: u16,
+    ) -> PathBuf {
+        self.get()
+            .archive_path(Path::new(archive_path), vpk_name, archive_index)
+    }
+
+    /// Returns the path of the directory file.
+    pub(crate) fn dir_path(&self, archive_path: &str, vpk_name: &str) -> PathBuf {
+        self.get().dir_path(Path::new(archive_path), vpk_name)
+    }
+}
+
+impl PartialEq for Naming {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Naming {}
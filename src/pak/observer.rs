@@ -0,0 +1,90 @@
+//! Observing the archive files opened and read by a VPK, see [`IoObserver`].
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Receives an event for every archive file a VPK opens and every read from it,
+/// e.g. to find out which archives an extraction touches and how often.
+///
+/// Set it on a reader with `set_observer`, for example [`VPKVersion1::set_observer`](super::v1::VPKVersion1::set_observer).
+/// The directory file is reported under the format's directory index when data stored in it is read,
+/// see [`v1::VPK_DIR_ARCHIVE_INDEX`](super::v1::VPK_DIR_ARCHIVE_INDEX).
+pub trait IoObserver: Send + Sync {
+    /// Called after the archive file with the index `index` was opened from `path`.
+    fn archive_opened(&self, index: u16, path: &Path) {
+        let _ = (index, path);
+    }
+
+    /// Called after `n` bytes were read from the archive with the index `index`.
+    /// Reads from memory-mapped archives are reported as well.
+    fn bytes_read(&self, index: u16, n: u64) {
+        let _ = (index, n);
+    }
+}
+
+/// The observer set on a reader, if any.
+///
+/// Observers don't take part in comparisons, two readers with different observers are equal if their data is.
+#[derive(Clone, Default)]
+pub(crate) struct Observer(Option<Arc<dyn IoObserver>>);
+
+impl Observer {
+    pub(crate) fn new(observer: Option<Arc<dyn IoObserver>>) -> Self {
+        Self(observer)
+    }
+
+    /// Opens the archive file at `path`, reporting it and every read from it to the observer.
+    pub(crate) fn open(&self, index: u16, path: &Path) -> io::Result<ArchiveFile> {
+        let file = File::open(path)?;
+
+        if let Some(observer) = &self.0 {
+            observer.archive_opened(index, path);
+        }
+
+        Ok(ArchiveFile {
+            file,
+            index,
+            observer: self.clone(),
+        })
+    }
+
+    /// Reports `n` bytes read from the archive `index`.
+    pub(crate) fn bytes_read(&self, index: u16, n: u64) {
+        if let Some(observer) = &self.0
+            && n > 0
+        {
+            observer.bytes_read(index, n);
+        }
+    }
+}
+
+impl PartialEq for Observer {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Observer {}
+
+/// An archive file opened with [`Observer::open`], reporting every read to the observer.
+pub(crate) struct ArchiveFile {
+    file: File,
+    index: u16,
+    observer: Observer,
+}
+
+impl Read for ArchiveFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.file.read(buf)?;
+        self.observer.bytes_read(self.index, read as u64);
+        Ok(read)
+    }
+}
+
+impl Seek for ArchiveFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
@@ -0,0 +1,107 @@
+//! Options to customize how VPK files are parsed, extracted and written.
+
+/// Options used when parsing a directory file, see [`PakWorker::from_reader_with_options`](super::PakWorker::from_reader_with_options).
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Skip validating the tree size in the header against the length of the file.
+    /// A tree extending past the end of the file is clamped instead, useful to analyze deliberately truncated files.
+    pub allow_truncated_tree: bool,
+    /// Record the byte range of every directory entry within the directory file, see [`VPKTree::entry_span`](super::VPKTree::entry_span).
+    pub record_entry_spans: bool,
+    /// How the file data section of VPK version 2 directory files is loaded. Other formats ignore this.
+    pub file_data: FileDataMode,
+    /// Accept directory entries whose terminator is not [`VPK_ENTRY_TERMINATOR`](super::VPK_ENTRY_TERMINATOR),
+    /// as written by some old tools. The terminator is kept, so [`PakReader::validate`](super::PakReader::validate)
+    /// still reports it and the entry cannot be written until it is fixed. Respawn VPKs have no entry terminator and ignore this.
+    pub allow_bad_terminators: bool,
+    /// What happens when a path appears more than once in a tree.
+    pub duplicates: DuplicatePolicy,
+    /// Record recoverable problems found while parsing the tree, see [`VPKTree::parse_warnings`](super::VPKTree::parse_warnings).
+    pub collect_warnings: bool,
+}
+
+/// What happens when a path appears more than once in a directory tree, see [`ParseOptions::duplicates`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// Keep the entry that appears last.
+    #[default]
+    LastWins,
+    /// Keep the entry that appears first.
+    FirstWins,
+    /// Fail to parse the tree.
+    Reject,
+}
+
+/// How the file data section following the tree of a VPK version 2 directory file is loaded, see [`ParseOptions::file_data`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileDataMode {
+    /// Read the whole section into memory while parsing.
+    #[default]
+    Eager,
+    /// Only record where the section starts and read the data of a file from the directory file when it is read,
+    /// for directory files with large sections such as Counter-Strike: Global Offensive's `pak01_dir.vpk`.
+    Lazy,
+}
+
+/// Options used when writing a directory file, e.g. with `VPKRespawn::write_dir_with`.
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// Fail instead of dropping data the reference tools reject, such as empty file parts of Respawn VPKs.
+    pub strict: bool,
+}
+
+/// The default number of bytes read at once when extracting a file.
+pub const DEFAULT_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// How file paths are matched against the paths in a [`VPKTree`](super::VPKTree), see [`VPKTree::find`](super::VPKTree::find).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaseSensitivity {
+    /// Paths must match exactly, apart from their separators.
+    #[default]
+    Sensitive,
+    /// Paths match regardless of case, like in the Source engine.
+    Insensitive,
+}
+
+/// Options used when extracting a file, see [`PakReader::extract_file_with`](super::PakReader::extract_file_with).
+#[derive(Debug, Clone)]
+pub struct ExtractOptions {
+    /// The maximum number of bytes read from an archive at once. Values below 1 are treated as 1.
+    /// Compressed file parts of Respawn VPKs are always read and decompressed whole, they are at most 1 MiB when uncompressed.
+    pub chunk_size: usize,
+    /// Whether to verify the CRC of the extracted data.
+    pub verify_crc: bool,
+    /// How the path of the file is matched against the paths in the tree.
+    pub case_sensitivity: CaseSensitivity,
+    /// Keep the partial output of a file when the file system runs out of space or its CRC does not match, renamed with a `.partial` suffix.
+    /// By default it is removed.
+    pub keep_partial: bool,
+    /// Whether WAV files of Respawn VPKs are extracted with a header generated from their CAM entry and truncated to their original size,
+    /// which is what audio players expect. When disabled, the data is extracted as stored in the archives, decompressed but otherwise untouched,
+    /// and its CRC is verified like that of any other file. Other formats ignore this.
+    pub wav_transcode: bool,
+    /// Whether the RIFF and data chunk sizes in the header of a transcoded WAV file are corrected when its data ends before
+    /// the size declared by its CAM entry, so strict WAV parsers accept the output.
+    /// The header is only corrected when extracting to a file or reading into memory, streamed output keeps the declared sizes.
+    pub fix_wav_sizes: bool,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            verify_crc: true,
+            case_sensitivity: CaseSensitivity::Sensitive,
+            keep_partial: false,
+            wav_transcode: true,
+            fix_wav_sizes: true,
+        }
+    }
+}
+
+impl ExtractOptions {
+    /// The chunk size, clamped to at least 1.
+    pub(crate) fn chunk_size(&self) -> usize {
+        self.chunk_size.max(1)
+    }
+}
@@ -0,0 +1,475 @@
+//! Streaming and incremental parsing of VPK directory trees.
+
+use super::{DirEntry, Error, ParseOptions, Result, VPKTree};
+use crate::util::{
+    self,
+    file::{MAX_STRING_LENGTH, VPKFileReader},
+};
+use std::fmt;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::ops::Range;
+
+/// How directory trees store an empty extension, directory or file name,
+/// an empty string would end the list it is part of.
+pub(crate) const EMPTY_TREE_COMPONENT: &str = " ";
+
+/// Joins the components of a file read from a directory tree into its path,
+/// leaving out the directory and extension when they are empty.
+fn join_tree_path(path: &str, file_name: &str, extension: &str) -> String {
+    let component = |s: &str| if s == EMPTY_TREE_COMPONENT { "" } else { s }.to_owned();
+    let mut file_path = component(file_name);
+
+    let path = component(path);
+    if !path.is_empty() {
+        file_path = format!("{path}/{file_path}");
+    }
+
+    let extension = component(extension);
+    if !extension.is_empty() {
+        file_path = format!("{file_path}.{extension}");
+    }
+
+    file_path
+}
+
+/// The kind of recoverable problem found while parsing a tree, see [`ParseWarning`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ParseWarningKind {
+    /// An entry's terminator is not [`VPK_ENTRY_TERMINATOR`](super::VPK_ENTRY_TERMINATOR), accepted with [`ParseOptions::allow_bad_terminators`].
+    BadTerminator,
+    /// A path appears more than once, see [`ParseOptions::duplicates`].
+    DuplicatePath,
+    /// The tree ends before the tree size in the header.
+    TrailingData,
+}
+
+/// A recoverable problem found while parsing a tree with [`ParseOptions::collect_warnings`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseWarning {
+    /// What kind of problem was found.
+    pub kind: ParseWarningKind,
+    /// The offset of the problem within the parsed data.
+    pub offset: u64,
+    /// The path of the offending file, or `None` for problems with the tree itself.
+    pub path: Option<String>,
+    /// A human-readable description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#X}: ", self.offset)?;
+        if let Some(path) = &self.path {
+            write!(f, "{path}: ")?;
+        }
+
+        write!(f, "{}", self.message)
+    }
+}
+
+/// A single file parsed from a directory tree.
+#[derive(Debug)]
+pub struct ParsedEntry<DirectoryEntry>
+where
+    DirectoryEntry: DirEntry,
+{
+    /// The full path of the file, e.g. `materials/models/test.vmt`.
+    pub file_path: String,
+    /// The directory entry of the file.
+    pub entry: DirectoryEntry,
+    /// The preload data of the file, if it has any.
+    pub preload: Option<Vec<u8>>,
+    /// The byte range the directory entry occupies in the parsed data, excluding the path and the preload data.
+    pub span: Range<u64>,
+}
+
+/// An iterator reading the entries of a directory tree one at a time.
+///
+/// The iterator stops after the first error.
+pub struct TreeEntries<R, DirectoryEntry>
+where
+    R: Read + Seek,
+    DirectoryEntry: DirEntry,
+{
+    reader: R,
+    end: u64,
+    extension: Option<String>,
+    path: Option<String>,
+    read_preload: bool,
+    options: ParseOptions,
+    warnings: Vec<ParseWarning>,
+    done: bool,
+    _entry: std::marker::PhantomData<DirectoryEntry>,
+}
+
+impl<R, DirectoryEntry> TreeEntries<R, DirectoryEntry>
+where
+    R: Read + Seek,
+    DirectoryEntry: DirEntry,
+{
+    /// Creates an iterator over the tree of `size` bytes starting at `start`.
+    /// # Errors
+    /// - When the reader cannot seek to `start`
+    pub fn new(mut reader: R, start: u64, size: u64) -> Result<Self> {
+        reader
+            .seek(SeekFrom::Start(start))
+            .map_err(Error::TreeNotFound)?;
+
+        Ok(Self {
+            reader,
+            end: start + size,
+            extension: None,
+            path: None,
+            read_preload: true,
+            options: ParseOptions::default(),
+            warnings: Vec::new(),
+            done: false,
+            _entry: std::marker::PhantomData,
+        })
+    }
+
+    /// Creates an iterator over the tree of `size` bytes starting at `start`, parsing entries with `options`.
+    /// Warnings are only recorded with [`ParseOptions::collect_warnings`], see [`Self::take_warnings`].
+    pub(crate) fn with_options(
+        reader: R,
+        start: u64,
+        size: u64,
+        options: &ParseOptions,
+    ) -> Result<Self> {
+        let mut entries = Self::new(reader, start, size)?;
+        entries.options = options.clone();
+
+        Ok(entries)
+    }
+
+    /// Takes the warnings recorded so far.
+    pub(crate) fn take_warnings(&mut self) -> Vec<ParseWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Records a warning if warnings are collected.
+    fn warn(&mut self, kind: ParseWarningKind, offset: u64, path: Option<&str>, message: String) {
+        if self.options.collect_warnings {
+            self.warnings.push(ParseWarning {
+                kind,
+                offset,
+                path: path.map(str::to_string),
+                message,
+            });
+        }
+    }
+
+    fn position(&mut self) -> Result<u64> {
+        self.reader.stream_position().map_err(Error::Io)
+    }
+
+    /// Reads a null-terminated string, which must end within the bounds of the tree
+    /// and be at most [`MAX_STRING_LENGTH`] bytes long.
+    fn read_string(&mut self, name: &str) -> Result<String> {
+        let mut remaining = self.end.saturating_sub(self.position()?);
+        let mut bytes = Vec::new();
+
+        loop {
+            if remaining == 0 {
+                return Err(Error::BadData(format!(
+                    "Unterminated {name} at the end of the tree"
+                )));
+            }
+
+            let mut b: [u8; 1] = [0];
+            if self.reader.read(&mut b).map_err(Error::Io)? == 0 {
+                return Err(Error::BadData(format!(
+                    "Unexpected end of data while reading {name}"
+                )));
+            }
+            remaining -= 1;
+
+            if b[0] == 0 {
+                break;
+            }
+
+            // A lying tree size must not make a single string take up the rest of the file
+            if bytes.len() == MAX_STRING_LENGTH {
+                return Err(Error::Util {
+                    source: util::Error::StringTooLong {
+                        max_len: MAX_STRING_LENGTH,
+                    },
+                    context: format!("Failed to read {name}"),
+                });
+            }
+
+            bytes.push(b[0]);
+        }
+
+        String::from_utf8(bytes).map_err(|e| Error::Util {
+            source: util::Error::Utf8(e),
+            context: format!("Failed to read {name}"),
+        })
+    }
+
+    /// Errors if the reader has moved past the end of the tree.
+    fn check_bounds(&mut self, file_path: &str) -> Result<()> {
+        if self.position()? > self.end {
+            return Err(Error::BadData(format!(
+                "Entry for {file_path} extends past the end of the tree"
+            )));
+        }
+
+        Ok(())
+    }
+
+    // Every iteration consumes at least one byte of the tree or returns,
+    // so malformed data can never make the parser loop forever.
+    fn read_next(&mut self) -> Result<Option<ParsedEntry<DirectoryEntry>>> {
+        loop {
+            let Some(extension) = self.extension.clone() else {
+                if self.position()? >= self.end {
+                    return Ok(None);
+                }
+
+                let extension = self.read_string("extension")?;
+                if extension.is_empty() {
+                    let position = self.position()?;
+                    if position < self.end {
+                        self.warn(
+                            ParseWarningKind::TrailingData,
+                            position,
+                            None,
+                            format!("{} bytes follow the end of the tree", self.end - position),
+                        );
+                    }
+
+                    return Ok(None);
+                }
+
+                self.extension = Some(extension);
+                continue;
+            };
+
+            let Some(path) = self.path.clone() else {
+                let path = self.read_string("path")?;
+                if path.is_empty() {
+                    self.extension = None;
+                } else {
+                    self.path = Some(path);
+                }
+                continue;
+            };
+
+            let file_name = self.read_string("file name")?;
+            if file_name.is_empty() {
+                self.path = None;
+                continue;
+            }
+
+            let file_path = join_tree_path(&path, &file_name, &extension);
+            self.check_bounds(&file_path)?;
+
+            let entry_start = self.position()?;
+            let entry = DirectoryEntry::from_with_options(&mut self.reader, &self.options)
+                .map_err(|e| match e {
+                    Error::Util {
+                        source: util::Error::Io(ref io),
+                        ..
+                    } if io.kind() == ErrorKind::UnexpectedEof => Error::BadData(format!(
+                        "Unexpected end of data while reading the entry for {file_path}"
+                    )),
+                    e => e,
+                })?;
+            self.check_bounds(&file_path)?;
+            let span = entry_start..self.position()?;
+
+            if !entry.has_valid_terminator() {
+                self.warn(
+                    ParseWarningKind::BadTerminator,
+                    span.end.saturating_sub(2),
+                    Some(&file_path),
+                    "entry terminator is not 0xFFFF".to_string(),
+                );
+            }
+
+            let preload = if entry.get_preload_length() > 0 && !self.read_preload {
+                let preload_length =
+                    i64::try_from(entry.get_preload_length()).map_err(|_| Error::DataTooLarge)?;
+                self.reader
+                    .seek(SeekFrom::Current(preload_length))
+                    .map_err(Error::Io)?;
+                self.check_bounds(&file_path)?;
+                None
+            } else if entry.get_preload_length() > 0 {
+                let preload = self
+                    .reader
+                    .read_bytes(entry.get_preload_length())
+                    .map_err(|e| match e {
+                        util::Error::UnexpectedEof { .. } => Error::BadData(format!(
+                            "Unexpected end of data while reading the preload data for {file_path}"
+                        )),
+                        e => Error::Util {
+                            source: e,
+                            context: "Failed to read preload data".to_string(),
+                        },
+                    })?;
+
+                self.check_bounds(&file_path)?;
+                Some(preload)
+            } else {
+                None
+            };
+
+            return Ok(Some(ParsedEntry {
+                file_path,
+                entry,
+                preload,
+                span,
+            }));
+        }
+    }
+}
+
+impl<R, DirectoryEntry> Iterator for TreeEntries<R, DirectoryEntry>
+where
+    R: Read + Seek,
+    DirectoryEntry: DirEntry,
+{
+    type Item = Result<ParsedEntry<DirectoryEntry>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.read_next().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+
+        result
+    }
+}
+
+/// An iterator lazily reading the files of a directory tree, yielding their paths and directory entries.
+///
+/// Unlike [`VPKTree::from`], nothing is accumulated and preload data is skipped rather than read,
+/// so a large tree can be scanned for a single file and left early. Use [`TreeEntries`] to read the preload data as well.
+///
+/// The iterator stops after the first error.
+pub struct VPKTreeIter<R, DirectoryEntry>
+where
+    R: Read + Seek,
+    DirectoryEntry: DirEntry,
+{
+    entries: TreeEntries<R, DirectoryEntry>,
+}
+
+impl<R, DirectoryEntry> VPKTreeIter<R, DirectoryEntry>
+where
+    R: Read + Seek,
+    DirectoryEntry: DirEntry,
+{
+    /// Creates an iterator over the tree of `size` bytes starting at `start`.
+    /// # Errors
+    /// - When the reader cannot seek to `start`
+    pub fn new(reader: R, start: u64, size: u64) -> Result<Self> {
+        let mut entries = TreeEntries::new(reader, start, size)?;
+        entries.read_preload = false;
+
+        Ok(Self { entries })
+    }
+}
+
+impl<R, DirectoryEntry> Iterator for VPKTreeIter<R, DirectoryEntry>
+where
+    R: Read + Seek,
+    DirectoryEntry: DirEntry,
+{
+    type Item = Result<(String, DirectoryEntry)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries
+            .next()
+            .map(|parsed| parsed.map(|parsed| (parsed.file_path, parsed.entry)))
+    }
+}
+
+/// The result of a single [`IncrementalTreeParser::step`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepResult {
+    /// The given number of entries were parsed and more entries remain.
+    Parsed(usize),
+    /// The whole tree has been parsed.
+    Done,
+}
+
+/// A directory tree parser that can be advanced a limited number of entries at a time.
+///
+/// Useful to parse large trees without blocking, e.g. by parsing a slice of entries every frame.
+pub struct IncrementalTreeParser<R, DirectoryEntry>
+where
+    R: Read + Seek,
+    DirectoryEntry: DirEntry,
+{
+    entries: TreeEntries<R, DirectoryEntry>,
+    tree: VPKTree<DirectoryEntry>,
+    done: bool,
+}
+
+impl<R, DirectoryEntry> IncrementalTreeParser<R, DirectoryEntry>
+where
+    R: Read + Seek,
+    DirectoryEntry: DirEntry,
+{
+    /// Creates a parser for the tree of `size` bytes starting at `start`.
+    /// # Errors
+    /// - When the reader cannot seek to `start`
+    pub fn new(reader: R, start: u64, size: u64) -> Result<Self> {
+        Ok(Self {
+            entries: TreeEntries::new(reader, start, size)?,
+            tree: VPKTree::new(),
+            done: false,
+        })
+    }
+
+    /// Parses at most `budget_entries` entries into the tree.
+    /// Returns [`StepResult::Done`] once the end of the tree has been reached.
+    ///
+    /// Parsing stops at the first error, after which further steps return [`StepResult::Done`] and the tree stays incomplete.
+    /// # Errors
+    /// - When the data is invalid
+    /// - When IO operations fail
+    pub fn step(&mut self, budget_entries: usize) -> Result<StepResult> {
+        if self.done {
+            return Ok(StepResult::Done);
+        }
+
+        let mut parsed = 0;
+        while parsed < budget_entries {
+            let Some(entry) = self.entries.next() else {
+                self.done = true;
+                return Ok(StepResult::Done);
+            };
+
+            self.tree.insert_parsed(entry?);
+            parsed += 1;
+        }
+
+        Ok(StepResult::Parsed(parsed))
+    }
+
+    /// Whether the whole tree has been parsed.
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// The tree parsed so far.
+    #[must_use]
+    pub fn tree(&self) -> &VPKTree<DirectoryEntry> {
+        &self.tree
+    }
+
+    /// Consumes the parser, returning the tree parsed so far.
+    #[must_use]
+    pub fn into_tree(self) -> VPKTree<DirectoryEntry> {
+        self.tree
+    }
+}
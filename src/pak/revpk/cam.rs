@@ -1,24 +1,45 @@
 #[cfg(feature = "mem-map")]
 use filebuffer::FileBuffer;
-use std::{
-    fs::File,
-    io::{Read, Seek, SeekFrom},
-};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
 
-use super::format::VPKRespawnCamEntry;
+use super::format::{VPKRespawnCam, VPKRespawnCamEntry};
+use crate::pak;
 
 const SAMPLE_DEPTH: u16 = 16;
 
-#[must_use]
-pub fn create_wav_header(cam_entry: &VPKRespawnCamEntry) -> Vec<u8> {
+/// Creates the 44 byte header of a 16-bit PCM WAV file described by a CAM entry.
+/// # Errors
+/// - When the sizes described by the CAM entry don't fit in a WAV header
+pub fn create_wav_header(cam_entry: &VPKRespawnCamEntry) -> pak::Result<Vec<u8>> {
+    let too_large = || {
+        pak::Error::BadData(format!(
+            "CAM entry at offset {} describes a WAV file too large for its header",
+            cam_entry.vpk_content_offset
+        ))
+    };
+
     let mut header = [0u8; 44];
 
     // "RIFF" magic
     header[0..4].copy_from_slice(&0x5249_4646_u32.to_be_bytes());
 
     // File size
-    let file_len: u32 = 2 * cam_entry.sample_count * u32::from(cam_entry.channels);
-    header[4..8].copy_from_slice(&(file_len - 8 + 44).to_le_bytes());
+    let file_len: u32 = cam_entry
+        .sample_count
+        .checked_mul(2 * u32::from(cam_entry.channels))
+        .ok_or_else(too_large)?;
+    header[4..8].copy_from_slice(
+        &file_len
+            .checked_add(36)
+            .ok_or_else(too_large)?
+            .to_le_bytes(),
+    );
 
     // "RIFF" magic
     header[8..12].copy_from_slice(&0x5741_5645_u32.to_be_bytes());
@@ -39,8 +60,11 @@ pub fn create_wav_header(cam_entry: &VPKRespawnCamEntry) -> Vec<u8> {
     header[24..28].copy_from_slice(&cam_entry.sample_rate.to_le_bytes());
 
     // Sample rate * sample depth * channels / 8
-    let bytes_per_sec =
-        cam_entry.sample_rate * u32::from(SAMPLE_DEPTH) * u32::from(cam_entry.channels) / 8;
+    let bytes_per_sec = cam_entry
+        .sample_rate
+        .checked_mul(u32::from(SAMPLE_DEPTH) * u32::from(cam_entry.channels))
+        .ok_or_else(too_large)?
+        / 8;
     header[28..32].copy_from_slice(&bytes_per_sec.to_le_bytes());
 
     // Sample depth * channels / 8
@@ -56,14 +80,42 @@ pub fn create_wav_header(cam_entry: &VPKRespawnCamEntry) -> Vec<u8> {
     // File length
     header[40..44].copy_from_slice(&file_len.to_le_bytes());
 
-    header.to_vec()
+    Ok(header.to_vec())
+}
+
+/// Corrects the RIFF chunk size and the data chunk size in the 44 byte `header` of a WAV file to match its actual length of `wav_len` bytes,
+/// e.g. after its data ended before the size declared by its CAM entry. Returns whether the header changed.
+///
+/// Files shorter than their header are left untouched.
+/// # Errors
+/// - When `wav_len` is too large for a WAV header
+pub(crate) fn fix_wav_sizes(header: &mut [u8; 44], wav_len: u64) -> pak::Result<bool> {
+    let Some(data_len) = wav_len.checked_sub(44) else {
+        return Ok(false);
+    };
+
+    let riff_len = u32::try_from(wav_len - 8).map_err(|_| pak::Error::DataTooLarge)?;
+    let data_len = u32::try_from(data_len).map_err(|_| pak::Error::DataTooLarge)?;
+
+    let mut fixed = *header;
+    fixed[4..8].copy_from_slice(&riff_len.to_le_bytes());
+    fixed[40..44].copy_from_slice(&data_len.to_le_bytes());
+
+    let changed = fixed != *header;
+    *header = fixed;
+    Ok(changed)
 }
 
-pub fn seek_to_wav_data(file: &mut File) -> Result<u64, std::io::Error> {
+/// Skips the 44 byte header and the padding after it at the start of a WAV file stored in an archive,
+/// returning the number of bytes skipped.
+/// # Errors
+/// - When the end of the file is reached before the WAV data
+/// - When an IO operation fails
+pub fn seek_to_wav_data<R: Read + Seek>(file: &mut R) -> Result<u64, std::io::Error> {
     let pos = file.seek(SeekFrom::Current(44))?;
     loop {
         let mut b: [u8; 1] = [0];
-        let _ = file.read(&mut b);
+        file.read_exact(&mut b)?;
 
         if b[0] != 0xCB {
             let res = file.seek(SeekFrom::Current(-1))?;
@@ -74,9 +126,13 @@ pub fn seek_to_wav_data(file: &mut File) -> Result<u64, std::io::Error> {
 
 #[cfg(feature = "mem-map")]
 pub fn seek_to_wav_data_mem_map(file: &FileBuffer, start_pos: u64) -> Result<u64, String> {
-    let mut pos = start_pos + 44;
+    let mut pos = start_pos
+        .checked_add(44)
+        .ok_or_else(|| "WAV header starts past 16 EiB".to_string())?;
     loop {
-        let b = file[pos as usize];
+        let b = *file
+            .get(usize::try_from(pos).map_err(|e| e.to_string())?)
+            .ok_or_else(|| "Reached the end of the archive before the WAV data".to_string())?;
         if b != 0xCB {
             return Ok(pos - start_pos);
         }
@@ -84,3 +140,86 @@ pub fn seek_to_wav_data_mem_map(file: &FileBuffer, start_pos: u64) -> Result<u64
         pos += 1;
     }
 }
+
+/// Parsed CAM files shared between several [`VPKRespawn`](super::VPKRespawn)s.
+///
+/// Many Respawn directory files use the CAM of the same archive, e.g. every Titanfall map uses
+/// `client_mp_common.bsp.pak000_000.vpk.cam`. Share one cache between them with
+/// [`VPKRespawn::set_cam_cache`](super::VPKRespawn::set_cam_cache) to parse such a CAM only once.
+///
+/// CAMs are keyed by their canonical path and modification time, a CAM modified on disk is parsed again.
+#[derive(Debug, Default)]
+pub struct CamCache {
+    cams: Mutex<HashMap<PathBuf, Arc<Mutex<Option<CachedCam>>>>>,
+    parse_count: AtomicUsize,
+}
+
+#[derive(Debug)]
+struct CachedCam {
+    modified: Option<SystemTime>,
+    cam: Arc<VPKRespawnCam>,
+}
+
+impl CamCache {
+    /// Creates an empty cache.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the parsed CAM at `cam_path`, parsing it if it is not cached or was modified since it was parsed.
+    /// # Errors
+    /// - When the CAM file cannot be opened
+    /// - When the CAM file cannot be parsed
+    pub fn get(&self, cam_path: &Path) -> pak::Result<Arc<VPKRespawnCam>> {
+        let canonical_path = cam_path.canonicalize().map_err(pak::Error::Io)?;
+        let mut cam_file = File::open(&canonical_path).map_err(pak::Error::Io)?;
+        let modified = cam_file
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok();
+
+        // Only the slot of this CAM stays locked while parsing, so a CAM requested by several threads at once is parsed once
+        // while other CAMs can be parsed at the same time
+        let slot = Arc::clone(
+            self.cams
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .entry(canonical_path)
+                .or_default(),
+        );
+        let mut slot = slot.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(cached) = slot.as_ref()
+            && modified.is_some()
+            && cached.modified == modified
+        {
+            return Ok(Arc::clone(&cached.cam));
+        }
+
+        let cam = Arc::new(VPKRespawnCam::from_file(&mut cam_file)?);
+        self.parse_count.fetch_add(1, Ordering::Relaxed);
+        *slot = Some(CachedCam {
+            modified,
+            cam: Arc::clone(&cam),
+        });
+
+        Ok(cam)
+    }
+
+    /// Removes the CAM at `cam_path` from the cache, so it is parsed again when requested next.
+    /// Does nothing if the CAM is not cached or the path cannot be resolved.
+    pub fn invalidate(&self, cam_path: &Path) {
+        if let Ok(canonical_path) = cam_path.canonicalize() {
+            self.cams
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .remove(&canonical_path);
+        }
+    }
+
+    /// The number of CAM files parsed by this cache, including CAMs parsed again after they changed.
+    #[must_use]
+    pub fn parse_count(&self) -> usize {
+        self.parse_count.load(Ordering::Relaxed)
+    }
+}
@@ -1,32 +1,61 @@
 //! Support for the Respawn VPK format.
 
 use crate::pak::{
-    DirEntry, Error, PakReader, PakWorker, PakWriter, Result, VPK_ENTRY_TERMINATOR, VPKTree,
+    ArchiveConsistency, ArchiveFile, ArchiveNaming, CrcValidatingReader, DirEntry, Error,
+    ExtensionFilter, ExtractOptions, ExtractProgress, ExtractSummary, FileLocation,
+    FilePartLocation, FormatCaps, IoObserver, Naming, Observer, PakReader, PakWorker, PakWriter,
+    ParseOptions, Result, VPK_ENTRY_TERMINATOR, VPKTree, ValidationIssue, ValidationIssueKind,
+    WriteOptions, checked_tree_size, extract_to_dir, is_dir_archive_index, output_path,
+    read_archive_chunk, sample_evenly, tree_map_remove, write_output_file,
+};
+use crate::util::lzham::{
+    LZHAM_MAX_DICT_SIZE_LOG2, LZHAM_MIN_DICT_SIZE_LOG2, LzhamParams, compress, decompress,
+};
+use crate::util::path::{normalize_vpk_path, split_dir_file_path};
+use crate::util::{
+    self,
+    file::{VPKFileReader, VPKFileWriter},
 };
-use crate::util::file::{VPKFileReader, VPKFileWriter};
-use crate::util::lzham::decompress;
 use crc::{CRC_32_ISO_HDLC, Crc};
-use std::collections::{HashMap, HashSet};
+use std::any::Any;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
-use std::io::{Seek, SeekFrom, Write};
-use std::path::Path;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[cfg(feature = "mem-map")]
 use filebuffer::FileBuffer;
 
 #[cfg(feature = "mem-map")]
 use super::cam::seek_to_wav_data_mem_map;
-use super::cam::{create_wav_header, seek_to_wav_data};
+use super::cam::{CamCache, create_wav_header, fix_wav_sizes, seek_to_wav_data};
 
 /// The 4-byte signature found in the header of a valid Respawn VPK file.
 pub const VPK_SIGNATURE_REVPK: u32 = 0x55AA_1234;
 /// The 4-byte version found in the header of a valid Respawn VPK file.
-pub const VPK_VERSION_REVPK: u32 = 196_610;
+///
+/// A major version of 2 in the low 16 bits and a minor version of 3 in the high 16 bits,
+/// which distinguishes Respawn VPKs from VPK version 2 files with the same signature.
+pub const VPK_VERSION_REVPK: u32 = 0x0003_0002;
 /// The 4-byte magic found at the start of a CAM file entry.
 pub const RESPAWN_CAM_ENTRY_MAGIC: u32 = 3_302_889_984;
+/// The maximum uncompressed size of a single file part written by [`VPKRespawn::write_archive`].
+pub const RESPAWN_MAX_PART_SIZE: usize = 1024 * 1024;
+
+/// The operations supported for Respawn VPK files. Archives are written by [`VPKRespawn::write_archive`].
+pub const VPK_CAPS_REVPK: FormatCaps = FormatCaps {
+    read: true,
+    write_dir: true,
+    write_archives: true,
+    compression: true,
+    signatures: false,
+};
 
 /// The header of a Respawn VPK file.
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VPKHeaderRespawn {
     /// VPK signature. Should be equal to [`VPK_SIGNATURE_REVPK`].
     pub signature: u32,
@@ -43,7 +72,7 @@ pub struct VPKHeaderRespawn {
 
 impl VPKHeaderRespawn {
     /// Read the header from a file.
-    pub fn from(file: &mut File) -> Result<Self> {
+    pub fn from<R: Read>(file: &mut R) -> Result<Self> {
         let signature = file.read_u32().map_err(|e| Error::Util {
             source: e,
             context: "Failed to read signature".to_string(),
@@ -93,7 +122,7 @@ impl VPKHeaderRespawn {
     }
 
     /// Write the header to a file.
-    pub fn write(&self, file: &mut File) -> Result<()> {
+    pub fn write<W: Write>(&self, file: &mut W) -> Result<()> {
         if self.signature != VPK_SIGNATURE_REVPK {
             return Err(Error::InvalidSignature(format!(
                 "Header signature should be {VPK_SIGNATURE_REVPK:#X} but is {:#X}",
@@ -132,7 +161,7 @@ impl VPKHeaderRespawn {
     }
 
     /// Check if a file is in the Respawn VPK format.
-    pub fn is_format(file: &mut File) -> bool {
+    pub fn is_format<R: Read + Seek>(file: &mut R) -> bool {
         let Ok(pos) = file.stream_position() else {
             return false;
         };
@@ -164,8 +193,9 @@ pub enum EPackedTextureFlags {
     TextureEnvironmentMap = 1 << 10,
 }
 
-/// The entry format used by Respawn VPKs. For the format used by VPK version 1 and version 2 see [`VPKDirectoryEntry`](crate::common::format::VPKDirectoryEntry).
-#[derive(Debug, PartialEq, Eq)]
+/// The entry format used by Respawn VPKs. For the format used by VPK version 1 and version 2 see [`VPKDirectoryEntry`](crate::pak::VPKDirectoryEntry).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VPKDirectoryEntryRespawn {
     /// A 32bit CRC of the file's data. Uses the CRC32 ISO HDLC algorithm.
     pub crc: u32,
@@ -190,10 +220,29 @@ impl VPKDirectoryEntryRespawn {
             file_parts: Vec::new(),
         }
     }
+
+    /// Returns the file parts written to a directory file by [`DirEntry::write`].
+    ///
+    /// Empty parts are skipped, as the reference tools reject directory files containing them.
+    /// Only an entry for an empty file, whose parts are all empty, keeps its first part.
+    #[must_use]
+    pub fn written_parts(&self) -> Vec<&VPKFilePartEntryRespawn> {
+        let parts: Vec<&VPKFilePartEntryRespawn> = self
+            .file_parts
+            .iter()
+            .filter(|part| part.entry_length_uncompressed > 0)
+            .collect();
+
+        if parts.is_empty() {
+            self.file_parts.iter().take(1).collect()
+        } else {
+            parts
+        }
+    }
 }
 
 impl DirEntry for VPKDirectoryEntryRespawn {
-    fn from(file: &mut File) -> Result<Self> {
+    fn from<R: Read + Seek>(file: &mut R) -> Result<Self> {
         let crc = file.read_u32().map_err(|e| Error::Util {
             source: e,
             context: "Failed to read CRC".to_string(),
@@ -206,17 +255,13 @@ impl DirEntry for VPKDirectoryEntryRespawn {
 
         let mut file_parts: Vec<VPKFilePartEntryRespawn> = Vec::new();
 
-        let pos = file.stream_position().map_err(Error::Io)?;
-        let end = file.seek(SeekFrom::End(0)).map_err(Error::Io)?;
-        let _ = file.seek(SeekFrom::Start(pos)).map_err(Error::Io)?;
-
         loop {
             let archive_index = file.read_u16().map_err(|e| Error::Util {
                 source: e,
                 context: "Failed to read archive index".to_string(),
             })?;
 
-            if archive_index == 0xFFFF || file.stream_position().map_err(Error::Io)? == end {
+            if archive_index == 0xFFFF {
                 break;
             }
 
@@ -256,7 +301,7 @@ impl DirEntry for VPKDirectoryEntryRespawn {
         })
     }
 
-    fn write(&self, file: &mut File) -> Result<()> {
+    fn write<W: Write>(&self, file: &mut W) -> Result<()> {
         file.write_u32(self.crc).map_err(|e| Error::Util {
             source: e,
             context: "Failed to write CRC".to_string(),
@@ -268,7 +313,7 @@ impl DirEntry for VPKDirectoryEntryRespawn {
                 context: "Failed to write preload length".to_string(),
             })?;
 
-        for file_part in &self.file_parts {
+        for file_part in self.written_parts() {
             file.write_u16(file_part.archive_index)
                 .map_err(|e| Error::Util {
                     source: e,
@@ -318,10 +363,15 @@ impl DirEntry for VPKDirectoryEntryRespawn {
     fn get_preload_length(&self) -> usize {
         self.preload_length.into()
     }
+
+    fn set_preload_length(&mut self, preload_length: u16) {
+        self.preload_length = preload_length;
+    }
 }
 
 /// A file part entry within a Respawn VPK directory entry.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VPKFilePartEntryRespawn {
     /// The archive index this part is contained in.
     pub archive_index: u16,
@@ -363,8 +413,55 @@ impl VPKFilePartEntryRespawn {
     }
 }
 
+impl VPKTree<VPKDirectoryEntryRespawn> {
+    /// Returns the indices of all archives the file parts in the tree are stored in, e.g. `4` for `englishclient_mp_colony.bsp.pak000_004.vpk`.
+    ///
+    /// Empty file parts and parts referring to the directory file itself are not counted.
+    #[must_use]
+    pub fn archive_indices(&self) -> HashSet<u16> {
+        self.file_parts()
+            .filter(|part| part.entry_length > 0)
+            .map(|part| part.archive_index)
+            .filter(|&archive_index| !is_dir_archive_index(archive_index))
+            .collect()
+    }
+
+    /// Returns the total size of all file parts in the tree after decompressing them. No archives are opened.
+    ///
+    /// Preload data is not counted, and neither are the sizes of sounds recorded in CAM files.
+    #[must_use]
+    pub fn total_uncompressed_size(&self) -> u64 {
+        self.file_parts()
+            .map(|part| part.entry_length_uncompressed)
+            .sum()
+    }
+
+    /// Returns the total number of bytes all file parts in the tree take up in the archives. No archives are opened.
+    #[must_use]
+    pub fn total_compressed_size(&self) -> u64 {
+        self.file_parts().map(|part| part.entry_length).sum()
+    }
+
+    /// Returns the ratio of [`Self::total_uncompressed_size`] to [`Self::total_compressed_size`],
+    /// e.g. `2.0` if the file parts take up half their size in the archives. Returns `1.0` for a tree without file data.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn compression_ratio(&self) -> f64 {
+        match self.total_compressed_size() {
+            0 => 1.0,
+            compressed => self.total_uncompressed_size() as f64 / compressed as f64,
+        }
+    }
+
+    /// Iterates over the file parts of all entries in the tree.
+    fn file_parts(&self) -> impl Iterator<Item = &VPKFilePartEntryRespawn> {
+        self.files.values().flat_map(|entry| &entry.file_parts)
+    }
+}
+
 /// A Respawn VPK CAM file.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VPKRespawnCam {
     /// The entries in the CAM file.
     /// Map key is VPK archive content offset of the file's first part.
@@ -435,12 +532,94 @@ impl VPKRespawnCam {
     pub fn find_entry(&self, vpk_content_offset: u64) -> Option<&VPKRespawnCamEntry> {
         self.entries.get(&vpk_content_offset)
     }
+
+    /// Write the CAM to a file.
+    /// Entries are written in ascending order of their VPK content offset, matching the layout read by [`Self::from_file`].
+    /// # Errors
+    /// - When an entry's magic is not [`RESPAWN_CAM_ENTRY_MAGIC`]
+    /// - When an IO operation fails
+    pub fn write<W: Write>(&self, file: &mut W) -> Result<()> {
+        let mut entries: Vec<&VPKRespawnCamEntry> = self.entries.values().collect();
+        entries.sort_by_key(|entry| entry.vpk_content_offset);
+
+        for entry in entries {
+            if entry.magic != RESPAWN_CAM_ENTRY_MAGIC {
+                return Err(Error::BadData(format!(
+                    "CAM entry magic should be {RESPAWN_CAM_ENTRY_MAGIC:#X} but is {:#X}",
+                    entry.magic
+                )));
+            }
+
+            file.write_u32(entry.magic).map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to write magic".to_string(),
+            })?;
+
+            file.write_u32(entry.original_size)
+                .map_err(|e| Error::Util {
+                    source: e,
+                    context: "Failed to write original size".to_string(),
+                })?;
+
+            file.write_u32(entry.compressed_size)
+                .map_err(|e| Error::Util {
+                    source: e,
+                    context: "Failed to write compressed size".to_string(),
+                })?;
+
+            file.write_u24(entry.sample_rate).map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to write sample rate".to_string(),
+            })?;
+
+            file.write_u8(entry.channels).map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to write channels".to_string(),
+            })?;
+
+            file.write_u32(entry.sample_count)
+                .map_err(|e| Error::Util {
+                    source: e,
+                    context: "Failed to write sample count".to_string(),
+                })?;
+
+            file.write_u32(entry.header_size).map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to write header size".to_string(),
+            })?;
+
+            file.write_u64(entry.vpk_content_offset)
+                .map_err(|e| Error::Util {
+                    source: e,
+                    context: "Failed to write VPK content offset".to_string(),
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the CAM to a file system location, creating parent directories as needed.
+    /// # Errors
+    /// - When an entry's magic is not [`RESPAWN_CAM_ENTRY_MAGIC`]
+    /// - When an IO operation fails
+    pub fn write_to_file(&self, output_path: &str) -> Result<()> {
+        let out_path = std::path::Path::new(output_path);
+        if let Some(prefix) = out_path.parent() {
+            std::fs::create_dir_all(prefix).map_err(Error::Io)?;
+        }
+
+        let mut out_file = BufWriter::new(File::create(out_path).map_err(Error::Io)?);
+
+        self.write(&mut out_file)?;
+        out_file.flush().map_err(Error::Io)
+    }
 }
 
 /// An entry in a CAM.
 ///
 /// Some audio files don't have a CAM entry, for this case we can generate a default entry with little effort (see [`Self::default`]).
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VPKRespawnCamEntry {
     /// The magic number of the entry. Should equal [`RESPAWN_CAM_ENTRY_MAGIC`].
     pub magic: u32,
@@ -483,528 +662,1429 @@ impl VPKRespawnCamEntry {
     }
 
     /// Create a CAM entry with default values for the given directory entry.
+    ///
+    /// Entries without file parts or smaller than a WAV header result in a CAM entry without samples.
     #[must_use]
     pub fn default(entry: &VPKDirectoryEntryRespawn) -> Self {
-        let original_size: u32 = entry
-            .file_parts
-            .iter()
-            .map(|e| e.entry_length_uncompressed as u32)
-            .sum();
+        // Sizes are summed as u64 and clamped, as they don't have to fit the u32 fields of the CAM entry
+        let total = |size: fn(&VPKFilePartEntryRespawn) -> u64| {
+            let total = entry
+                .file_parts
+                .iter()
+                .fold(0u64, |total, part| total.saturating_add(size(part)));
+            u32::try_from(total).unwrap_or(u32::MAX)
+        };
+        let original_size = total(|part| part.entry_length_uncompressed);
 
         VPKRespawnCamEntry {
             magic: RESPAWN_CAM_ENTRY_MAGIC,
             original_size,
-            compressed_size: entry
-                .file_parts
-                .iter()
-                .map(|e: &VPKFilePartEntryRespawn| e.entry_length as u32)
-                .sum(),
+            compressed_size: total(|part| part.entry_length),
             sample_rate: 44100,
             channels: 1,
-            sample_count: (original_size - 44 + 8) / 2,
+            sample_count: original_size
+                .checked_sub(44)
+                .map_or(0, |data_size| data_size.saturating_add(8) / 2),
             header_size: 44,
-            vpk_content_offset: entry.file_parts[0].entry_offset,
+            vpk_content_offset: entry.file_parts.first().map_or(0, |part| part.entry_offset),
         }
     }
-}
-
-/// The Respawn VPK format.
-#[derive(PartialEq, Eq)]
-pub struct VPKRespawn {
-    /// The VPK's header.
-    pub header: VPKHeaderRespawn,
-    /// The tree of files in the VPK.
-    pub tree: VPKTree<VPKDirectoryEntryRespawn>,
-    /// The parsed CAM files for this VPK (external files, not included int dir.vpk file)
-    pub archive_cams: HashMap<u16, VPKRespawnCam>,
-}
-
-impl PakReader for VPKRespawn {
-    fn read_file(&self, archive_path: &str, vpk_name: &str, file_path: &str) -> Option<Vec<u8>> {
-        let entry: &VPKDirectoryEntryRespawn = self.tree.files.get(file_path)?;
-        let mut buf: Vec<u8> = Vec::new();
-
-        if entry.preload_length > 0 {
-            buf.append(self.tree.preload.get(file_path)?.clone().as_mut());
-        }
 
-        if entry.file_parts.is_empty() {
-            return None;
+    /// Create a CAM entry describing a 16-bit PCM WAV file from its contents, e.g. to generate the CAM entries of WAV files packed into a Respawn VPK.
+    ///
+    /// The channels, sample rate and sample count are read from the `fmt ` and `data` chunks of the RIFF header.
+    /// The compressed size is left at `0` to be filled in once the file is packed.
+    /// # Errors
+    /// - When the data is not a RIFF WAVE file or its `fmt ` or `data` chunk is missing
+    /// - When the audio is not 16-bit PCM, which is what the header generated when extracting describes
+    /// - When the file is larger than 4 GiB
+    pub fn from_wav_bytes(wav_data: &[u8], vpk_content_offset: u64) -> Result<Self> {
+        if wav_data.len() < 12 || &wav_data[0..4] != b"RIFF" || &wav_data[8..12] != b"WAVE" {
+            return Err(Error::BadData("Not a RIFF WAVE file".to_string()));
         }
 
-        let mut archive_index = entry.file_parts[0].archive_index;
-        let path = Path::new(archive_path).join(format!(
-            "{}_{:0>3}.vpk",
-            vpk_name,
-            archive_index.to_string()
-        ));
-
-        let mut archive_file = File::open(&path)
-            .or(Err("Failed to open archive file"))
-            .ok()?;
-
-        // We have to do extra processing if it's a wav file
-        let mut expected_len = 0;
-        if std::path::Path::new(file_path)
-            .extension()
-            .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
-        {
-            let cam_entry = if let Some(cam) = self.archive_cams.get(&archive_index) {
-                if let Some(cam_entry) = cam.find_entry(entry.file_parts[0].entry_offset) {
-                    cam_entry.to_owned()
-                } else {
-                    VPKRespawnCamEntry::default(entry)
-                }
-            } else {
-                VPKRespawnCamEntry::default(entry)
-            };
-
-            expected_len = cam_entry.original_size;
+        let mut format = None;
+        let mut data_len = None;
+        let mut pos = 12;
 
-            let mut header = create_wav_header(&cam_entry);
-            buf.append(&mut header);
-        }
+        while let Some(chunk_header) = wav_data.get(pos..).and_then(|rest| rest.get(..8)) {
+            let chunk_len = u32::from_le_bytes(chunk_header[4..8].try_into().expect("4 bytes"));
+            let body = &wav_data[pos + 8..];
 
-        let mut total_len = 0;
-        for (i, file_part) in entry.file_parts.iter().enumerate() {
-            if file_part.entry_length_uncompressed > 0 {
-                if file_part.archive_index != archive_index {
-                    archive_index = file_part.archive_index;
-                    let path = Path::new(archive_path).join(format!(
-                        "{}_{:0>3}.vpk",
-                        vpk_name,
-                        archive_index.to_string()
-                    ));
-                    archive_file = File::open(path)
-                        .or(Err("Failed to open archive file"))
-                        .ok()?;
+            match &chunk_header[0..4] {
+                b"fmt " => {
+                    let fmt = body
+                        .get(..16)
+                        .ok_or(Error::BadData("WAV fmt chunk is too short".to_string()))?;
+                    format = Some(fmt);
                 }
-
-                let _ = archive_file.seek(SeekFrom::Start(file_part.entry_offset));
-
-                let mut entry_len = file_part.entry_length;
-
-                if i == 0
-                    && std::path::Path::new(file_path)
-                        .extension()
-                        .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
-                {
-                    entry_len -= seek_to_wav_data(&mut archive_file).ok()?;
+                // The data chunk may be truncated, its declared size is what the header describes
+                b"data" => {
+                    data_len = Some(chunk_len);
+                    break;
                 }
+                _ => {}
+            }
 
-                total_len += entry_len;
-
-                if file_part.entry_length == file_part.entry_length_uncompressed {
-                    let mut part = archive_file.read_bytes(entry_len as usize).ok()?;
-
-                    // Truncate WAV files that exceed their expected length
-                    if expected_len > 0
-                        && std::path::Path::new(file_path)
-                            .extension()
-                            .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
-                        && total_len > expected_len.into()
-                    {
-                        let new_len = entry_len + u64::from(expected_len) - total_len;
-                        part.truncate(new_len.try_into().ok()?);
-                    }
+            // Chunks are padded to an even length
+            let chunk_len = usize::try_from(chunk_len).map_err(|_| Error::DataTooLarge)?;
+            pos = pos.saturating_add(8 + chunk_len + chunk_len % 2);
+        }
 
-                    buf.append(&mut part);
-                } else {
-                    let compressed_data =
-                        archive_file.read_bytes(entry_len.try_into().ok()?).ok()?;
+        let fmt = format.ok_or(Error::BadData("WAV file has no fmt chunk".to_string()))?;
+        let data_len = data_len.ok_or(Error::BadData("WAV file has no data chunk".to_string()))?;
 
-                    let mut decompressed = decompress(
-                        &compressed_data,
-                        file_part.entry_length_uncompressed.try_into().ok()?,
-                    );
-                    buf.append(&mut decompressed);
-                }
-            }
-        }
+        let audio_format = u16::from_le_bytes([fmt[0], fmt[1]]);
+        let channels = u16::from_le_bytes([fmt[2], fmt[3]]);
+        let sample_rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+        let bits_per_sample = u16::from_le_bytes([fmt[14], fmt[15]]);
 
-        // Truncate WAV files that exceed their expected length
-        if expected_len > 0
-            && std::path::Path::new(file_path)
-                .extension()
-                .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
-        {
-            buf.truncate(expected_len.try_into().ok()?);
+        if audio_format != 1 || bits_per_sample != 16 {
+            return Err(Error::BadData(format!(
+                "WAV file is not 16-bit PCM (format {audio_format}, {bits_per_sample} bits per sample)"
+            )));
         }
 
-        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
-        let mut digest = crc.digest();
-        digest.update(&buf);
+        let channels = u8::try_from(channels)
+            .ok()
+            .filter(|&channels| channels > 0)
+            .ok_or(Error::BadData(format!(
+                "WAV file has an unsupported number of channels: {channels}"
+            )))?;
 
-        // We can't check CRCs on wav files because the CRC wasn't calculated with the actual unpacked data
-        if digest.finalize() != entry.crc
-            && !std::path::Path::new(file_path)
-                .extension()
-                .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
-        {
-            None
-        } else {
-            Some(buf)
-        }
+        Ok(Self {
+            magic: RESPAWN_CAM_ENTRY_MAGIC,
+            original_size: u32::try_from(wav_data.len()).map_err(|_| Error::DataTooLarge)?,
+            compressed_size: 0,
+            sample_rate,
+            channels,
+            sample_count: data_len / (2 * u32::from(channels)),
+            header_size: 44,
+            vpk_content_offset,
+        })
     }
+}
 
-    fn extract_file(
-        &self,
+/// Reads the file parts of a Respawn VPK entry one after another, see [`VPKRespawn::read_file_streaming`].
+///
+/// Uncompressed parts are read from the archive as needed, compressed parts are decompressed one at a time.
+struct FilePartsReader<'a> {
+    archive_path: String,
+    vpk_name: String,
+    parts: std::iter::Enumerate<std::slice::Iter<'a, VPKFilePartEntryRespawn>>,
+    file_path: String,
+    is_wav: bool,
+    observer: Observer,
+    naming: Naming,
+    archive: Option<(u16, BufReader<ArchiveFile>)>,
+    remaining: u64,
+    decompressed: Option<io::Cursor<Vec<u8>>>,
+}
+
+impl<'a> FilePartsReader<'a> {
+    /// Creates a reader for the parts of the file at `file_path`, opening the archive of the first part.
+    fn new(
+        vpk: &VPKRespawn,
         archive_path: &str,
         vpk_name: &str,
         file_path: &str,
-        output_path: &str,
-    ) -> Result<()> {
-        let entry: &VPKDirectoryEntryRespawn = self
-            .tree
-            .files
-            .get(file_path)
-            .ok_or(Error::FileNotFound(file_path.to_string()))?;
+        parts: &'a [VPKFilePartEntryRespawn],
+    ) -> Result<Self> {
+        let mut reader = Self {
+            archive_path: archive_path.to_string(),
+            vpk_name: vpk_name.to_string(),
+            parts: parts.iter().enumerate(),
+            file_path: file_path.to_string(),
+            is_wav: is_wav(file_path),
+            observer: vpk.observer.clone(),
+            naming: vpk.naming.clone(),
+            archive: None,
+            remaining: 0,
+            decompressed: None,
+        };
 
-        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
-        let mut digest = crc.digest();
+        reader.next_part()?;
+        Ok(reader)
+    }
 
-        let out_path = std::path::Path::new(output_path);
-        if let Some(prefix) = out_path.parent() {
-            std::fs::create_dir_all(prefix).map_err(Error::Io)?;
-        }
+    /// Moves to the next file part with data, returning `false` once all parts have been read.
+    fn next_part(&mut self) -> Result<bool> {
+        for (i, file_part) in self.parts.by_ref() {
+            if file_part.entry_length_uncompressed == 0 {
+                continue;
+            }
+
+            let archive_file = match &mut self.archive {
+                Some((archive_index, archive_file))
+                    if *archive_index == file_part.archive_index =>
+                {
+                    archive_file
+                }
+                archive => {
+                    let archive_file = VPKRespawn::open_archive(
+                        &self.observer,
+                        &self.naming,
+                        &self.archive_path,
+                        &self.vpk_name,
+                        file_part.archive_index,
+                    )?;
+                    &mut archive
+                        .insert((file_part.archive_index, BufReader::new(archive_file)))
+                        .1
+                }
+            };
 
-        let mut out_file = File::create(out_path).map_err(Error::Io)?;
+            archive_file
+                .seek(SeekFrom::Start(file_part.entry_offset))
+                .map_err(Error::Io)?;
 
-        if entry.preload_length > 0 {
-            let preload_data = self
-                .tree
-                .preload
-                .get(file_path)
-                .ok_or(Error::DataNotFound(file_path.to_string()))?;
+            let mut entry_len = file_part.entry_length;
 
-            digest.update(preload_data);
+            if i == 0 && self.is_wav {
+                entry_len = wav_data_length(archive_file, entry_len, &self.file_path)?;
+            }
 
-            out_file.write_all(preload_data).map_err(Error::Io)?;
-        }
+            if file_part.entry_length == file_part.entry_length_uncompressed {
+                self.remaining = entry_len;
+            } else {
+                let compressed_data =
+                    read_compressed_part(archive_file, entry_len, i, &self.file_path)?;
+
+                self.decompressed = Some(io::Cursor::new(decompress_part(
+                    &compressed_data,
+                    file_part,
+                    &self.file_path,
+                )?));
+            }
 
-        if entry.file_parts.is_empty() {
-            return Err(Error::BadData("File had no parts".to_string()));
+            return Ok(true);
         }
 
-        let mut archive_index = entry.file_parts[0].archive_index;
-        let path = Path::new(archive_path).join(format!(
-            "{}_{:0>3}.vpk",
-            vpk_name,
-            archive_index.to_string()
-        ));
+        Ok(false)
+    }
+}
 
-        let mut archive_file = File::open(&path).map_err(Error::Io)?;
+impl Read for FilePartsReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
 
-        // We have to do extra processing if it's a wav file
-        let mut expected_len = 0;
-        if std::path::Path::new(file_path)
-            .extension()
-            .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
-        {
-            let cam_entry = if let Some(cam) = self.archive_cams.get(&archive_index) {
-                if let Some(cam_entry) = cam.find_entry(entry.file_parts[0].entry_offset) {
-                    cam_entry.to_owned()
-                } else {
-                    VPKRespawnCamEntry::default(entry)
+        loop {
+            if let Some(decompressed) = &mut self.decompressed {
+                let read = decompressed.read(buf)?;
+                if read > 0 {
+                    return Ok(read);
                 }
-            } else {
-                VPKRespawnCamEntry::default(entry)
-            };
-
-            expected_len = cam_entry.original_size;
 
-            let header = create_wav_header(&cam_entry);
-            digest.update(&header);
-            out_file.write_all(&header).map_err(Error::Io)?;
-        }
+                self.decompressed = None;
+            }
 
-        let mut total_len = 0;
-        for (i, file_part) in entry.file_parts.iter().enumerate() {
-            if file_part.entry_length_uncompressed > 0 {
-                if file_part.archive_index != archive_index {
-                    archive_index = file_part.archive_index;
-                    let path = Path::new(archive_path)
-                        .join(format!("{vpk_name}_{archive_index:0>3}.vpk",));
-                    archive_file = File::open(path).map_err(Error::Io)?;
+            if self.remaining > 0
+                && let Some((_, archive_file)) = &mut self.archive
+            {
+                let len = buf
+                    .len()
+                    .min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+                let read = archive_file.read(&mut buf[..len])?;
+                if read == 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
                 }
 
-                let _ = archive_file.seek(SeekFrom::Start(file_part.entry_offset));
+                self.remaining -= read as u64;
+                return Ok(read);
+            }
 
-                let mut entry_len = file_part.entry_length;
+            if !self.next_part().map_err(io::Error::other)? {
+                return Ok(0);
+            }
+        }
+    }
+}
 
-                if i == 0
-                    && std::path::Path::new(file_path)
-                        .extension()
-                        .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
-                {
-                    entry_len -= seek_to_wav_data(&mut archive_file).map_err(Error::Io)?;
-                }
+/// The Respawn VPK format.
+#[derive(PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VPKRespawn {
+    /// The VPK's header.
+    pub header: VPKHeaderRespawn,
+    /// The tree of files in the VPK.
+    pub tree: VPKTree<VPKDirectoryEntryRespawn>,
+    /// The parsed CAM files for this VPK (external files, not included int dir.vpk file)
+    ///
+    /// CAMs taken from a CAM cache are shared with the cache and every other VPK using it, see [`Self::set_cam_cache`].
+    pub archive_cams: HashMap<u16, Arc<VPKRespawnCam>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    observer: Observer,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    naming: Naming,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    cam_cache: SharedCamCache,
+}
 
-                total_len += entry_len;
+/// The CAM cache set on a [`VPKRespawn`], if any.
+///
+/// Caches don't take part in comparisons, like observers.
+#[derive(Clone, Default)]
+struct SharedCamCache(Option<Arc<CamCache>>);
 
-                if file_part.entry_length == file_part.entry_length_uncompressed {
-                    let mut part = archive_file
-                        .read_bytes(entry_len.try_into().map_err(|_| Error::DataTooLarge)?)
-                        .map_err(|e| Error::Util {
-                            source: e,
-                            context: "Failed to read from archive file".to_string(),
-                        })?;
+impl PartialEq for SharedCamCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
 
-                    // Truncate WAV files that exceed their expected length
-                    if expected_len > 0
-                        && std::path::Path::new(file_path)
-                            .extension()
-                            .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
-                        && total_len > expected_len.into()
-                    {
-                        let new_len = entry_len + u64::from(expected_len) - total_len;
-                        part.truncate(new_len.try_into().map_err(|_| Error::DataTooLarge)?);
-                    }
+impl Eq for SharedCamCache {}
 
-                    out_file.write_all(&part).map_err(Error::Io)?;
+impl VPKRespawn {
+    /// Recomputes the tree size in the header from the tree, e.g. after files were added or removed.
+    ///
+    /// [`PakWriter::write_dir`] always writes the size of the tree it writes, this updates the header kept in memory to match.
+    /// # Errors
+    /// - When the preload data does not match the entries
+    /// - When the tree is too large for the header
+    pub fn rebuild_header_tree_size(&mut self) -> Result<()> {
+        self.header.tree_size = self.tree.serialize()?.1;
+        Ok(())
+    }
 
-                    digest.update(&part);
-                } else {
-                    let compressed_data = archive_file
-                        .read_bytes(entry_len.try_into().map_err(|_| Error::DataTooLarge)?)
-                        .map_err(|e| Error::Util {
-                            source: e,
-                            context: "Failed to read from archive files".to_string(),
-                        })?;
+    /// Sets the observer notified of every archive file opened and read by this VPK, or removes it when `None`.
+    /// CAM files are not reported.
+    pub fn set_observer(&mut self, observer: Option<Arc<dyn IoObserver>>) {
+        self.observer = Observer::new(observer);
+    }
+
+    /// Sets how archive indices map to the paths of archive files, or restores Valve's naming when `None`.
+    /// CAM files are expected next to the archives, with `.cam` appended to their file names.
+    pub fn set_archive_naming(&mut self, naming: Option<Arc<dyn ArchiveNaming>>) {
+        self.naming = Naming::new(naming);
+    }
 
-                    let decompressed = decompress(
-                        &compressed_data,
-                        file_part
-                            .entry_length_uncompressed
-                            .try_into()
-                            .map_err(|_| Error::DataTooLarge)?,
-                    );
+    /// Sets the cache [`Self::read_cam`] and [`Self::read_all_cams`] take parsed CAMs from, or removes it when `None`.
+    /// Share one cache between several VPKs using the same CAM files to parse them only once.
+    pub fn set_cam_cache(&mut self, cam_cache: Option<Arc<CamCache>>) {
+        self.cam_cache = SharedCamCache(cam_cache);
+    }
 
-                    out_file.write_all(&decompressed).map_err(Error::Io)?;
+    /// Opens the archive file for an archive index, located with `naming` and reported to `observer`.
+    fn open_archive(
+        observer: &Observer,
+        naming: &Naming,
+        archive_path: &str,
+        vpk_name: &str,
+        archive_index: u16,
+    ) -> Result<ArchiveFile> {
+        let path = naming.archive_path(archive_path, vpk_name, archive_index);
+
+        observer
+            .open(archive_index, &path)
+            .map_err(|e| Error::ArchiveOpen {
+                path: path.display().to_string(),
+                source: e,
+            })
+    }
 
-                    digest.update(&decompressed);
-                }
-            }
+    /// Returns the archive file for an archive index, opening it if it is not in `archives` yet.
+    fn cached_archive<'a>(
+        &self,
+        archives: &'a mut HashMap<u16, ArchiveFile>,
+        archive_path: &str,
+        vpk_name: &str,
+        archive_index: u16,
+    ) -> Result<&'a mut ArchiveFile> {
+        match archives.entry(archive_index) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => Ok(entry.insert(Self::open_archive(
+                &self.observer,
+                &self.naming,
+                archive_path,
+                vpk_name,
+                archive_index,
+            )?)),
         }
+    }
 
-        // We can't check CRCs on wav files because the CRC wasn't calculated with the actual unpacked data
-        if digest.finalize() != entry.crc
-            && !std::path::Path::new(file_path)
-                .extension()
-                .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
-        {
-            Err(Error::BadData("CRC must match".to_string()))
-        } else {
-            Ok(())
+    /// Reads the contents of a file stored in the VPK into memory.
+    fn read_entry(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        options: &ExtractOptions,
+    ) -> Result<Vec<u8>> {
+        self.read_entry_from(
+            &mut HashMap::new(),
+            archive_path,
+            vpk_name,
+            file_path,
+            options,
+        )
+    }
+
+    /// Returns the CAM entry describing the WAV file stored in `entry`.
+    ///
+    /// Without a CAM for the archive of the first file part, a default CAM entry is created, see [`VPKRespawnCamEntry::default`].
+    /// # Errors
+    /// - When the entry has no file parts
+    /// - When the CAM of the archive has no entry for the file
+    fn wav_cam_entry(
+        &self,
+        file_path: &str,
+        entry: &VPKDirectoryEntryRespawn,
+    ) -> Result<VPKRespawnCamEntry> {
+        let first_part = entry
+            .file_parts
+            .first()
+            .ok_or(Error::BadData("File had no parts".to_string()))?;
+
+        match self.archive_cams.get(&first_part.archive_index) {
+            Some(cam) => cam
+                .find_entry(first_part.entry_offset)
+                .copied()
+                .ok_or_else(|| {
+                    Error::DataNotFound(format!(
+                        "CAM entry for {file_path} at offset {} of archive {}",
+                        first_part.entry_offset, first_part.archive_index
+                    ))
+                }),
+            None => Ok(VPKRespawnCamEntry::default(entry)),
         }
     }
 
-    #[cfg(feature = "mem-map")]
-    fn extract_file_mem_map(
+    /// Reads the contents of a file stored in the VPK into memory.
+    /// Archive files are opened as needed and kept in `archives` so they can be reused.
+    fn read_entry_from(
         &self,
+        archives: &mut HashMap<u16, ArchiveFile>,
         archive_path: &str,
-        archive_mmaps: &HashMap<u16, FileBuffer>,
         vpk_name: &str,
         file_path: &str,
-        output_path: &str,
-    ) -> Result<()> {
-        let entry: &VPKDirectoryEntryRespawn = self
+        options: &ExtractOptions,
+    ) -> Result<Vec<u8>> {
+        let (file_path, entry) = self
             .tree
-            .files
-            .get(file_path)
+            .find(file_path, options.case_sensitivity)
             .ok_or(Error::FileNotFound(file_path.to_string()))?;
+        let mut buf = io::Cursor::new(Vec::new());
 
-        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
-        let mut digest = crc.digest();
+        self.write_entry(
+            archives,
+            archive_path,
+            vpk_name,
+            file_path,
+            entry,
+            &mut buf,
+            options,
+            &mut |_, _| {},
+        )?;
+        fix_extracted_wav(file_path, entry, &mut buf, options)?;
+
+        Ok(buf.into_inner())
+    }
 
-        let out_path = std::path::Path::new(output_path);
-        if let Some(prefix) = out_path.parent() {
-            std::fs::create_dir_all(prefix).map_err(Error::Io)?;
-        }
+    /// Writes the preload and archive data of an entry to `out` and verifies its CRC.
+    /// Archive files are opened as needed and kept in `archives` so they can be reused.
+    /// `progress` is called after every chunk with the bytes written so far and the total size of the entry.
+    ///
+    /// Uncompressed file parts are streamed in chunks of at most the chunk size and compressed file parts are decompressed one at a time,
+    /// so at most the chunk size or the compressed and uncompressed size of the largest compressed part is held in memory.
+    /// WAV files are written with a header generated from their CAM entry and truncated to the original size in the CAM entry,
+    /// unless [`ExtractOptions::wav_transcode`] is disabled.
+    #[allow(clippy::too_many_arguments)]
+    fn write_entry<W: Write>(
+        &self,
+        archives: &mut HashMap<u16, ArchiveFile>,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        entry: &VPKDirectoryEntryRespawn,
+        out: &mut W,
+        options: &ExtractOptions,
+        progress: &mut dyn FnMut(u64, u64),
+    ) -> Result<u64> {
+        self.write_entry_parts(
+            file_path,
+            entry,
+            out,
+            options,
+            progress,
+            &mut |i, file_part, skip_wav_header, limit, emit| {
+                let archive_file =
+                    self.cached_archive(archives, archive_path, vpk_name, file_part.archive_index)?;
+
+                archive_file
+                    .seek(SeekFrom::Start(file_part.entry_offset))
+                    .map_err(Error::Io)?;
+
+                let mut entry_len = file_part.entry_length;
+
+                if skip_wav_header {
+                    entry_len = wav_data_length(archive_file, entry_len, file_path)?;
+                }
+
+                if file_part.entry_length == file_part.entry_length_uncompressed {
+                    // Stop reading once a truncated WAV file is complete
+                    let mut remaining = entry_len.min(limit);
+                    while remaining > 0 {
+                        let chunk = read_archive_chunk(
+                            archive_file,
+                            usize::try_from(remaining)
+                                .unwrap_or(usize::MAX)
+                                .min(options.chunk_size()),
+                            remaining,
+                            file_path,
+                        )?;
+
+                        remaining -= chunk.len() as u64;
+                        emit(&chunk)?;
+                    }
+                } else {
+                    let compressed_data =
+                        read_compressed_part(archive_file, entry_len, i, file_path)?;
+
+                    emit(&decompress_part(&compressed_data, file_part, file_path)?)?;
+                }
+
+                Ok(())
+            },
+        )
+    }
+
+    /// Writes the preload and archive data of an entry to `out` and verifies its CRC, see [`Self::write_entry`].
+    ///
+    /// The data of every file part that is not empty is read by `read_part`, which is called with the index of the part, the part,
+    /// whether the WAV header stored in front of its data must be skipped and the number of bytes that may still be written.
+    /// It passes the data to the given function, uncompressed parts may be passed in several chunks.
+    /// Data past the number of bytes that may be written is dropped.
+    fn write_entry_parts<W: Write>(
+        &self,
+        file_path: &str,
+        entry: &VPKDirectoryEntryRespawn,
+        out: &mut W,
+        options: &ExtractOptions,
+        progress: &mut dyn FnMut(u64, u64),
+        read_part: &mut ReadPart,
+    ) -> Result<u64> {
+        if entry.file_parts.is_empty() {
+            return Err(Error::BadData("File had no parts".to_string()));
+        }
 
-        let mut out_file = File::create(out_path).map_err(Error::Io)?;
+        // Untranscoded WAV files are handled like any other file
+        let is_wav = is_wav(file_path) && options.wav_transcode;
+
+        // We have to do extra processing if it's a wav file
+        let cam_entry = if is_wav {
+            Some(self.wav_cam_entry(file_path, entry)?)
+        } else {
+            None
+        };
+
+        // WAV files are truncated to the original size in their CAM entry, other files are never truncated
+        let (limit, total) = match cam_entry {
+            Some(cam_entry) if cam_entry.original_size > 0 => (
+                u64::from(cam_entry.original_size),
+                u64::from(cam_entry.original_size),
+            ),
+            _ => (
+                u64::MAX,
+                u64::from(entry.preload_length)
+                    + entry
+                        .file_parts
+                        .iter()
+                        .map(|part| part.entry_length_uncompressed)
+                        .sum::<u64>(),
+            ),
+        };
+
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+        let mut digest = crc.digest();
+        let mut written = 0;
+        let mut emit = |chunk: &[u8], written: &mut u64| -> Result<()> {
+            let len = chunk
+                .len()
+                .min(usize::try_from(limit - *written).unwrap_or(usize::MAX));
+
+            out.write_all(&chunk[..len]).map_err(Error::Io)?;
+            digest.update(&chunk[..len]);
+            *written += len as u64;
+            progress(*written, total);
+            Ok(())
+        };
 
         if entry.preload_length > 0 {
             let preload_data = self
                 .tree
-                .preload
-                .get(file_path)
+                .get_preload(file_path)
                 .ok_or(Error::DataNotFound(file_path.to_string()))?;
 
-            digest.update(preload_data);
+            emit(preload_data, &mut written)?;
+        }
+
+        if let Some(cam_entry) = &cam_entry {
+            emit(&create_wav_header(cam_entry)?, &mut written)?;
+        }
+
+        for (i, file_part) in entry.file_parts.iter().enumerate() {
+            if file_part.entry_length_uncompressed == 0 || written >= limit {
+                continue;
+            }
 
-            out_file.write_all(preload_data).map_err(Error::Io)?;
+            read_part(
+                i,
+                file_part,
+                i == 0 && is_wav,
+                limit - written,
+                &mut |chunk| emit(chunk, &mut written),
+            )?;
         }
 
+        // We can't check CRCs on wav files because the CRC wasn't calculated with the actual unpacked data
+        let found = digest.finalize();
+        if options.verify_crc && found != entry.crc && !is_wav {
+            Err(Error::CrcMismatch {
+                expected: entry.crc,
+                found,
+                file: file_path.to_string(),
+            })
+        } else {
+            Ok(written)
+        }
+    }
+
+    /// Inserts or replaces the entry for a file stored in the given file parts.
+    ///
+    /// The CRC is computed over `preload` followed by `data`, the uncompressed contents of all file parts,
+    /// and the preload data is stored in the tree. Writing the file parts to the archives is left to the caller.
+    /// # Errors
+    /// - When `preload` is too large to be described by an entry
+    pub fn upsert_entry(
+        &mut self,
+        file_path: &str,
+        data: &[u8],
+        file_parts: &[VPKFilePartEntryRespawn],
+        preload: Option<Vec<u8>>,
+    ) -> Result<()> {
+        let preload = preload.unwrap_or_default();
+
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+        let mut digest = crc.digest();
+        digest.update(&preload);
+        digest.update(data);
+
+        let entry = VPKDirectoryEntryRespawn {
+            crc: digest.finalize(),
+            preload_length: 0,
+            file_parts: file_parts.to_vec(),
+        };
+
+        self.tree
+            .insert_file_with_preload(file_path, entry, preload)
+    }
+
+    /// Removes a file and its preload data from the tree, returning its entry if it existed.
+    pub fn remove_entry(&mut self, file_path: &str) -> Option<VPKDirectoryEntryRespawn> {
+        self.tree.remove_file(file_path)
+    }
+
+    /// Verifies the CRC of every file stored in the VPK, returning the result for each file.
+    ///
+    /// Files are read grouped by archive in the order they are stored in, and every archive is only opened once.
+    /// WAV files are skipped, as their CRC is not calculated over the unpacked data.
+    #[must_use]
+    pub fn verify_all_crcs(&self, archive_path: &str, vpk_name: &str) -> Vec<(String, Result<()>)> {
+        self.verify_crcs(archive_path, vpk_name, self.tree.files.iter().collect())
+    }
+
+    /// Verifies the CRC of every file matching `filter` like [`Self::verify_all_crcs`].
+    #[must_use]
+    pub fn verify_filtered_crcs(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        filter: &ExtensionFilter,
+    ) -> Vec<(String, Result<()>)> {
+        let entries = filter
+            .select_from(&self.extension_index())
+            .into_iter()
+            .filter_map(|file_path| self.tree.files.get_key_value(file_path))
+            .collect();
+        self.verify_crcs(archive_path, vpk_name, entries)
+    }
+
+    fn verify_crcs(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        mut entries: Vec<(&String, &VPKDirectoryEntryRespawn)>,
+    ) -> Vec<(String, Result<()>)> {
+        entries.retain(|(file_path, _)| !is_wav(file_path));
+        entries.sort_by_key(|(file_path, entry)| {
+            (
+                entry
+                    .file_parts
+                    .first()
+                    .map(|part| (part.archive_index, part.entry_offset)),
+                *file_path,
+            )
+        });
+
+        let mut archives = HashMap::new();
+        entries
+            .into_iter()
+            .map(|(file_path, entry)| {
+                let result = self
+                    .write_entry(
+                        &mut archives,
+                        archive_path,
+                        vpk_name,
+                        file_path,
+                        entry,
+                        &mut io::sink(),
+                        &ExtractOptions::default(),
+                        &mut |_, _| {},
+                    )
+                    .map(|_| ());
+
+                (file_path.clone(), result)
+            })
+            .collect()
+    }
+
+    /// Extracts every file stored in the VPK into `output_dir`, mirroring the directory structure of the VPK,
+    /// and returns a summary of the extraction.
+    ///
+    /// Files that already exist in `output_dir` are skipped and counted in [`ExtractSummary::files_skipped`] unless `overwrite` is set.
+    /// WAV files are extracted using their CAM entries, see [`Self::read_all_cams`].
+    /// # Errors
+    /// - When the output directory cannot be created
+    pub fn extract_to_dir(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        output_dir: &str,
+        overwrite: bool,
+    ) -> Result<ExtractSummary> {
+        extract_to_dir(self, archive_path, vpk_name, output_dir, overwrite)
+    }
+
+    /// Read the contents of a file stored in the VPK, locating the archives next to the directory file at `dir_file_path`.
+    ///
+    /// The language prefix of the directory file name (e.g. `english` in `englishclient_mp_common.bsp.pak000_dir.vpk`) is not part of the archive names.
+    /// # Errors
+    /// - When `dir_file_path` is not the path of a directory file
+    /// - When the file does not exist in the VPK
+    /// - When an archive file cannot be opened, the error contains the path that was attempted
+    /// - When the data is invalid
+    pub fn read_file_rel(&self, dir_file_path: &Path, entry: &str) -> Result<Vec<u8>> {
+        let (archive_path, vpk_name) =
+            split_dir_file_path(dir_file_path).ok_or(Error::BadData(format!(
+                "Not the path of a directory file: {}",
+                dir_file_path.display()
+            )))?;
+
+        self.read_entry(
+            archive_path,
+            strip_language_prefix(vpk_name),
+            entry,
+            &ExtractOptions::default(),
+        )
+    }
+}
+
+/// Reads the `entry_len` bytes of data of the compressed file part `part_index` of the file at `file_path`.
+///
+/// A short read is reported before the data reaches LZHAM, which would otherwise fail with a less specific error.
+/// # Errors
+/// - When the archive ends before the end of the part ([`Error::TruncatedPart`])
+/// - When an IO operation fails
+fn read_compressed_part<R: Read>(
+    archive_file: &mut R,
+    entry_len: u64,
+    part_index: usize,
+    file_path: &str,
+) -> Result<Vec<u8>> {
+    archive_file
+        .read_bytes(entry_len.try_into().map_err(|_| Error::DataTooLarge)?)
+        .map_err(|e| match e {
+            util::Error::UnexpectedEof { available, .. } => Error::TruncatedPart {
+                path: file_path.to_string(),
+                part_index,
+                expected: entry_len,
+                got: available as u64,
+            },
+            e => Error::Util {
+                source: e,
+                context: "Failed to read from archive file".to_string(),
+            },
+        })
+}
+
+/// Decompresses the data of a compressed file part of the file at `file_path`.
+/// # Errors
+/// - When the data cannot be decompressed or doesn't match its checksum ([`Error::Decompress`])
+fn decompress_part(
+    compressed_data: &[u8],
+    file_part: &VPKFilePartEntryRespawn,
+    file_path: &str,
+) -> Result<Vec<u8>> {
+    decompress(
+        compressed_data,
+        file_part
+            .entry_length_uncompressed
+            .try_into()
+            .map_err(|_| Error::DataTooLarge)?,
+    )
+    .map_err(|e| Error::Decompress {
+        file: file_path.to_string(),
+        source: e,
+    })
+}
+/// Corrects the sizes in the header of a WAV file whose data ended early, see [`ExtractOptions::fix_wav_sizes`].
+/// `out` holds the whole extracted file, starting with the preload data of `entry`.
+/// # Errors
+/// - When an IO operation fails
+fn fix_extracted_wav<F: Read + Write + Seek>(
+    file_path: &str,
+    entry: &VPKDirectoryEntryRespawn,
+    out: &mut F,
+    options: &ExtractOptions,
+) -> Result<()> {
+    if !fixes_wav_sizes(file_path, options) {
+        return Ok(());
+    }
+
+    let header_offset = u64::from(entry.preload_length);
+    let wav_len = out
+        .seek(SeekFrom::End(0))
+        .map_err(Error::Io)?
+        .saturating_sub(header_offset);
+    if wav_len < 44 {
+        return Ok(());
+    }
+
+    let mut header = [0u8; 44];
+    out.seek(SeekFrom::Start(header_offset))
+        .map_err(Error::Io)?;
+    out.read_exact(&mut header).map_err(Error::Io)?;
+
+    if fix_wav_sizes(&mut header, wav_len)? {
+        out.seek(SeekFrom::Start(header_offset))
+            .map_err(Error::Io)?;
+        out.write_all(&header).map_err(Error::Io)?;
+    }
+
+    Ok(())
+}
+
+/// Corrects the sizes in the header of a WAV file extracted to `out_path`, see [`fix_extracted_wav`].
+fn fix_extracted_wav_file(
+    file_path: &str,
+    entry: &VPKDirectoryEntryRespawn,
+    out_path: &Path,
+    options: &ExtractOptions,
+) -> Result<()> {
+    if !fixes_wav_sizes(file_path, options) {
+        return Ok(());
+    }
+
+    let mut out_file = File::options()
+        .read(true)
+        .write(true)
+        .open(out_path)
+        .map_err(Error::Io)?;
+
+    fix_extracted_wav(file_path, entry, &mut out_file, options)
+}
+
+/// Whether the sizes in the header of the file are corrected after extracting it, see [`ExtractOptions::fix_wav_sizes`].
+fn fixes_wav_sizes(file_path: &str, options: &ExtractOptions) -> bool {
+    is_wav(file_path) && options.wav_transcode && options.fix_wav_sizes
+}
+
+/// Returns the `length` bytes of a memory-mapped archive starting at `offset`.
+/// # Errors
+/// - When the range lies outside of the archive, e.g. for a forged file part
+#[cfg(feature = "mem-map")]
+fn slice_archive<'a>(
+    archive_file: &'a FileBuffer,
+    offset: u64,
+    length: u64,
+    file_path: &str,
+) -> Result<&'a [u8]> {
+    usize::try_from(offset)
+        .ok()
+        .zip(usize::try_from(length).ok())
+        .and_then(|(start, length)| Some(start..start.checked_add(length)?))
+        .and_then(|range| archive_file.get(range))
+        .ok_or_else(|| {
+            Error::BadData(format!(
+                "Data of {file_path} at {offset}..{} exceeds the {} bytes of the archive",
+                u128::from(offset) + u128::from(length),
+                archive_file.len()
+            ))
+        })
+}
+
+/// Prefetches a file part from a memory-mapped archive, parts outside of the archive fail when they are read instead.
+#[cfg(feature = "mem-map")]
+fn prefetch_part(archive_file: &FileBuffer, file_part: &VPKFilePartEntryRespawn) {
+    if let (Ok(offset), Ok(length)) = (
+        usize::try_from(file_part.entry_offset),
+        usize::try_from(file_part.entry_length),
+    ) && offset
+        .checked_add(length)
+        .is_some_and(|end| end <= archive_file.len())
+    {
+        archive_file.prefetch(offset, length);
+    }
+}
+
+/// Reads the data of a file part for [`VPKRespawn::write_entry_parts`].
+type ReadPart<'a> = dyn FnMut(
+        usize,
+        &VPKFilePartEntryRespawn,
+        bool,
+        u64,
+        &mut dyn FnMut(&[u8]) -> Result<()>,
+    ) -> Result<()>
+    + 'a;
+
+/// Whether the file is a WAV file, whose CRC is calculated over the data before it was packed.
+fn is_wav(file_path: &str) -> bool {
+    Path::new(file_path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
+}
+
+/// The language prefixes used in the directory file names of Respawn VPKs.
+pub const RESPAWN_LANGUAGES: [&str; 11] = [
+    "english",
+    "french",
+    "german",
+    "italian",
+    "japanese",
+    "korean",
+    "polish",
+    "portuguese",
+    "russian",
+    "spanish",
+    "tchinese",
+];
+
+/// Skips the header and padding at the start of the first file part of a WAV file,
+/// returning the length of the WAV data left in the part.
+fn wav_data_length<R: Read + Seek>(
+    archive_file: &mut R,
+    entry_len: u64,
+    file_path: &str,
+) -> Result<u64> {
+    let skipped = seek_to_wav_data(archive_file)
+        .map_err(|e| Error::BadData(format!("Failed to find the WAV data of {file_path}: {e}")))?;
+
+    entry_len.checked_sub(skipped).ok_or_else(|| {
+        Error::BadData(format!(
+            "WAV data of {file_path} starts past the end of its first file part"
+        ))
+    })
+}
+
+/// Strips the language prefix from the VPK name of a Respawn directory file, the archives are shared between languages.
+#[must_use]
+pub fn strip_language_prefix(vpk_name: &str) -> &str {
+    RESPAWN_LANGUAGES
+        .iter()
+        .find_map(|language| vpk_name.strip_prefix(language))
+        .unwrap_or(vpk_name)
+}
+
+impl PakReader for VPKRespawn {
+    fn file_paths(&self) -> Vec<&str> {
+        self.tree.files.keys().map(String::as_str).collect()
+    }
+
+    /// WAV files are extracted with a generated header and truncated to the original size in their CAM entry,
+    /// which is the size returned for them. Returns `None` for WAV files without a CAM entry in a loaded CAM,
+    /// as they cannot be extracted either. Disabling [`ExtractOptions::wav_transcode`] extracts them as large as their preload data and file parts instead.
+    fn file_size(&self, file_path: &str) -> Option<u64> {
+        let entry = self.tree.get(file_path)?;
+
+        if is_wav(file_path) {
+            let cam_entry = self.wav_cam_entry(file_path, entry).ok()?;
+            if cam_entry.original_size > 0 {
+                return Some(cam_entry.original_size.into());
+            }
+        }
+
+        Some(
+            u64::from(entry.preload_length)
+                + entry
+                    .file_parts
+                    .iter()
+                    .map(|part| part.entry_length_uncompressed)
+                    .sum::<u64>(),
+        )
+    }
+
+    fn file_crc(&self, file_path: &str) -> Option<u32> {
+        self.tree.get(file_path).map(|entry| entry.crc)
+    }
+
+    fn file_location(&self, file_path: &str) -> Option<FileLocation> {
+        let entry = self.tree.get(file_path)?;
+
+        Some(if entry.file_parts.is_empty() {
+            FileLocation::Preload
+        } else {
+            FileLocation::MultiPart {
+                parts: entry
+                    .file_parts
+                    .iter()
+                    .map(|part| FilePartLocation {
+                        index: part.archive_index,
+                        offset: part.entry_offset,
+                        length: part.entry_length,
+                        uncompressed_length: part.entry_length_uncompressed,
+                    })
+                    .collect(),
+            }
+        })
+    }
+
+    /// WAV files keep the sizes declared by their CAM entry in their header, see [`ExtractOptions::fix_wav_sizes`].
+    fn read_file_streaming(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+    ) -> Result<Box<dyn Read + '_>> {
+        let entry = self
+            .tree
+            .get(file_path)
+            .ok_or(Error::FileNotFound(file_path.to_string()))?;
+
+        let preload: &[u8] = if entry.preload_length > 0 {
+            self.tree
+                .get_preload(file_path)
+                .ok_or(Error::DataNotFound(file_path.to_string()))?
+        } else {
+            &[]
+        };
+
         if entry.file_parts.is_empty() {
             return Err(Error::BadData("File had no parts".to_string()));
         }
 
-        let mut archive_index = entry.file_parts[0].archive_index;
-        let _path = Path::new(archive_path).join(format!(
-            "{}_{:0>3}.vpk",
-            vpk_name,
-            archive_index.to_string()
-        ));
-
-        let mut archive_file = archive_mmaps
-            .get(&archive_index)
-            .ok_or(Error::MemoryMappedFileNotFound(archive_index))?;
-
-        archive_file.prefetch(
-            entry.file_parts[0]
-                .entry_offset
-                .try_into()
-                .map_err(|_| Error::DataTooLarge)?,
-            entry.file_parts[0]
-                .entry_length
-                .try_into()
-                .map_err(|_| Error::DataTooLarge)?,
-        );
+        let is_wav = is_wav(file_path);
+        let parts =
+            FilePartsReader::new(self, archive_path, vpk_name, file_path, &entry.file_parts)?;
 
-        // We have to do extra processing if it's a wav file
-        let mut expected_len = entry
-            .file_parts
-            .iter()
-            .map(|e| e.entry_length_uncompressed as u32)
-            .sum();
-        if std::path::Path::new(file_path)
-            .extension()
-            .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
-        {
-            let cam_entry = if let Some(cam) = self.archive_cams.get(&archive_index) {
-                if let Some(cam_entry) = cam.find_entry(entry.file_parts[0].entry_offset) {
-                    cam_entry.to_owned()
-                } else {
-                    VPKRespawnCamEntry::default(entry)
-                }
-            } else {
-                VPKRespawnCamEntry::default(entry)
-            };
+        if !is_wav {
+            return Ok(Box::new(CrcValidatingReader::new(
+                preload.chain(parts),
+                entry.crc,
+                file_path,
+            )));
+        }
 
-            expected_len = cam_entry.original_size;
+        // We can't check CRCs on wav files because the CRC wasn't calculated with the actual unpacked data
+        let cam_entry = self.wav_cam_entry(file_path, entry)?;
 
-            let header = create_wav_header(&cam_entry);
-            digest.update(&header);
-            out_file.write_all(&header).map_err(Error::Io)?;
+        let reader = preload
+            .chain(io::Cursor::new(create_wav_header(&cam_entry)?))
+            .chain(parts);
+
+        // WAV files are truncated to the original size in their CAM entry
+        if cam_entry.original_size > 0 {
+            Ok(Box::new(reader.take(cam_entry.original_size.into())))
+        } else {
+            Ok(Box::new(reader))
         }
+    }
 
-        // Set the length of the file
-        out_file.set_len(expected_len.into()).map_err(Error::Io)?;
+    fn try_read_file_with(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        options: &ExtractOptions,
+    ) -> Result<Vec<u8>> {
+        self.read_entry(archive_path, vpk_name, file_path, options)
+    }
 
-        let mut total_len = 0;
-        for (i, file_part) in entry.file_parts.iter().enumerate() {
-            // Prefetch next file part
-            if i < entry.file_parts.len() - 1 {
-                archive_mmaps
-                    .get(&archive_index)
-                    .ok_or(Error::MemoryMappedFileNotFound(archive_index))?
-                    .prefetch(
-                        entry.file_parts[i + 1]
-                            .entry_offset
-                            .try_into()
-                            .map_err(|_| Error::DataTooLarge)?,
-                        entry.file_parts[i + 1]
-                            .entry_length
-                            .try_into()
-                            .map_err(|_| Error::DataTooLarge)?,
-                    );
-            }
+    fn extract_file_with(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        output_path: &str,
+        options: &ExtractOptions,
+        progress: &mut dyn FnMut(u64, u64),
+    ) -> Result<()> {
+        let (file_path, entry) = self
+            .tree
+            .find(file_path, options.case_sensitivity)
+            .ok_or(Error::FileNotFound(file_path.to_string()))?;
+        let out_path = Path::new(output_path);
+
+        write_output_file(out_path, file_path, options.keep_partial, |mut out_file| {
+            self.write_entry(
+                &mut HashMap::new(),
+                archive_path,
+                vpk_name,
+                file_path,
+                entry,
+                &mut out_file,
+                options,
+                progress,
+            )
+        })?;
 
-            if file_part.entry_length_uncompressed > 0 {
-                if file_part.archive_index != archive_index {
-                    archive_index = file_part.archive_index;
+        fix_extracted_wav_file(file_path, entry, out_path, options)
+    }
 
-                    archive_file = archive_mmaps
-                        .get(&archive_index)
-                        .ok_or(Error::MemoryMappedFileNotFound(archive_index))?;
-                }
+    /// The sizes in the header of a WAV file whose data ends early are only corrected when extracting to a file
+    /// or reading into memory, see [`ExtractOptions::fix_wav_sizes`].
+    fn read_file_to_with(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        mut out: &mut dyn Write,
+        options: &ExtractOptions,
+        progress: &mut dyn FnMut(u64, u64),
+    ) -> Result<u64> {
+        let (file_path, entry) = self
+            .tree
+            .find(file_path, options.case_sensitivity)
+            .ok_or(Error::FileNotFound(file_path.to_string()))?;
 
-                let mut entry_offset = file_part.entry_offset;
-                let mut entry_len = file_part.entry_length;
+        self.write_entry(
+            &mut HashMap::new(),
+            archive_path,
+            vpk_name,
+            file_path,
+            entry,
+            &mut out,
+            options,
+            progress,
+        )
+    }
 
-                if i == 0
-                    && std::path::Path::new(file_path)
-                        .extension()
-                        .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
-                {
-                    let seek = seek_to_wav_data_mem_map(archive_file, entry_offset)
-                        .map_err(|e| Error::BadData(e.to_string()))?;
-                    entry_offset += seek;
-                    entry_len -= seek;
+    fn extract_files(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_paths: &[&str],
+        output_dir: &str,
+        mut progress: Option<ExtractProgress>,
+    ) -> Result<ExtractSummary> {
+        std::fs::create_dir_all(output_dir).map_err(Error::Io)?;
+
+        let mut summary = ExtractSummary::default();
+        let total = file_paths.len();
+
+        // Group entries by the archive of their first part and read every archive front to back
+        let mut entries = Vec::with_capacity(total);
+        for &file_path in file_paths {
+            match self.tree.get(file_path) {
+                Some(entry) => entries.push((file_path, entry)),
+                None => {
+                    summary.record(file_path, Err(Error::FileNotFound(file_path.to_string())));
                 }
+            }
+        }
+        entries.sort_by_key(|(file_path, entry)| {
+            (
+                entry
+                    .file_parts
+                    .first()
+                    .map(|part| (part.archive_index, part.entry_offset)),
+                *file_path,
+            )
+        });
+
+        let mut archives = HashMap::new();
+        let mut done = summary.errors.len();
+
+        for (i, &(file_path, entry)) in entries.iter().enumerate() {
+            let result = output_path(output_dir, file_path).and_then(|out_path| {
+                let written = write_output_file(&out_path, file_path, false, |mut out_file| {
+                    self.write_entry(
+                        &mut archives,
+                        archive_path,
+                        vpk_name,
+                        file_path,
+                        entry,
+                        &mut out_file,
+                        &ExtractOptions::default(),
+                        &mut |_, _| {},
+                    )
+                })?;
 
-                total_len += entry_len;
+                fix_extracted_wav_file(file_path, entry, &out_path, &ExtractOptions::default())?;
+                Ok(written)
+            });
 
-                if file_part.entry_length == file_part.entry_length_uncompressed {
-                    // Truncate WAV files that exceed their expected length
-                    if expected_len > 0
-                        && std::path::Path::new(file_path)
-                            .extension()
-                            .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
-                        && total_len > expected_len.into()
-                    {
-                        entry_len = entry_len + u64::from(expected_len) - total_len;
-                    }
+            let proceed = summary.record(file_path, result);
+            done += 1;
+
+            if let Some(progress) = progress.as_mut() {
+                progress(file_path, done, total);
+            }
 
-                    let part =
-                        &archive_file[(entry_offset as usize)..(entry_offset + entry_len) as usize];
+            if !proceed {
+                summary.not_attempted.extend(
+                    entries[i..]
+                        .iter()
+                        .map(|(file_path, _)| (*file_path).to_string()),
+                );
+                break;
+            }
+        }
 
-                    out_file.write_all(part).map_err(Error::Io)?;
+        Ok(summary)
+    }
 
-                    digest.update(part);
-                } else {
-                    let compressed_data = archive_file
-                        .get(
-                            file_part.entry_offset as usize
-                                ..(file_part.entry_offset + entry_len) as usize,
+    fn check_archive_consistency(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        samples_per_archive: usize,
+    ) -> Vec<ArchiveConsistency> {
+        let mut by_archive: BTreeMap<u16, Vec<(&String, &VPKDirectoryEntryRespawn)>> =
+            BTreeMap::new();
+        for (file_path, entry) in &self.tree.files {
+            // WAV files can't be verified, files are attributed to the archive of their first part
+            if let Some(file_part) = entry.file_parts.first()
+                && !is_wav(file_path)
+            {
+                by_archive
+                    .entry(file_part.archive_index)
+                    .or_default()
+                    .push((file_path, entry));
+            }
+        }
+
+        let mut archives = HashMap::new();
+        by_archive
+            .into_iter()
+            .map(|(archive_index, mut entries)| {
+                entries.sort_unstable_by_key(|(file_path, entry)| {
+                    (entry.file_parts[0].entry_offset, *file_path)
+                });
+
+                let result = sample_evenly(&entries, samples_per_archive).try_for_each(
+                    |(file_path, entry)| {
+                        self.write_entry(
+                            &mut archives,
+                            archive_path,
+                            vpk_name,
+                            file_path,
+                            entry,
+                            &mut io::sink(),
+                            &ExtractOptions::default(),
+                            &mut |_, _| {},
                         )
-                        .ok_or(Error::FileNotFound(
-                            "Failed to read from archive file".to_string(),
-                        ))?
-                        .to_vec();
-
-                    let decompressed = decompress(
-                        &compressed_data,
-                        file_part
-                            .entry_length_uncompressed
-                            .try_into()
-                            .map_err(|_| Error::DataTooLarge)?,
-                    );
-
-                    out_file.write_all(&decompressed).map_err(Error::Io)?;
-
-                    digest.update(&decompressed);
+                        .map(|_| ())
+                    },
+                );
+
+                ArchiveConsistency::from_result(archive_index, result)
+            })
+            .collect()
+    }
+
+    fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = self.tree.validate();
+
+        let mut file_paths: Vec<&String> = self.tree.files.keys().collect();
+        file_paths.sort_unstable();
+
+        for file_path in file_paths {
+            let entry = &self.tree.files[file_path];
+            if entry.file_parts.is_empty() {
+                issues.push(ValidationIssue::file(
+                    ValidationIssueKind::NoFileParts,
+                    file_path,
+                    "Entry has no file parts".to_string(),
+                ));
+            }
+
+            for (i, part) in entry.file_parts.iter().enumerate() {
+                if part.entry_length > part.entry_length_uncompressed {
+                    issues.push(ValidationIssue::file(
+                        ValidationIssueKind::FilePartTooLarge,
+                        file_path,
+                        format!(
+                            "File part {i} is {} bytes but only {} bytes uncompressed",
+                            part.entry_length, part.entry_length_uncompressed
+                        ),
+                    ));
                 }
             }
         }
 
-        // We can't check CRCs on wav files because the CRC wasn't calculated with the actual unpacked data
-        if digest.finalize() != entry.crc
-            && !std::path::Path::new(file_path)
-                .extension()
-                .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
-        {
-            Err(Error::BadData("CRC must match".to_string()))
-        } else {
-            Ok(())
+        issues.sort_by(|a, b| a.path.cmp(&b.path));
+        issues
+    }
+
+    #[cfg(feature = "mem-map")]
+    fn extract_file_mem_map_with(
+        &self,
+        _archive_path: &str,
+        archive_mmaps: &HashMap<u16, FileBuffer>,
+        _vpk_name: &str,
+        file_path: &str,
+        output_path: &str,
+        options: &ExtractOptions,
+    ) -> Result<()> {
+        let (file_path, entry) = self
+            .tree
+            .find(file_path, options.case_sensitivity)
+            .ok_or(Error::FileNotFound(file_path.to_string()))?;
+        let out_path = Path::new(output_path);
+
+        let archive_mmap = |archive_index| {
+            archive_mmaps
+                .get(&archive_index)
+                .ok_or(Error::MemoryMappedFileNotFound(archive_index))
+        };
+
+        if let Some(first_part) = entry.file_parts.first() {
+            prefetch_part(archive_mmap(first_part.archive_index)?, first_part);
         }
+
+        write_output_file(out_path, file_path, options.keep_partial, |mut out_file| {
+            self.write_entry_parts(
+                file_path,
+                entry,
+                &mut out_file,
+                options,
+                &mut |_, _| {},
+                &mut |i, file_part, skip_wav_header, limit, emit| {
+                    let archive_file = archive_mmap(file_part.archive_index)?;
+
+                    if let Some(next_part) = entry.file_parts.get(i + 1) {
+                        prefetch_part(archive_mmap(next_part.archive_index)?, next_part);
+                    }
+
+                    let mut entry_offset = file_part.entry_offset;
+                    let mut entry_len = file_part.entry_length;
+
+                    if skip_wav_header {
+                        let seek = seek_to_wav_data_mem_map(archive_file, entry_offset)
+                            .map_err(|e| Error::BadData(format!("{file_path}: {e}")))?;
+                        entry_offset = entry_offset.checked_add(seek).ok_or_else(|| {
+                            Error::BadData(format!("WAV data of {file_path} starts past 16 EiB"))
+                        })?;
+                        entry_len = entry_len.checked_sub(seek).ok_or_else(|| {
+                            Error::BadData(format!(
+                                "WAV data of {file_path} starts past the end of its first file part"
+                            ))
+                        })?;
+                    }
+
+                    if file_part.entry_length == file_part.entry_length_uncompressed {
+                        // Stop reading once a truncated WAV file is complete
+                        let part = slice_archive(
+                            archive_file,
+                            entry_offset,
+                            entry_len.min(limit),
+                            file_path,
+                        )?;
+                        self.observer
+                            .bytes_read(file_part.archive_index, part.len() as u64);
+
+                        emit(part)
+                    } else {
+                        let start = usize::try_from(entry_offset).unwrap_or(usize::MAX);
+                        let compressed_data = usize::try_from(entry_len)
+                            .ok()
+                            .and_then(|len| start.checked_add(len))
+                            .and_then(|end| archive_file.get(start..end))
+                            .ok_or_else(|| Error::TruncatedPart {
+                                path: file_path.to_string(),
+                                part_index: i,
+                                expected: entry_len,
+                                got: archive_file.len().saturating_sub(start) as u64,
+                            })?;
+                        self.observer
+                            .bytes_read(file_part.archive_index, compressed_data.len() as u64);
+
+                        emit(&decompress_part(compressed_data, file_part, file_path)?)
+                    }
+                },
+            )
+        })?;
+
+        fix_extracted_wav_file(file_path, entry, out_path, options)
     }
 }
 
 impl PakWriter for VPKRespawn {
     fn write_dir(&self, output_path: &str) -> Result<()> {
+        self.write_dir_with(output_path, &WriteOptions::default())
+            .map(|_| ())
+    }
+}
+
+/// A summary of the changes made to the tree while writing it with [`VPKRespawn::write_dir_with`].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RespawnWriteReport {
+    /// The paths of files whose empty file parts were dropped, see [`VPKDirectoryEntryRespawn::written_parts`]. Sorted alphabetically.
+    pub dropped_empty_parts: Vec<String>,
+}
+
+impl VPKRespawn {
+    /// Writes the directory file like [`PakWriter::write_dir`] and reports the changes made to the tree while writing it.
+    ///
+    /// Empty file parts are dropped, as the reference tools reject directory files containing them.
+    /// The offsets of all other parts are left untouched.
+    /// # Errors
+    /// - When `options.strict` is set and a file has empty file parts
+    /// - When the tree is too large
+    /// - When an IO operation fails
+    pub fn write_dir_with(
+        &self,
+        output_path: &str,
+        options: &WriteOptions,
+    ) -> Result<RespawnWriteReport> {
+        let mut dropped_empty_parts: Vec<String> = self
+            .tree
+            .files
+            .iter()
+            .filter(|(_, entry)| entry.written_parts().len() != entry.file_parts.len())
+            .map(|(file_path, _)| file_path.clone())
+            .collect();
+        dropped_empty_parts.sort_unstable();
+
+        if options.strict && !dropped_empty_parts.is_empty() {
+            return Err(Error::BadData(format!(
+                "Files contain empty file parts: {}",
+                dropped_empty_parts.join(", ")
+            )));
+        }
+
         let out_path = std::path::Path::new(output_path);
         if let Some(prefix) = out_path.parent() {
             std::fs::create_dir_all(prefix).map_err(Error::Io)?;
         };
 
-        let mut out_file = File::create(out_path).map_err(Error::Io)?;
+        // The header always describes the tree as written, even if the tree changed since it was parsed
+        let (tree, tree_size) = self.tree.serialize()?;
 
-        self.header.write(&mut out_file)?;
-        self.tree.write(&mut out_file)?;
+        let mut out_file = BufWriter::new(File::create(out_path).map_err(Error::Io)?);
+        VPKHeaderRespawn {
+            tree_size,
+            ..self.header.clone()
+        }
+        .write(&mut out_file)?;
+        out_file.write_all(&tree).map_err(Error::Io)?;
+        out_file.flush().map_err(Error::Io)?;
 
-        Ok(())
+        Ok(RespawnWriteReport {
+            dropped_empty_parts,
+        })
     }
 }
 
@@ -1019,14 +2099,21 @@ impl PakWorker for VPKRespawn {
             },
             tree: VPKTree::new(),
             archive_cams: HashMap::new(),
+            observer: Observer::default(),
+            naming: Naming::default(),
+            cam_cache: SharedCamCache::default(),
         }
     }
 
-    fn from_file(file: &mut File) -> Result<Self> {
+    fn from_reader_with_options<R: Read + Seek>(
+        file: &mut R,
+        options: &ParseOptions,
+    ) -> Result<Self> {
         let header = VPKHeaderRespawn::from(file)?;
 
         let tree_start = file.stream_position().map_err(Error::Io)?;
-        let tree = VPKTree::from(file, tree_start, header.tree_size.into())?;
+        let tree_size = checked_tree_size(file, tree_start, header.tree_size.into(), options)?;
+        let tree = VPKTree::from_with_options(file, tree_start, tree_size, options)?;
 
         let archive_cams = HashMap::new();
 
@@ -1034,42 +2121,63 @@ impl PakWorker for VPKRespawn {
             header,
             tree,
             archive_cams,
+            observer: Observer::default(),
+            naming: Naming::default(),
+            cam_cache: SharedCamCache::default(),
         })
     }
+
+    #[cfg(feature = "detect")]
+    fn format(&self) -> crate::detect::PakFormat {
+        crate::detect::PakFormat::VPKRespawn
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
 }
 
 impl VPKRespawn {
-    /// Reads a CAM file and adds it to the map of parsed CAMs for this VPK
+    /// Reads a CAM file and adds it to the map of parsed CAMs for this VPK.
+    /// The CAM is taken from the CAM cache if one is set, see [`Self::set_cam_cache`].
     pub fn read_cam(&mut self, archive_index: u16, cam_path: &String) -> Result<()> {
-        let mut cam_file = File::open(cam_path).map_err(Error::Io)?;
-
-        let cam = VPKRespawnCam::from_file(&mut cam_file)?;
+        let cam = match &self.cam_cache.0 {
+            Some(cam_cache) => cam_cache.get(Path::new(cam_path))?,
+            None => {
+                let mut cam_file = File::open(cam_path).map_err(Error::Io)?;
+                Arc::new(VPKRespawnCam::from_file(&mut cam_file)?)
+            }
+        };
         self.archive_cams.insert(archive_index, cam);
 
         Ok(())
     }
 
-    /// Reads all CAM files for this VPK and adds them to the map of parsed CAMs for this VPK
-    pub fn read_all_cams(&mut self, archive_path: &String, vpk_name: &String) -> Result<()> {
+    /// Reads all CAM files for this VPK and adds them to the map of parsed CAMs for this VPK.
+    /// CAMs are taken from the CAM cache if one is set, see [`Self::set_cam_cache`].
+    pub fn read_all_cams(&mut self, archive_path: &str, vpk_name: &str) -> Result<()> {
         let mut archive_indices = HashSet::<u16>::new();
-        for (path, entry) in &mut self.tree.files {
-            let archive_index = entry.file_parts[0].archive_index;
-            if path.ends_with(".wav") {
-                archive_indices.insert(archive_index);
+        for (path, entry) in &self.tree.files {
+            // Placeholder entries have no data, so they have no CAM entry either
+            let Some(file_part) = entry.file_parts.first() else {
+                continue;
+            };
+
+            if is_wav(path) {
+                archive_indices.insert(file_part.archive_index);
             }
         }
 
         let mut res = Result::Ok(());
 
-        let path = Path::new(archive_path);
         for archive_index in archive_indices {
             if !self.archive_cams.contains_key(&archive_index) {
-                let cam_path = path
-                    .join(format!(
-                        "{}_{:0>3}.vpk.cam",
-                        vpk_name,
-                        archive_index.to_string()
-                    ))
+                let cam_path = self.cam_path(archive_path, vpk_name, archive_index);
+                let cam_path = cam_path
                     .to_str()
                     .ok_or(Error::BadData(format!(
                         "Failed to determine CAM path for archive {archive_index}"
@@ -1092,6 +2200,299 @@ impl VPKRespawn {
 
         res
     }
+
+    /// Writes every parsed CAM of this VPK to the CAM file of its archive, `{vpk_name}_{archive_index}.vpk.cam` in `archive_path`,
+    /// the files [`Self::read_all_cams`] reads. Existing CAM files are replaced.
+    /// # Errors
+    /// - When an entry's magic is not [`RESPAWN_CAM_ENTRY_MAGIC`]
+    /// - When an IO operation fails
+    pub fn write_all_cams(&self, archive_path: &str, vpk_name: &str) -> Result<()> {
+        let mut archive_indices: Vec<&u16> = self.archive_cams.keys().collect();
+        archive_indices.sort_unstable();
+
+        for &archive_index in archive_indices {
+            let cam_path = self.cam_path(archive_path, vpk_name, archive_index);
+            let cam_path = cam_path.to_str().ok_or(Error::BadData(format!(
+                "Failed to determine CAM path for archive {archive_index}"
+            )))?;
+
+            self.archive_cams[&archive_index].write_to_file(cam_path)?;
+        }
+
+        Ok(())
+    }
+
+    /// The path of the CAM file of an archive, the path of the archive with `.cam` appended.
+    fn cam_path(&self, archive_path: &str, vpk_name: &str, archive_index: u16) -> PathBuf {
+        let mut cam_path = self
+            .naming
+            .archive_path(archive_path, vpk_name, archive_index)
+            .into_os_string();
+        cam_path.push(".cam");
+        cam_path.into()
+    }
+
+    /// Writes the given files to the archive `{vpk_name}_{archive_index}.vpk` in `archive_path` and adds their entries to the tree.
+    ///
+    /// Files are split into parts of at most [`RESPAWN_MAX_PART_SIZE`] bytes. Each part is compressed with LZHAM,
+    /// but stored uncompressed if compression does not make it smaller. Flags of files already in the tree are kept.
+    ///
+    /// WAV files are stored as-is, without a CAM entry.
+    /// The archive is named like the archives read by this VPK, see [`Self::set_archive_naming`].
+    /// # Errors
+    /// - When an IO operation fails
+    pub fn write_archive(
+        &mut self,
+        archive_path: &str,
+        vpk_name: &str,
+        archive_index: u16,
+        files: &[(&str, &[u8])],
+    ) -> Result<()> {
+        self.write_archive_with(
+            archive_path,
+            vpk_name,
+            archive_index,
+            files,
+            &LzhamParams::default(),
+        )
+    }
+
+    /// Writes the given files to the archive `{vpk_name}_{archive_index}.vpk` in `archive_path` and adds their entries to the tree,
+    /// compressing them with the given LZHAM parameters, see [`Self::write_archive`].
+    /// # Errors
+    /// - When the dictionary size is not supported by LZHAM
+    /// - When an IO operation fails
+    pub fn write_archive_with(
+        &mut self,
+        archive_path: &str,
+        vpk_name: &str,
+        archive_index: u16,
+        files: &[(&str, &[u8])],
+        params: &LzhamParams,
+    ) -> Result<()> {
+        if !(LZHAM_MIN_DICT_SIZE_LOG2..=LZHAM_MAX_DICT_SIZE_LOG2).contains(&params.dict_size_log2) {
+            return Err(Error::BadData(format!(
+                "LZHAM dictionary size 2^{} is not between 2^{LZHAM_MIN_DICT_SIZE_LOG2} and 2^{LZHAM_MAX_DICT_SIZE_LOG2}",
+                params.dict_size_log2
+            )));
+        }
+
+        let mut out_file = self.create_archive(archive_path, vpk_name, archive_index)?;
+        let mut offset = 0;
+
+        for &(file_path, mut data) in files {
+            self.write_archive_entry(
+                &mut out_file,
+                &mut offset,
+                archive_index,
+                file_path,
+                &mut data,
+                params,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the files at the given source paths to the archive `{vpk_name}_{archive_index}.vpk` in `archive_path`
+    /// and adds their entries to the tree, see [`Self::write_archive`].
+    ///
+    /// Files are read one part at a time, so files of any size can be written, including files larger than 4 GiB.
+    /// # Errors
+    /// - When a source file cannot be read
+    /// - When an IO operation fails
+    pub fn write_archive_from_paths(
+        &mut self,
+        archive_path: &str,
+        vpk_name: &str,
+        archive_index: u16,
+        files: &[(&str, &Path)],
+    ) -> Result<()> {
+        let mut out_file = self.create_archive(archive_path, vpk_name, archive_index)?;
+        let mut offset = 0;
+
+        for (file_path, source_path) in files {
+            let mut source = BufReader::new(File::open(source_path).map_err(Error::Io)?);
+            self.write_archive_entry(
+                &mut out_file,
+                &mut offset,
+                archive_index,
+                file_path,
+                &mut source,
+                &LzhamParams::default(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Creates the archive file with the index `archive_index`, located with the archive naming of this VPK, along with its directory.
+    fn create_archive(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        archive_index: u16,
+    ) -> Result<File> {
+        let out_path = self
+            .naming
+            .archive_path(archive_path, vpk_name, archive_index);
+        if let Some(prefix) = out_path.parent() {
+            std::fs::create_dir_all(prefix).map_err(Error::Io)?;
+        }
+
+        File::create(out_path).map_err(Error::Io)
+    }
+
+    /// Writes the data read from `data` to `out_file` at `offset` in parts of at most [`RESPAWN_MAX_PART_SIZE`] bytes,
+    /// and adds the entry for `file_path`, normalized with [`normalize_vpk_path`], to the tree. `offset` is moved past the written parts.
+    fn write_archive_entry<R: Read>(
+        &mut self,
+        out_file: &mut File,
+        offset: &mut u64,
+        archive_index: u16,
+        file_path: &str,
+        data: &mut R,
+        params: &LzhamParams,
+    ) -> Result<()> {
+        let file_path = normalize_vpk_path(file_path, false);
+        let (load_flags, texture_flags) = self
+            .tree
+            .files
+            .get(file_path.as_ref())
+            .and_then(|entry| entry.file_parts.first())
+            .map_or((EPackedLoadFlags::LoadVisible as u16, 0), |part| {
+                (part.load_flags, part.texture_flags)
+            });
+
+        let (file_parts, crc) = Self::write_parts(out_file, data, offset, params)?;
+
+        let mut entry = VPKDirectoryEntryRespawn::new();
+        entry.crc = crc;
+        entry.file_parts = file_parts
+            .into_iter()
+            .map(|file_part| VPKFilePartEntryRespawn {
+                archive_index,
+                load_flags,
+                texture_flags,
+                ..file_part
+            })
+            .collect();
+
+        tree_map_remove(&mut self.tree.preload, &file_path);
+        self.tree.files.insert(file_path.into_owned(), entry);
+
+        Ok(())
+    }
+
+    /// Writes the given files to a new archive at `output_path`, one after another, and returns the file parts describing each file.
+    ///
+    /// Files are split into parts of at most [`RESPAWN_MAX_PART_SIZE`] bytes and every part is compressed with LZHAM,
+    /// but stored uncompressed if compression does not make it smaller. Empty files are described by a single empty part.
+    /// Unlike [`Self::write_archive`], the tree is not modified, the returned parts are meant to be
+    /// added to a [`VPKDirectoryEntryRespawn`] by the caller. Their archive index is 0 and must be set to the index of the archive.
+    /// # Errors
+    /// - When an IO operation fails
+    pub fn write_compressed_archive(
+        files: &[(String, Vec<u8>)],
+        output_path: &str,
+    ) -> Result<Vec<Vec<VPKFilePartEntryRespawn>>> {
+        let mut out_file = File::create(output_path).map_err(Error::Io)?;
+
+        let mut offset = 0;
+        files
+            .iter()
+            .map(|(_, data)| {
+                Self::write_parts(
+                    &mut out_file,
+                    &mut data.as_slice(),
+                    &mut offset,
+                    &LzhamParams::default(),
+                )
+                .map(|(file_parts, _)| file_parts)
+            })
+            .collect()
+    }
+
+    /// Writes the data read from `data` at `offset` in an archive in parts of at most [`RESPAWN_MAX_PART_SIZE`] bytes,
+    /// returning the entries of the parts and the CRC of the data. `offset` is moved past the written parts.
+    ///
+    /// Empty data is written as a single empty part.
+    fn write_parts<W: Write, R: Read>(
+        out: &mut W,
+        data: &mut R,
+        offset: &mut u64,
+        params: &LzhamParams,
+    ) -> Result<(Vec<VPKFilePartEntryRespawn>, u32)> {
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+        let mut digest = crc.digest();
+        let mut file_parts = Vec::new();
+        let mut part = Vec::with_capacity(RESPAWN_MAX_PART_SIZE);
+
+        loop {
+            part.clear();
+            data.by_ref()
+                .take(RESPAWN_MAX_PART_SIZE as u64)
+                .read_to_end(&mut part)
+                .map_err(Error::Io)?;
+
+            // Empty files still need a single (empty) part
+            if part.is_empty() && !file_parts.is_empty() {
+                break;
+            }
+
+            digest.update(&part);
+
+            let file_part = Self::write_part(out, &part, *offset, params)?;
+            *offset += file_part.entry_length;
+            file_parts.push(file_part);
+
+            if part.len() < RESPAWN_MAX_PART_SIZE {
+                break;
+            }
+        }
+
+        Ok((file_parts, digest.finalize()))
+    }
+
+    /// Writes a single file part at `offset` in an archive, compressed if that makes it smaller, and returns its entry.
+    fn write_part<W: Write>(
+        out: &mut W,
+        part: &[u8],
+        offset: u64,
+        params: &LzhamParams,
+    ) -> Result<VPKFilePartEntryRespawn> {
+        if part.is_empty() {
+            return Ok(VPKFilePartEntryRespawn {
+                archive_index: 0,
+                load_flags: EPackedLoadFlags::LoadVisible as u16,
+                texture_flags: 0,
+                entry_offset: offset,
+                entry_length: 0,
+                entry_length_uncompressed: 0,
+            });
+        }
+
+        let compressed = compress(part, params).map_err(|e| Error::Util {
+            source: util::Error::Lzham(e),
+            context: "Failed to compress file part".to_string(),
+        })?;
+        let stored = if compressed.len() < part.len() {
+            compressed.as_slice()
+        } else {
+            part
+        };
+
+        out.write_all(stored).map_err(Error::Io)?;
+
+        Ok(VPKFilePartEntryRespawn {
+            archive_index: 0,
+            load_flags: EPackedLoadFlags::LoadVisible as u16,
+            texture_flags: 0,
+            entry_offset: offset,
+            entry_length: stored.len() as u64,
+            entry_length_uncompressed: part.len() as u64,
+        })
+    }
 }
 
 impl TryFrom<&mut File> for VPKRespawn {
@@ -3,5 +3,10 @@
 pub use cam::*;
 pub use format::*;
 
+pub use crate::util::lzham::{
+    LZHAM_DECOMP_STATUS_FAILED_ADLER32, LZHAM_MAX_DICT_SIZE_LOG2, LZHAM_MIN_DICT_SIZE_LOG2,
+    LzhamError, LzhamLevel, LzhamParams, decompress_with_adler32,
+};
+
 mod cam;
 mod format;
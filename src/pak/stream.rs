@@ -0,0 +1,76 @@
+//! Streaming the contents of files stored in a VPK, see [`PakReader::read_file_streaming`](super::PakReader::read_file_streaming).
+
+use super::Error;
+use crc::{CRC_32_ISO_HDLC, Crc, Digest};
+use std::io::{self, Read};
+
+static CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+/// A reader that computes the CRC of the data read through it and verifies it once the inner reader is fully consumed.
+///
+/// When the end of the data is reached and the CRC does not match, the final read fails with an [`io::Error`]
+/// of kind [`io::ErrorKind::InvalidData`] wrapping an [`Error::CrcMismatch`]. Every later read fails with the same error,
+/// so the mismatch can't be mistaken for the end of the data.
+pub struct CrcValidatingReader<R> {
+    inner: R,
+    digest: Option<Digest<'static, u32>>,
+    expected: u32,
+    file_path: String,
+    /// The CRC of the data, once it turned out not to match.
+    mismatch: Option<u32>,
+}
+
+impl<R: Read> CrcValidatingReader<R> {
+    /// Wraps `inner`, expecting its data to have the CRC `expected`. `file_path` is used in the error on a mismatch.
+    pub fn new(inner: R, expected: u32, file_path: &str) -> Self {
+        Self {
+            inner,
+            digest: Some(CRC.digest()),
+            expected,
+            file_path: file_path.to_string(),
+            mismatch: None,
+        }
+    }
+
+    /// Returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn mismatch_error(&self, found: u32) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            Error::CrcMismatch {
+                expected: self.expected,
+                found,
+                file: self.file_path.clone(),
+            },
+        )
+    }
+}
+
+impl<R: Read> Read for CrcValidatingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(found) = self.mismatch {
+            return Err(self.mismatch_error(found));
+        }
+
+        let read = self.inner.read(buf)?;
+
+        if read > 0 {
+            if let Some(digest) = self.digest.as_mut() {
+                digest.update(&buf[..read]);
+            }
+        } else if !buf.is_empty()
+            && let Some(digest) = self.digest.take()
+        {
+            let found = digest.finalize();
+            if found != self.expected {
+                self.mismatch = Some(found);
+                return Err(self.mismatch_error(found));
+            }
+        }
+
+        Ok(read)
+    }
+}
@@ -1,28 +1,50 @@
 //! Support for the VPK version 1 format.
 
-use super::{Error, PakReader, PakWorker, PakWriter, Result, VPKDirectoryEntry, VPKTree};
+use super::{
+    ArchiveConsistency, ArchiveFile, ArchiveNaming, CaseSensitivity, CrcValidatingReader, Error,
+    ExtensionFilter, ExtractOptions, ExtractProgress, ExtractSummary, FileLocation, FormatCaps,
+    IoObserver, Naming, Observer, PakReader, PakWorker, PakWriter, ParseOptions, Result,
+    VPKDirectoryEntry, VPKTree, ValidationIssue, checked_tree_size, extract_to_dir, output_path,
+    read_archive_chunk, sample_evenly, validate::validate_tree, write_output_file,
+};
 use crate::util::file::{VPKFileReader, VPKFileWriter};
+use crate::util::path::split_dir_file_path;
 use crc::{CRC_32_ISO_HDLC, Crc};
+use std::any::Any;
 use std::cmp::min;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io::{Seek, SeekFrom, Write};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::mem;
 use std::path::Path;
+use std::sync::Arc;
 
 #[cfg(feature = "mem-map")]
 use filebuffer::FileBuffer;
 
-#[cfg(feature = "mem-map")]
-use std::collections::HashMap;
-
 /// The 4-byte signature found in the header of a valid VPK version 1 file.
 pub const VPK_SIGNATURE_V1: u32 = 0x55AA_1234;
 /// The 4-byte version found in the header of a valid VPK version 1 file.
 pub const VPK_VERSION_V1: u32 = 1;
+/// The archive index of an entry whose data is stored in the directory file, directly after the tree.
+///
+/// When using [`PakReader::extract_file_mem_map`], the memory-mapped directory file must be provided under this key.
+pub const VPK_DIR_ARCHIVE_INDEX: u16 = 0xFF7F;
+
+/// The operations supported for VPK version 1 files. Archives are written by [`PakBuilder`](crate::pak::PakBuilder).
+pub const VPK_CAPS_V1: FormatCaps = FormatCaps {
+    read: true,
+    write_dir: true,
+    write_archives: true,
+    compression: false,
+    signatures: false,
+};
 
 /// The header of a VPK version 1 file.
 #[repr(C)]
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VPKHeaderV1 {
     /// VPK signature. Should be equal to [`VPK_SIGNATURE_V1`].
     pub signature: u32,
@@ -39,7 +61,7 @@ impl VPKHeaderV1 {
     /// - When the data is invalid
     /// - When the signature is invalid
     /// - When the version does not match
-    pub fn from(file: &mut File) -> Result<Self> {
+    pub fn from<R: Read>(file: &mut R) -> Result<Self> {
         let signature = file.read_u32().map_err(|e| Error::Util {
             source: e,
             context: "Failed to read signature".to_string(),
@@ -81,7 +103,7 @@ impl VPKHeaderV1 {
     /// - When the data is invalid
     /// - When the signature is invalid
     /// - When the version does not match
-    pub fn write(&self, file: &mut File) -> Result<()> {
+    pub fn write<W: Write>(&self, file: &mut W) -> Result<()> {
         if self.signature != VPK_SIGNATURE_V1 {
             return Err(Error::InvalidSignature(format!(
                 "Header signature should be {VPK_SIGNATURE_V1:#X} but is {:#X}",
@@ -115,7 +137,7 @@ impl VPKHeaderV1 {
     }
 
     /// Check if a file is in the VPK version 1 format.
-    pub fn is_format(file: &mut File) -> bool {
+    pub fn is_format<R: Read + Seek>(file: &mut R) -> bool {
         let Ok(pos) = file.stream_position() else {
             return false;
         };
@@ -131,236 +153,768 @@ impl VPKHeaderV1 {
 
 /// The VPK version 1 format.
 #[derive(PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VPKVersion1 {
     /// The VPK's header.
     pub header: VPKHeaderV1,
     /// The tree of files in the VPK.
     pub tree: VPKTree<VPKDirectoryEntry>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    observer: Observer,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    naming: Naming,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    archive_ends: ArchiveEnds,
 }
 
-impl PakReader for VPKVersion1 {
-    fn read_file(&self, archive_path: &str, vpk_name: &str, file_path: &str) -> Option<Vec<u8>> {
-        let entry = self.tree.files.get(file_path)?;
-        let mut buf: Vec<u8> = Vec::new();
-
-        if entry.preload_length > 0 {
-            buf.append(self.tree.preload.get(file_path)?.clone().as_mut());
+/// The end of the data of every archive, tracked so [`VPKVersion1::add_file`] doesn't scan the tree for every file added.
+///
+/// Computed from the tree on the first [`VPKVersion1::add_file`] and kept up to date by [`VPKVersion1::upsert_entry`].
+#[derive(Default)]
+struct ArchiveEnds(Option<HashMap<u16, u64>>);
+
+impl ArchiveEnds {
+    /// Records that the archive `archive_index` contains data up to `end`, if the ends are tracked.
+    fn extend(&mut self, archive_index: u16, end: u64) {
+        if let Some(ends) = &mut self.0 {
+            let archive_end = ends.entry(archive_index).or_default();
+            *archive_end = (*archive_end).max(end);
         }
+    }
+}
 
-        if entry.entry_length > 0 {
-            let mut archive_file = if entry.archive_index == 0xFF7F {
-                let path = Path::new(archive_path).join(format!("{vpk_name}_dir.vpk"));
-
-                let mut archive_file = File::open(path).ok()?;
-                let _ = archive_file.seek(SeekFrom::Start(
-                    mem::size_of::<VPKHeaderV1>() as u64
-                        + u64::from(self.header.tree_size)
-                        + u64::from(entry.entry_offset),
-                ));
-                archive_file
-            } else {
-                let path = Path::new(archive_path).join(format!(
-                    "{}_{:0>3}.vpk",
-                    vpk_name,
-                    entry.archive_index.to_string()
-                ));
-
-                let mut archive_file = File::open(path).ok()?;
-                let _ = archive_file.seek(SeekFrom::Start(entry.entry_offset.into()));
-                archive_file
-            };
-
-            buf.append(
-                archive_file
-                    .read_bytes(entry.entry_length.try_into().ok()?)
-                    .ok()?
-                    .as_mut(),
-            );
-        }
+impl PartialEq for ArchiveEnds {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
 
-        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
-        let mut digest = crc.digest();
-        digest.update(&buf);
+impl Eq for ArchiveEnds {}
 
-        if digest.finalize() == entry.crc {
-            Some(buf)
-        } else {
-            None
-        }
+impl VPKVersion1 {
+    /// Recomputes the tree size in the header from the tree, e.g. after files were added or removed.
+    ///
+    /// [`PakWriter::write_dir`] always writes the size of the tree it writes, this updates the header kept in memory to match.
+    /// # Errors
+    /// - When the preload data does not match the entries
+    /// - When the tree is too large for the header
+    pub fn rebuild_header_tree_size(&mut self) -> Result<()> {
+        self.header.tree_size = self.tree.serialize()?.1;
+        Ok(())
+    }
+
+    /// Sets the observer notified of every archive file opened and read by this VPK, or removes it when `None`.
+    pub fn set_observer(&mut self, observer: Option<Arc<dyn IoObserver>>) {
+        self.observer = Observer::new(observer);
+    }
+
+    /// Sets how archive indices map to the paths of archive files, or restores Valve's naming when `None`.
+    pub fn set_archive_naming(&mut self, naming: Option<Arc<dyn ArchiveNaming>>) {
+        self.naming = Naming::new(naming);
     }
 
-    fn extract_file(
+    /// Opens the archive file for an archive index, or the directory file for [`VPK_DIR_ARCHIVE_INDEX`].
+    fn archive_file(
         &self,
         archive_path: &str,
         vpk_name: &str,
-        file_path: &str,
-        output_path: &str,
-    ) -> Result<()> {
-        let entry = self
-            .tree
-            .files
-            .get(file_path)
-            .ok_or(Error::FileNotFound(file_path.to_string()))?;
+        archive_index: u16,
+    ) -> Result<ArchiveFile> {
+        let path = if archive_index == VPK_DIR_ARCHIVE_INDEX {
+            self.naming.dir_path(archive_path, vpk_name)
+        } else {
+            self.naming
+                .archive_path(archive_path, vpk_name, archive_index)
+        };
 
-        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
-        let mut digest = crc.digest();
+        self.observer
+            .open(archive_index, &path)
+            .map_err(|e| Error::ArchiveOpen {
+                path: path.display().to_string(),
+                source: e,
+            })
+    }
 
-        let out_path = std::path::Path::new(output_path);
-        if let Some(prefix) = out_path.parent() {
-            std::fs::create_dir_all(prefix).map_err(Error::Io)?;
+    /// Returns the archive file for an archive index, opening it if it isn't in `archives` yet.
+    fn open_archive<'a>(
+        &self,
+        archives: &'a mut HashMap<u16, ArchiveFile>,
+        archive_path: &str,
+        vpk_name: &str,
+        archive_index: u16,
+    ) -> Result<&'a mut ArchiveFile> {
+        match archives.entry(archive_index) {
+            Entry::Occupied(archive_file) => Ok(archive_file.into_mut()),
+            Entry::Vacant(slot) => {
+                Ok(slot.insert(self.archive_file(archive_path, vpk_name, archive_index)?))
+            }
         }
+    }
 
-        let mut out_file = File::create(out_path).map_err(Error::Io)?;
+    /// The offset of the data of an entry in its archive file.
+    fn data_offset(&self, entry: &VPKDirectoryEntry) -> u64 {
+        // Data stored in the directory file starts after the header and tree
+        if entry.archive_index == VPK_DIR_ARCHIVE_INDEX {
+            mem::size_of::<VPKHeaderV1>() as u64
+                + u64::from(self.header.tree_size)
+                + u64::from(entry.entry_offset)
+        } else {
+            entry.entry_offset.into()
+        }
+    }
 
-        // Set the length of the file
-        out_file
-            .set_len(entry.entry_length.into())
-            .map_err(Error::Io)?;
+    /// Writes the preload and archive data of an entry to `out` and verifies its CRC.
+    /// Archive files are opened as needed and kept in `archives` so they can be reused.
+    /// `progress` is called after every chunk with the bytes written so far and the total size of the entry.
+    #[allow(clippy::too_many_arguments)]
+    fn write_entry<W: Write>(
+        &self,
+        archives: &mut HashMap<u16, ArchiveFile>,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        entry: &VPKDirectoryEntry,
+        out: &mut W,
+        options: &ExtractOptions,
+        progress: &mut dyn FnMut(u64, u64),
+    ) -> Result<u64> {
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+        let mut digest = crc.digest();
+        let mut written = 0;
+        let total = u64::from(entry.preload_length) + u64::from(entry.entry_length);
 
         if entry.preload_length > 0 {
             let chunk = self
                 .tree
-                .preload
-                .get(file_path)
+                .get_preload(file_path)
                 .ok_or(Error::DataNotFound(file_path.to_string()))?;
 
-            out_file.write_all(chunk).map_err(Error::Io)?;
+            out.write_all(chunk).map_err(Error::Io)?;
+            written += chunk.len() as u64;
+            progress(written, total);
 
             digest.update(chunk);
         }
 
         if entry.entry_length > 0 {
-            let mut archive_file = if entry.archive_index == 0xFF7F {
-                let path = Path::new(archive_path).join(format!("{vpk_name}_dir.vpk"));
-
-                let mut archive_file = File::open(path).map_err(Error::Io)?;
-                let _ = archive_file.seek(SeekFrom::Start(
-                    mem::size_of::<VPKHeaderV1>() as u64
-                        + u64::from(self.header.tree_size)
-                        + u64::from(entry.entry_offset),
-                ));
-                archive_file
-            } else {
-                let path = Path::new(archive_path).join(format!(
-                    "{}_{:0>3}.vpk",
-                    vpk_name,
-                    entry.archive_index.to_string()
-                ));
-
-                let mut archive_file = File::open(path).map_err(Error::Io)?;
-                let _ = archive_file.seek(SeekFrom::Start(entry.entry_offset.into()));
-                archive_file
-            };
-
-            // read chunks of 1MB max into buffer and write to the output file
+            let archive_file =
+                self.open_archive(archives, archive_path, vpk_name, entry.archive_index)?;
+            archive_file
+                .seek(SeekFrom::Start(self.data_offset(entry)))
+                .map_err(Error::Io)?;
+
+            // read chunks of at most the chunk size into buffer and write to the output file
             let mut remaining = entry.entry_length as usize;
             while remaining > 0 {
-                let chunk = archive_file
-                    .read_bytes(min(1024 * 1024, remaining))
-                    .map_err(|e| Error::Util {
-                        source: e,
-                        context: "Failed to read archive section".to_string(),
-                    })?;
-
-                if chunk.is_empty() {
-                    return Err(Error::BadData("Archive is empty".to_string()));
-                }
+                let chunk = read_archive_chunk(
+                    archive_file,
+                    min(options.chunk_size(), remaining),
+                    remaining as u64,
+                    file_path,
+                )?;
 
-                out_file.write_all(&chunk).map_err(Error::Io)?;
+                out.write_all(&chunk).map_err(Error::Io)?;
+                written += chunk.len() as u64;
+                progress(written, total);
 
-                if remaining >= chunk.len() {
-                    remaining -= chunk.len();
-                } else {
-                    remaining = 0;
-                }
+                remaining -= chunk.len();
 
                 digest.update(&chunk);
             }
         }
 
-        if digest.finalize() == entry.crc {
-            Ok(())
+        let found = digest.finalize();
+        if !options.verify_crc || found == entry.crc {
+            Ok(written)
         } else {
-            Err(Error::BadData("CRC must match".to_string()))
+            Err(Error::CrcMismatch {
+                expected: entry.crc,
+                found,
+                file: file_path.to_string(),
+            })
         }
     }
+}
 
-    #[cfg(feature = "mem-map")]
-    fn extract_file_mem_map(
+impl VPKVersion1 {
+    /// Reads the contents of a file stored in the VPK into memory.
+    /// Archive files are opened as needed and kept in `archives` so they can be reused.
+    fn read_entry(
         &self,
-        _archive_path: &str,
-        archive_mmaps: &HashMap<u16, FileBuffer>,
-        _vpk_name: &str,
+        archives: &mut HashMap<u16, ArchiveFile>,
+        archive_path: &str,
+        vpk_name: &str,
         file_path: &str,
-        output_path: &str,
-    ) -> Result<()> {
-        let entry = self
+        options: &ExtractOptions,
+    ) -> Result<Vec<u8>> {
+        let (file_path, entry) = self
             .tree
-            .files
-            .get(file_path)
+            .find(file_path, options.case_sensitivity)
             .ok_or(Error::FileNotFound(file_path.to_string()))?;
+        let mut buf: Vec<u8> = Vec::new();
+
+        self.write_entry(
+            archives,
+            archive_path,
+            vpk_name,
+            file_path,
+            entry,
+            &mut buf,
+            options,
+            &mut |_, _| {},
+        )?;
+
+        Ok(buf)
+    }
+
+    /// Creates a reader for the archives `{vpk_name}_000.vpk`, ... in `archive_path`
+    /// that keeps every archive file open between reads, see [`ArchiveReader`].
+    #[must_use]
+    pub fn reader(&self, archive_path: &str, vpk_name: &str) -> ArchiveReader<'_> {
+        ArchiveReader {
+            vpk: self,
+            archive_path: archive_path.to_string(),
+            vpk_name: vpk_name.to_string(),
+            archives: HashMap::new(),
+        }
+    }
+
+    /// Read the contents of a file stored in the VPK, locating the archives next to the directory file at `dir_file_path`.
+    /// # Errors
+    /// - When `dir_file_path` is not the path of a directory file
+    /// - When the file does not exist in the VPK
+    /// - When an archive file cannot be opened, the error contains the path that was attempted
+    /// - When the data is invalid
+    pub fn read_file_rel(&self, dir_file_path: &Path, entry: &str) -> Result<Vec<u8>> {
+        let (archive_path, vpk_name) =
+            split_dir_file_path(dir_file_path).ok_or(Error::BadData(format!(
+                "Not the path of a directory file: {}",
+                dir_file_path.display()
+            )))?;
+
+        self.read_entry(
+            &mut HashMap::new(),
+            archive_path,
+            vpk_name,
+            entry,
+            &ExtractOptions::default(),
+        )
+    }
+
+    /// Inserts or replaces the entry for a file whose data is stored at `entry_offset` in the archive `archive_index`.
+    ///
+    /// The CRC is computed over `preload` followed by `data`, and the preload data is stored in the tree.
+    /// Writing `data` to the archive is left to the caller.
+    /// # Errors
+    /// - When `data` or `preload` are too large to be described by an entry
+    pub fn upsert_entry(
+        &mut self,
+        file_path: &str,
+        data: &[u8],
+        archive_index: u16,
+        entry_offset: u32,
+        preload: Option<Vec<u8>>,
+    ) -> Result<()> {
+        let preload = preload.unwrap_or_default();
 
         let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
         let mut digest = crc.digest();
+        digest.update(&preload);
+        digest.update(data);
+
+        let entry = VPKDirectoryEntry {
+            crc: digest.finalize(),
+            archive_index,
+            entry_offset,
+            entry_length: u32::try_from(data.len()).map_err(|_| Error::EntryTooLarge {
+                path: file_path.to_string(),
+                size: data.len() as u64,
+            })?,
+            ..Default::default()
+        };
 
-        let out_path = std::path::Path::new(output_path);
-        if let Some(prefix) = out_path.parent() {
-            std::fs::create_dir_all(prefix).map_err(Error::Io)?;
+        let end = u64::from(entry.entry_offset) + u64::from(entry.entry_length);
+        self.tree
+            .insert_file_with_preload(file_path, entry, preload)?;
+        self.archive_ends.extend(archive_index, end);
+
+        Ok(())
+    }
+
+    /// Adds a file whose data will be appended to the archive `archive_index`, returning the offset to write `data` at.
+    ///
+    /// The offset is the end of the data of all files already in that archive, so files must be written in the order they are added.
+    /// The ends of the archives are computed from the tree on the first call and tracked afterwards,
+    /// so removing files does not free up their space and entries changed through [`Self::tree`] directly are not taken into account.
+    /// # Errors
+    /// - When `data` is too large to be described by an entry
+    /// - When the archive would grow past the maximum offset
+    pub fn add_file(&mut self, file_path: &str, data: &[u8], archive_index: u16) -> Result<u32> {
+        if self.archive_ends.0.is_none() {
+            let mut ends = ArchiveEnds(Some(HashMap::new()));
+            for entry in self.tree.files.values() {
+                ends.extend(
+                    entry.archive_index,
+                    u64::from(entry.entry_offset) + u64::from(entry.entry_length),
+                );
+            }
+            self.archive_ends = ends;
         }
 
-        let mut out_file = File::create(out_path).map_err(Error::Io)?;
+        let entry_offset = self
+            .archive_ends
+            .0
+            .as_ref()
+            .and_then(|ends| ends.get(&archive_index).copied())
+            .unwrap_or(0);
+        let entry_offset = u32::try_from(entry_offset).map_err(|_| Error::DataTooLarge)?;
 
-        // Set the length of the file
-        out_file
-            .set_len(entry.entry_length.into())
-            .map_err(Error::Io)?;
+        self.upsert_entry(file_path, data, archive_index, entry_offset, None)?;
 
-        if entry.preload_length > 0 {
-            let chunk = self
-                .tree
-                .preload
-                .get(file_path)
-                .ok_or(Error::DataNotFound(file_path.to_string()))?;
+        Ok(entry_offset)
+    }
 
-            out_file.write_all(chunk).map_err(Error::Io)?;
+    /// Removes a file and its preload data from the tree, returning its entry if it existed.
+    pub fn remove_entry(&mut self, file_path: &str) -> Option<VPKDirectoryEntry> {
+        self.tree.remove_file(file_path)
+    }
 
-            digest.update(chunk);
-        }
+    /// Extracts every file stored in the VPK into `output_dir`, mirroring the directory structure of the VPK,
+    /// and returns a summary of the extraction.
+    ///
+    /// Files that already exist in `output_dir` are skipped and counted in [`ExtractSummary::files_skipped`] unless `overwrite` is set.
+    /// # Errors
+    /// - When the output directory cannot be created
+    pub fn extract_to_dir(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        output_dir: &str,
+        overwrite: bool,
+    ) -> Result<ExtractSummary> {
+        extract_to_dir(self, archive_path, vpk_name, output_dir, overwrite)
+    }
 
-        if entry.entry_length > 0 {
-            let archive_file = archive_mmaps
-                .get(&entry.archive_index)
-                .ok_or(Error::MemoryMappedFileNotFound(entry.archive_index))?;
+    /// Verifies the CRC of every file stored in the VPK, returning the result for each file.
+    ///
+    /// Files are read grouped by archive in the order they are stored in, and every archive is only opened once.
+    #[must_use]
+    pub fn verify_all_crcs(&self, archive_path: &str, vpk_name: &str) -> Vec<(String, Result<()>)> {
+        self.verify_crcs(archive_path, vpk_name, self.tree.files.iter().collect())
+    }
 
-            // read chunks of 1MB max into buffer and write to the output file
-            let mut remaining = entry.entry_length as usize;
-            let mut i = entry.entry_offset as usize;
-            while remaining > 0 {
-                let chunk = &archive_file[i..(i + min(1024 * 1024, remaining))];
+    /// Verifies the CRC of every file matching `filter` like [`Self::verify_all_crcs`].
+    #[must_use]
+    pub fn verify_filtered_crcs(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        filter: &ExtensionFilter,
+    ) -> Vec<(String, Result<()>)> {
+        let entries = filter
+            .select_from(&self.extension_index())
+            .into_iter()
+            .filter_map(|file_path| self.tree.files.get_key_value(file_path))
+            .collect();
+        self.verify_crcs(archive_path, vpk_name, entries)
+    }
 
-                if chunk.is_empty() {
-                    return Err(Error::BadData("Archive is empty".to_string()));
-                }
+    fn verify_crcs(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        mut entries: Vec<(&String, &VPKDirectoryEntry)>,
+    ) -> Vec<(String, Result<()>)> {
+        entries.sort_by_key(|(file_path, entry)| {
+            (entry.archive_index, entry.entry_offset, *file_path)
+        });
+
+        let mut archives = HashMap::new();
+        entries
+            .into_iter()
+            .map(|(file_path, entry)| {
+                let result = self
+                    .write_entry(
+                        &mut archives,
+                        archive_path,
+                        vpk_name,
+                        file_path,
+                        entry,
+                        &mut io::sink(),
+                        &ExtractOptions::default(),
+                        &mut |_, _| {},
+                    )
+                    .map(|_| ());
+
+                (file_path.clone(), result)
+            })
+            .collect()
+    }
+}
+
+/// Reads files from the archives of a [`VPKVersion1`], created with [`VPKVersion1::reader`].
+///
+/// Every archive file is opened on its first read and kept open, so reading many files
+/// from the same archive only seeks instead of opening the archive again for every file.
+pub struct ArchiveReader<'a> {
+    vpk: &'a VPKVersion1,
+    archive_path: String,
+    vpk_name: String,
+    archives: HashMap<u16, ArchiveFile>,
+}
 
-                out_file.write_all(chunk).map_err(Error::Io)?;
+impl ArchiveReader<'_> {
+    /// Read the contents of a file stored in the VPK into memory.
+    /// # Errors
+    /// - When the file does not exist in the VPK
+    /// - When an archive file cannot be opened
+    /// - When the data is invalid or its CRC does not match
+    pub fn read_file(&mut self, file_path: &str) -> Result<Vec<u8>> {
+        self.read_file_with(file_path, &ExtractOptions::default())
+    }
 
-                i += chunk.len();
+    /// Read the contents of a file stored in the VPK into memory, see [`ExtractOptions`].
+    /// # Errors
+    /// - When the file does not exist in the VPK
+    /// - When an archive file cannot be opened
+    /// - When the data is invalid or its CRC does not match
+    pub fn read_file_with(&mut self, file_path: &str, options: &ExtractOptions) -> Result<Vec<u8>> {
+        self.vpk.read_entry(
+            &mut self.archives,
+            &self.archive_path,
+            &self.vpk_name,
+            file_path,
+            options,
+        )
+    }
 
-                if remaining >= chunk.len() {
-                    remaining -= chunk.len();
-                } else {
-                    remaining = 0;
+    /// Write the contents of a file stored in the VPK to `out`, returning the number of bytes written.
+    /// # Errors
+    /// - When the file does not exist in the VPK
+    /// - When an archive file cannot be opened
+    /// - When the data is invalid or its CRC does not match
+    /// - When writing to `out` fails
+    pub fn read_file_to(&mut self, file_path: &str, mut out: &mut dyn Write) -> Result<u64> {
+        let (file_path, entry) = self
+            .vpk
+            .tree
+            .find(file_path, CaseSensitivity::Sensitive)
+            .ok_or(Error::FileNotFound(file_path.to_string()))?;
+
+        self.vpk.write_entry(
+            &mut self.archives,
+            &self.archive_path,
+            &self.vpk_name,
+            file_path,
+            entry,
+            &mut out,
+            &ExtractOptions::default(),
+            &mut |_, _| {},
+        )
+    }
+
+    /// Returns the number of archive files currently kept open.
+    #[must_use]
+    pub fn open_archive_count(&self) -> usize {
+        self.archives.len()
+    }
+}
+
+impl PakReader for VPKVersion1 {
+    fn file_paths(&self) -> Vec<&str> {
+        self.tree.files.keys().map(String::as_str).collect()
+    }
+
+    fn file_size(&self, file_path: &str) -> Option<u64> {
+        self.tree
+            .get(file_path)
+            .map(|entry| u64::from(entry.preload_length) + u64::from(entry.entry_length))
+    }
+
+    fn file_crc(&self, file_path: &str) -> Option<u32> {
+        self.tree.get(file_path).map(|entry| entry.crc)
+    }
+
+    fn file_location(&self, file_path: &str) -> Option<FileLocation> {
+        let entry = self.tree.get(file_path)?;
+        let length = u64::from(entry.entry_length);
+
+        Some(if length == 0 {
+            FileLocation::Preload
+        } else if entry.archive_index == VPK_DIR_ARCHIVE_INDEX {
+            FileLocation::InDirectory {
+                offset: self.data_offset(entry),
+                length,
+            }
+        } else {
+            FileLocation::Archive {
+                index: entry.archive_index,
+                offset: entry.entry_offset.into(),
+                length,
+            }
+        })
+    }
+
+    fn read_file_streaming(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+    ) -> Result<Box<dyn Read + '_>> {
+        let entry = self
+            .tree
+            .get(file_path)
+            .ok_or(Error::FileNotFound(file_path.to_string()))?;
+
+        let preload: &[u8] = if entry.preload_length > 0 {
+            self.tree
+                .get_preload(file_path)
+                .ok_or(Error::DataNotFound(file_path.to_string()))?
+        } else {
+            &[]
+        };
+
+        let data: Box<dyn Read> = if entry.entry_length > 0 {
+            let mut archive_file =
+                self.archive_file(archive_path, vpk_name, entry.archive_index)?;
+            archive_file
+                .seek(SeekFrom::Start(self.data_offset(entry)))
+                .map_err(Error::Io)?;
+
+            Box::new(BufReader::new(archive_file).take(entry.entry_length.into()))
+        } else {
+            Box::new(io::empty())
+        };
+
+        Ok(Box::new(CrcValidatingReader::new(
+            preload.chain(data),
+            entry.crc,
+            file_path,
+        )))
+    }
+
+    fn read_file_to_with(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        mut out: &mut dyn Write,
+        options: &ExtractOptions,
+        progress: &mut dyn FnMut(u64, u64),
+    ) -> Result<u64> {
+        let (file_path, entry) = self
+            .tree
+            .find(file_path, options.case_sensitivity)
+            .ok_or(Error::FileNotFound(file_path.to_string()))?;
+
+        self.write_entry(
+            &mut HashMap::new(),
+            archive_path,
+            vpk_name,
+            file_path,
+            entry,
+            &mut out,
+            options,
+            progress,
+        )
+    }
+
+    fn extract_files(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_paths: &[&str],
+        output_dir: &str,
+        mut progress: Option<ExtractProgress>,
+    ) -> Result<ExtractSummary> {
+        std::fs::create_dir_all(output_dir).map_err(Error::Io)?;
+
+        let mut summary = ExtractSummary::default();
+        let total = file_paths.len();
+
+        // Group entries by archive and read every archive front to back
+        let mut entries = Vec::with_capacity(total);
+        for &file_path in file_paths {
+            match self.tree.get(file_path) {
+                Some(entry) => entries.push((file_path, entry)),
+                None => {
+                    summary.record(file_path, Err(Error::FileNotFound(file_path.to_string())));
                 }
+            }
+        }
+        entries.sort_by_key(|(file_path, entry)| {
+            (entry.archive_index, entry.entry_offset, *file_path)
+        });
+
+        let mut archives = HashMap::new();
+        let mut done = summary.errors.len();
+
+        for (i, &(file_path, entry)) in entries.iter().enumerate() {
+            let result = output_path(output_dir, file_path).and_then(|out_path| {
+                write_output_file(&out_path, file_path, false, |mut out_file| {
+                    self.write_entry(
+                        &mut archives,
+                        archive_path,
+                        vpk_name,
+                        file_path,
+                        entry,
+                        &mut out_file,
+                        &ExtractOptions::default(),
+                        &mut |_, _| {},
+                    )
+                })
+            });
+
+            let proceed = summary.record(file_path, result);
+            done += 1;
+
+            if let Some(progress) = progress.as_mut() {
+                progress(file_path, done, total);
+            }
 
-                digest.update(chunk);
+            if !proceed {
+                summary.not_attempted.extend(
+                    entries[i..]
+                        .iter()
+                        .map(|(file_path, _)| (*file_path).to_string()),
+                );
+                break;
             }
         }
 
-        if digest.finalize() == entry.crc {
-            Ok(())
-        } else {
-            Err(Error::BadData("CRC must match".to_string()))
+        Ok(summary)
+    }
+
+    fn check_archive_consistency(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        samples_per_archive: usize,
+    ) -> Vec<ArchiveConsistency> {
+        let mut by_archive: BTreeMap<u16, Vec<(&String, &VPKDirectoryEntry)>> = BTreeMap::new();
+        for (file_path, entry) in &self.tree.files {
+            // Files stored entirely in preload data don't touch the archives
+            if entry.entry_length > 0 {
+                by_archive
+                    .entry(entry.archive_index)
+                    .or_default()
+                    .push((file_path, entry));
+            }
         }
+
+        let mut archives = HashMap::new();
+        by_archive
+            .into_iter()
+            .map(|(archive_index, mut entries)| {
+                entries.sort_by_key(|(file_path, entry)| (entry.entry_offset, *file_path));
+
+                let result = sample_evenly(&entries, samples_per_archive).try_for_each(
+                    |(file_path, entry)| {
+                        self.write_entry(
+                            &mut archives,
+                            archive_path,
+                            vpk_name,
+                            file_path,
+                            entry,
+                            &mut io::sink(),
+                            &ExtractOptions::default(),
+                            &mut |_, _| {},
+                        )
+                        .map(|_| ())
+                    },
+                );
+
+                ArchiveConsistency::from_result(archive_index, result)
+            })
+            .collect()
+    }
+
+    fn validate(&self) -> Vec<ValidationIssue> {
+        validate_tree(&self.tree)
+    }
+
+    #[cfg(feature = "mem-map")]
+    fn extract_file_mem_map_with(
+        &self,
+        _archive_path: &str,
+        archive_mmaps: &HashMap<u16, FileBuffer>,
+        _vpk_name: &str,
+        file_path: &str,
+        output_path: &str,
+        options: &ExtractOptions,
+    ) -> Result<()> {
+        let (file_path, entry) = self
+            .tree
+            .find(file_path, options.case_sensitivity)
+            .ok_or(Error::FileNotFound(file_path.to_string()))?;
+
+        write_output_file(
+            Path::new(output_path),
+            file_path,
+            options.keep_partial,
+            |out_file| {
+                let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+                let mut digest = crc.digest();
+                let mut written = 0;
+
+                if entry.preload_length > 0 {
+                    let chunk = self
+                        .tree
+                        .get_preload(file_path)
+                        .ok_or(Error::DataNotFound(file_path.to_string()))?;
+
+                    out_file.write_all(chunk).map_err(Error::Io)?;
+                    written += chunk.len() as u64;
+
+                    digest.update(chunk);
+                }
+
+                if entry.entry_length > 0 {
+                    let archive_file = archive_mmaps
+                        .get(&entry.archive_index)
+                        .ok_or(Error::MemoryMappedFileNotFound(entry.archive_index))?;
+
+                    // read chunks of at most the chunk size into buffer and write to the output file
+                    let mut remaining = entry.entry_length as usize;
+                    let mut i = usize::try_from(self.data_offset(entry))
+                        .map_err(|_| Error::DataTooLarge)?;
+                    while remaining > 0 {
+                        let chunk = i
+                            .checked_add(min(options.chunk_size(), remaining))
+                            .and_then(|end| archive_file.get(i..end))
+                            .ok_or_else(|| Error::UnexpectedEof {
+                                file: file_path.to_string(),
+                                missing: (remaining
+                                    - archive_file.len().saturating_sub(i).min(remaining))
+                                    as u64,
+                            })?;
+
+                        self.observer
+                            .bytes_read(entry.archive_index, chunk.len() as u64);
+                        out_file.write_all(chunk).map_err(Error::Io)?;
+                        written += chunk.len() as u64;
+
+                        i += chunk.len();
+                        remaining -= chunk.len();
+
+                        digest.update(chunk);
+                    }
+                }
+
+                let found = digest.finalize();
+                if !options.verify_crc || found == entry.crc {
+                    Ok(written)
+                } else {
+                    Err(Error::CrcMismatch {
+                        expected: entry.crc,
+                        found,
+                        file: file_path.to_string(),
+                    })
+                }
+            },
+        )?;
+
+        Ok(())
     }
 }
 
@@ -371,10 +925,17 @@ impl PakWriter for VPKVersion1 {
             std::fs::create_dir_all(prefix).map_err(Error::Io)?;
         }
 
-        let mut out_file = File::create(out_path).map_err(Error::Io)?;
+        // The header always describes the tree as written, even if the tree changed since it was parsed
+        let (tree, tree_size) = self.tree.serialize()?;
 
-        self.header.write(&mut out_file)?;
-        self.tree.write(&mut out_file)?;
+        let mut out_file = BufWriter::new(File::create(out_path).map_err(Error::Io)?);
+        VPKHeaderV1 {
+            tree_size,
+            ..self.header.clone()
+        }
+        .write(&mut out_file)?;
+        out_file.write_all(&tree).map_err(Error::Io)?;
+        out_file.flush().map_err(Error::Io)?;
 
         Ok(())
     }
@@ -389,16 +950,42 @@ impl PakWorker for VPKVersion1 {
                 tree_size: 0,
             },
             tree: VPKTree::new(),
+            observer: Observer::default(),
+            naming: Naming::default(),
+            archive_ends: ArchiveEnds::default(),
         }
     }
 
-    fn from_file(file: &mut File) -> Result<Self> {
+    fn from_reader_with_options<R: Read + Seek>(
+        file: &mut R,
+        options: &ParseOptions,
+    ) -> Result<Self> {
         let header = VPKHeaderV1::from(file)?;
 
         let tree_start = file.stream_position().map_err(Error::Io)?;
-        let tree = VPKTree::from(file, tree_start, header.tree_size.into())?;
+        let tree_size = checked_tree_size(file, tree_start, header.tree_size.into(), options)?;
+        let tree = VPKTree::from_with_options(file, tree_start, tree_size, options)?;
+
+        Ok(Self {
+            header,
+            tree,
+            observer: Observer::default(),
+            naming: Naming::default(),
+            archive_ends: ArchiveEnds::default(),
+        })
+    }
+
+    #[cfg(feature = "detect")]
+    fn format(&self) -> crate::detect::PakFormat {
+        crate::detect::PakFormat::VPKVersion1
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 
-        Ok(Self { header, tree })
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
     }
 }
 
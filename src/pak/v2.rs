@@ -1,13 +1,36 @@
 //! Support for the VPK version 1 format.
 
-use super::{Error, PakReader, PakWorker, PakWriter, Result, VPKDirectoryEntry, VPKTree};
-use crate::util::file::VPKFileReader;
-use std::{fs::File, io::Seek};
+use super::{
+    ArchiveConsistency, ArchiveFile, ArchiveNaming, CrcValidatingReader, Error, ExtractOptions,
+    ExtractSummary, FileDataMode, FileLocation, FormatCaps, IoObserver, Naming, Observer,
+    PakReader, PakWorker, PakWriter, ParseOptions, Result, VPKDirectoryEntry, VPKTree,
+    ValidationIssue, ValidationIssueKind, checked_tree_size, extract_to_dir, read_archive_chunk,
+    sample_evenly, validate::validate_tree,
+};
+use crate::util::file::{VPKFileReader, VPKFileWriter};
+use crc::{CRC_32_ISO_HDLC, Crc};
+use std::{
+    any::Any,
+    cmp::min,
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    mem,
+    sync::Arc,
+};
+
+#[cfg(any(feature = "md5", feature = "mem-map"))]
+use std::collections::HashMap;
+#[cfg(feature = "md5")]
+use std::{
+    collections::hash_map::Entry,
+    io::{BufWriter, ErrorKind},
+};
 
 #[cfg(feature = "mem-map")]
-use filebuffer::FileBuffer;
+use super::write_output_file;
 #[cfg(feature = "mem-map")]
-use std::collections::HashMap;
+use filebuffer::FileBuffer;
 
 /// The 4-byte signature found in the header of a valid VPK version 2 file.
 pub const VPK_SIGNATURE_V2: u32 = 0x55AA_1234;
@@ -15,8 +38,22 @@ pub const VPK_SIGNATURE_V2: u32 = 0x55AA_1234;
 /// The 4-byte version found in the header of a valid VPK version 2 file.
 pub const VPK_VERSION_V2: u32 = 2;
 
+/// The archive index used in the archive MD5 section for data stored in the directory file, after the tree.
+pub const VPK_DIR_ARCHIVE_INDEX_V2: u32 = 0x7FFF;
+
+/// The operations supported for VPK version 2 files. Signatures can be verified with the `signature-verify` feature.
+/// Writing directory files needs their MD5 checksums, computed with the `md5` feature.
+pub const VPK_CAPS_V2: FormatCaps = FormatCaps {
+    read: true,
+    write_dir: cfg!(feature = "md5"),
+    write_archives: false,
+    compression: false,
+    signatures: cfg!(feature = "signature-verify"),
+};
+
 /// The header of a VPK version 2 file.
 #[derive(PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VPKHeaderV2 {
     /// VPK signature. Should be equal to [`VPK_SIGNATURE_V2`].
     pub signature: u32,
@@ -42,28 +79,37 @@ pub struct VPKHeaderV2 {
 
 #[repr(C)]
 #[derive(PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VPKArchiveMD5SectionEntry {
     pub archive_index: u32,
-    pub starting_offset: u32,   // where to start reading bytes
-    pub count: u32,             // how many bytes to check
+    pub starting_offset: u32, // where to start reading bytes
+    pub count: u32,           // how many bytes to check
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex"))]
     pub md5_checksum: [u8; 16], // expected checksum. len: 16
 }
 
 #[repr(C)]
 #[derive(PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VPKOtherMD5Section {
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex"))]
     pub tree_checksum: [u8; 16],
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex"))]
     pub archive_md5_section_checksum: [u8; 16],
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex"))]
     pub unknown: [u8; 16],
 }
 
 #[repr(C)]
 #[derive(PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VPKSignatureSection {
     pub public_key_size: u32, // always seen as 160 (0xA0) bytes
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex"))]
     pub public_key: [u8; 160],
 
     pub signature_size: u32, // always seen as 128 (0x80) bytes
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex"))]
     pub signature: [u8; 128],
 }
 
@@ -73,7 +119,7 @@ impl VPKHeaderV2 {
     /// - When the data is invalid
     /// - When the signature is invalid
     /// - When the version does not match
-    pub fn from(file: &mut File) -> Result<Self> {
+    pub fn from<R: Read>(file: &mut R) -> Result<Self> {
         let signature = file.read_u32().map_err(|e| Error::Util {
             source: e,
             context: "Failed to read signature".to_string(),
@@ -114,7 +160,9 @@ impl VPKHeaderV2 {
         })?;
 
         // Check the archive md5 section size
-        if archive_md5_section_size as usize % size_of::<VPKArchiveMD5SectionEntry>() != 0 {
+        if !(archive_md5_section_size as usize)
+            .is_multiple_of(size_of::<VPKArchiveMD5SectionEntry>())
+        {
             return Err(Error::BadData(format!(
                 "Header archive MD5 section size should be a multiple of 28 but is {archive_md5_section_size}"
             )));
@@ -156,8 +204,96 @@ impl VPKHeaderV2 {
         })
     }
 
+    /// Write the header to a file.
+    /// # Errors
+    /// - When the signature is invalid
+    /// - When the version does not match
+    /// - When a section size is invalid
+    /// - When an IO operation fails
+    pub fn write<W: Write>(&self, file: &mut W) -> Result<()> {
+        if self.signature != VPK_SIGNATURE_V2 {
+            return Err(Error::InvalidSignature(format!(
+                "Header signature should be {VPK_SIGNATURE_V2:#X} but is {:#X}",
+                self.signature
+            )));
+        }
+
+        if self.version != VPK_VERSION_V2 {
+            return Err(Error::BadVersion(format!(
+                "Header version should be {VPK_VERSION_V2} but is {}",
+                self.version
+            )));
+        }
+
+        if !(self.archive_md5_section_size as usize)
+            .is_multiple_of(size_of::<VPKArchiveMD5SectionEntry>())
+        {
+            return Err(Error::BadData(format!(
+                "Header archive MD5 section size should be a multiple of 28 but is {}",
+                self.archive_md5_section_size
+            )));
+        }
+
+        if self.other_md5_section_size as usize != size_of::<VPKOtherMD5Section>() {
+            return Err(Error::BadData(format!(
+                "Header other MD5 section size should be 48 but is {}",
+                self.other_md5_section_size
+            )));
+        }
+
+        if self.signature_section_size != 0
+            && self.signature_section_size as usize != size_of::<VPKSignatureSection>()
+        {
+            return Err(Error::BadData(format!(
+                "Header signature section size should be 0 or 296 but is {}",
+                self.signature_section_size
+            )));
+        }
+
+        file.write_u32(self.signature).map_err(|e| Error::Util {
+            source: e,
+            context: "Failed to write signature".to_string(),
+        })?;
+
+        file.write_u32(self.version).map_err(|e| Error::Util {
+            source: e,
+            context: "Failed to write version".to_string(),
+        })?;
+
+        file.write_u32(self.tree_size).map_err(|e| Error::Util {
+            source: e,
+            context: "Failed to write tree size".to_string(),
+        })?;
+
+        file.write_u32(self.file_data_section_size)
+            .map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to write file data section size".to_string(),
+            })?;
+
+        file.write_u32(self.archive_md5_section_size)
+            .map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to write MD5 section size".to_string(),
+            })?;
+
+        file.write_u32(self.other_md5_section_size)
+            .map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to write other MD5 section size".to_string(),
+            })?;
+
+        file.write_u32(self.signature_section_size)
+            .map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to write signature size".to_string(),
+            })?;
+
+        Ok(())
+    }
+
     /// Check if a file is in the VPK version 2 format.
-    pub fn is_format(file: &mut File) -> bool {
+    pub fn is_format<R: Read + Seek>(file: &mut R) -> bool {
         let Ok(pos) = file.stream_position() else {
             return false;
         };
@@ -171,6 +307,69 @@ impl VPKHeaderV2 {
     }
 }
 
+impl VPKArchiveMD5SectionEntry {
+    /// Read an archive MD5 section entry from a file.
+    /// # Errors
+    /// - When an IO operation fails
+    pub fn from<R: Read>(file: &mut R) -> Result<Self> {
+        Ok(Self {
+            archive_index: file.read_u32().map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to read archive md5 section archive index".to_string(),
+            })?,
+
+            starting_offset: file.read_u32().map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to read archive md5 section offset".to_string(),
+            })?,
+
+            count: file.read_u32().map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to read archive md5 section count".to_string(),
+            })?,
+
+            md5_checksum: file
+                .read_bytes(16)
+                .map_err(|e| Error::Util {
+                    source: e,
+                    context: "Failed to read archive md5 section signature".to_string(),
+                })?
+                .try_into()
+                .expect("Bytes read should match parameter value"),
+        })
+    }
+
+    /// Write the archive MD5 section entry to a file.
+    /// # Errors
+    /// - When an IO operation fails
+    pub fn write<W: Write>(&self, file: &mut W) -> Result<()> {
+        file.write_u32(self.archive_index)
+            .map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to write archive md5 section archive index".to_string(),
+            })?;
+
+        file.write_u32(self.starting_offset)
+            .map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to write archive md5 section offset".to_string(),
+            })?;
+
+        file.write_u32(self.count).map_err(|e| Error::Util {
+            source: e,
+            context: "Failed to write archive md5 section count".to_string(),
+        })?;
+
+        file.write_bytes(&self.md5_checksum)
+            .map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to write archive md5 section signature".to_string(),
+            })?;
+
+        Ok(())
+    }
+}
+
 impl Default for VPKOtherMD5Section {
     fn default() -> Self {
         Self::new()
@@ -186,9 +385,160 @@ impl VPKOtherMD5Section {
             unknown: [0; 16],
         }
     }
+
+    /// Read the other MD5 section from a file.
+    /// # Errors
+    /// - When an IO operation fails
+    pub fn from<R: Read>(file: &mut R) -> Result<Self> {
+        Ok(Self {
+            tree_checksum: file
+                .read_bytes(16)
+                .map_err(|e| Error::Util {
+                    source: e,
+                    context: "Failed to read other md5 section tree checksum".to_string(),
+                })?
+                .try_into()
+                .expect("Bytes read should match parameter value"),
+
+            archive_md5_section_checksum: file
+                .read_bytes(16)
+                .map_err(|e| Error::Util {
+                    source: e,
+                    context: "Failed to read other md5 section checksum".to_string(),
+                })?
+                .try_into()
+                .expect("Bytes read should match parameter value"),
+
+            unknown: file
+                .read_bytes(16)
+                .map_err(|e| Error::Util {
+                    source: e,
+                    context: "Failed to read other md5 section unknown".to_string(),
+                })?
+                .try_into()
+                .expect("Bytes read should match parameter value"),
+        })
+    }
+
+    /// Write the other MD5 section to a file.
+    /// # Errors
+    /// - When an IO operation fails
+    pub fn write<W: Write>(&self, file: &mut W) -> Result<()> {
+        file.write_bytes(&self.tree_checksum)
+            .map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to write other md5 section tree checksum".to_string(),
+            })?;
+
+        file.write_bytes(&self.archive_md5_section_checksum)
+            .map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to write other md5 section checksum".to_string(),
+            })?;
+
+        file.write_bytes(&self.unknown).map_err(|e| Error::Util {
+            source: e,
+            context: "Failed to write other md5 section unknown".to_string(),
+        })?;
+
+        Ok(())
+    }
+}
+
+impl VPKSignatureSection {
+    /// Read the signature section from a file.
+    /// # Errors
+    /// - When the public key is not 160 bytes or the signature is not 128 bytes
+    /// - When an IO operation fails
+    pub fn from<R: Read>(file: &mut R) -> Result<Self> {
+        let public_key_size = file.read_u32().map_err(|e| Error::Util {
+            source: e,
+            context: "Failed reading signature public key size".to_string(),
+        })?;
+
+        let public_key = file
+            .read_bytes(
+                public_key_size
+                    .try_into()
+                    .map_err(|_| Error::DataTooLarge)?,
+            )
+            .map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to read signature public key".to_string(),
+            })?;
+
+        let signature_size = file.read_u32().map_err(|e| Error::Util {
+            source: e,
+            context: "Failed to read signature size".to_string(),
+        })?;
+
+        let signature = file
+            .read_bytes(signature_size.try_into().map_err(|_| Error::DataTooLarge)?)
+            .map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to read signature".to_string(),
+            })?;
+
+        Ok(Self {
+            public_key_size,
+            public_key: public_key
+                .try_into()
+                .map_err(|_| Error::BadData("Public key must be 160 bytes".to_string()))?,
+            signature_size,
+            signature: signature
+                .try_into()
+                .map_err(|_| Error::BadData("Signature must be 128 bytes".to_string()))?,
+        })
+    }
+
+    /// Write the signature section to a file.
+    /// # Errors
+    /// - When the public key size is not 160 or the signature size is not 128
+    /// - When an IO operation fails
+    pub fn write<W: Write>(&self, file: &mut W) -> Result<()> {
+        if self.public_key_size as usize != self.public_key.len() {
+            return Err(Error::BadData(format!(
+                "Public key size should be 160 but is {}",
+                self.public_key_size
+            )));
+        }
+
+        if self.signature_size as usize != self.signature.len() {
+            return Err(Error::BadData(format!(
+                "Signature size should be 128 but is {}",
+                self.signature_size
+            )));
+        }
+
+        file.write_u32(self.public_key_size)
+            .map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to write signature public key size".to_string(),
+            })?;
+
+        file.write_bytes(&self.public_key)
+            .map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to write signature public key".to_string(),
+            })?;
+
+        file.write_u32(self.signature_size)
+            .map_err(|e| Error::Util {
+                source: e,
+                context: "Failed to write signature size".to_string(),
+            })?;
+
+        file.write_bytes(&self.signature).map_err(|e| Error::Util {
+            source: e,
+            context: "Failed to write signature".to_string(),
+        })?;
+
+        Ok(())
+    }
 }
 
 /// The VPK version 2 format.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VPKVersion2 {
     /// The VPK's header.
     pub header: VPKHeaderV2,
@@ -196,9 +546,15 @@ pub struct VPKVersion2 {
     /// The tree of files in the VPK.
     pub tree: VPKTree<VPKDirectoryEntry>,
 
-    /// The file data section of the VPK.
+    /// The file data section of the VPK, containing the data of files stored in the directory file.
+    /// Empty when the section is loaded lazily, see [`FileDataMode::Lazy`].
+    #[cfg_attr(feature = "serde", serde(with = "crate::util::hex"))]
     pub file_data: Vec<u8>,
 
+    /// The offset of the file data section in the directory file when it is loaded lazily.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    file_data_offset: Option<u64>,
+
     /// The archive md5 section of the VPK.
     pub archive_md5_section_entries: Vec<VPKArchiveMD5SectionEntry>,
 
@@ -207,39 +563,787 @@ pub struct VPKVersion2 {
 
     /// The signature section of the VPK.
     pub signature_section: Option<VPKSignatureSection>,
+
+    #[cfg_attr(feature = "serde", serde(skip))]
+    observer: Observer,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    naming: Naming,
+}
+
+/// The data an MD5 checksum of a VPK version 2 file is computed over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MD5Target {
+    /// A range of bytes in an archive, described by an entry in the archive MD5 section.
+    Archive {
+        archive_index: u32,
+        starting_offset: u32,
+        count: u32,
+    },
+    /// The directory tree.
+    Tree,
+    /// The archive MD5 section.
+    ArchiveMD5Section,
+    /// The whole directory file up to and including the tree and archive MD5 section checksums.
+    File,
+}
+
+/// A checksum in a VPK version 2 file that does not match the data it covers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveMD5Mismatch {
+    /// The data the checksum was computed over.
+    pub target: MD5Target,
+    /// The checksum stored in the VPK.
+    pub expected: [u8; 16],
+    /// The checksum of the actual data.
+    pub actual: [u8; 16],
+}
+
+impl VPKVersion2 {
+    /// Recomputes the tree size in the header from the tree, e.g. after files were added or removed.
+    ///
+    /// The sizes of the other sections are left unchanged.
+    /// # Errors
+    /// - When the preload data does not match the entries
+    /// - When the tree is too large for the header
+    pub fn rebuild_header_tree_size(&mut self) -> Result<()> {
+        self.header.tree_size = self.serialize_tree()?.1;
+        Ok(())
+    }
+
+    /// Serializes the tree as written to the directory file, returning the data along with its size for the header.
+    fn serialize_tree(&self) -> Result<(Vec<u8>, u32)> {
+        // Sections follow the tree, so an empty tree still needs its terminator like in Valve's files
+        let (mut tree, _) = self.tree.serialize()?;
+        if tree.is_empty() {
+            tree.push(0);
+        }
+
+        let tree_size = u32::try_from(tree.len()).map_err(|_| Error::DataTooLarge)?;
+        Ok((tree, tree_size))
+    }
+
+    /// Sets the observer notified of every archive file opened and read by this VPK, or removes it when `None`.
+    /// The directory file is reported under [`VPK_DIR_ARCHIVE_INDEX_V2`].
+    pub fn set_observer(&mut self, observer: Option<Arc<dyn IoObserver>>) {
+        self.observer = Observer::new(observer);
+    }
+
+    /// Sets how archive indices map to the paths of archive files, or restores Valve's naming when `None`.
+    pub fn set_archive_naming(&mut self, naming: Option<Arc<dyn ArchiveNaming>>) {
+        self.naming = Naming::new(naming);
+    }
+
+    /// Extracts every file stored in the VPK into `output_dir`, mirroring the directory structure of the VPK,
+    /// and returns a summary of the extraction.
+    ///
+    /// Files that already exist in `output_dir` are skipped and counted in [`ExtractSummary::files_skipped`] unless `overwrite` is set.
+    /// # Errors
+    /// - When the output directory cannot be created
+    pub fn extract_to_dir(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        output_dir: &str,
+        overwrite: bool,
+    ) -> Result<ExtractSummary> {
+        extract_to_dir(self, archive_path, vpk_name, output_dir, overwrite)
+    }
+
+    /// Opens the archive file for an archive index, or the directory file for [`VPK_DIR_ARCHIVE_INDEX_V2`].
+    fn archive_file(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        archive_index: u32,
+    ) -> Result<ArchiveFile> {
+        let is_dir = archive_index == VPK_DIR_ARCHIVE_INDEX_V2;
+        let archive_index = u16::try_from(archive_index)
+            .map_err(|_| Error::BadData(format!("Invalid archive index {archive_index}")))?;
+
+        let path = if is_dir {
+            self.naming.dir_path(archive_path, vpk_name)
+        } else {
+            self.naming
+                .archive_path(archive_path, vpk_name, archive_index)
+        };
+
+        self.observer
+            .open(archive_index, &path)
+            .map_err(|e| Error::ArchiveOpen {
+                path: path.display().to_string(),
+                source: e,
+            })
+    }
+
+    /// Returns the preload data of an entry, empty if it has none.
+    fn preload(&self, file_path: &str, entry: &VPKDirectoryEntry) -> Result<&[u8]> {
+        if entry.preload_length > 0 {
+            self.tree
+                .get_preload(file_path)
+                .map(Vec::as_slice)
+                .ok_or(Error::DataNotFound(file_path.to_string()))
+        } else {
+            Ok(&[])
+        }
+    }
+
+    /// Returns a reader for the data of an entry following its preload data.
+    ///
+    /// Data stored in the directory file, under [`VPK_DIR_ARCHIVE_INDEX_V2`], is located relative to the start of the file data section.
+    /// When the section is loaded lazily it is read from the directory file `{vpk_name}_dir.vpk` in `archive_path`, like the archives.
+    fn entry_data(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        entry: &VPKDirectoryEntry,
+    ) -> Result<Box<dyn Read + '_>> {
+        if entry.entry_length == 0 {
+            return Ok(Box::new(io::empty()));
+        }
+
+        let offset = u64::from(entry.entry_offset);
+        let length = u64::from(entry.entry_length);
+
+        if u32::from(entry.archive_index) != VPK_DIR_ARCHIVE_INDEX_V2 {
+            let mut archive_file =
+                self.archive_file(archive_path, vpk_name, entry.archive_index.into())?;
+            archive_file
+                .seek(SeekFrom::Start(offset))
+                .map_err(Error::Io)?;
+
+            return Ok(Box::new(BufReader::new(archive_file).take(length)));
+        }
+
+        match self.file_data_offset {
+            None => {
+                let file_data_len = self.file_data.len() as u64;
+                let data = usize::try_from(offset)
+                    .ok()
+                    .zip(usize::try_from(offset + length).ok())
+                    .and_then(|(start, end)| self.file_data.get(start..end))
+                    .ok_or_else(|| Error::UnexpectedEof {
+                        file: file_path.to_string(),
+                        missing: (offset + length).saturating_sub(file_data_len).min(length),
+                    })?;
+
+                Ok(Box::new(data))
+            }
+            Some(file_data_offset) => {
+                let mut dir_file =
+                    self.archive_file(archive_path, vpk_name, VPK_DIR_ARCHIVE_INDEX_V2)?;
+                dir_file
+                    .seek(SeekFrom::Start(file_data_offset + offset))
+                    .map_err(Error::Io)?;
+
+                Ok(Box::new(BufReader::new(dir_file).take(length)))
+            }
+        }
+    }
+
+    /// Verifies the MD5 checksums of the VPK against the directory file `{vpk_name}_dir.vpk` and its archives in `archive_path`.
+    ///
+    /// Checks every entry of the archive MD5 section, as well as the tree, archive MD5 section and whole file checksums.
+    /// Returns every checksum that does not match, an empty list means the VPK is intact.
+    /// # Errors
+    /// - When the directory file or an archive cannot be opened
+    /// - When IO operations fail
+    #[cfg(feature = "md5")]
+    pub fn verify_archives(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+    ) -> Result<Vec<ArchiveMD5Mismatch>> {
+        let mut mismatches = Vec::new();
+        let mut check = |target, expected: [u8; 16], actual: [u8; 16]| {
+            if expected != actual {
+                mismatches.push(ArchiveMD5Mismatch {
+                    target,
+                    expected,
+                    actual,
+                });
+            }
+        };
+
+        let mut dir_file = self.archive_file(archive_path, vpk_name, VPK_DIR_ARCHIVE_INDEX_V2)?;
+
+        let tree_start = mem::size_of::<VPKHeaderV2>() as u64;
+        let tree_end = tree_start + u64::from(self.header.tree_size);
+        let md5_section_start = tree_end + u64::from(self.header.file_data_section_size);
+        let md5_section_size = u64::from(self.header.archive_md5_section_size);
+
+        let mut archives: HashMap<u32, ArchiveFile> = HashMap::new();
+        for md5_entry in &self.archive_md5_section_entries {
+            let (archive_file, base) = if md5_entry.archive_index == VPK_DIR_ARCHIVE_INDEX_V2 {
+                (&mut dir_file, tree_end)
+            } else {
+                let archive_file = match archives.entry(md5_entry.archive_index) {
+                    Entry::Occupied(archive_file) => archive_file.into_mut(),
+                    Entry::Vacant(slot) => slot.insert(self.archive_file(
+                        archive_path,
+                        vpk_name,
+                        md5_entry.archive_index,
+                    )?),
+                };
+                (archive_file, 0)
+            };
+
+            let actual = md5_range(
+                archive_file,
+                base + u64::from(md5_entry.starting_offset),
+                md5_entry.count.into(),
+            )?;
+
+            check(
+                MD5Target::Archive {
+                    archive_index: md5_entry.archive_index,
+                    starting_offset: md5_entry.starting_offset,
+                    count: md5_entry.count,
+                },
+                md5_entry.md5_checksum,
+                actual,
+            );
+        }
+
+        check(
+            MD5Target::Tree,
+            self.other_md5_section.tree_checksum,
+            md5_range(&mut dir_file, tree_start, self.header.tree_size.into())?,
+        );
+
+        check(
+            MD5Target::ArchiveMD5Section,
+            self.other_md5_section.archive_md5_section_checksum,
+            md5_range(&mut dir_file, md5_section_start, md5_section_size)?,
+        );
+
+        // The file checksum covers everything up to the unknown checksum itself
+        check(
+            MD5Target::File,
+            self.other_md5_section.unknown,
+            md5_range(&mut dir_file, 0, md5_section_start + md5_section_size + 32)?,
+        );
+
+        Ok(mismatches)
+    }
+}
+
+#[cfg(feature = "md5")]
+impl VPKVersion2 {
+    /// Verifies the given archive MD5 section entries of a single archive, returning whether they all match.
+    fn check_archive_md5<'a>(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        archive_index: u16,
+        md5_entries: impl Iterator<Item = &'a &'a VPKArchiveMD5SectionEntry>,
+    ) -> Result<bool> {
+        // Data stored in the directory file starts after the tree
+        let base = if u32::from(archive_index) == VPK_DIR_ARCHIVE_INDEX_V2 {
+            mem::size_of::<VPKHeaderV2>() as u64 + u64::from(self.header.tree_size)
+        } else {
+            0
+        };
+
+        let mut archive_file = self.archive_file(archive_path, vpk_name, archive_index.into())?;
+
+        for md5_entry in md5_entries {
+            let actual = md5_range(
+                &mut archive_file,
+                base + u64::from(md5_entry.starting_offset),
+                md5_entry.count.into(),
+            )?;
+
+            if actual != md5_entry.md5_checksum {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Computes the MD5 checksum of `count` bytes starting at `offset`. Data missing at the end of the file is left out.
+#[cfg(feature = "md5")]
+fn md5_range<R: Read + Seek>(reader: &mut R, offset: u64, count: u64) -> Result<[u8; 16]> {
+    reader.seek(SeekFrom::Start(offset)).map_err(Error::Io)?;
+
+    let mut context = md5::Context::new();
+    let mut buffer = vec![0; min(1024 * 1024, count.try_into().unwrap_or(usize::MAX))];
+    let mut remaining = count;
+    while remaining > 0 {
+        let len = min(buffer.len(), remaining.try_into().unwrap_or(usize::MAX));
+        let read = match reader.read(&mut buffer[..len]) {
+            Ok(read) => read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(Error::Io(e)),
+        };
+
+        if read == 0 {
+            break;
+        }
+
+        context.consume(&buffer[..read]);
+        remaining -= read as u64;
+    }
+
+    Ok(context.finalize().into())
+}
+
+/// Computes the SHA-256 hash of `count` bytes starting at `offset`.
+#[cfg(feature = "signature-verify")]
+fn sha256_range<R: Read + Seek>(reader: &mut R, offset: u64, count: u64) -> Result<[u8; 32]> {
+    use sha2::{Digest, Sha256};
+
+    reader.seek(SeekFrom::Start(offset)).map_err(Error::Io)?;
+
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut reader.take(count), &mut hasher).map_err(Error::Io)?;
+
+    Ok(hasher.finalize().into())
+}
+
+#[cfg(feature = "signature-verify")]
+impl VPKVersion2 {
+    /// Verifies the RSA signature of the directory file against the public key stored next to it,
+    /// reading the signed data from the directory file, see [`Self::verify_signature_from`].
+    ///
+    /// Returns `Ok(false)` without opening the directory file when the VPK has no signature section.
+    /// # Errors
+    /// - When the directory file cannot be opened or read
+    /// - See [`Self::verify_signature_from`]
+    pub fn verify_signature(&self, archive_path: &str, vpk_name: &str) -> Result<bool> {
+        if self.signature_section.is_none() {
+            return Ok(false);
+        }
+
+        let mut dir_file = self.archive_file(archive_path, vpk_name, VPK_DIR_ARCHIVE_INDEX_V2)?;
+        self.verify_signature_from(&mut dir_file)
+    }
+
+    /// Verifies the RSA signature of the directory file read by `reader` against the public key stored next to it.
+    ///
+    /// The signature is a PKCS#1 v1.5 signature of the SHA-256 hash of the directory file up to the signature section,
+    /// which covers the header, tree, file data and both MD5 sections. The signed data is only hashed here, not when the VPK is parsed,
+    /// and the section sizes of [`Self::header`] decide how much of `reader` is hashed.
+    ///
+    /// Returns `Ok(false)` when the VPK has no signature section or the signature does not match.
+    /// Note that the public key is taken from the file itself, compare it against a known key to verify who signed it.
+    /// # Errors
+    /// - When the public key or signature sizes are invalid
+    /// - When the public key cannot be parsed
+    /// - When an IO operation fails
+    pub fn verify_signature_from<R: Read + Seek>(&self, reader: &mut R) -> Result<bool> {
+        use rsa::{Pkcs1v15Sign, RsaPublicKey, pkcs8::DecodePublicKey};
+        use sha2::Sha256;
+
+        let Some(signature_section) = &self.signature_section else {
+            return Ok(false);
+        };
+
+        let public_key = signature_section
+            .public_key
+            .get(..signature_section.public_key_size as usize)
+            .ok_or(Error::BadData(format!(
+                "Public key size should be at most 160 but is {}",
+                signature_section.public_key_size
+            )))?;
+        let signature = signature_section
+            .signature
+            .get(..signature_section.signature_size as usize)
+            .ok_or(Error::BadData(format!(
+                "Signature size should be at most 128 but is {}",
+                signature_section.signature_size
+            )))?;
+
+        let public_key = RsaPublicKey::from_public_key_der(public_key)
+            .map_err(|e| Error::BadData(format!("Failed to parse public key: {e}")))?;
+
+        let signed_data_len = mem::size_of::<VPKHeaderV2>() as u64
+            + u64::from(self.header.tree_size)
+            + u64::from(self.header.file_data_section_size)
+            + u64::from(self.header.archive_md5_section_size)
+            + u64::from(self.header.other_md5_section_size);
+        let signed_data_hash = sha256_range(reader, 0, signed_data_len)?;
+
+        Ok(public_key
+            .verify(Pkcs1v15Sign::new::<Sha256>(), &signed_data_hash, signature)
+            .is_ok())
+    }
 }
 
 impl PakReader for VPKVersion2 {
-    fn read_file(&self, _archive_path: &str, _vpk_name: &str, _file_path: &str) -> Option<Vec<u8>> {
-        todo!()
+    fn file_paths(&self) -> Vec<&str> {
+        self.tree.files.keys().map(String::as_str).collect()
+    }
+
+    fn file_size(&self, file_path: &str) -> Option<u64> {
+        self.tree
+            .get(file_path)
+            .map(|entry| u64::from(entry.preload_length) + u64::from(entry.entry_length))
+    }
+
+    fn file_crc(&self, file_path: &str) -> Option<u32> {
+        self.tree.get(file_path).map(|entry| entry.crc)
     }
 
-    fn extract_file(
+    fn file_location(&self, file_path: &str) -> Option<FileLocation> {
+        let entry = self.tree.get(file_path)?;
+        let length = u64::from(entry.entry_length);
+
+        Some(if length == 0 {
+            FileLocation::Preload
+        } else if u32::from(entry.archive_index) == VPK_DIR_ARCHIVE_INDEX_V2 {
+            // Data stored in the directory file starts after the tree
+            FileLocation::InDirectory {
+                offset: mem::size_of::<VPKHeaderV2>() as u64
+                    + u64::from(self.header.tree_size)
+                    + u64::from(entry.entry_offset),
+                length,
+            }
+        } else {
+            FileLocation::Archive {
+                index: entry.archive_index,
+                offset: entry.entry_offset.into(),
+                length,
+            }
+        })
+    }
+
+    fn read_file_streaming(
         &self,
-        _archive_path: &str,
-        _vpk_name: &str,
-        _file_path: &str,
-        _output_path: &str,
-    ) -> Result<()> {
-        todo!()
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+    ) -> Result<Box<dyn Read + '_>> {
+        let entry = self
+            .tree
+            .get(file_path)
+            .ok_or(Error::FileNotFound(file_path.to_string()))?;
+
+        let data = self.entry_data(archive_path, vpk_name, file_path, entry)?;
+
+        Ok(Box::new(CrcValidatingReader::new(
+            self.preload(file_path, entry)?.chain(data),
+            entry.crc,
+            file_path,
+        )))
     }
 
+    fn read_file_to_with(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        file_path: &str,
+        out: &mut dyn Write,
+        options: &ExtractOptions,
+        progress: &mut dyn FnMut(u64, u64),
+    ) -> Result<u64> {
+        let (file_path, entry) = self
+            .tree
+            .find(file_path, options.case_sensitivity)
+            .ok_or(Error::FileNotFound(file_path.to_string()))?;
+
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+        let mut digest = crc.digest();
+        let total = u64::from(entry.preload_length) + u64::from(entry.entry_length);
+
+        let preload = self.preload(file_path, entry)?;
+        out.write_all(preload).map_err(Error::Io)?;
+        digest.update(preload);
+        let mut written = preload.len() as u64;
+        if written > 0 {
+            progress(written, total);
+        }
+
+        let mut data = self.entry_data(archive_path, vpk_name, file_path, entry)?;
+        let mut remaining = u64::from(entry.entry_length);
+        while remaining > 0 {
+            let count = usize::try_from(remaining).map_or(options.chunk_size(), |remaining| {
+                min(options.chunk_size(), remaining)
+            });
+            let chunk = read_archive_chunk(&mut data, count, remaining, file_path)?;
+
+            out.write_all(&chunk).map_err(Error::Io)?;
+            written += chunk.len() as u64;
+            progress(written, total);
+
+            remaining -= chunk.len() as u64;
+
+            digest.update(&chunk);
+        }
+
+        let found = digest.finalize();
+        if !options.verify_crc || found == entry.crc {
+            Ok(written)
+        } else {
+            Err(Error::CrcMismatch {
+                expected: entry.crc,
+                found,
+                file: file_path.to_string(),
+            })
+        }
+    }
+
+    /// Uses the archive MD5 section to check the archives, archives without checksums are not reported.
+    #[cfg(feature = "md5")]
+    fn check_archive_consistency(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        samples_per_archive: usize,
+    ) -> Vec<ArchiveConsistency> {
+        let mut by_archive: BTreeMap<u16, Vec<&VPKArchiveMD5SectionEntry>> = BTreeMap::new();
+        for md5_entry in &self.archive_md5_section_entries {
+            if let Ok(archive_index) = u16::try_from(md5_entry.archive_index) {
+                by_archive.entry(archive_index).or_default().push(md5_entry);
+            }
+        }
+
+        by_archive
+            .into_iter()
+            .map(|(archive_index, md5_entries)| {
+                match self.check_archive_md5(
+                    archive_path,
+                    vpk_name,
+                    archive_index,
+                    sample_evenly(&md5_entries, samples_per_archive),
+                ) {
+                    Ok(true) => ArchiveConsistency::Consistent(archive_index),
+                    Ok(false) => ArchiveConsistency::ArchiveStale(archive_index),
+                    Err(e) => ArchiveConsistency::Unreadable(archive_index, e),
+                }
+            })
+            .collect()
+    }
+
+    /// Without the `md5` feature the archive MD5 section can't be checked, so the CRCs of sampled files are checked instead.
+    #[cfg(not(feature = "md5"))]
+    fn check_archive_consistency(
+        &self,
+        archive_path: &str,
+        vpk_name: &str,
+        samples_per_archive: usize,
+    ) -> Vec<ArchiveConsistency> {
+        let mut by_archive: BTreeMap<u16, Vec<(&String, &VPKDirectoryEntry)>> = BTreeMap::new();
+        for (file_path, entry) in &self.tree.files {
+            // Files stored entirely in preload data don't touch the archives
+            if entry.entry_length > 0 {
+                by_archive
+                    .entry(entry.archive_index)
+                    .or_default()
+                    .push((file_path, entry));
+            }
+        }
+
+        by_archive
+            .into_iter()
+            .map(|(archive_index, mut entries)| {
+                entries.sort_by_key(|(file_path, entry)| (entry.entry_offset, *file_path));
+
+                let result =
+                    sample_evenly(&entries, samples_per_archive).try_for_each(|(file_path, _)| {
+                        self.read_file_to(archive_path, vpk_name, file_path, &mut io::sink())
+                            .map(|_| ())
+                    });
+
+                ArchiveConsistency::from_result(archive_index, result)
+            })
+            .collect()
+    }
+
+    fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+
+        let sections = [
+            (
+                "file data",
+                self.header.file_data_section_size,
+                // A lazily loaded section is not held in memory
+                match self.file_data_offset {
+                    Some(_) => self.header.file_data_section_size as usize,
+                    None => self.file_data.len(),
+                },
+            ),
+            (
+                "archive MD5",
+                self.header.archive_md5_section_size,
+                self.archive_md5_section_entries.len() * size_of::<VPKArchiveMD5SectionEntry>(),
+            ),
+            (
+                "other MD5",
+                self.header.other_md5_section_size,
+                size_of::<VPKOtherMD5Section>(),
+            ),
+            (
+                "signature",
+                self.header.signature_section_size,
+                self.signature_section
+                    .as_ref()
+                    .map_or(0, |_| size_of::<VPKSignatureSection>()),
+            ),
+        ];
+
+        for (section, header_size, parsed_size) in sections {
+            if usize::try_from(header_size).ok() != Some(parsed_size) {
+                issues.push(ValidationIssue::header(
+                    ValidationIssueKind::SectionSizeMismatch,
+                    format!(
+                        "Header {section} section size is {header_size} but the parsed section is {parsed_size} bytes"
+                    ),
+                ));
+            }
+        }
+
+        issues.extend(validate_tree(&self.tree));
+        issues
+    }
+
+    /// Data stored in the directory file is read from the memory-mapped directory file under [`VPK_DIR_ARCHIVE_INDEX_V2`].
     #[cfg(feature = "mem-map")]
-    fn extract_file_mem_map(
+    fn extract_file_mem_map_with(
         &self,
         _archive_path: &str,
-        _archive_mmaps: &HashMap<u16, FileBuffer>,
+        archive_mmaps: &HashMap<u16, FileBuffer>,
         _vpk_name: &str,
-        _file_path: &str,
-        _output_path: &str,
+        file_path: &str,
+        output_path: &str,
+        options: &ExtractOptions,
     ) -> Result<()> {
-        todo!()
+        let (file_path, entry) = self
+            .tree
+            .find(file_path, options.case_sensitivity)
+            .ok_or(Error::FileNotFound(file_path.to_string()))?;
+
+        write_output_file(
+            std::path::Path::new(output_path),
+            file_path,
+            options.keep_partial,
+            |out_file| {
+                let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+                let mut digest = crc.digest();
+
+                let preload = self.preload(file_path, entry)?;
+                out_file.write_all(preload).map_err(Error::Io)?;
+                digest.update(preload);
+                let mut written = preload.len() as u64;
+
+                if let Some(
+                    FileLocation::InDirectory { offset, length }
+                    | FileLocation::Archive { offset, length, .. },
+                ) = self.file_location(file_path)
+                {
+                    let archive_file = archive_mmaps
+                        .get(&entry.archive_index)
+                        .ok_or(Error::MemoryMappedFileNotFound(entry.archive_index))?;
+
+                    let data = usize::try_from(offset)
+                        .ok()
+                        .zip(usize::try_from(offset + length).ok())
+                        .and_then(|(start, end)| archive_file.get(start..end))
+                        .ok_or_else(|| Error::UnexpectedEof {
+                            file: file_path.to_string(),
+                            missing: (offset + length)
+                                .saturating_sub(archive_file.len() as u64)
+                                .min(length),
+                        })?;
+
+                    self.observer.bytes_read(entry.archive_index, length);
+                    out_file.write_all(data).map_err(Error::Io)?;
+                    digest.update(data);
+                    written += length;
+                }
+
+                let found = digest.finalize();
+                if !options.verify_crc || found == entry.crc {
+                    Ok(written)
+                } else {
+                    Err(Error::CrcMismatch {
+                        expected: entry.crc,
+                        found,
+                        file: file_path.to_string(),
+                    })
+                }
+            },
+        )?;
+
+        Ok(())
     }
 }
 
 impl PakWriter for VPKVersion2 {
-    fn write_dir(&self, _out_path: &str) -> Result<()> {
-        todo!()
+    /// Writes the directory file with the tree, file data section and archive MD5 section of this VPK.
+    ///
+    /// The section sizes in the header and the tree, archive MD5 section and file checksums are computed from the written data.
+    /// The signature section is written unchanged, so it only verifies if the signed data did not change.
+    /// The file data section must have been loaded eagerly, see [`FileDataMode`].
+    #[cfg(feature = "md5")]
+    fn write_dir(&self, output_path: &str) -> Result<()> {
+        if self.file_data_offset.is_some() {
+            return Err(Error::BadData(
+                "The file data section was loaded lazily and is not available to write".to_string(),
+            ));
+        }
+
+        let out_path = std::path::Path::new(output_path);
+        if let Some(prefix) = out_path.parent() {
+            std::fs::create_dir_all(prefix).map_err(Error::Io)?;
+        }
+
+        let (tree, tree_size) = self.serialize_tree()?;
+
+        let mut archive_md5_section = Vec::new();
+        for md5_entry in &self.archive_md5_section_entries {
+            md5_entry.write(&mut archive_md5_section)?;
+        }
+
+        let mut signature_section = Vec::new();
+        if let Some(section) = &self.signature_section {
+            section.write(&mut signature_section)?;
+        }
+
+        let header = VPKHeaderV2 {
+            signature: self.header.signature,
+            version: self.header.version,
+            tree_size,
+            file_data_section_size: u32::try_from(self.file_data.len())
+                .map_err(|_| Error::DataTooLarge)?,
+            archive_md5_section_size: u32::try_from(archive_md5_section.len())
+                .map_err(|_| Error::DataTooLarge)?,
+            other_md5_section_size: size_of::<VPKOtherMD5Section>() as u32,
+            signature_section_size: u32::try_from(signature_section.len())
+                .map_err(|_| Error::DataTooLarge)?,
+        };
+
+        let mut data = Vec::new();
+        header.write(&mut data)?;
+        data.extend_from_slice(&tree);
+        data.extend_from_slice(&self.file_data);
+        data.extend_from_slice(&archive_md5_section);
+
+        let tree_checksum: [u8; 16] = md5::compute(&tree).into();
+        let archive_md5_section_checksum: [u8; 16] = md5::compute(&archive_md5_section).into();
+        data.extend_from_slice(&tree_checksum);
+        data.extend_from_slice(&archive_md5_section_checksum);
+
+        // The file checksum covers everything up to the file checksum itself
+        let file_checksum: [u8; 16] = md5::compute(&data).into();
+
+        let mut out_file = BufWriter::new(File::create(out_path).map_err(Error::Io)?);
+        out_file.write_all(&data).map_err(Error::Io)?;
+        out_file.write_all(&file_checksum).map_err(Error::Io)?;
+        out_file.write_all(&signature_section).map_err(Error::Io)?;
+        out_file.flush().map_err(Error::Io)
+    }
+
+    /// Writing the directory file needs the `md5` feature to compute its checksums, see [`VPK_CAPS_V2`].
+    /// # Errors
+    /// - Always, without the `md5` feature
+    #[cfg(not(feature = "md5"))]
+    fn write_dir(&self, _output_path: &str) -> Result<()> {
+        Err(Error::BadData(
+            "Writing VPK version 2 directory files requires the md5 feature".to_string(),
+        ))
     }
 }
 
@@ -257,131 +1361,59 @@ impl PakWorker for VPKVersion2 {
             },
             tree: VPKTree::new(),
             file_data: Vec::new(),
+            file_data_offset: None,
             archive_md5_section_entries: Vec::new(),
             other_md5_section: VPKOtherMD5Section::new(),
             signature_section: None,
+            observer: Observer::default(),
+            naming: Naming::default(),
         }
     }
 
-    fn from_file(file: &mut File) -> Result<Self> {
+    fn from_reader_with_options<R: Read + Seek>(
+        file: &mut R,
+        options: &ParseOptions,
+    ) -> Result<Self> {
         let header = VPKHeaderV2::from(file)?;
 
         let tree_start = file.stream_position().map_err(Error::Io)?;
-        let tree = VPKTree::from(file, tree_start, header.tree_size.into())?;
-
-        let file_data = file
-            .read_bytes(
-                header
-                    .file_data_section_size
-                    .try_into()
-                    .map_err(|_| Error::DataTooLarge)?,
-            )
-            .map_err(|e| Error::Util {
-                source: e,
-                context: "Failed to read file data section".to_string(),
-            })?;
-
-        let mut archive_md5_section_entries = Vec::new();
-        while archive_md5_section_entries.len()
-            < (header.archive_md5_section_size / 28)
-                .try_into()
-                .map_err(|_| Error::DataTooLarge)?
-        {
-            archive_md5_section_entries.push(VPKArchiveMD5SectionEntry {
-                archive_index: file.read_u32().map_err(|e| Error::Util {
-                    source: e,
-                    context: "Failed to read archive md5 section archive index".to_string(),
-                })?,
-
-                starting_offset: file.read_u32().map_err(|e| Error::Util {
-                    source: e,
-                    context: "Failed to read archive md5 section offset".to_string(),
-                })?,
+        let tree_size = checked_tree_size(file, tree_start, header.tree_size.into(), options)?;
+        let tree = VPKTree::from_with_options(file, tree_start, tree_size, options)?;
 
-                count: file.read_u32().map_err(|e| Error::Util {
-                    source: e,
-                    context: "Failed to read archive md5 section count".to_string(),
-                })?,
-
-                md5_checksum: file
-                    .read_bytes(16)
+        let (file_data, file_data_offset) = match options.file_data {
+            FileDataMode::Eager => {
+                let file_data = file
+                    .read_bytes(
+                        header
+                            .file_data_section_size
+                            .try_into()
+                            .map_err(|_| Error::DataTooLarge)?,
+                    )
                     .map_err(|e| Error::Util {
                         source: e,
-                        context: "Failed to read archive md5 section signature".to_string(),
-                    })?
-                    .try_into()
-                    .expect("Bytes read should match parameter value"),
-            });
-        }
-
-        let other_md5_section = VPKOtherMD5Section {
-            tree_checksum: file
-                .read_bytes(16)
-                .map_err(|e| Error::Util {
-                    source: e,
-                    context: "Failed to read other md5 section tree checksum".to_string(),
-                })?
-                .try_into()
-                .expect("Bytes read should match parameter value"),
-
-            archive_md5_section_checksum: file
-                .read_bytes(16)
-                .map_err(|e| Error::Util {
-                    source: e,
-                    context: "Failed to read other md5 section checksum".to_string(),
-                })?
-                .try_into()
-                .expect("Bytes read should match parameter value"),
-
-            unknown: file
-                .read_bytes(16)
-                .map_err(|e| Error::Util {
-                    source: e,
-                    context: "Failed to read other md5 section unknown".to_string(),
-                })?
-                .try_into()
-                .expect("Bytes read should match parameter value"),
+                        context: "Failed to read file data section".to_string(),
+                    })?;
+                (file_data, None)
+            }
+            FileDataMode::Lazy => {
+                let offset = file.stream_position().map_err(Error::Io)?;
+                file.seek(SeekFrom::Current(header.file_data_section_size.into()))
+                    .map_err(Error::Io)?;
+                (Vec::new(), Some(offset))
+            }
         };
 
-        let signature_section = if header.signature_section_size == 296 {
-            let public_key_size = file.read_u32().map_err(|e| Error::Util {
-                source: e,
-                context: "Failed reading signature public key size".to_string(),
-            })?;
-
-            let public_key = file
-                .read_bytes(
-                    public_key_size
-                        .try_into()
-                        .map_err(|_| Error::DataTooLarge)?,
-                )
-                .map_err(|e| Error::Util {
-                    source: e,
-                    context: "Failed to read signature public key".to_string(),
-                })?;
-
-            let signature_size = file.read_u32().map_err(|e| Error::Util {
-                source: e,
-                context: "Failed to read signature size".to_string(),
-            })?;
+        let archive_md5_section_count =
+            header.archive_md5_section_size as usize / size_of::<VPKArchiveMD5SectionEntry>();
+        let mut archive_md5_section_entries = Vec::new();
+        while archive_md5_section_entries.len() < archive_md5_section_count {
+            archive_md5_section_entries.push(VPKArchiveMD5SectionEntry::from(file)?);
+        }
 
-            let signature = file
-                .read_bytes(signature_size.try_into().map_err(|_| Error::DataTooLarge)?)
-                .map_err(|e| Error::Util {
-                    source: e,
-                    context: "Failed to read signature".to_string(),
-                })?;
+        let other_md5_section = VPKOtherMD5Section::from(file)?;
 
-            Some(VPKSignatureSection {
-                public_key_size,
-                public_key: public_key
-                    .try_into()
-                    .map_err(|_| Error::BadData("Public key must be 160 bytes".to_string()))?,
-                signature_size,
-                signature: signature
-                    .try_into()
-                    .map_err(|_| Error::BadData("Signature must be 128 bytes".to_string()))?,
-            })
+        let signature_section = if header.signature_section_size == 296 {
+            Some(VPKSignatureSection::from(file)?)
         } else {
             let _ = file.seek(std::io::SeekFrom::Current(
                 header.signature_section_size.into(),
@@ -393,11 +1425,27 @@ impl PakWorker for VPKVersion2 {
             header,
             tree,
             file_data,
+            file_data_offset,
             archive_md5_section_entries,
             other_md5_section,
             signature_section,
+            observer: Observer::default(),
+            naming: Naming::default(),
         })
     }
+
+    #[cfg(feature = "detect")]
+    fn format(&self) -> crate::detect::PakFormat {
+        crate::detect::PakFormat::VPKVersion2
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
 }
 
 impl TryFrom<&mut File> for VPKVersion2 {
@@ -0,0 +1,197 @@
+//! Dry-run consistency checks for loaded VPKs, see [`PakReader::validate`](super::PakReader::validate).
+
+use super::{DirEntry, VPK_ENTRY_TERMINATOR, VPKDirectoryEntry, VPKTree};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The kind of problem found by [`PakReader::validate`](super::PakReader::validate).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ValidationIssueKind {
+    /// An entry's terminator is not [`VPK_ENTRY_TERMINATOR`].
+    BadTerminator,
+    /// An entry has a preload length but no preload data.
+    MissingPreload,
+    /// Preload data exists for a path whose entry has no preload length, or no entry at all.
+    UnexpectedPreload,
+    /// An entry's preload data differs in size from its preload length.
+    PreloadLengthMismatch,
+    /// A Respawn entry has no file parts.
+    NoFileParts,
+    /// A Respawn file part is larger than its uncompressed length.
+    FilePartTooLarge,
+    /// Another path in the tree differs from this one only by case.
+    CaseCollision,
+    /// The path contains a backslash.
+    Backslash,
+    /// The path contains a `..` component.
+    ParentComponent,
+    /// A section size in the header does not match the parsed section.
+    SectionSizeMismatch,
+}
+
+/// A single problem found by [`PakReader::validate`](super::PakReader::validate).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    /// What kind of problem was found.
+    pub kind: ValidationIssueKind,
+    /// The path of the offending file, or `None` for problems with the header.
+    pub path: Option<String>,
+    /// A human-readable description of the problem.
+    pub message: String,
+}
+
+impl ValidationIssue {
+    /// Creates an issue for the file at `path`.
+    pub(crate) fn file(kind: ValidationIssueKind, path: &str, message: String) -> Self {
+        Self {
+            kind,
+            path: Some(path.to_string()),
+            message,
+        }
+    }
+
+    /// Creates an issue for the header.
+    pub(crate) fn header(kind: ValidationIssueKind, message: String) -> Self {
+        Self {
+            kind,
+            path: None,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{path}: {}", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl<DirectoryEntry> VPKTree<DirectoryEntry>
+where
+    DirectoryEntry: DirEntry,
+{
+    /// Checks the paths and preload data of the tree without touching any archives.
+    ///
+    /// Reports preload data that does not match the preload lengths of the entries,
+    /// paths containing backslashes or `..` components, and paths that differ only by case.
+    /// Issues are ordered by path.
+    #[must_use]
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut file_paths: Vec<&String> = self.files.keys().collect();
+        file_paths.sort_unstable();
+
+        let mut issues = Vec::new();
+        let mut by_lowercase: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+
+        for file_path in file_paths {
+            let preload_length = self.files[file_path].get_preload_length();
+            match self.preload.get(file_path) {
+                None if preload_length > 0 => issues.push(ValidationIssue::file(
+                    ValidationIssueKind::MissingPreload,
+                    file_path,
+                    format!("Preload length is {preload_length} but there is no preload data"),
+                )),
+                Some(_) if preload_length == 0 => issues.push(ValidationIssue::file(
+                    ValidationIssueKind::UnexpectedPreload,
+                    file_path,
+                    "Preload data exists but the preload length is 0".to_string(),
+                )),
+                Some(preload) if preload.len() != preload_length => {
+                    issues.push(ValidationIssue::file(
+                        ValidationIssueKind::PreloadLengthMismatch,
+                        file_path,
+                        format!(
+                            "Preload length is {preload_length} but there are {} bytes of preload data",
+                            preload.len()
+                        ),
+                    ));
+                }
+                _ => {}
+            }
+
+            if file_path.contains('\\') {
+                issues.push(ValidationIssue::file(
+                    ValidationIssueKind::Backslash,
+                    file_path,
+                    "Path contains a backslash".to_string(),
+                ));
+            }
+
+            if file_path
+                .split(['/', '\\'])
+                .any(|component| component == "..")
+            {
+                issues.push(ValidationIssue::file(
+                    ValidationIssueKind::ParentComponent,
+                    file_path,
+                    "Path contains a '..' component".to_string(),
+                ));
+            }
+
+            by_lowercase
+                .entry(file_path.to_lowercase())
+                .or_default()
+                .push(file_path);
+        }
+
+        let mut orphaned: Vec<&String> = self
+            .preload
+            .keys()
+            .filter(|file_path| !self.files.contains_key(*file_path))
+            .collect();
+        orphaned.sort_unstable();
+        issues.extend(orphaned.into_iter().map(|file_path| {
+            ValidationIssue::file(
+                ValidationIssueKind::UnexpectedPreload,
+                file_path,
+                "Preload data exists but there is no entry".to_string(),
+            )
+        }));
+
+        for colliding in by_lowercase.values().filter(|paths| paths.len() > 1) {
+            for file_path in colliding {
+                let others: Vec<&str> = colliding
+                    .iter()
+                    .copied()
+                    .filter(|other| other != file_path)
+                    .collect();
+
+                issues.push(ValidationIssue::file(
+                    ValidationIssueKind::CaseCollision,
+                    file_path,
+                    format!("Path differs only by case from {}", others.join(", ")),
+                ));
+            }
+        }
+
+        issues.sort_by(|a, b| a.path.cmp(&b.path));
+        issues
+    }
+}
+
+/// Checks the paths and preload data of a tree using [`VPKDirectoryEntry`] as well as the terminator of every entry.
+pub(crate) fn validate_tree(tree: &VPKTree<VPKDirectoryEntry>) -> Vec<ValidationIssue> {
+    let mut issues = tree.validate();
+
+    issues.extend(
+        tree.files
+            .iter()
+            .filter(|(_, entry)| entry.terminator != VPK_ENTRY_TERMINATOR)
+            .map(|(file_path, entry)| {
+                ValidationIssue::file(
+                    ValidationIssueKind::BadTerminator,
+                    file_path,
+                    format!(
+                        "Terminator is {:#X} instead of {VPK_ENTRY_TERMINATOR:#X}",
+                        entry.terminator
+                    ),
+                )
+            }),
+    );
+
+    issues.sort_by(|a, b| a.path.cmp(&b.path));
+    issues
+}
@@ -0,0 +1,155 @@
+use crate::{pak, util};
+use std::error::Error;
+use std::io;
+
+fn chain_depth(error: &dyn Error) -> usize {
+    let mut depth = 1;
+    let mut current = error.source();
+    while let Some(source) = current {
+        depth += 1;
+        current = source.source();
+    }
+    depth
+}
+
+#[test]
+fn test_util_error_source() {
+    let error = util::Error::Io(io::Error::other("inner"));
+    assert_eq!(chain_depth(&error), 2);
+
+    let error = util::Error::Utf8(String::from_utf8(vec![0xFF]).unwrap_err());
+    assert_eq!(chain_depth(&error), 2);
+}
+
+#[test]
+fn test_pak_error_source() {
+    assert_eq!(chain_depth(&pak::Error::Io(io::Error::other("inner"))), 2);
+    assert_eq!(
+        chain_depth(&pak::Error::TreeNotFound(io::Error::other("inner"))),
+        2
+    );
+    assert_eq!(
+        chain_depth(&pak::Error::ArchiveOpen {
+            path: "pak01_000.vpk".to_string(),
+            source: io::Error::other("inner"),
+        }),
+        2
+    );
+    assert_eq!(chain_depth(&pak::Error::DataTooLarge), 1);
+
+    let error = pak::Error::Util {
+        source: util::Error::Io(io::Error::other("inner")),
+        context: "Failed to read".to_string(),
+    };
+    assert_eq!(chain_depth(&error), 3);
+    assert_eq!(
+        error.source().unwrap().source().unwrap().to_string(),
+        "inner"
+    );
+}
+
+#[test]
+fn test_display_leaves_out_source() {
+    let error = pak::Error::Util {
+        source: util::Error::Io(io::Error::other("inner")),
+        context: "Failed to read".to_string(),
+    };
+    assert_eq!(error.to_string(), "Failed to read");
+    assert_eq!(error.source().unwrap().to_string(), "IO error");
+
+    let error = pak::Error::ArchiveOpen {
+        path: "pak01_000.vpk".to_string(),
+        source: io::Error::other("inner"),
+    };
+    assert_eq!(error.to_string(), "failed to open archive pak01_000.vpk");
+}
+
+#[cfg(feature = "detect")]
+#[test]
+fn test_detect_error_source() {
+    use crate::detect;
+
+    let error = detect::Error::Pak(pak::Error::Util {
+        source: util::Error::Io(io::Error::other("inner")),
+        context: "Failed to read".to_string(),
+    });
+    assert_eq!(chain_depth(&error), 3);
+    assert_eq!(chain_depth(&detect::Error::UnknownFormat), 1);
+}
+
+fn util_operation() -> util::Result<()> {
+    Err(util::Error::InvalidPattern("[".to_string()))
+}
+
+fn pak_operation() -> pak::Result<()> {
+    Err(pak::Error::Util {
+        source: util::Error::Io(io::Error::other("inner")),
+        context: "Failed to read".to_string(),
+    })
+}
+
+fn root_from_util() -> crate::Result<()> {
+    util_operation()?;
+    Ok(())
+}
+
+fn root_from_pak() -> crate::Result<()> {
+    pak_operation()?;
+    Ok(())
+}
+
+#[test]
+fn test_root_error_from_util() {
+    let error = root_from_util().unwrap_err();
+    assert!(matches!(
+        error,
+        crate::Error::Util(util::Error::InvalidPattern(_))
+    ));
+    assert_eq!(error.to_string(), util_operation().unwrap_err().to_string());
+    assert_eq!(chain_depth(&error), 1);
+}
+
+#[test]
+fn test_root_error_from_pak() {
+    let error = root_from_pak().unwrap_err();
+    assert!(matches!(error, crate::Error::Pak(pak::Error::Util { .. })));
+    assert_eq!(error.to_string(), pak_operation().unwrap_err().to_string());
+
+    // The source chain of the wrapped error is passed through without an extra level
+    assert_eq!(chain_depth(&error), 3);
+    assert_eq!(
+        error.source().unwrap().source().unwrap().to_string(),
+        "inner"
+    );
+}
+
+#[cfg(feature = "detect")]
+#[test]
+fn test_root_error_from_detect() {
+    use crate::detect;
+
+    fn detect_operation() -> detect::Result<()> {
+        pak_operation().map_err(detect::Error::Pak)
+    }
+
+    fn root_from_detect() -> crate::Result<()> {
+        detect_operation()?;
+        Ok(())
+    }
+
+    let error = root_from_detect().unwrap_err();
+    assert!(matches!(
+        error,
+        crate::Error::Detect(detect::Error::Pak(pak::Error::Util { .. }))
+    ));
+    assert_eq!(
+        error.to_string(),
+        detect_operation().unwrap_err().to_string()
+    );
+    assert_eq!(chain_depth(&error), 3);
+
+    assert!(matches!(
+        crate::open(std::path::Path::new("missing_dir.vpk")),
+        Err(crate::Error::Detect(_))
+    ));
+}
@@ -1,6 +1,6 @@
 use crate::util::file::*;
 use crate::util::{Error, Result};
-use std::io::{Seek, SeekFrom, Write};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 use tempfile::tempfile;
 
 #[test]
@@ -43,6 +43,40 @@ fn test_u32() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_u48() -> Result<()> {
+    let mut file = tempfile().map_err(Error::Io)?;
+    file.write_u48(0xBEEF_CAFE_BABE)?;
+    file.write_u48(MAX_U48)?;
+
+    file.seek(SeekFrom::Start(0)).map_err(Error::Io)?;
+    assert_eq!(file.read_u48()?, 0xBEEF_CAFE_BABE);
+    assert_eq!(file.read_u48()?, MAX_U48);
+    assert_eq!(
+        file.stream_position().map_err(Error::Io)?,
+        12,
+        "Each value should take 6 bytes"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_u48_out_of_range() {
+    let mut data = Vec::new();
+    let result = data.write_u48(MAX_U48 + 1);
+    assert!(
+        matches!(
+            result,
+            Err(Error::ValueOutOfRange {
+                value,
+                max: MAX_U48
+            }) if value == MAX_U48 + 1
+        ),
+        "Expected error for a value larger than 48 bits"
+    );
+    assert!(data.is_empty(), "Nothing should be written");
+}
+
 #[test]
 fn test_u64() -> Result<()> {
     let mut file = tempfile().map_err(Error::Io)?;
@@ -89,6 +123,87 @@ fn test_partial_read_bytes() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_read_bytes_across_boundary() -> Result<()> {
+    // A chain returns the data of the first reader on its own before moving on to the second one
+    let mut reader = Cursor::new(vec![1, 2, 3]).chain(Cursor::new(vec![4, 5, 6]));
+    let read = reader.read_bytes(5)?;
+    assert_eq!(read, vec![1, 2, 3, 4, 5]);
+    Ok(())
+}
+
+#[test]
+fn test_read_bytes_past_eof() {
+    let mut reader = Cursor::new(vec![1, 2, 3]);
+    let result = reader.read_bytes(5);
+    assert!(
+        matches!(
+            result,
+            Err(Error::UnexpectedEof {
+                requested: 5,
+                available: 3
+            })
+        ),
+        "Expected unexpected EOF error for short read"
+    );
+}
+
+#[test]
+fn test_len_prefixed_bytes() -> Result<()> {
+    let mut file = tempfile().map_err(Error::Io)?;
+    file.write_len_prefixed_bytes(&[1, 2, 3, 4, 5])?;
+    file.write_len_prefixed_bytes(&[])?;
+
+    file.seek(SeekFrom::Start(0)).map_err(Error::Io)?;
+    assert_eq!(file.read_u32()?, 5, "The length should be written as a u32");
+
+    file.seek(SeekFrom::Start(0)).map_err(Error::Io)?;
+    assert_eq!(file.read_len_prefixed_bytes(5)?, vec![1, 2, 3, 4, 5]);
+    assert_eq!(file.read_len_prefixed_bytes(0)?, Vec::<u8>::new());
+    Ok(())
+}
+
+#[test]
+fn test_len_prefixed_bytes_too_long() -> Result<()> {
+    let mut data = Vec::new();
+    data.write_len_prefixed_bytes(&[7; 10])?;
+
+    let mut reader = Cursor::new(data);
+    let result = reader.read_len_prefixed_bytes(9);
+    assert!(
+        matches!(
+            result,
+            Err(Error::BlockTooLong {
+                len: 10,
+                max_len: 9
+            })
+        ),
+        "Expected error for a block longer than the limit"
+    );
+    assert_eq!(reader.position(), 4, "Only the length should be read");
+    Ok(())
+}
+
+#[test]
+fn test_len_prefixed_bytes_past_eof() -> Result<()> {
+    let mut data = Vec::new();
+    data.write_u32(10)?;
+    data.write_bytes(&[1, 2, 3])?;
+
+    let result = Cursor::new(data).read_len_prefixed_bytes(usize::MAX);
+    assert!(
+        matches!(
+            result,
+            Err(Error::UnexpectedEof {
+                requested: 10,
+                available: 3
+            })
+        ),
+        "Expected unexpected EOF error for a truncated block"
+    );
+    Ok(())
+}
+
 #[test]
 fn test_read_u16_from_empty_file() {
     let mut file = tempfile().unwrap();
@@ -128,6 +243,36 @@ fn test_read_string_without_null_terminator_eof() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_read_string_limited() -> Result<()> {
+    let mut data = Cursor::new(b"materials\0".to_vec());
+    assert_eq!(data.read_string_limited(9)?, "materials");
+
+    let mut data = Cursor::new(b"materials\0".to_vec());
+    let result = data.read_string_limited(8);
+    assert!(
+        matches!(result, Err(Error::StringTooLong { max_len: 8 })),
+        "Expected error for a string longer than the limit"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_read_string_limited_stops_early() {
+    let mut data = Cursor::new(vec![b'a'; 10 * 1024 * 1024]);
+
+    let result = data.read_string_limited(MAX_STRING_LENGTH);
+    assert!(
+        matches!(result, Err(Error::StringTooLong { max_len }) if max_len == MAX_STRING_LENGTH),
+        "Expected error for 10 MB without a null terminator"
+    );
+    assert_eq!(
+        data.position(),
+        MAX_STRING_LENGTH as u64 + 1,
+        "Reading should stop right after the limit"
+    );
+}
+
 #[test]
 fn test_invalid_utf8_string() -> Result<()> {
     let mut file = tempfile().map_err(Error::Io)?;
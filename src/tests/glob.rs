@@ -0,0 +1,86 @@
+use crate::util::glob::Pattern;
+use crate::util::{Error, Result};
+
+#[test]
+fn test_glob_single_segment() -> Result<()> {
+    let pattern = Pattern::new("sound/music/*.wav")?;
+
+    assert!(pattern.matches("sound/music/theme.wav"));
+    assert!(pattern.matches("sound/music/.wav"));
+    assert!(!pattern.matches("sound/music/theme.mp3"));
+    assert!(!pattern.matches("sound/music/menu/theme.wav"));
+    assert!(!pattern.matches("sound/theme.wav"));
+    Ok(())
+}
+
+#[test]
+fn test_glob_recursive() -> Result<()> {
+    let pattern = Pattern::new("materials/**/*.vmt")?;
+
+    assert!(pattern.matches("materials/test.vmt"));
+    assert!(pattern.matches("materials/models/props/test.vmt"));
+    assert!(!pattern.matches("materials/models/props/test.vtf"));
+    assert!(!pattern.matches("models/materials/test.vmt"));
+
+    let pattern = Pattern::new("**")?;
+    assert!(pattern.matches("materials/models/props/test.vmt"));
+    Ok(())
+}
+
+#[test]
+fn test_glob_characters() -> Result<()> {
+    let pattern = Pattern::new("models/props/crate0?.mdl")?;
+
+    assert!(pattern.matches("models/props/crate01.mdl"));
+    assert!(!pattern.matches("models/props/crate1.mdl"));
+    assert!(!pattern.matches("models/props/crate001.mdl"));
+    assert!(!pattern.matches("Models/props/crate01.mdl"));
+    Ok(())
+}
+
+#[test]
+fn test_glob_invalid() {
+    for pattern in [
+        "",
+        "materials//test.vmt",
+        "materials/**.vmt",
+        "materials/***",
+    ] {
+        assert!(
+            matches!(Pattern::new(pattern), Err(Error::InvalidPattern(_))),
+            "Pattern {pattern:?} should be invalid"
+        );
+    }
+}
+
+#[test]
+fn test_glob_pathological() -> Result<()> {
+    // Recursive backtracking takes exponential time on these, they must fail quickly
+    let text = "a".repeat(100);
+
+    let pattern = Pattern::new("*a*a*a*a*a*a*a*a*a*a*b")?;
+    assert!(!pattern.matches(&text));
+    assert!(pattern.matches(&format!("{text}b")));
+
+    let pattern = Pattern::new(&format!("{}/b", ["**"; 10].join("/")))?;
+    let path = ["a"; 100].join("/");
+    assert!(!pattern.matches(&path));
+    assert!(pattern.matches(&format!("{path}/b")));
+    Ok(())
+}
+
+#[test]
+fn test_glob_stars() -> Result<()> {
+    let pattern = Pattern::new("*a*b?")?;
+
+    assert!(pattern.matches("abc"));
+    assert!(pattern.matches("xxaxxbxbc"));
+    assert!(!pattern.matches("ab"));
+    assert!(!pattern.matches("xxbac"));
+
+    let pattern = Pattern::new("**/*.vmt/**")?;
+    assert!(pattern.matches("a.vmt"));
+    assert!(pattern.matches("materials/a.vmt/b/c"));
+    assert!(!pattern.matches("materials/a.vtf/b"));
+    Ok(())
+}
@@ -1 +1,4 @@
+mod error;
 mod file;
+mod glob;
+mod path;
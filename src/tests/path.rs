@@ -0,0 +1,105 @@
+#[cfg(feature = "detect")]
+use crate::util::path::split_pak_path;
+use crate::util::path::{join_sanitized, normalize_vpk_path, split_dir_file_path};
+use std::borrow::Cow;
+use std::path::Path;
+
+#[test]
+fn test_join_sanitized() {
+    let base = Path::new("out");
+
+    assert_eq!(
+        join_sanitized(base, "materials/models/test.vmt"),
+        Some(base.join("materials").join("models").join("test.vmt"))
+    );
+    assert_eq!(
+        join_sanitized(base, "./materials\\test.vmt"),
+        Some(base.join("materials").join("test.vmt"))
+    );
+}
+
+#[test]
+fn test_join_sanitized_escape() {
+    let base = Path::new("out");
+
+    assert_eq!(join_sanitized(base, "../evil.txt"), None);
+    assert_eq!(join_sanitized(base, "materials/../../evil.txt"), None);
+    assert_eq!(
+        join_sanitized(base, "/etc/passwd"),
+        Some(base.join("etc").join("passwd"))
+    );
+    assert_eq!(join_sanitized(base, "C:/evil.txt"), None);
+    assert_eq!(join_sanitized(base, ""), None);
+}
+
+#[test]
+fn test_split_dir_file_path() {
+    assert_eq!(
+        split_dir_file_path(Path::new("tests/data/pak01_dir.vpk")),
+        Some(("tests/data", "pak01"))
+    );
+    assert_eq!(
+        split_dir_file_path(Path::new("pak01_dir.vpk")),
+        Some(("", "pak01"))
+    );
+    assert_eq!(
+        split_dir_file_path(Path::new("tests/data/pak01_000.vpk")),
+        None
+    );
+    assert_eq!(split_dir_file_path(Path::new("tests/data/_dir.vpk")), None);
+    assert_eq!(split_dir_file_path(Path::new("tests/data")), None);
+}
+
+#[cfg(feature = "detect")]
+#[test]
+fn test_split_pak_path() {
+    assert_eq!(
+        split_pak_path(Path::new("tests/data/pak01_dir.vpk")),
+        Some(("tests/data", "pak01"))
+    );
+    assert_eq!(
+        split_pak_path(Path::new("pak01_dir.vpk")),
+        Some(("", "pak01"))
+    );
+    assert_eq!(
+        split_pak_path(Path::new("tests/data/pak01.vpk")),
+        Some(("tests/data", "pak01"))
+    );
+    assert_eq!(
+        split_pak_path(Path::new("tests/data/_dir.vpk")),
+        Some(("tests/data", "_dir"))
+    );
+    assert_eq!(split_pak_path(Path::new("/")), None);
+}
+
+#[test]
+fn test_normalize_vpk_path() {
+    assert!(matches!(
+        normalize_vpk_path("materials/models/test.vmt", false),
+        Cow::Borrowed("materials/models/test.vmt")
+    ));
+    assert_eq!(
+        normalize_vpk_path("materials\\models/test.vmt", false),
+        "materials/models/test.vmt"
+    );
+    assert_eq!(
+        normalize_vpk_path("\\materials\\Models\\Test.vmt", false),
+        "materials/Models/Test.vmt"
+    );
+    assert_eq!(
+        normalize_vpk_path("/Materials\\Models/Test.VMT", true),
+        "materials/models/test.vmt"
+    );
+    assert_eq!(
+        normalize_vpk_path("./materials//models\\\\test.vmt", false),
+        "materials/models/test.vmt"
+    );
+    assert_eq!(
+        normalize_vpk_path(".\\./materials/models/", false),
+        "materials/models"
+    );
+    assert!(matches!(
+        normalize_vpk_path("./materials/test.vmt", false),
+        Cow::Borrowed("materials/test.vmt")
+    ));
+}
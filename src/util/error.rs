@@ -6,12 +6,66 @@ pub type Result<T> = core::result::Result<T, Error>;
 pub enum Error {
     Io(std::io::Error),
     Utf8(std::string::FromUtf8Error),
+    InvalidPattern(String),
+    UnexpectedEof {
+        requested: usize,
+        available: usize,
+    },
+    #[cfg(feature = "revpk")]
+    Lzham(super::lzham::LzhamError),
+    StringTooLong {
+        max_len: usize,
+    },
+    ValueOutOfRange {
+        value: u64,
+        max: u64,
+    },
+    BlockTooLong {
+        len: u64,
+        max_len: usize,
+    },
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", &self)
+        match self {
+            Error::Io(_) => write!(f, "IO error"),
+            Error::Utf8(_) => write!(f, "invalid UTF-8"),
+            Error::InvalidPattern(msg) => write!(f, "invalid pattern: {msg}"),
+            Error::UnexpectedEof {
+                requested,
+                available,
+            } => write!(
+                f,
+                "unexpected end of data, requested {requested} bytes but only {available} are available"
+            ),
+            #[cfg(feature = "revpk")]
+            Error::Lzham(_) => write!(f, "LZHAM error"),
+            Error::StringTooLong { max_len } => {
+                write!(f, "string longer than {max_len} bytes")
+            }
+            Error::ValueOutOfRange { value, max } => {
+                write!(f, "value {value} out of range, the maximum is {max}")
+            }
+            Error::BlockTooLong { len, max_len } => {
+                write!(f, "block of {len} bytes longer than {max_len} bytes")
+            }
+        }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Utf8(e) => Some(e),
+            #[cfg(feature = "revpk")]
+            Error::Lzham(e) => Some(e),
+            Error::InvalidPattern(_)
+            | Error::UnexpectedEof { .. }
+            | Error::StringTooLong { .. }
+            | Error::ValueOutOfRange { .. }
+            | Error::BlockTooLong { .. } => None,
+        }
+    }
+}
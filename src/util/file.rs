@@ -2,14 +2,20 @@
 
 use super::{Error, Result};
 
-use std::{
-    fs::File,
-    io::{Read, Write},
-};
+use std::io::{ErrorKind, Read, Write};
+
+/// The maximum length in bytes of a single path component read by the tree parsers.
+///
+/// No real path component comes close, a longer string means the data is corrupt.
+pub const MAX_STRING_LENGTH: usize = 4096;
+
+/// The largest value that fits in the 6 bytes written by [`VPKFileWriter::write_u48`].
+pub const MAX_U48: u64 = (1 << 48) - 1;
 
 /// Trait for reading data from binary files.
 ///
 /// Always uses little-endian byte order. Moves cursor forward after reading.
+#[allow(dead_code)]
 pub trait VPKFileReader {
     /// Reads a single byte from the file into a [`u8`].
     fn read_u8(&mut self) -> Result<u8>;
@@ -23,18 +29,37 @@ pub trait VPKFileReader {
     /// Reads 4 bytes from the file into a [`u32`].
     fn read_u32(&mut self) -> Result<u32>;
 
+    /// Reads 6 bytes from the file into a [`u64`].
+    fn read_u48(&mut self) -> Result<u64>;
+
     /// Reads 8 bytes from the file into a [`u64`].
     fn read_u64(&mut self) -> Result<u64>;
 
     /// Reads a null-terminated string from the file.
+    ///
+    /// Reads until a null byte or the end of the file, however far away that is.
+    /// Use [`read_string_limited`](VPKFileReader::read_string_limited) for data that may be corrupt.
     fn read_string(&mut self) -> Result<String>;
 
+    /// Reads a null-terminated string of at most `max_len` bytes, excluding the terminator, from the file.
+    ///
+    /// Fails with [`Error::StringTooLong`] without reading further once `max_len` bytes were read without finding the terminator.
+    fn read_string_limited(&mut self, max_len: usize) -> Result<String>;
+
     /// Reads a specified number of bytes from the file into a [`Vec<u8>`].
+    ///
+    /// Keeps reading until `count` bytes were read, fails with [`Error::UnexpectedEof`] if the data ends before that.
     fn read_bytes(&mut self, count: usize) -> Result<Vec<u8>>;
+
+    /// Reads a block of bytes prefixed with its length as a [`u32`] from the file.
+    ///
+    /// Fails with [`Error::BlockTooLong`] without reading the block if its length is larger than `max_len`,
+    /// and with [`Error::UnexpectedEof`] if the data ends before the end of the block.
+    fn read_len_prefixed_bytes(&mut self, max_len: usize) -> Result<Vec<u8>>;
 }
 
 #[allow(dead_code)]
-impl VPKFileReader for File {
+impl<R: Read> VPKFileReader for R {
     fn read_u8(&mut self) -> Result<u8> {
         let mut b: [u8; 1] = [0];
         self.read_exact(&mut b).map_err(Error::Io)?;
@@ -65,6 +90,15 @@ impl VPKFileReader for File {
         Ok(u32::from_le_bytes(b))
     }
 
+    fn read_u48(&mut self) -> Result<u64> {
+        let mut b: [u8; 6] = [0, 0, 0, 0, 0, 0];
+        self.read_exact(&mut b).map_err(Error::Io)?;
+
+        let b_u64: [u8; 8] = [b[0], b[1], b[2], b[3], b[4], b[5], 0, 0];
+
+        Ok(u64::from_le_bytes(b_u64))
+    }
+
     fn read_u64(&mut self) -> Result<u64> {
         let mut b: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 0];
         self.read_exact(&mut b).map_err(Error::Io)?;
@@ -73,6 +107,10 @@ impl VPKFileReader for File {
     }
 
     fn read_string(&mut self) -> Result<String> {
+        self.read_string_limited(usize::MAX)
+    }
+
+    fn read_string_limited(&mut self, max_len: usize) -> Result<String> {
         let mut str = Vec::new();
         loop {
             let mut b: [u8; 1] = [0];
@@ -82,6 +120,10 @@ impl VPKFileReader for File {
                 break;
             }
 
+            if str.len() == max_len {
+                return Err(Error::StringTooLong { max_len });
+            }
+
             str.push(b[0]);
         }
 
@@ -90,11 +132,38 @@ impl VPKFileReader for File {
 
     fn read_bytes(&mut self, count: usize) -> Result<Vec<u8>> {
         let mut buffer = vec![0; count];
-        let size = self.read(&mut buffer).map_err(Error::Io)?;
-        buffer.truncate(size);
+        let mut filled = 0;
+
+        // A single read may return fewer bytes than requested without having reached the end
+        while filled < count {
+            match self.read(&mut buffer[filled..]) {
+                Ok(0) => {
+                    return Err(Error::UnexpectedEof {
+                        requested: count,
+                        available: filled,
+                    });
+                }
+                Ok(read) => filled += read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(Error::Io(e)),
+            }
+        }
 
         Ok(buffer)
     }
+
+    fn read_len_prefixed_bytes(&mut self, max_len: usize) -> Result<Vec<u8>> {
+        let len = self.read_u32()?;
+        let count = usize::try_from(len)
+            .ok()
+            .filter(|&count| count <= max_len)
+            .ok_or(Error::BlockTooLong {
+                len: len.into(),
+                max_len,
+            })?;
+
+        self.read_bytes(count)
+    }
 }
 
 /// Trait for writing data to binary files.
@@ -114,6 +183,11 @@ pub trait VPKFileWriter {
     /// Writes 4 bytes to the file from a [`u32`].
     fn write_u32(&mut self, val: u32) -> Result<()>;
 
+    /// Writes 6 bytes to the file from a [`u64`].
+    ///
+    /// Fails with [`Error::ValueOutOfRange`] without writing anything if `val` is larger than [`MAX_U48`].
+    fn write_u48(&mut self, val: u64) -> Result<()>;
+
     /// Writes 8 bytes to the file from a [`u64`].
     fn write_u64(&mut self, val: u64) -> Result<()>;
 
@@ -122,10 +196,15 @@ pub trait VPKFileWriter {
 
     /// Writes a number of bytes to the file from a [`Vec<u8>`].
     fn write_bytes(&mut self, bytes: &[u8]) -> Result<()>;
+
+    /// Writes a block of bytes prefixed with its length as a [`u32`] to the file.
+    ///
+    /// Fails with [`Error::ValueOutOfRange`] without writing anything if the block is longer than [`u32::MAX`] bytes.
+    fn write_len_prefixed_bytes(&mut self, bytes: &[u8]) -> Result<()>;
 }
 
 #[allow(dead_code)]
-impl VPKFileWriter for File {
+impl<W: Write> VPKFileWriter for W {
     fn write_u8(&mut self, val: u8) -> Result<()> {
         let b = u8::to_le_bytes(val);
         self.write_all(&b).map_err(Error::Io)?;
@@ -154,6 +233,20 @@ impl VPKFileWriter for File {
         Ok(())
     }
 
+    fn write_u48(&mut self, val: u64) -> Result<()> {
+        if val > MAX_U48 {
+            return Err(Error::ValueOutOfRange {
+                value: val,
+                max: MAX_U48,
+            });
+        }
+
+        let b = u64::to_le_bytes(val);
+        self.write_all(&b[0..6]).map_err(Error::Io)?;
+
+        Ok(())
+    }
+
     fn write_u64(&mut self, val: u64) -> Result<()> {
         let b = u64::to_le_bytes(val);
         self.write_all(&b).map_err(Error::Io)?;
@@ -175,4 +268,13 @@ impl VPKFileWriter for File {
 
         Ok(())
     }
+    fn write_len_prefixed_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        let len = u32::try_from(bytes.len()).map_err(|_| Error::ValueOutOfRange {
+            value: bytes.len() as u64,
+            max: u32::MAX.into(),
+        })?;
+
+        self.write_u32(len)?;
+        self.write_bytes(bytes)
+    }
 }
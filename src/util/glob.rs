@@ -0,0 +1,111 @@
+//! A minimal glob pattern matcher for VPK file paths.
+
+use super::{Error, Result};
+
+/// A single `/` separated segment of a [`Pattern`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// `**`, matching any number of segments, including none.
+    AnyDepth,
+    /// A segment in which `*` matches any number of characters and `?` matches a single character.
+    Wildcard(Vec<char>),
+}
+
+/// A glob pattern matched against full VPK file paths, e.g. `materials/**/*.vmt`.
+///
+/// Supports `*` and `?` within a path segment and `**` as a whole segment to match any number of directories.
+/// Matching is case-sensitive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    segments: Vec<Segment>,
+}
+
+impl Pattern {
+    /// Parses a glob pattern.
+    /// # Errors
+    /// - When the pattern is empty or contains an empty segment
+    /// - When `**` is combined with other characters in a segment
+    pub fn new(pattern: &str) -> Result<Self> {
+        let segments = pattern
+            .split('/')
+            .map(|segment| match segment {
+                "" => Err(Error::InvalidPattern(format!(
+                    "Empty segment in pattern: {pattern}"
+                ))),
+                "**" => Ok(Segment::AnyDepth),
+                _ if segment.contains("**") => Err(Error::InvalidPattern(format!(
+                    "** must be a whole segment in pattern: {pattern}"
+                ))),
+                _ => Ok(Segment::Wildcard(segment.chars().collect())),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { segments })
+    }
+
+    /// Whether the full path `file_path` matches the pattern.
+    #[must_use]
+    pub fn matches(&self, file_path: &str) -> bool {
+        let parts: Vec<&str> = file_path.split('/').collect();
+        matches_segments(&self.segments, &parts)
+    }
+}
+
+/// Matches the path segments like [`matches_wildcard`] matches characters, with `**` in place of `*`.
+fn matches_segments(segments: &[Segment], parts: &[&str]) -> bool {
+    let matches_part = |segment: &Segment, part: &str| match segment {
+        Segment::AnyDepth => false,
+        Segment::Wildcard(pattern) => matches_wildcard(pattern, &part.chars().collect::<Vec<_>>()),
+    };
+
+    let (mut s, mut p) = (0, 0);
+    let mut backtrack = None;
+
+    while p < parts.len() {
+        if s < segments.len() && segments[s] == Segment::AnyDepth {
+            backtrack = Some((s, p));
+            s += 1;
+        } else if s < segments.len() && matches_part(&segments[s], parts[p]) {
+            s += 1;
+            p += 1;
+        } else if let Some((any_depth, start)) = backtrack {
+            // Let the last ** match one more segment and retry from there
+            backtrack = Some((any_depth, start + 1));
+            s = any_depth + 1;
+            p = start + 1;
+        } else {
+            return false;
+        }
+    }
+
+    segments[s..]
+        .iter()
+        .all(|segment| *segment == Segment::AnyDepth)
+}
+
+/// Matches `text` against a pattern of characters, `*` and `?`.
+///
+/// Only the most recent `*` is ever backtracked to, extending what it matches by one character at a time.
+/// Earlier stars never need to be revisited, so matching takes at most `pattern.len() * text.len()` steps.
+fn matches_wildcard(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if let Some((star, start)) = backtrack {
+            backtrack = Some((star, start + 1));
+            p = star + 1;
+            t = start + 1;
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,45 @@
+//! Serialization of binary blobs as hex strings, for use with `#[serde(with = "crate::util::hex")]`.
+
+use serde::{Deserialize, Deserializer, Serializer, de::Error};
+use std::{borrow::Cow, fmt::Write};
+
+/// Serializes the bytes as a lowercase hex string.
+pub fn serialize<T, S>(bytes: &T, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    T: AsRef<[u8]>,
+    S: Serializer,
+{
+    let bytes = bytes.as_ref();
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(hex, "{byte:02x}");
+    }
+
+    serializer.serialize_str(&hex)
+}
+
+/// Deserializes bytes from a hex string. Both upper and lowercase digits are accepted.
+pub fn deserialize<'de, T, D>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    T: TryFrom<Vec<u8>>,
+    D: Deserializer<'de>,
+{
+    let hex = Cow::<'de, str>::deserialize(deserializer)?;
+    if hex.len() % 2 != 0 {
+        return Err(D::Error::custom("hex string has an odd length"));
+    }
+
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                // from_str_radix accepts a leading sign, which is not a hex digit
+                .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| D::Error::custom(format!("invalid hex digits at offset {i}")))
+        })
+        .collect::<std::result::Result<Vec<u8>, _>>()?;
+
+    let len = bytes.len();
+    T::try_from(bytes).map_err(|_| D::Error::custom(format!("unexpected blob length {len}")))
+}
@@ -2,32 +2,251 @@
 //!
 //! Uses the [`lzham_alpha_sys`] crate for bindings to the LZHAM alpha library.
 
+use std::fmt;
 use std::mem::size_of;
 use std::ptr::null;
 
 use lzham_alpha_sys::{
-    lzham_compress_flags_LZHAM_COMP_FLAG_DETERMINISTIC_PARSING,
+    lzham_compress_flags_LZHAM_COMP_FLAG_DETERMINISTIC_PARSING, lzham_compress_level,
+    lzham_compress_level_LZHAM_COMP_LEVEL_BETTER, lzham_compress_level_LZHAM_COMP_LEVEL_DEFAULT,
+    lzham_compress_level_LZHAM_COMP_LEVEL_FASTER, lzham_compress_level_LZHAM_COMP_LEVEL_FASTEST,
     lzham_compress_level_LZHAM_COMP_LEVEL_UBER, lzham_compress_memory, lzham_compress_params,
+    lzham_compress_status_t_LZHAM_COMP_STATUS_FAILED,
+    lzham_compress_status_t_LZHAM_COMP_STATUS_FAILED_INITIALIZING,
+    lzham_compress_status_t_LZHAM_COMP_STATUS_INVALID_PARAMETER,
+    lzham_compress_status_t_LZHAM_COMP_STATUS_OUTPUT_BUF_TOO_SMALL,
     lzham_compress_status_t_LZHAM_COMP_STATUS_SUCCESS,
     lzham_decompress_flags_LZHAM_DECOMP_FLAG_COMPUTE_ADLER32,
     lzham_decompress_flags_LZHAM_DECOMP_FLAG_OUTPUT_UNBUFFERED, lzham_decompress_memory,
-    lzham_decompress_params, lzham_uint32,
+    lzham_decompress_params, lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_ADLER32,
+    lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_BAD_CODE,
+    lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_BAD_COMP_BLOCK_SYNC_CHECK,
+    lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_BAD_RAW_BLOCK,
+    lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_DEST_BUF_TOO_SMALL,
+    lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_EXPECTED_MORE_RAW_BYTES,
+    lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_INITIALIZING,
+    lzham_decompress_status_t_LZHAM_DECOMP_STATUS_INVALID_PARAMETER,
+    lzham_decompress_status_t_LZHAM_DECOMP_STATUS_SUCCESS, lzham_uint32,
 };
 
 const TFLZHAM_DICT_SIZE: u32 = 20; // required for compatibility
 
-#[allow(dead_code)]
-const TFLZHAM_COMPRESS_PARAMS: lzham_compress_params = lzham_compress_params {
-    m_struct_size: size_of::<lzham_compress_params>() as _,
-    m_dict_size_log2: TFLZHAM_DICT_SIZE,
-    m_compress_flags: lzham_compress_flags_LZHAM_COMP_FLAG_DETERMINISTIC_PARSING as _,
-    m_level: lzham_compress_level_LZHAM_COMP_LEVEL_UBER,
-    m_max_helper_threads: -1,
-    m_cpucache_total_lines: 0,
-    m_cpucache_line_size: 0,
-    m_num_seed_bytes: 0,
-    m_pSeed_bytes: null(),
-};
+/// The smallest dictionary size supported by LZHAM, as a power of two.
+pub const LZHAM_MIN_DICT_SIZE_LOG2: u32 = 15;
+/// The largest dictionary size supported by LZHAM, as a power of two.
+pub const LZHAM_MAX_DICT_SIZE_LOG2: u32 = 29;
+
+/// The raw status of [`LzhamError::FailedAdler32`], returned when the decompressed data doesn't match the Adler-32 checksum stored in the compressed stream.
+pub const LZHAM_DECOMP_STATUS_FAILED_ADLER32: u32 =
+    lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_ADLER32;
+
+/// Why LZHAM failed to compress or decompress data, mapped from the status codes of the LZHAM library.
+///
+/// The raw status code is available with [`LzhamError::status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LzhamError {
+    /// The decompressed data doesn't fit in the destination buffer.
+    DestBufferTooSmall,
+    /// The compressed data ended before the end of the stream.
+    ExpectedMoreRawBytes,
+    /// The compressed data contains an invalid code, e.g. because it is corrupt or not LZHAM data at all.
+    BadCode,
+    /// The decompressed data doesn't match the Adler-32 checksum stored in the compressed stream.
+    FailedAdler32,
+    /// The compressed data contains an invalid uncompressed block.
+    BadRawBlock,
+    /// The compressed data contains a block with an invalid sync check.
+    BadCompBlockSyncCheck,
+    /// The decompressor could not be initialized.
+    DecompressFailedInitializing,
+    /// The decompression parameters are invalid.
+    DecompressInvalidParameter,
+    /// Decompression ended with an unexpected status, e.g. because the stream is incomplete.
+    Decompress(u32),
+    /// The compressor failed.
+    CompressFailed,
+    /// The compressor could not be initialized.
+    CompressFailedInitializing,
+    /// The compression parameters are invalid, e.g. the dictionary size.
+    CompressInvalidParameter,
+    /// The compressed data doesn't fit in the destination buffer.
+    OutputBufferTooSmall,
+    /// Compression ended with an unexpected status.
+    Compress(u32),
+}
+
+impl LzhamError {
+    /// Maps a status returned by `lzham_decompress_memory` other than success.
+    #[must_use]
+    pub fn from_decompress_status(status: u32) -> Self {
+        #[allow(non_upper_case_globals)]
+        match status {
+            lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_DEST_BUF_TOO_SMALL => {
+                LzhamError::DestBufferTooSmall
+            }
+            lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_EXPECTED_MORE_RAW_BYTES => {
+                LzhamError::ExpectedMoreRawBytes
+            }
+            lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_BAD_CODE => LzhamError::BadCode,
+            lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_ADLER32 => {
+                LzhamError::FailedAdler32
+            }
+            lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_BAD_RAW_BLOCK => {
+                LzhamError::BadRawBlock
+            }
+            lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_BAD_COMP_BLOCK_SYNC_CHECK => {
+                LzhamError::BadCompBlockSyncCheck
+            }
+            lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_INITIALIZING => {
+                LzhamError::DecompressFailedInitializing
+            }
+            lzham_decompress_status_t_LZHAM_DECOMP_STATUS_INVALID_PARAMETER => {
+                LzhamError::DecompressInvalidParameter
+            }
+            status => LzhamError::Decompress(status),
+        }
+    }
+
+    /// Maps a status returned by `lzham_compress_memory` other than success.
+    #[must_use]
+    pub fn from_compress_status(status: u32) -> Self {
+        #[allow(non_upper_case_globals)]
+        match status {
+            lzham_compress_status_t_LZHAM_COMP_STATUS_FAILED => LzhamError::CompressFailed,
+            lzham_compress_status_t_LZHAM_COMP_STATUS_FAILED_INITIALIZING => {
+                LzhamError::CompressFailedInitializing
+            }
+            lzham_compress_status_t_LZHAM_COMP_STATUS_INVALID_PARAMETER => {
+                LzhamError::CompressInvalidParameter
+            }
+            lzham_compress_status_t_LZHAM_COMP_STATUS_OUTPUT_BUF_TOO_SMALL => {
+                LzhamError::OutputBufferTooSmall
+            }
+            status => LzhamError::Compress(status),
+        }
+    }
+
+    /// The raw status code returned by the LZHAM library.
+    #[must_use]
+    pub fn status(self) -> u32 {
+        match self {
+            LzhamError::DestBufferTooSmall => {
+                lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_DEST_BUF_TOO_SMALL
+            }
+            LzhamError::ExpectedMoreRawBytes => {
+                lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_EXPECTED_MORE_RAW_BYTES
+            }
+            LzhamError::BadCode => lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_BAD_CODE,
+            LzhamError::FailedAdler32 => {
+                lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_ADLER32
+            }
+            LzhamError::BadRawBlock => {
+                lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_BAD_RAW_BLOCK
+            }
+            LzhamError::BadCompBlockSyncCheck => {
+                lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_BAD_COMP_BLOCK_SYNC_CHECK
+            }
+            LzhamError::DecompressFailedInitializing => {
+                lzham_decompress_status_t_LZHAM_DECOMP_STATUS_FAILED_INITIALIZING
+            }
+            LzhamError::DecompressInvalidParameter => {
+                lzham_decompress_status_t_LZHAM_DECOMP_STATUS_INVALID_PARAMETER
+            }
+            LzhamError::CompressFailed => lzham_compress_status_t_LZHAM_COMP_STATUS_FAILED,
+            LzhamError::CompressFailedInitializing => {
+                lzham_compress_status_t_LZHAM_COMP_STATUS_FAILED_INITIALIZING
+            }
+            LzhamError::CompressInvalidParameter => {
+                lzham_compress_status_t_LZHAM_COMP_STATUS_INVALID_PARAMETER
+            }
+            LzhamError::OutputBufferTooSmall => {
+                lzham_compress_status_t_LZHAM_COMP_STATUS_OUTPUT_BUF_TOO_SMALL
+            }
+            LzhamError::Decompress(status) | LzhamError::Compress(status) => status,
+        }
+    }
+}
+
+impl fmt::Display for LzhamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{self:?} (LZHAM status {})", self.status())
+    }
+}
+
+impl std::error::Error for LzhamError {}
+
+/// The compression level used by LZHAM, trading speed for a better compression ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LzhamLevel {
+    /// The fastest level, with the worst compression ratio.
+    Fastest,
+    Faster,
+    /// LZHAM's default level.
+    Default,
+    Better,
+    /// The level used by Respawn.
+    #[default]
+    Uber,
+}
+
+impl LzhamLevel {
+    /// All levels, from fastest to best compression.
+    pub const ALL: [LzhamLevel; 5] = [
+        LzhamLevel::Fastest,
+        LzhamLevel::Faster,
+        LzhamLevel::Default,
+        LzhamLevel::Better,
+        LzhamLevel::Uber,
+    ];
+
+    fn as_raw(self) -> lzham_compress_level {
+        match self {
+            LzhamLevel::Fastest => lzham_compress_level_LZHAM_COMP_LEVEL_FASTEST,
+            LzhamLevel::Faster => lzham_compress_level_LZHAM_COMP_LEVEL_FASTER,
+            LzhamLevel::Default => lzham_compress_level_LZHAM_COMP_LEVEL_DEFAULT,
+            LzhamLevel::Better => lzham_compress_level_LZHAM_COMP_LEVEL_BETTER,
+            LzhamLevel::Uber => lzham_compress_level_LZHAM_COMP_LEVEL_UBER,
+        }
+    }
+}
+
+/// The parameters used to compress data with LZHAM.
+///
+/// The default matches the parameters used by Respawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LzhamParams {
+    /// The compression level.
+    pub level: LzhamLevel,
+    /// The dictionary size as a power of two, between [`LZHAM_MIN_DICT_SIZE_LOG2`] and [`LZHAM_MAX_DICT_SIZE_LOG2`].
+    /// Data is always decompressed with a dictionary size of 2^20, like the game does, so only change this to match
+    /// a build that uses a different dictionary size.
+    pub dict_size_log2: u32,
+}
+
+impl Default for LzhamParams {
+    fn default() -> Self {
+        Self {
+            level: LzhamLevel::Uber,
+            dict_size_log2: TFLZHAM_DICT_SIZE,
+        }
+    }
+}
+
+impl LzhamParams {
+    fn compress_params(self) -> lzham_compress_params {
+        lzham_compress_params {
+            m_struct_size: size_of::<lzham_compress_params>() as _,
+            m_dict_size_log2: self.dict_size_log2,
+            m_compress_flags: lzham_compress_flags_LZHAM_COMP_FLAG_DETERMINISTIC_PARSING as _,
+            m_level: self.level.as_raw(),
+            m_max_helper_threads: -1,
+            m_cpucache_total_lines: 0,
+            m_cpucache_line_size: 0,
+            m_num_seed_bytes: 0,
+            m_pSeed_bytes: null(),
+        }
+    }
+}
 
 const TFLZHAM_DECOMPRESS_PARAMS: lzham_decompress_params = lzham_decompress_params {
     m_struct_size: size_of::<lzham_decompress_params>() as _,
@@ -38,42 +257,62 @@ const TFLZHAM_DECOMPRESS_PARAMS: lzham_decompress_params = lzham_decompress_para
     m_pSeed_bytes: null(),
 };
 
-#[allow(dead_code)]
-#[must_use]
-pub fn compress(src: &mut [u8]) -> Vec<u8> {
+/// Compresses `src` with the given parameters, use [`LzhamParams::default`] for the parameters used by Respawn VPKs.
+/// # Errors
+/// - When LZHAM fails to compress the data, e.g. because of invalid parameters ([`LzhamError::CompressInvalidParameter`])
+pub fn compress(src: &[u8], params: &LzhamParams) -> Result<Vec<u8>, LzhamError> {
     let max_compressed_size = (1 + src.len()) * 10;
     let mut dst = vec![0; max_compressed_size];
     let mut dst_len = max_compressed_size;
 
     let mut adler32: lzham_uint32 = 0;
+    let compress_params = params.compress_params();
 
     let status = unsafe {
         lzham_compress_memory(
-            &TFLZHAM_COMPRESS_PARAMS,
+            &compress_params,
             dst.as_mut_ptr(),
             &mut dst_len,
-            src.as_mut_ptr(),
-            max_compressed_size,
+            src.as_ptr(),
+            src.len(),
             &mut adler32,
         )
     };
 
     if status != lzham_compress_status_t_LZHAM_COMP_STATUS_SUCCESS {
-        eprintln!("LZHAM compression failed with status {status:?}");
-        return Vec::new();
+        return Err(LzhamError::from_compress_status(status));
     }
 
     dst.truncate(dst_len);
 
-    dst
+    Ok(dst)
+}
+
+/// Decompresses `src` with the parameters used by Respawn VPKs into a buffer of at most `dst_len` bytes.
+///
+/// LZHAM stores an Adler-32 checksum of the uncompressed data at the end of every compressed stream,
+/// which is verified against the decompressed data.
+/// # Errors
+/// - When `src` is not valid LZHAM data, e.g. because it is truncated or corrupt ([`LzhamError::BadCode`], ...)
+/// - When the decompressed data is larger than `dst_len` ([`LzhamError::DestBufferTooSmall`])
+/// - When the decompressed data doesn't match the stored checksum ([`LzhamError::FailedAdler32`])
+pub fn decompress(src: &[u8], dst_len: usize) -> Result<Vec<u8>, LzhamError> {
+    decompress_with_adler32(src, dst_len).map(|(dst, _)| dst)
 }
 
-#[must_use]
-pub fn decompress(src: &[u8], mut dst_len: usize) -> Vec<u8> {
+/// Decompresses `src` like [`decompress`], also returning the verified Adler-32 checksum of the decompressed data.
+/// # Errors
+/// - When `src` is not valid LZHAM data, e.g. because it is truncated or corrupt ([`LzhamError::BadCode`], ...)
+/// - When the decompressed data is larger than `dst_len` ([`LzhamError::DestBufferTooSmall`])
+/// - When the decompressed data doesn't match the stored checksum ([`LzhamError::FailedAdler32`])
+pub fn decompress_with_adler32(
+    src: &[u8],
+    mut dst_len: usize,
+) -> Result<(Vec<u8>, u32), LzhamError> {
     let mut dst = vec![0; dst_len];
     let mut adler32: lzham_uint32 = 0;
 
-    let () = unsafe {
+    let status = unsafe {
         lzham_decompress_memory(
             &TFLZHAM_DECOMPRESS_PARAMS,
             dst.as_mut_ptr(),
@@ -81,10 +320,14 @@ pub fn decompress(src: &[u8], mut dst_len: usize) -> Vec<u8> {
             src.as_ptr(),
             src.len(),
             &mut adler32,
-        );
+        )
     };
 
+    if status != lzham_decompress_status_t_LZHAM_DECOMP_STATUS_SUCCESS {
+        return Err(LzhamError::from_decompress_status(status));
+    }
+
     dst.truncate(dst_len);
 
-    dst
+    Ok((dst, adler32))
 }
@@ -1,11 +1,15 @@
 //! Common utilities for the library.
 //!
-//! Includes file handling, format detection, and, when the `revpk` feature is enabled, support for the [LZHAM alpha](https://github.com/richgel999/lzham_alpha) compression format.
+//! Includes file handling, path sanitization, glob patterns, format detection, and, when the `revpk` feature is enabled, support for the [LZHAM alpha](https://github.com/richgel999/lzham_alpha) compression format.
 
 pub use error::{Error, Result};
 
 pub mod file;
+pub mod glob;
+#[cfg(feature = "serde")]
+pub(crate) mod hex;
 #[cfg(feature = "revpk")]
 pub mod lzham;
+pub mod path;
 
 mod error;
@@ -0,0 +1,100 @@
+//! Path utilities for mapping VPK paths onto the file system.
+
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+
+/// Joins a VPK file path onto a base directory.
+///
+/// Both `/` and `\` are treated as separators. Returns [`None`] if the path contains components
+/// that could escape the base directory, such as `..` or a drive prefix.
+/// Leading separators are ignored, so absolute paths are joined relative to the base directory.
+pub fn join_sanitized(base: &Path, vpk_path: &str) -> Option<PathBuf> {
+    let mut path = base.to_path_buf();
+
+    for part in vpk_path.split(['/', '\\']) {
+        if part.is_empty() || part == "." {
+            continue;
+        }
+
+        // Drive prefixes are only parsed as such on Windows, so reject them everywhere
+        if part.contains(':') {
+            return None;
+        }
+
+        let mut components = Path::new(part).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(component)), None) => path.push(component),
+            _ => return None,
+        }
+    }
+
+    if path == base { None } else { Some(path) }
+}
+
+/// Normalizes a file path for looking it up in a VPK tree.
+///
+/// VPK paths are always `/`-delimited, so backslashes are converted to `/`, duplicate separators are collapsed
+/// and leading separators and `./` components are removed.
+/// If `lowercase` is set the path is also lowercased, which matches the casing used by Valve's own tools.
+/// Returns the path unchanged, without allocating, if it is already normalized.
+#[must_use]
+pub fn normalize_vpk_path(path: &str, lowercase: bool) -> Cow<'_, str> {
+    let mut trimmed = path.trim_start_matches(['/', '\\']);
+    while let Some(rest) = trimmed
+        .strip_prefix("./")
+        .or_else(|| trimmed.strip_prefix(".\\"))
+    {
+        trimmed = rest.trim_start_matches(['/', '\\']);
+    }
+
+    let needs_lowercase = lowercase && trimmed.chars().any(char::is_uppercase);
+    let needs_separators =
+        trimmed.contains('\\') || trimmed.contains("//") || trimmed.ends_with('/');
+
+    if !needs_separators && !needs_lowercase {
+        return Cow::Borrowed(trimmed);
+    }
+
+    let normalized = trimmed
+        .split(['/', '\\'])
+        .filter(|component| !component.is_empty())
+        .collect::<Vec<_>>()
+        .join("/");
+    if lowercase {
+        Cow::Owned(normalized.to_lowercase())
+    } else {
+        Cow::Owned(normalized)
+    }
+}
+
+/// Splits the path of a directory file such as `dir/pak01_dir.vpk` into the directory containing the archives and the VPK name.
+/// Returns [`None`] if the file name does not end in `_dir.vpk`.
+pub fn split_dir_file_path(dir_file_path: &Path) -> Option<(&str, &str)> {
+    let vpk_name = dir_file_path
+        .file_name()?
+        .to_str()?
+        .strip_suffix("_dir.vpk")
+        .filter(|vpk_name| !vpk_name.is_empty())?;
+
+    let archive_path = dir_file_path.parent()?.to_str()?;
+
+    Some((archive_path, vpk_name))
+}
+
+/// Splits the path of a VPK file into the directory containing the archives and the VPK name.
+///
+/// Unlike [`split_dir_file_path`], any extension is accepted and a trailing `_dir` is only stripped if present,
+/// so both `dir/pak01_dir.vpk` and `dir/pak01.vpk` result in `("dir", "pak01")`.
+/// Returns [`None`] if the path has no file name.
+#[cfg(feature = "detect")]
+pub fn split_pak_path(pak_path: &Path) -> Option<(&str, &str)> {
+    let stem = pak_path.file_stem()?.to_str()?;
+    let vpk_name = stem
+        .strip_suffix("_dir")
+        .filter(|vpk_name| !vpk_name.is_empty())
+        .unwrap_or(stem);
+
+    let archive_path = pak_path.parent()?.to_str()?;
+
+    Some((archive_path, vpk_name))
+}
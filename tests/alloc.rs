@@ -0,0 +1,191 @@
+//! Checks the peak memory use of extracting large files, using an allocator that tracks the allocated bytes.
+//! This is a separate test binary so the allocator doesn't affect the other tests.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fs::File,
+    io::{BufWriter, Write},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use crc::{CRC_32_ISO_HDLC, Crc};
+use vpk_plumber::pak::{
+    DEFAULT_CHUNK_SIZE, ExtractOptions, PakReader, PakWorker, VPKDirectoryEntry, v1::VPKVersion1,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// The size of the synthetic file that is extracted, large enough to span many chunks while keeping the test fast.
+const ENTRY_SIZE: usize = 32 * DEFAULT_CHUNK_SIZE;
+
+/// The smallest ratio between the extracted size and the peak allocation, so reading the whole file into memory fails.
+const MIN_SIZE_TO_PEAK_RATIO: usize = 16;
+
+/// The documented bound of [`PakReader::extract_file_with`]: a single chunk, with some headroom for small allocations.
+const PEAK_BOUND: usize = 2 * DEFAULT_CHUNK_SIZE;
+
+struct TrackingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+// Tests in this binary measure the peak of the whole process, so they must not run concurrently
+static MEASURING: Mutex<()> = Mutex::new(());
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(allocated, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: TrackingAllocator = TrackingAllocator;
+
+/// Runs `f` and returns the peak number of bytes allocated on top of what was allocated before.
+fn peak_allocated<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let baseline = ALLOCATED.load(Ordering::SeqCst);
+    PEAK.store(baseline, Ordering::SeqCst);
+
+    let result = f();
+
+    (result, PEAK.load(Ordering::SeqCst) - baseline)
+}
+
+/// Writes `len` bytes of synthetic data to `file` in small chunks, returning the CRC of the data.
+fn write_synthetic_data(file: File, len: usize) -> Result<u32> {
+    let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+    let mut digest = crc.digest();
+    let mut out = BufWriter::new(file);
+
+    // Never contains 0xCB, which would be skipped as WAV padding
+    let chunk: Vec<u8> = (0..=u8::MAX)
+        .cycle()
+        .take(64 * 1024)
+        .map(|b| b % 0xCB)
+        .collect();
+    let mut remaining = len;
+    while remaining > 0 {
+        let part = &chunk[..remaining.min(chunk.len())];
+        out.write_all(part)?;
+        digest.update(part);
+        remaining -= part.len();
+    }
+
+    out.flush()?;
+    Ok(digest.finalize())
+}
+
+#[test]
+fn extract_large_v1() -> Result<()> {
+    let _guard = MEASURING.lock();
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    let crc = write_synthetic_data(
+        File::create(out_dir.path().join("large_000.vpk"))?,
+        ENTRY_SIZE,
+    )?;
+
+    let mut vpk = VPKVersion1::new();
+    vpk.tree.files.insert(
+        "large.bin".to_string(),
+        VPKDirectoryEntry {
+            crc,
+            entry_length: u32::try_from(ENTRY_SIZE)?,
+            ..Default::default()
+        },
+    );
+
+    let output_path = out_dir.path().join("large.bin");
+    let (result, peak) = peak_allocated(|| {
+        vpk.extract_file_with(
+            archive_path,
+            "large",
+            "large.bin",
+            output_path.to_str().unwrap(),
+            &ExtractOptions::default(),
+            &mut |_, _| {},
+        )
+    });
+
+    result?;
+    assert_eq!(std::fs::metadata(&output_path)?.len(), ENTRY_SIZE as u64);
+    assert!(
+        peak < PEAK_BOUND,
+        "Peak allocation of {peak} bytes exceeds {PEAK_BOUND}"
+    );
+    assert!(
+        peak * MIN_SIZE_TO_PEAK_RATIO <= ENTRY_SIZE,
+        "Peak allocation of {peak} bytes is too large for a {ENTRY_SIZE} byte file"
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "revpk")]
+#[test]
+fn extract_large_revpk_wav() -> Result<()> {
+    use vpk_plumber::pak::revpk::{VPKDirectoryEntryRespawn, VPKFilePartEntryRespawn, VPKRespawn};
+
+    let _guard = MEASURING.lock();
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    // The stored WAV file has a 44 byte header, which is replaced by one generated from the CAM entry
+    let part_length = ENTRY_SIZE as u64 + 44;
+    write_synthetic_data(
+        File::create(out_dir.path().join("large_000.vpk"))?,
+        usize::try_from(part_length)?,
+    )?;
+
+    let mut vpk = VPKRespawn::new();
+    vpk.tree.files.insert(
+        "sound/large.wav".to_string(),
+        VPKDirectoryEntryRespawn {
+            file_parts: vec![VPKFilePartEntryRespawn {
+                entry_length: part_length,
+                entry_length_uncompressed: part_length,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    );
+
+    let output_path = out_dir.path().join("large.wav");
+    let (result, peak) = peak_allocated(|| {
+        vpk.extract_file_with(
+            archive_path,
+            "large",
+            "sound/large.wav",
+            output_path.to_str().unwrap(),
+            &ExtractOptions::default(),
+            &mut |_, _| {},
+        )
+    });
+
+    result?;
+    assert_eq!(std::fs::metadata(&output_path)?.len(), part_length);
+    assert!(
+        peak < PEAK_BOUND,
+        "Peak allocation of {peak} bytes exceeds {PEAK_BOUND}"
+    );
+    assert!(
+        peak * MIN_SIZE_TO_PEAK_RATIO <= ENTRY_SIZE,
+        "Peak allocation of {peak} bytes is too large for a {ENTRY_SIZE} byte file"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use std::{fs::File, sync::Arc};
+
+use vpk_plumber::pak::{Error, PakWorker, async_reader::AsyncPakReader, v1::VPKVersion1};
+
+use crate::common::{self, Result};
+
+#[tokio::test]
+async fn read_file() -> Result<()> {
+    let vpk = Arc::new(VPKVersion1::from_file(&mut File::open(
+        common::PAK_V1_SINGLE_FILE,
+    )?)?);
+
+    assert_eq!(
+        vpk.read_file(
+            common::DIR_V1,
+            common::SINGLE_FILE_ARCHIVE,
+            common::SINGLE_FILE_NAME
+        )
+        .await,
+        Some(common::SINGLE_FILE_CONTENT.as_bytes().to_vec()),
+        "Content does not match expected"
+    );
+
+    assert!(
+        matches!(
+            vpk.try_read_file(common::DIR_V1, common::SINGLE_FILE_ARCHIVE, "missing.txt")
+                .await,
+            Err(Error::FileNotFound(_))
+        ),
+        "Missing files should not be found"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn extract_file() -> Result<()> {
+    let vpk = Arc::new(VPKVersion1::from_file(&mut File::open(
+        common::PAK_V1_SINGLE_FILE,
+    )?)?);
+
+    let out_dir = tempfile::tempdir()?;
+    let out_path = out_dir.path().join("file.txt");
+    vpk.extract_file(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+        out_path.to_str().unwrap(),
+    )
+    .await?;
+
+    assert_eq!(
+        std::fs::read(&out_path)?,
+        common::SINGLE_FILE_CONTENT.as_bytes(),
+        "Extracted content does not match expected"
+    );
+
+    Ok(())
+}
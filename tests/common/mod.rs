@@ -1,4 +1,9 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+
 use const_format::concatcp;
+use vpk_plumber::pak::IoObserver;
 
 pub type Result<T> = std::result::Result<T, Error>;
 pub type Error = Box<dyn std::error::Error>;
@@ -10,9 +15,12 @@ pub const DIR_V2: &str = concatcp!(DIR_TEST_DATA, "v2/");
 pub const DIR_REVPK: &str = concatcp!(DIR_TEST_DATA, "revpk/");
 
 pub const SINGLE_FILE_ARCHIVE: &str = "single_file";
+pub const SINGLE_FILE_EOF_ARCHIVE: &str = "single_file_eof";
 pub const EMPTY_ARCHIVE: &str = "empty";
 
 pub const SINGLE_FILE_NAME: &str = "test/file.txt";
+/// The file in the VPK version 2 single file fixture, stored in the root directory.
+pub const SINGLE_FILE_NAME_V2: &str = "test file.txt";
 pub const SINGLE_FILE_CONTENT: &str = "test text";
 
 // Data
@@ -29,11 +37,46 @@ pub const PAK_V2_PORTAL: &str = concatcp!(DIR_V2, "portal/portal_pak_dir.vpk");
 
 pub const PAK_REVPK_ARCHIVE: &str = concatcp!(DIR_REVPK, "single_file_000.vpk");
 pub const PAK_REVPK_SINGLE_FILE: &str = concatcp!(DIR_REVPK, "single_file_dir.vpk");
+pub const PAK_REVPK_SHORT_TREE: &str = concatcp!(DIR_REVPK, "single_file_short_tree_dir.vpk");
 pub const PAK_REVPK_TITANFALL: &str = concatcp!(
     DIR_REVPK,
     "titanfall/englishclient_mp_colony.bsp.pak000_dir.vpk"
 );
+pub const CAM_REVPK_TITANFALL: &str = concatcp!(
+    DIR_REVPK,
+    "titanfall/client_mp_common.bsp.pak000_000.vpk.cam"
+);
 
 pub const PORTAL_TREE_COUNT: usize = 3509;
 pub const PORTAL2_TREE_COUNT: usize = 29657;
 pub const TITANFALL_TREE_COUNT: usize = 5723;
+pub const PORTAL2_VMT_COUNT: usize = 3431;
+
+/// Counts the archive files opened and the bytes read from every archive.
+#[derive(Default)]
+pub struct CountingObserver {
+    opened: Mutex<BTreeMap<u16, usize>>,
+    bytes_read: Mutex<BTreeMap<u16, u64>>,
+}
+
+impl CountingObserver {
+    /// The number of times every archive was opened, by archive index.
+    pub fn opened(&self) -> BTreeMap<u16, usize> {
+        self.opened.lock().unwrap().clone()
+    }
+
+    /// The number of bytes read from every archive, by archive index.
+    pub fn bytes_read(&self) -> BTreeMap<u16, u64> {
+        self.bytes_read.lock().unwrap().clone()
+    }
+}
+
+impl IoObserver for CountingObserver {
+    fn archive_opened(&self, index: u16, _path: &Path) {
+        *self.opened.lock().unwrap().entry(index).or_default() += 1;
+    }
+
+    fn bytes_read(&self, index: u16, n: u64) {
+        *self.bytes_read.lock().unwrap().entry(index).or_default() += n;
+    }
+}
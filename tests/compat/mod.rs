@@ -0,0 +1,91 @@
+//! Call sites written against sourcepak 0.x, with only their imports changed to the compatibility layout.
+#![allow(deprecated)]
+
+use std::fs::File;
+
+use vpk_plumber::compat::{
+    common::format::{PakReader, PakWriter},
+    pak::{v1::format::VPKVersion1, v2::format::VPKVersion2},
+};
+
+use crate::common::{self, Result};
+
+#[test]
+fn v1_read() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let vpk = VPKVersion1::from_file(&mut file)?;
+
+    let data = vpk.read_file(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+    );
+    assert_eq!(
+        data,
+        Some(common::SINGLE_FILE_CONTENT.as_bytes().to_vec()),
+        "Content does not match expected"
+    );
+
+    let out_path = tempfile::NamedTempFile::new()?;
+    vpk.extract_file(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+        out_path.path().to_str().unwrap(),
+    )?;
+    assert_eq!(
+        std::fs::read_to_string(&out_path)?,
+        common::SINGLE_FILE_CONTENT,
+        "Extracted content does not match expected"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn v1_new_write() -> Result<()> {
+    let vpk = VPKVersion1::new();
+
+    let out_path = tempfile::NamedTempFile::new()?;
+    vpk.write_dir(out_path.path().to_str().unwrap())?;
+
+    let vpk_result = VPKVersion1::from_file(&mut File::open(&out_path)?)?;
+    assert!(vpk_result.tree.files.is_empty(), "Tree should be empty");
+
+    Ok(())
+}
+
+#[test]
+fn v2_read() -> Result<()> {
+    let mut file = File::open(common::PAK_V2_PORTAL)?;
+    let vpk = VPKVersion2::from_file(&mut file)?;
+
+    assert_eq!(
+        vpk.tree.files.len(),
+        common::PORTAL_TREE_COUNT,
+        "Tree size does not match"
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "revpk")]
+#[test]
+fn revpk_read() -> Result<()> {
+    use vpk_plumber::compat::pak::revpk::format::VPKRespawn;
+
+    let mut file = File::open(common::PAK_REVPK_SINGLE_FILE)?;
+    let vpk = VPKRespawn::from_file(&mut file)?;
+
+    assert_eq!(
+        vpk.read_file(
+            common::DIR_REVPK,
+            common::SINGLE_FILE_ARCHIVE,
+            common::SINGLE_FILE_NAME,
+        ),
+        Some(common::SINGLE_FILE_CONTENT.as_bytes().to_vec()),
+        "Content does not match expected"
+    );
+
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use std::{fs::File, path::Path};
 
 use vpk_plumber::detect::{self, PakFormat};
+use vpk_plumber::pak::{FormatCaps, PakWorker, v2::VPKVersion2};
 
 use crate::common::{self, Result};
 
@@ -12,6 +13,39 @@ fn invalid() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn from_path_file_names() {
+    let titanfall_archive =
+        Path::new(common::DIR_REVPK).join("titanfall/client_mp_colony.bsp.pak000_004.vpk");
+    assert_eq!(
+        detect::detect_pak_format_from_path(&titanfall_archive),
+        PakFormat::Unknown
+    );
+
+    // Files are only opened when the name is ambiguous
+    let missing = Path::new(common::DIR_TEST_DATA).join("missing");
+    assert_eq!(
+        detect::detect_pak_format_from_path(&missing.join("pak01_dir.vpk")),
+        PakFormat::Unknown
+    );
+    assert_eq!(
+        detect::detect_pak_format_from_path(&missing.join("PAK01_000.VPK")),
+        PakFormat::Unknown
+    );
+
+    let respawn_format = if cfg!(feature = "revpk") {
+        PakFormat::VPKRespawn
+    } else {
+        PakFormat::Unknown
+    };
+    assert_eq!(
+        detect::detect_pak_format_from_path(
+            &missing.join("englishclient_mp_common.bsp.pak000_dir.vpk")
+        ),
+        respawn_format
+    );
+}
+
 #[test]
 fn empty_v1() -> Result<()> {
     assert_format(common::PAK_V1_EMPTY, &PakFormat::VPKVersion1)
@@ -47,22 +81,431 @@ fn large_v2() -> Result<()> {
     assert_format(common::PAK_V2_PORTAL, &PakFormat::VPKVersion2)
 }
 
+#[cfg(feature = "revpk")]
 #[test]
 fn single_file_revpk() -> Result<()> {
     assert_format(common::PAK_REVPK_SINGLE_FILE, &PakFormat::VPKRespawn)
 }
 
+#[cfg(feature = "revpk")]
 #[test]
 fn large_revpk() -> Result<()> {
     assert_format(common::PAK_REVPK_TITANFALL, &PakFormat::VPKRespawn)
 }
 
+#[test]
+fn from_header() {
+    assert_eq!(
+        PakFormat::from_header(0x55AA_1234, 1),
+        PakFormat::VPKVersion1
+    );
+    assert_eq!(
+        PakFormat::from_header(0x55AA_1234, 2),
+        PakFormat::VPKVersion2
+    );
+    assert_eq!(
+        PakFormat::from_header(0x55AA_1234, 196_610),
+        if cfg!(feature = "revpk") {
+            PakFormat::VPKRespawn
+        } else {
+            PakFormat::UnknownVersioned {
+                signature: 0x55AA_1234,
+                version: 196_610,
+            }
+        },
+        "Respawn VPKs should never be detected as version 2"
+    );
+
+    // Only the low 16 bits match version 2
+    assert_eq!(
+        PakFormat::from_header(0x55AA_1234, 0x0001_0002),
+        PakFormat::UnknownVersioned {
+            signature: 0x55AA_1234,
+            version: 0x0001_0002,
+        },
+        "Versions should be compared in full"
+    );
+    assert_eq!(PakFormat::from_header(0x1234_55AA, 2), PakFormat::Unknown);
+}
+
+#[test]
+fn revpk_not_v2() -> Result<()> {
+    for path in [common::PAK_REVPK_SINGLE_FILE, common::PAK_REVPK_TITANFALL] {
+        let format = detect::detect_pak_format(&mut File::open(path)?);
+        assert_ne!(
+            format,
+            PakFormat::VPKVersion2,
+            "{path} should not be detected as version 2"
+        );
+
+        assert!(
+            matches!(
+                VPKVersion2::from_file(&mut File::open(path)?),
+                Err(vpk_plumber::pak::Error::BadVersion(_))
+            ),
+            "{path} should not parse as version 2"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn bytes() -> Result<()> {
+    assert_format_bytes(common::PAK_V1_ARCHIVE, &PakFormat::Unknown)?;
+    assert_format_bytes(common::PAK_V1_SINGLE_FILE, &PakFormat::VPKVersion1)?;
+    assert_format_bytes(common::PAK_V2_SINGLE_FILE, &PakFormat::VPKVersion2)?;
+    #[cfg(feature = "revpk")]
+    assert_format_bytes(common::PAK_REVPK_SINGLE_FILE, &PakFormat::VPKRespawn)?;
+    Ok(())
+}
+
+#[test]
+fn bytes_empty() {
+    assert_eq!(
+        detect::detect_pak_format_bytes(&[]),
+        PakFormat::Unknown,
+        "Empty data should have an unknown format"
+    );
+}
+
+#[test]
+fn worker_bytes() -> Result<()> {
+    let data = std::fs::read(common::PAK_V1_PORTAL2)?;
+    let _vpk = detect::find_pak_worker_bytes(&data)?;
+
+    let data = std::fs::read(common::PAK_V1_ARCHIVE)?;
+    assert!(
+        matches!(
+            detect::find_pak_worker_bytes(&data),
+            Err(detect::Error::UnknownFormat)
+        ),
+        "Archive data should not be detected as a VPK"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn unknown_version() {
+    let mut data = std::fs::read(common::PAK_V1_SINGLE_FILE).unwrap();
+    data[4..8].copy_from_slice(&3u32.to_le_bytes());
+
+    let format = detect::detect_pak_format_bytes(&data);
+    assert_eq!(
+        format,
+        PakFormat::UnknownVersioned {
+            signature: 0x55AA_1234,
+            version: 3
+        },
+        "Unknown versions should be reported"
+    );
+    assert_eq!(
+        format.to_string(),
+        "Unknown (signature 0x55AA1234, version 3)"
+    );
+    assert_eq!(format.capabilities(), FormatCaps::default());
+
+    assert!(
+        matches!(
+            detect::find_pak_worker_bytes(&data),
+            Err(detect::Error::UnknownVersion {
+                signature: 0x55AA_1234,
+                version: 3
+            })
+        ),
+        "Error should contain the signature and version"
+    );
+}
+
+#[test]
+fn open_single_file() -> Result<()> {
+    let vpk = detect::open(Path::new(common::PAK_V1_SINGLE_FILE))?;
+
+    assert_eq!(vpk.format(), &PakFormat::VPKVersion1);
+    assert_eq!(vpk.vpk_name(), common::SINGLE_FILE_ARCHIVE);
+    assert_eq!(
+        vpk.read_file(common::SINGLE_FILE_NAME)?,
+        common::SINGLE_FILE_CONTENT.as_bytes(),
+        "Content does not match expected"
+    );
+
+    assert!(
+        matches!(
+            vpk.read_file("test/missing.txt"),
+            Err(detect::Error::Pak(vpk_plumber::pak::Error::FileNotFound(_)))
+        ),
+        "Missing file should not be found"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn open_without_dir_suffix() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    std::fs::copy(
+        common::PAK_V1_SINGLE_FILE,
+        dir.path().join("single_file.vpk"),
+    )?;
+    std::fs::copy(
+        common::PAK_V1_ARCHIVE,
+        dir.path().join("single_file_000.vpk"),
+    )?;
+
+    let vpk = detect::open(&dir.path().join("single_file.vpk"))?;
+
+    assert_eq!(vpk.vpk_name(), common::SINGLE_FILE_ARCHIVE);
+    assert_eq!(
+        vpk.read_file(common::SINGLE_FILE_NAME)?,
+        common::SINGLE_FILE_CONTENT.as_bytes(),
+        "Content does not match expected"
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn open_read_only() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::SystemTime;
+
+    let source = tempfile::tempdir()?;
+    let output = tempfile::tempdir()?;
+
+    #[cfg_attr(not(feature = "revpk"), allow(unused_mut))]
+    let mut fixtures = vec![
+        (common::PAK_V1_SINGLE_FILE, "v1/single_file_dir.vpk"),
+        (common::PAK_V1_ARCHIVE, "v1/single_file_000.vpk"),
+    ];
+    #[cfg(feature = "revpk")]
+    fixtures.extend([
+        (common::PAK_REVPK_SINGLE_FILE, "revpk/single_file_dir.vpk"),
+        (common::PAK_REVPK_ARCHIVE, "revpk/single_file_000.vpk"),
+        (
+            common::PAK_REVPK_TITANFALL,
+            "titanfall/englishclient_mp_colony.bsp.pak000_dir.vpk",
+        ),
+        (
+            common::CAM_REVPK_TITANFALL,
+            "titanfall/client_mp_common.bsp.pak000_000.vpk.cam",
+        ),
+        (
+            const_format::concatcp!(
+                common::DIR_REVPK,
+                "titanfall/client_mp_colony.bsp.pak000_004.vpk"
+            ),
+            "titanfall/client_mp_colony.bsp.pak000_004.vpk",
+        ),
+    ]);
+
+    for (fixture, copy) in &fixtures {
+        let copy = source.path().join(copy);
+        std::fs::create_dir_all(copy.parent().unwrap())?;
+        std::fs::copy(fixture, &copy)?;
+        std::fs::set_permissions(&copy, std::fs::Permissions::from_mode(0o444))?;
+    }
+
+    let dirs: Vec<_> = std::fs::read_dir(source.path())?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<_>>()?;
+    for dir in &dirs {
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o555))?;
+    }
+
+    // Permissions don't stop root from writing, so also check nothing changed
+    let snapshot = || -> Result<Vec<(String, u64, SystemTime)>> {
+        let mut files = Vec::new();
+        for dir in &dirs {
+            for entry in std::fs::read_dir(dir)? {
+                let entry = entry?;
+                let metadata = entry.metadata()?;
+                files.push((
+                    entry.path().display().to_string(),
+                    metadata.len(),
+                    metadata.modified()?,
+                ));
+            }
+        }
+        files.sort();
+        Ok(files)
+    };
+    let before = snapshot()?;
+
+    let result = (|| -> Result<()> {
+        let dir_files = [
+            "v1/single_file_dir.vpk",
+            #[cfg(feature = "revpk")]
+            "revpk/single_file_dir.vpk",
+        ];
+
+        for (i, dir_file) in dir_files.iter().enumerate() {
+            let vpk = detect::open(&source.path().join(dir_file))?;
+            for file_path in vpk.file_paths() {
+                assert_eq!(
+                    vpk.read_file(file_path)?,
+                    common::SINGLE_FILE_CONTENT.as_bytes()
+                );
+
+                let out_path = output.path().join(i.to_string()).join(file_path);
+                vpk.extract_file(file_path, out_path.to_str().unwrap())?;
+                assert_eq!(
+                    std::fs::read(out_path)?,
+                    common::SINGLE_FILE_CONTENT.as_bytes()
+                );
+            }
+        }
+
+        #[cfg(feature = "mem-map")]
+        {
+            use filebuffer::FileBuffer;
+            use std::collections::HashMap;
+            use vpk_plumber::pak::{PakReader, PakWorker, v1::VPKVersion1};
+
+            let v1 = source.path().join("v1");
+            let vpk = VPKVersion1::from_file(&mut File::open(v1.join("single_file_dir.vpk"))?)?;
+            let archive_mmaps =
+                HashMap::from([(0, FileBuffer::open(v1.join("single_file_000.vpk"))?)]);
+
+            let out_path = output.path().join("mem-map").join(common::SINGLE_FILE_NAME);
+            vpk.extract_file_mem_map(
+                v1.to_str().unwrap(),
+                &archive_mmaps,
+                "single_file",
+                common::SINGLE_FILE_NAME,
+                out_path.to_str().unwrap(),
+            )?;
+            assert_eq!(
+                std::fs::read(out_path)?,
+                common::SINGLE_FILE_CONTENT.as_bytes()
+            );
+        }
+
+        #[cfg(feature = "revpk")]
+        {
+            use vpk_plumber::pak::{PakWorker, revpk::VPKRespawn};
+
+            let titanfall = source.path().join("titanfall");
+            let mut vpk = VPKRespawn::from_file(&mut File::open(
+                titanfall.join("englishclient_mp_colony.bsp.pak000_dir.vpk"),
+            )?)?;
+            vpk.read_cam(
+                0,
+                &titanfall
+                    .join("client_mp_common.bsp.pak000_000.vpk.cam")
+                    .display()
+                    .to_string(),
+            )?;
+
+            let cache = vpk_plumber::pak::revpk::CamCache::new();
+            cache.get(&titanfall.join("client_mp_common.bsp.pak000_000.vpk.cam"))?;
+
+            // Only the archive with the index 4 is included in the test data, the other files fail to extract
+            vpk.extract_to_dir(
+                titanfall.to_str().unwrap(),
+                "client_mp_colony.bsp.pak000",
+                output.path().join("titanfall").to_str().unwrap(),
+                true,
+            )?;
+        }
+
+        Ok(())
+    })();
+
+    for dir in &dirs {
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o755))?;
+    }
+    result?;
+
+    assert_eq!(
+        snapshot()?,
+        before,
+        "Reading should not write next to the source files"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn open_large_v1() -> Result<()> {
+    let vpk = detect::open(Path::new(common::PAK_V1_PORTAL2))?;
+
+    assert_eq!(vpk.vpk_name(), "pak01");
+    assert_eq!(vpk.file_paths().len(), common::PORTAL2_TREE_COUNT);
+
+    // Only the directory file is included in the test data, so read a file stored entirely in the preload data
+    let result = vpk.read_file("materials/models/props/faith_plate.vmt")?;
+    assert_eq!(result.len(), 156, "Preload size does not match");
+
+    Ok(())
+}
+
+#[cfg(feature = "revpk")]
+#[test]
+fn open_large_revpk() -> Result<()> {
+    let vpk = detect::open(Path::new(common::PAK_REVPK_TITANFALL))?;
+
+    assert_eq!(vpk.format(), &PakFormat::VPKRespawn);
+    assert_eq!(vpk.vpk_name(), "client_mp_colony.bsp.pak000");
+    assert_eq!(vpk.file_paths().len(), common::TITANFALL_TREE_COUNT);
+
+    // Only archive 4 is included in the test data
+    let output = tempfile::NamedTempFile::new()?;
+    let result = vpk.extract_file(
+        "materials/models/domestic/canvas_backing.vmt",
+        output.path().to_str().unwrap(),
+    );
+
+    assert!(
+        matches!(
+            result,
+            Err(detect::Error::Pak(vpk_plumber::pak::Error::ArchiveOpen { ref path, .. }))
+                if Path::new(path).ends_with("client_mp_colony.bsp.pak000_000.vpk")
+        ),
+        "Archives should be looked up without the language prefix"
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "revpk")]
+#[test]
+fn open_downcast_revpk() -> Result<()> {
+    use vpk_plumber::pak::{revpk::VPKRespawn, v1::VPKVersion1};
+
+    let mut vpk = detect::open(Path::new(common::PAK_REVPK_TITANFALL))?;
+    assert_eq!(vpk.worker().format(), PakFormat::VPKRespawn);
+    assert!(vpk.worker().downcast_ref::<VPKVersion1>().is_none());
+
+    let (archive_path, vpk_name) = (vpk.archive_path().to_string(), vpk.vpk_name().to_string());
+    let respawn = vpk
+        .worker_mut()
+        .downcast_mut::<VPKRespawn>()
+        .expect("Worker should be a Respawn VPK");
+
+    // The colony VPK stores no sounds, so it has no CAMs to read
+    respawn.read_all_cams(&archive_path, &vpk_name)?;
+    assert!(respawn.archive_cams.is_empty());
+
+    respawn.read_cam(
+        0,
+        &Path::new(&archive_path)
+            .join("client_mp_common.bsp.pak000_000.vpk.cam")
+            .display()
+            .to_string(),
+    )?;
+    assert_eq!(respawn.archive_cams.len(), 1);
+
+    Ok(())
+}
+
 fn assert_format<P>(path: P, expected_format: &PakFormat) -> Result<()>
 where
     P: AsRef<Path>,
 {
     // Read a vpk file
-    let mut file = File::open(path)?;
+    let mut file = File::open(&path)?;
     let format = detect::detect_pak_format(&mut file);
 
     assert_eq!(
@@ -70,5 +513,66 @@ where
         "Format does not match expected format!"
     );
 
+    assert_eq!(
+        detect::detect_pak_format_from_path(path.as_ref()),
+        *expected_format,
+        "Format detected from the path does not match expected format!"
+    );
+
+    Ok(())
+}
+
+fn assert_format_bytes<P>(path: P, expected_format: &PakFormat) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let data = std::fs::read(path)?;
+    let format = detect::detect_pak_format_bytes(&data);
+
+    assert_eq!(
+        format, *expected_format,
+        "Format does not match expected format!"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn capabilities() {
+    assert_eq!(PakFormat::Unknown.capabilities(), FormatCaps::default());
+
+    let v1 = PakFormat::VPKVersion1.capabilities();
+    assert!(v1.read && v1.write_dir && v1.write_archives);
+    assert!(!v1.compression && !v1.signatures);
+
+    assert_eq!(
+        PakFormat::VPKRespawn.capabilities().compression,
+        cfg!(feature = "revpk"),
+        "Respawn VPKs are only supported with the revpk feature"
+    );
+}
+
+#[test]
+fn worker_format() -> Result<()> {
+    let samples = [
+        (common::PAK_V1_EMPTY, PakFormat::VPKVersion1),
+        (common::PAK_V1_SINGLE_FILE, PakFormat::VPKVersion1),
+        (common::PAK_V1_SINGLE_FILE_EOF, PakFormat::VPKVersion1),
+        (common::PAK_V1_PORTAL2, PakFormat::VPKVersion1),
+        (common::PAK_V2_EMPTY, PakFormat::VPKVersion2),
+        (common::PAK_V2_SINGLE_FILE, PakFormat::VPKVersion2),
+        (common::PAK_V2_PORTAL, PakFormat::VPKVersion2),
+    ];
+    #[cfg(feature = "revpk")]
+    let samples = samples.into_iter().chain([
+        (common::PAK_REVPK_SINGLE_FILE, PakFormat::VPKRespawn),
+        (common::PAK_REVPK_TITANFALL, PakFormat::VPKRespawn),
+    ]);
+
+    for (path, format) in samples {
+        let worker = detect::find_pak_worker(&mut File::open(path)?)?;
+        assert_eq!(worker.format(), format, "Format of {path} should match");
+    }
+
     Ok(())
 }
@@ -24,29 +24,28 @@ fn large_v1() -> Result<()> {
     roundtrip(common::PAK_V1_PORTAL2, &PakFormat::VPKVersion1)
 }
 
-#[ignore = "not yet implemented"]
 #[test]
 fn empty_v2() -> Result<()> {
     roundtrip(common::PAK_V2_EMPTY, &PakFormat::VPKVersion2)
 }
 
-#[ignore = "not yet implemented"]
 #[test]
 fn single_file_v2() -> Result<()> {
     roundtrip(common::PAK_V2_SINGLE_FILE, &PakFormat::VPKVersion2)
 }
 
-#[ignore = "not yet implemented"]
 #[test]
 fn large_v2() -> Result<()> {
     roundtrip(common::PAK_V2_PORTAL, &PakFormat::VPKVersion2)
 }
 
+#[cfg(feature = "revpk")]
 #[test]
 fn single_file_revpk() -> Result<()> {
     roundtrip(common::PAK_REVPK_SINGLE_FILE, &PakFormat::VPKRespawn)
 }
 
+#[cfg(feature = "revpk")]
 #[test]
 fn large_revpk() -> Result<()> {
     roundtrip(common::PAK_REVPK_TITANFALL, &PakFormat::VPKRespawn)
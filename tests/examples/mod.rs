@@ -0,0 +1,78 @@
+//! Runs the example programs against small VPKs, so they keep working as the API evolves.
+
+use std::path::Path;
+
+use crate::common::{self, Result};
+
+#[path = "../../examples/extract_one.rs"]
+#[allow(dead_code)]
+mod extract_one;
+#[path = "../../examples/list.rs"]
+#[allow(dead_code)]
+mod list;
+#[path = "../../examples/pack.rs"]
+#[allow(dead_code)]
+mod pack;
+
+#[test]
+fn list_fixture() -> Result<()> {
+    let mut out = Vec::new();
+    list::list(Path::new(common::PAK_V1_SINGLE_FILE), &mut out)?;
+
+    let out = String::from_utf8(out)?;
+    let expected = format!(
+        "{}\t{}",
+        common::SINGLE_FILE_NAME,
+        common::SINGLE_FILE_CONTENT.len()
+    );
+    assert!(
+        out.lines().any(|line| line == expected),
+        "Listing should contain the file: {out}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn pack_list_extract() -> Result<()> {
+    let source_dir = tempfile::tempdir()?;
+    let output_dir = tempfile::tempdir()?;
+    let output_path = output_dir.path().to_str().unwrap();
+
+    let files = [
+        ("materials/a.vmt", "shared"),
+        ("materials/nested/b.vmt", "shared"),
+        ("scripts/c.txt", "script"),
+    ];
+    for (file_path, content) in files {
+        let path = source_dir.path().join(file_path);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(path, content)?;
+    }
+
+    let mut out = Vec::new();
+    pack::pack(source_dir.path(), output_path, "pak01", &mut out)?;
+    assert!(String::from_utf8(out)?.starts_with("Packed 3 files"));
+
+    let dir_vpk_path = output_dir.path().join("pak01_dir.vpk");
+    let mut out = Vec::new();
+    list::list(&dir_vpk_path, &mut out)?;
+    let out = String::from_utf8(out)?;
+    for (file_path, content) in files {
+        assert!(
+            out.lines()
+                .any(|line| line == format!("{file_path}\t{}", content.len())),
+            "Listing should contain {file_path}: {out}"
+        );
+    }
+
+    let extracted = output_dir.path().join("b.vmt");
+    extract_one::extract_one(
+        &dir_vpk_path,
+        "materials/nested/b.vmt",
+        extracted.to_str().unwrap(),
+    )?;
+    assert_eq!(std::fs::read_to_string(extracted)?, "shared");
+
+    Ok(())
+}
@@ -1,6 +1,9 @@
-use std::fs::File;
+use std::{fs::File, path::Path};
 
-use vpk_plumber::pak::{PakReader, revpk::VPKRespawn};
+use vpk_plumber::pak::{
+    ArchiveConsistency, ExtractOptions, PakReader, ValidationIssueKind,
+    revpk::{VPKFilePartEntryRespawn, VPKRespawn},
+};
 
 use crate::common::{self, Result};
 
@@ -42,6 +45,115 @@ fn vpk_single_file() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn vpk_single_file_skip_crc() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_SINGLE_FILE)?;
+    let mut vpk = VPKRespawn::try_from(&mut file)?;
+
+    vpk.tree
+        .files
+        .get_mut(common::SINGLE_FILE_NAME)
+        .unwrap()
+        .crc ^= 1;
+
+    let result = vpk.read_file(
+        common::DIR_REVPK,
+        common::SINGLE_FILE_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+    );
+    assert!(result.is_none(), "Mismatching CRC should fail the read");
+
+    let options = ExtractOptions {
+        verify_crc: false,
+        ..Default::default()
+    };
+    let result = vpk
+        .read_file_with(
+            common::DIR_REVPK,
+            common::SINGLE_FILE_ARCHIVE,
+            common::SINGLE_FILE_NAME,
+            &options,
+        )
+        .unwrap();
+
+    assert_eq!(
+        result,
+        common::SINGLE_FILE_CONTENT.as_bytes(),
+        "Content does not match expected"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_verify_all_crcs() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_SINGLE_FILE)?;
+    let mut vpk = VPKRespawn::try_from(&mut file)?;
+
+    let results = vpk.verify_all_crcs(common::DIR_REVPK, common::SINGLE_FILE_ARCHIVE);
+    assert!(
+        matches!(results.as_slice(), [(file_path, Ok(()))] if file_path == common::SINGLE_FILE_NAME),
+        "All CRCs should match"
+    );
+
+    vpk.tree
+        .files
+        .get_mut(common::SINGLE_FILE_NAME)
+        .unwrap()
+        .crc ^= 1;
+
+    let results = vpk.verify_all_crcs(common::DIR_REVPK, common::SINGLE_FILE_ARCHIVE);
+    assert!(
+        matches!(
+            results.as_slice(),
+            [(file_path, Err(vpk_plumber::pak::Error::CrcMismatch { .. }))] if file_path == common::SINGLE_FILE_NAME
+        ),
+        "Mismatching CRC should be reported"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_archive_consistency() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_SINGLE_FILE)?;
+    let vpk = VPKRespawn::try_from(&mut file)?;
+
+    let dir = tempfile::tempdir()?;
+    let archive_path = dir.path().to_str().unwrap();
+    let archive = dir.path().join("single_file_000.vpk");
+    std::fs::copy(common::PAK_REVPK_ARCHIVE, &archive)?;
+
+    let results = vpk.check_archive_consistency(archive_path, common::SINGLE_FILE_ARCHIVE, 4);
+    assert!(
+        matches!(results.as_slice(), [ArchiveConsistency::Consistent(0)]),
+        "Archive should be consistent"
+    );
+
+    // Simulate an archive left over from a different version of the directory file
+    let mut data = std::fs::read(&archive)?;
+    for byte in &mut data {
+        *byte = byte.wrapping_add(1);
+    }
+    std::fs::write(&archive, data)?;
+
+    let results = vpk.check_archive_consistency(archive_path, common::SINGLE_FILE_ARCHIVE, 4);
+    assert!(
+        matches!(results.as_slice(), [ArchiveConsistency::ArchiveStale(0)]),
+        "Modified archive should be stale"
+    );
+
+    std::fs::remove_file(&archive)?;
+
+    let results = vpk.check_archive_consistency(archive_path, common::SINGLE_FILE_ARCHIVE, 4);
+    assert!(
+        matches!(results.as_slice(), [ArchiveConsistency::Unreadable(0, _)]),
+        "Missing archive should be unreadable"
+    );
+
+    Ok(())
+}
+
 #[test]
 fn vpk_large() -> Result<()> {
     let mut file = File::open(common::PAK_REVPK_TITANFALL)?;
@@ -55,3 +167,93 @@ fn vpk_large() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn vpk_single_file_rel() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_SINGLE_FILE)?;
+    let vpk = VPKRespawn::try_from(&mut file)?;
+
+    let result = vpk.read_file_rel(
+        Path::new(common::PAK_REVPK_SINGLE_FILE),
+        common::SINGLE_FILE_NAME,
+    )?;
+
+    assert_eq!(
+        result,
+        common::SINGLE_FILE_CONTENT.as_bytes(),
+        "Content does not match expected"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_large_rel_language() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_TITANFALL)?;
+    let vpk = VPKRespawn::try_from(&mut file)?;
+
+    // Only archive 4 is included in the test data
+    let (file_path, _) = vpk
+        .tree
+        .files
+        .iter()
+        .find(|(_, entry)| entry.file_parts[0].archive_index == 0)
+        .unwrap();
+
+    let result = vpk.read_file_rel(Path::new(common::PAK_REVPK_TITANFALL), file_path);
+
+    assert!(
+        result.is_err_and(|x| matches!(
+            x,
+            vpk_plumber::pak::Error::ArchiveOpen { ref path, .. }
+                if Path::new(path).ends_with("client_mp_colony.bsp.pak000_000.vpk")
+        )),
+        "Archive path should not contain the language prefix",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_validate() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_TITANFALL)?;
+    let mut vpk = VPKRespawn::try_from(&mut file)?;
+    assert!(vpk.validate().is_empty(), "Loaded VPK should be valid");
+
+    let mut file_paths: Vec<String> = vpk.tree.files.keys().cloned().collect();
+    file_paths.sort();
+
+    vpk.tree
+        .files
+        .get_mut(&file_paths[0])
+        .unwrap()
+        .file_parts
+        .clear();
+    vpk.tree.files.get_mut(&file_paths[1]).unwrap().file_parts = vec![VPKFilePartEntryRespawn {
+        entry_length: 2,
+        entry_length_uncompressed: 1,
+        ..Default::default()
+    }];
+
+    let issues = vpk.validate();
+    let kinds: Vec<(Option<&str>, ValidationIssueKind)> = issues
+        .iter()
+        .map(|issue| (issue.path.as_deref(), issue.kind))
+        .collect();
+
+    assert_eq!(
+        kinds,
+        [
+            (
+                Some(file_paths[0].as_str()),
+                ValidationIssueKind::NoFileParts
+            ),
+            (
+                Some(file_paths[1].as_str()),
+                ValidationIssueKind::FilePartTooLarge
+            ),
+        ]
+    );
+
+    Ok(())
+}
@@ -1,11 +1,19 @@
 use std::{fs::File, io::Read};
 
-use vpk_plumber::pak::{PakReader, revpk::VPKRespawn};
+use vpk_plumber::pak::{
+    Error, ExtractOptions, FileLocation, FilePartLocation, PakReader, PakWorker,
+    revpk::{
+        RESPAWN_MAX_PART_SIZE, VPKFilePartEntryRespawn, VPKRespawn, VPKRespawnCam,
+        VPKRespawnCamEntry,
+    },
+};
 
 use crate::common::{self, Result};
 
+#[cfg(feature = "mem-map")]
 use filebuffer::FileBuffer;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 #[test]
 fn vpk_single_file() -> Result<()> {
@@ -62,3 +70,882 @@ fn vpk_single_file_mem_map() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn vpk_extract_all() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_SINGLE_FILE)?;
+    let vpk = VPKRespawn::try_from(&mut file)?;
+
+    let out_dir = tempfile::tempdir()?;
+
+    let summary = vpk.extract_all(
+        common::DIR_REVPK,
+        common::SINGLE_FILE_ARCHIVE,
+        out_dir.path().to_str().unwrap(),
+        None,
+    )?;
+
+    assert_eq!(summary.files_extracted, 1, "One file should be extracted");
+    assert!(summary.errors.is_empty(), "No errors should be reported");
+
+    let mut result = String::new();
+    File::open(out_dir.path().join(common::SINGLE_FILE_NAME))?.read_to_string(&mut result)?;
+
+    assert_eq!(
+        result,
+        common::SINGLE_FILE_CONTENT,
+        "File contents should match",
+    );
+    Ok(())
+}
+
+#[test]
+fn vpk_extract_to_dir() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_SINGLE_FILE)?;
+    let vpk = VPKRespawn::try_from(&mut file)?;
+
+    let out_dir = tempfile::tempdir()?;
+    let summary = vpk.extract_to_dir(
+        common::DIR_REVPK,
+        common::SINGLE_FILE_ARCHIVE,
+        out_dir.path().to_str().unwrap(),
+        false,
+    )?;
+
+    assert_eq!(summary.files_extracted, 1, "File should be extracted");
+    assert!(summary.errors.is_empty(), "No errors should be reported");
+    assert_eq!(
+        std::fs::read_to_string(out_dir.path().join(common::SINGLE_FILE_NAME))?,
+        common::SINGLE_FILE_CONTENT,
+        "File contents should match",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_extract_to_dir_large() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_TITANFALL)?;
+    let vpk = VPKRespawn::try_from(&mut file)?;
+
+    let out_dir = tempfile::tempdir()?;
+    let summary = vpk.extract_to_dir(
+        &format!("{}titanfall", common::DIR_REVPK),
+        "client_mp_colony.bsp.pak000",
+        out_dir.path().to_str().unwrap(),
+        true,
+    )?;
+
+    assert_eq!(
+        summary.files_extracted + summary.errors.len(),
+        common::TITANFALL_TREE_COUNT,
+        "Every file should be extracted or have an error"
+    );
+
+    // Only archive 4 is included in the test data, so only files stored in it can be extracted
+    let failed: HashSet<&str> = summary
+        .errors
+        .iter()
+        .map(|(file_path, _)| file_path.as_str())
+        .collect();
+    for file_path in vpk.file_paths() {
+        assert_eq!(
+            out_dir.path().join(file_path).is_file(),
+            !failed.contains(file_path),
+            "{file_path} should only be extracted if it did not fail"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn vpk_single_file_progress() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_SINGLE_FILE)?;
+    let vpk = VPKRespawn::try_from(&mut file)?;
+
+    let out_path = tempfile::NamedTempFile::new()?;
+    let mut calls = Vec::new();
+
+    vpk.extract_file_with_progress(
+        common::DIR_REVPK,
+        common::SINGLE_FILE_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+        out_path.path().to_str().unwrap(),
+        &mut |written, total| calls.push((written, total)),
+    )?;
+
+    let len = common::SINGLE_FILE_CONTENT.len() as u64;
+    assert_eq!(
+        calls.last(),
+        Some(&(len, len)),
+        "Progress should end at the file size",
+    );
+    assert!(
+        calls.windows(2).all(|w| w[0].0 <= w[1].0),
+        "Progress should never go backwards",
+    );
+    Ok(())
+}
+
+#[test]
+fn vpk_single_file_crc_mismatch() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_SINGLE_FILE)?;
+    let mut vpk = VPKRespawn::try_from(&mut file)?;
+
+    vpk.tree
+        .files
+        .get_mut(common::SINGLE_FILE_NAME)
+        .unwrap()
+        .crc ^= 1;
+
+    let out_path = tempfile::NamedTempFile::new()?;
+    let result = vpk.extract_file(
+        common::DIR_REVPK,
+        common::SINGLE_FILE_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+        out_path.path().to_str().unwrap(),
+    );
+
+    assert!(
+        result.is_err_and(|x| matches!(
+            x,
+            vpk_plumber::pak::Error::CrcMismatch { expected, found, ref file }
+                if expected == found ^ 1 && file == common::SINGLE_FILE_NAME
+        )),
+        "Mismatching CRC should report the expected and computed CRC",
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn vpk_extract_files_parallel() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_TITANFALL)?;
+    let vpk = VPKRespawn::try_from(&mut file)?;
+
+    let archive_path = &format!("{}titanfall", common::DIR_REVPK);
+    let vpk_name = "client_mp_colony.bsp.pak000";
+
+    // Only archive 4 is included in the test data
+    let mut file_paths: Vec<&str> = vpk
+        .tree
+        .files
+        .iter()
+        .filter(|(_, entry)| entry.file_parts[0].archive_index == 4)
+        .map(|(file_path, _)| file_path.as_str())
+        .collect();
+    file_paths.sort_unstable();
+    file_paths.truncate(100);
+
+    let serial_dir = tempfile::tempdir()?;
+    let serial = vpk.extract_files(
+        archive_path,
+        vpk_name,
+        &file_paths,
+        serial_dir.path().to_str().unwrap(),
+        None,
+    )?;
+
+    let parallel_dir = tempfile::tempdir()?;
+    let parallel = vpk.extract_files_parallel(
+        archive_path,
+        vpk_name,
+        &file_paths,
+        parallel_dir.path().to_str().unwrap(),
+    );
+
+    assert_eq!(
+        parallel.iter().filter(|(_, result)| result.is_ok()).count(),
+        serial.files_extracted,
+        "Extracted file count should match"
+    );
+
+    let mut serial_errors: Vec<&str> = serial.errors.iter().map(|(p, _)| p.as_str()).collect();
+    serial_errors.sort_unstable();
+    let parallel_errors: Vec<&str> = parallel
+        .iter()
+        .filter(|(_, result)| result.is_err())
+        .map(|(p, _)| p.as_str())
+        .collect();
+    assert_eq!(parallel_errors, serial_errors, "Failed files should match");
+
+    for file_path in file_paths {
+        assert_eq!(
+            std::fs::read(parallel_dir.path().join(file_path)).ok(),
+            std::fs::read(serial_dir.path().join(file_path)).ok(),
+            "Contents of {file_path} should match"
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn vpk_extract_all_parallel() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_TITANFALL)?;
+    let vpk = VPKRespawn::try_from(&mut file)?;
+
+    let archive_path = &format!("{}titanfall", common::DIR_REVPK);
+    let vpk_name = "client_mp_colony.bsp.pak000";
+
+    // Only archive 4 is included in the test data, files in other archives fail to extract
+    let serial_dir = tempfile::tempdir()?;
+    let serial = vpk.extract_all(
+        archive_path,
+        vpk_name,
+        serial_dir.path().to_str().unwrap(),
+        None,
+    )?;
+
+    let parallel_dir = tempfile::tempdir()?;
+    let parallel = vpk.extract_all_parallel(
+        archive_path,
+        vpk_name,
+        parallel_dir.path().to_str().unwrap(),
+    );
+
+    assert_eq!(
+        parallel.iter().filter(|(_, result)| result.is_ok()).count(),
+        serial.files_extracted,
+        "Extracted file count should match"
+    );
+
+    let mut serial_errors: Vec<&str> = serial.errors.iter().map(|(p, _)| p.as_str()).collect();
+    serial_errors.sort_unstable();
+    let parallel_errors: Vec<&str> = parallel
+        .iter()
+        .filter(|(_, result)| result.is_err())
+        .map(|(p, _)| p.as_str())
+        .collect();
+    assert_eq!(parallel_errors, serial_errors, "Failed files should match");
+
+    Ok(())
+}
+
+#[test]
+fn vpk_try_read_file() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_SINGLE_FILE)?;
+    let vpk = VPKRespawn::try_from(&mut file)?;
+
+    assert_eq!(
+        vpk.try_read_file(
+            common::DIR_REVPK,
+            common::SINGLE_FILE_ARCHIVE,
+            common::SINGLE_FILE_NAME
+        )?,
+        common::SINGLE_FILE_CONTENT.as_bytes(),
+        "Content does not match expected"
+    );
+
+    assert!(
+        matches!(
+            vpk.try_read_file(
+                common::DIR_REVPK,
+                common::SINGLE_FILE_ARCHIVE,
+                "test/missing.txt"
+            ),
+            Err(Error::FileNotFound(_))
+        ),
+        "Nonexistent files should not be found"
+    );
+
+    assert!(
+        matches!(
+            vpk.try_read_file(common::DIR_REVPK, "missing", common::SINGLE_FILE_NAME),
+            Err(Error::ArchiveOpen { .. })
+        ),
+        "Missing archives should not open"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_file_metadata() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_SINGLE_FILE)?;
+    let vpk = VPKRespawn::try_from(&mut file)?;
+
+    assert_eq!(vpk.file_crc(common::SINGLE_FILE_NAME), Some(0x4570_FA16));
+    assert_eq!(
+        vpk.file_location(common::SINGLE_FILE_NAME),
+        Some(FileLocation::MultiPart {
+            parts: vec![FilePartLocation {
+                index: 0,
+                offset: 0,
+                length: 9,
+                uncompressed_length: 9,
+            }]
+        })
+    );
+    assert_eq!(vpk.file_location("test/missing.txt"), None);
+    Ok(())
+}
+
+#[test]
+fn vpk_read_wav_raw() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&56u32.to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&[
+        16, 0, 0, 0, 1, 0, 1, 0, 0x44, 0xAC, 0, 0, 0x88, 0x58, 1, 0, 2, 0, 16, 0,
+    ]);
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&20u32.to_le_bytes());
+    // Respawn pads the stored header before the samples
+    wav.extend_from_slice(&[0xCB; 4]);
+    wav.extend_from_slice(&[0x42; 20]);
+
+    let mut vpk = VPKRespawn::new();
+    vpk.write_archive(archive_path, "scratch", 0, &[("sound/test.wav", &wav)])?;
+
+    let raw_options = ExtractOptions {
+        wav_transcode: false,
+        ..Default::default()
+    };
+    let raw = vpk.try_read_file_with(archive_path, "scratch", "sound/test.wav", &raw_options)?;
+    assert_eq!(raw, wav, "Raw data should be returned as stored");
+
+    let transcoded = vpk.try_read_file(archive_path, "scratch", "sound/test.wav")?;
+    assert_eq!(&transcoded[..4], b"RIFF");
+    assert!(
+        !transcoded.contains(&0xCB),
+        "Padding should be skipped when transcoding"
+    );
+
+    // Only raw data is checked against the CRC
+    vpk.tree
+        .files
+        .get_mut("sound/test.wav")
+        .expect("File should be in the tree")
+        .crc ^= 1;
+    assert!(matches!(
+        vpk.try_read_file_with(archive_path, "scratch", "sound/test.wav", &raw_options),
+        Err(Error::CrcMismatch { .. })
+    ));
+    assert!(
+        vpk.try_read_file(archive_path, "scratch", "sound/test.wav")
+            .is_ok()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_extract_wav_fix_sizes() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&56u32.to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&[
+        16, 0, 0, 0, 1, 0, 1, 0, 0x44, 0xAC, 0, 0, 0x88, 0x58, 1, 0, 2, 0, 16, 0,
+    ]);
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&20u32.to_le_bytes());
+    wav.extend_from_slice(&[0x42; 20]);
+
+    let mut vpk = VPKRespawn::new();
+    vpk.write_archive(archive_path, "scratch", 0, &[("sound/test.wav", &wav)])?;
+
+    // The CAM entry claims 100 bytes of samples, but only 20 are stored
+    let offset = vpk.tree.files["sound/test.wav"].file_parts[0].entry_offset;
+    let cam_entry = VPKRespawnCamEntry {
+        original_size: 144,
+        sample_rate: 44100,
+        channels: 1,
+        sample_count: 50,
+        header_size: 44,
+        vpk_content_offset: offset,
+        ..VPKRespawnCamEntry::new()
+    };
+    vpk.archive_cams.insert(
+        0,
+        Arc::new(VPKRespawnCam {
+            entries: HashMap::from([(offset, cam_entry)]),
+        }),
+    );
+
+    let assert_sizes = |data: &[u8], riff_len: u32, data_len: u32| {
+        assert_eq!(&data[4..8], &riff_len.to_le_bytes(), "RIFF chunk size");
+        assert_eq!(&data[40..44], &data_len.to_le_bytes(), "Data chunk size");
+    };
+
+    let out_path = out_dir.path().join("test.wav");
+    vpk.extract_file(
+        archive_path,
+        "scratch",
+        "sound/test.wav",
+        out_path.to_str().unwrap(),
+    )?;
+    let extracted = std::fs::read(&out_path)?;
+    assert_eq!(extracted.len(), 64);
+    assert_sizes(&extracted, 56, 20);
+
+    let read = vpk.try_read_file(archive_path, "scratch", "sound/test.wav")?;
+    assert_eq!(
+        read, extracted,
+        "Data read into memory should be corrected as well"
+    );
+
+    let summary = vpk.extract_files(
+        archive_path,
+        "scratch",
+        &["sound/test.wav"],
+        out_dir.path().join("all").to_str().unwrap(),
+        None,
+    )?;
+    assert!(summary.errors.is_empty());
+    assert_eq!(
+        std::fs::read(out_dir.path().join("all/sound/test.wav"))?,
+        extracted
+    );
+
+    // Streams can't be corrected and keep the sizes declared by the CAM entry
+    let mut streamed = Vec::new();
+    vpk.read_file_to(archive_path, "scratch", "sound/test.wav", &mut streamed)?;
+    assert_sizes(&streamed, 136, 100);
+
+    let options = ExtractOptions {
+        fix_wav_sizes: false,
+        ..Default::default()
+    };
+    let read = vpk.try_read_file_with(archive_path, "scratch", "sound/test.wav", &options)?;
+    assert_sizes(&read, 136, 100);
+
+    Ok(())
+}
+
+#[cfg(feature = "mem-map")]
+#[test]
+fn vpk_extract_wav_mem_map() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&56u32.to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&[
+        16, 0, 0, 0, 1, 0, 1, 0, 0x44, 0xAC, 0, 0, 0x88, 0x58, 1, 0, 2, 0, 16, 0,
+    ]);
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&20u32.to_le_bytes());
+    wav.extend_from_slice(&[0xCB; 4]);
+    wav.extend_from_slice(&[0x42; 20]);
+
+    let mut vpk = VPKRespawn::new();
+    vpk.write_archive(archive_path, "scratch", 0, &[("sound/test.wav", &wav)])?;
+
+    // The original size includes the header, only 10 bytes of samples are kept
+    let offset = vpk.tree.files["sound/test.wav"].file_parts[0].entry_offset;
+    let cam_entry = VPKRespawnCamEntry {
+        original_size: 54,
+        sample_rate: 44100,
+        channels: 1,
+        sample_count: 5,
+        header_size: 44,
+        vpk_content_offset: offset,
+        ..VPKRespawnCamEntry::new()
+    };
+    vpk.archive_cams.insert(
+        0,
+        Arc::new(VPKRespawnCam {
+            entries: HashMap::from([(offset, cam_entry)]),
+        }),
+    );
+
+    let mut archive_mmaps = HashMap::new();
+    archive_mmaps.insert(
+        0,
+        FileBuffer::open(out_dir.path().join("scratch_000.vpk")).unwrap(),
+    );
+
+    let out_path = out_dir.path().join("test.wav");
+    vpk.extract_file(
+        archive_path,
+        "scratch",
+        "sound/test.wav",
+        out_path.to_str().unwrap(),
+    )?;
+    let extracted = std::fs::read(&out_path)?;
+    assert_eq!(extracted.len(), 54, "Output should be truncated");
+
+    let mem_map_path = out_dir.path().join("test_mem_map.wav");
+    vpk.extract_file_mem_map(
+        archive_path,
+        &archive_mmaps,
+        "scratch",
+        "sound/test.wav",
+        mem_map_path.to_str().unwrap(),
+    )?;
+    assert_eq!(
+        std::fs::read(&mem_map_path)?,
+        extracted,
+        "Output should match the output of extract_file"
+    );
+
+    let raw_options = ExtractOptions {
+        wav_transcode: false,
+        ..Default::default()
+    };
+    vpk.extract_file_mem_map_with(
+        archive_path,
+        &archive_mmaps,
+        "scratch",
+        "sound/test.wav",
+        mem_map_path.to_str().unwrap(),
+        &raw_options,
+    )?;
+    assert_eq!(
+        std::fs::read(&mem_map_path)?,
+        wav,
+        "Raw data should be extracted as stored"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_file_size() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_SINGLE_FILE)?;
+    let vpk = VPKRespawn::try_from(&mut file)?;
+
+    let data = vpk
+        .read_file(
+            common::DIR_REVPK,
+            common::SINGLE_FILE_ARCHIVE,
+            common::SINGLE_FILE_NAME,
+        )
+        .expect("File should be readable");
+    assert_eq!(
+        vpk.file_size(common::SINGLE_FILE_NAME),
+        Some(data.len() as u64)
+    );
+    assert_eq!(vpk.file_size("test/missing.txt"), None);
+
+    // A compressed file split into parts and a WAV file whose header is replaced when extracting
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&56u32.to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&[
+        16, 0, 0, 0, 1, 0, 1, 0, 0x44, 0xAC, 0, 0, 0x88, 0x58, 1, 0, 2, 0, 16, 0,
+    ]);
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&20u32.to_le_bytes());
+    wav.extend_from_slice(&[0x42; 20]);
+
+    let large = vec![b'a'; RESPAWN_MAX_PART_SIZE + 512];
+    let mut vpk = VPKRespawn::new();
+    vpk.write_archive(
+        archive_path,
+        "scratch",
+        0,
+        &[("sound/test.wav", &wav), ("test/large.txt", &large)],
+    )?;
+
+    for file_path in ["sound/test.wav", "test/large.txt"] {
+        let data = vpk
+            .read_file(archive_path, "scratch", file_path)
+            .expect("File should be readable");
+
+        assert_eq!(
+            vpk.file_size(file_path),
+            Some(data.len() as u64),
+            "Size of {file_path} should match its contents"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn vpk_try_read_file_truncated_compressed() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    let data = vec![b'a'; 4096];
+    let mut vpk = VPKRespawn::new();
+    vpk.write_archive(
+        archive_path,
+        "scratch",
+        0,
+        &[("test/compressed.txt", &data)],
+    )?;
+
+    // Cut off the end of the compressed part
+    let part = &mut vpk
+        .tree
+        .files
+        .get_mut("test/compressed.txt")
+        .unwrap()
+        .file_parts[0];
+    assert!(
+        part.entry_length < part.entry_length_uncompressed,
+        "Part should be compressed"
+    );
+    part.entry_length -= 1;
+
+    assert!(
+        matches!(
+            vpk.try_read_file(archive_path, "scratch", "test/compressed.txt"),
+            Err(Error::Decompress { ref file, .. }) if file == "test/compressed.txt"
+        ),
+        "Truncated compressed data should fail to decompress"
+    );
+
+    let out_path = out_dir.path().join("compressed.txt");
+    assert!(
+        matches!(
+            vpk.extract_file(
+                archive_path,
+                "scratch",
+                "test/compressed.txt",
+                out_path.to_str().unwrap()
+            ),
+            Err(Error::Decompress { .. })
+        ),
+        "Truncated compressed data should fail to extract"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_read_truncated_archive_compressed() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    let data = vec![b'a'; 4096];
+    let mut vpk = VPKRespawn::new();
+    vpk.write_archive(
+        archive_path,
+        "scratch",
+        0,
+        &[("test/compressed.txt", &data)],
+    )?;
+
+    // Cut off the archive in the middle of the compressed part
+    let part = vpk.tree.files["test/compressed.txt"].file_parts[0].clone();
+    assert!(
+        part.entry_length < part.entry_length_uncompressed,
+        "Part should be compressed"
+    );
+    let got = part.entry_length / 2;
+    File::options()
+        .write(true)
+        .open(out_dir.path().join("scratch_000.vpk"))?
+        .set_len(part.entry_offset + got)?;
+
+    let is_truncated = |error: Option<&Error>| {
+        matches!(
+            error,
+            Some(Error::TruncatedPart { path, part_index: 0, expected, got: read })
+                if path == "test/compressed.txt" && *expected == part.entry_length && *read == got
+        )
+    };
+
+    let result = vpk.try_read_file(archive_path, "scratch", "test/compressed.txt");
+    assert!(
+        is_truncated(result.as_ref().err()),
+        "Reading a truncated part should fail: {:?}",
+        result.err()
+    );
+
+    let result = vpk.read_file_streaming(archive_path, "scratch", "test/compressed.txt");
+    assert!(
+        is_truncated(result.as_ref().err()),
+        "Streaming a truncated part should fail: {:?}",
+        result.err()
+    );
+
+    #[cfg(feature = "mem-map")]
+    {
+        let mut archive_mmaps = HashMap::new();
+        archive_mmaps.insert(
+            0,
+            FileBuffer::open(out_dir.path().join("scratch_000.vpk")).unwrap(),
+        );
+        let out_path = out_dir.path().join("compressed.txt");
+
+        let result = vpk.extract_file_mem_map(
+            archive_path,
+            &archive_mmaps,
+            "scratch",
+            "test/compressed.txt",
+            out_path.to_str().unwrap(),
+        );
+        assert!(
+            is_truncated(result.as_ref().err()),
+            "Extracting a truncated part from a memory-mapped archive should fail: {:?}",
+            result.err()
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn vpk_try_read_file_wav() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    // A WAV file whose padding runs until the end of the archive
+    let data = vec![0xCB; 64];
+    std::fs::write(out_dir.path().join("scratch_000.vpk"), &data)?;
+
+    let mut vpk = VPKRespawn::new();
+    vpk.upsert_entry(
+        "sound/broken.wav",
+        &data,
+        &[VPKFilePartEntryRespawn {
+            entry_length: data.len() as u64,
+            entry_length_uncompressed: data.len() as u64,
+            ..VPKFilePartEntryRespawn::new()
+        }],
+        None,
+    )?;
+
+    assert!(
+        matches!(
+            vpk.try_read_file(archive_path, "scratch", "sound/broken.wav"),
+            Err(Error::BadData(_))
+        ),
+        "WAV data past the end of the archive should be reported"
+    );
+
+    // A CAM that doesn't describe the file
+    vpk.archive_cams.insert(
+        0,
+        Arc::new(VPKRespawnCam {
+            entries: HashMap::new(),
+        }),
+    );
+    assert!(
+        matches!(
+            vpk.try_read_file(archive_path, "scratch", "sound/broken.wav"),
+            Err(Error::DataNotFound(_))
+        ),
+        "Missing CAM entries should be reported"
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "mem-map")]
+#[test]
+fn vpk_forged_parts_mem_map() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    std::fs::write(out_dir.path().join("scratch_000.vpk"), [b'a'; 16])?;
+    let mut archive_mmaps = HashMap::new();
+    archive_mmaps.insert(
+        0,
+        FileBuffer::open(out_dir.path().join("scratch_000.vpk")).unwrap(),
+    );
+
+    // Parts declaring data past the end of the archive, with offsets close to overflowing
+    let forged_parts = [
+        ("test/past_end.txt", 8, 16, 16),
+        ("test/overflow.txt", u64::MAX - 4, 16, 16),
+        ("test/compressed.txt", u64::MAX - 4, 16, 32),
+        ("sound/overflow.wav", u64::MAX - 4, 64, 64),
+    ];
+
+    let mut vpk = VPKRespawn::new();
+    for (file_path, entry_offset, entry_length, entry_length_uncompressed) in forged_parts {
+        vpk.upsert_entry(
+            file_path,
+            &[],
+            &[VPKFilePartEntryRespawn {
+                entry_offset,
+                entry_length,
+                entry_length_uncompressed,
+                ..VPKFilePartEntryRespawn::new()
+            }],
+            None,
+        )?;
+    }
+
+    for (file_path, ..) in forged_parts {
+        let out_path = out_dir.path().join("out");
+        let result = vpk.extract_file_mem_map(
+            archive_path,
+            &archive_mmaps,
+            "scratch",
+            file_path,
+            out_path.to_str().unwrap(),
+        );
+
+        if file_path == "test/compressed.txt" {
+            assert!(
+                matches!(result, Err(Error::TruncatedPart { .. })),
+                "Extracting {file_path} should report a truncated part: {result:?}"
+            );
+        } else {
+            assert!(
+                matches!(result, Err(Error::BadData(_))),
+                "Extracting {file_path} should report bad data: {result:?}"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn vpk_extract_files_observed() -> Result<()> {
+    use std::sync::Arc;
+
+    let mut file = File::open(common::PAK_REVPK_TITANFALL)?;
+    let mut vpk = VPKRespawn::try_from(&mut file)?;
+
+    let observer = Arc::new(common::CountingObserver::default());
+    vpk.set_observer(Some(observer.clone()));
+
+    let archive_path = &format!("{}titanfall", common::DIR_REVPK);
+    let vpk_name = "client_mp_colony.bsp.pak000";
+
+    // Only archive 4 is included in the test data
+    let file_paths: Vec<&str> = vpk
+        .tree
+        .files
+        .iter()
+        .filter(|(_, entry)| entry.file_parts.iter().all(|part| part.archive_index == 4))
+        .map(|(file_path, _)| file_path.as_str())
+        .collect();
+
+    let out_dir = tempfile::tempdir()?;
+    vpk.extract_files(
+        archive_path,
+        vpk_name,
+        &file_paths,
+        out_dir.path().to_str().unwrap(),
+        None,
+    )?;
+
+    assert_eq!(
+        observer.opened(),
+        [(4, 1)].into(),
+        "Archive 4 should be opened once for all files"
+    );
+    assert!(
+        observer.bytes_read()[&4] > 0,
+        "Reads from archive 4 should be reported"
+    );
+
+    Ok(())
+}
@@ -1,6 +1,12 @@
-use std::fs::File;
+use std::{collections::HashMap, fs::File, io::Cursor, path::Path, sync::Arc};
 
-use vpk_plumber::pak::revpk::VPKRespawn;
+use vpk_plumber::pak::{
+    PakReader, PakWorker, VPKTree, VPKTreeIter,
+    revpk::{
+        CamCache, RESPAWN_CAM_ENTRY_MAGIC, VPKDirectoryEntryRespawn, VPKFilePartEntryRespawn,
+        VPKHeaderRespawn, VPKRespawn, VPKRespawnCamEntry, create_wav_header,
+    },
+};
 
 use crate::common::{self, Result};
 
@@ -11,6 +17,55 @@ fn valid_vpk_single_file() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn valid_vpk_from_bytes() -> Result<()> {
+    let vpk = VPKRespawn::from_bytes(&std::fs::read(common::PAK_REVPK_TITANFALL)?)?;
+    let mut file = File::open(common::PAK_REVPK_TITANFALL)?;
+    assert!(
+        vpk == VPKRespawn::try_from(&mut file)?,
+        "VPK from bytes should match VPK from file"
+    );
+    Ok(())
+}
+
+#[test]
+fn truncated_tree() -> Result<()> {
+    // Respawn headers are 16 bytes
+    let data = std::fs::read(common::PAK_REVPK_SINGLE_FILE)?;
+    let tree_size = (data.len() - 16) as u64;
+
+    for len in 16..data.len() {
+        let tree = VPKTree::<VPKDirectoryEntryRespawn>::from(
+            &mut Cursor::new(&data[..len]),
+            16,
+            tree_size,
+        );
+
+        assert!(
+            tree.is_err_and(|x| matches!(x, vpk_plumber::pak::Error::BadData(_))),
+            "Tree truncated to {len} bytes should be invalid",
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn invalid_tree_size() -> Result<()> {
+    for tree_size in [0, u32::MAX] {
+        let mut data = std::fs::read(common::PAK_REVPK_SINGLE_FILE)?;
+        data[8..12].copy_from_slice(&tree_size.to_le_bytes());
+
+        let vpk = VPKRespawn::from_reader(&mut Cursor::new(&data));
+        assert!(
+            vpk.is_err_and(|x| matches!(x, vpk_plumber::pak::Error::InvalidTreeSize(_))),
+            "Tree size of {tree_size} should be invalid",
+        );
+    }
+
+    Ok(())
+}
+
 #[test]
 fn valid_vpk_large() -> Result<()> {
     let mut file = File::open(common::PAK_REVPK_TITANFALL)?;
@@ -95,3 +150,312 @@ fn vpk_v2_single_file() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn read_all_cams_without_file_parts() -> Result<()> {
+    let mut vpk = VPKRespawn::new();
+    vpk.tree.files.insert(
+        "sound/placeholder.wav".to_string(),
+        VPKDirectoryEntryRespawn::new(),
+    );
+
+    let dir = tempfile::tempdir()?;
+    vpk.read_all_cams(dir.path().to_str().unwrap(), "client_mp_common.bsp.pak000")?;
+
+    assert!(vpk.archive_cams.is_empty(), "No CAM files should be read");
+    Ok(())
+}
+
+#[test]
+fn default_cam_entry() {
+    let mut entry = VPKDirectoryEntryRespawn::new();
+
+    let cam_entry = VPKRespawnCamEntry::default(&entry);
+    assert_eq!(cam_entry.original_size, 0);
+    assert_eq!(cam_entry.sample_count, 0);
+    assert_eq!(cam_entry.vpk_content_offset, 0);
+
+    entry.file_parts.push(VPKFilePartEntryRespawn {
+        entry_offset: 128,
+        entry_length: 20,
+        entry_length_uncompressed: 20,
+        ..VPKFilePartEntryRespawn::new()
+    });
+
+    let cam_entry = VPKRespawnCamEntry::default(&entry);
+    assert_eq!(cam_entry.original_size, 20);
+    assert_eq!(
+        cam_entry.sample_count, 0,
+        "Entries smaller than a WAV header have no samples"
+    );
+    assert_eq!(cam_entry.vpk_content_offset, 128);
+
+    entry.file_parts[0].entry_length_uncompressed = 144;
+
+    let cam_entry = VPKRespawnCamEntry::default(&entry);
+    assert_eq!(cam_entry.sample_count, 54);
+
+    // Sizes that don't fit the CAM entry are clamped instead of wrapping around
+    entry.file_parts[0].entry_length = u64::from(u32::MAX) + 2;
+    entry.file_parts[0].entry_length_uncompressed = u64::from(u32::MAX) + 2;
+
+    let cam_entry = VPKRespawnCamEntry::default(&entry);
+    assert_eq!(cam_entry.original_size, u32::MAX);
+    assert_eq!(cam_entry.compressed_size, u32::MAX);
+}
+
+#[test]
+fn cam_entry_from_wav_bytes() -> Result<()> {
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&60u32.to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    // Chunks before the fmt chunk are skipped, including their padding
+    wav.extend_from_slice(b"LIST");
+    wav.extend_from_slice(&3u32.to_le_bytes());
+    wav.extend_from_slice(&[1, 2, 3, 0]);
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&[
+        16, 0, 0, 0, 1, 0, 2, 0, 0x80, 0xBB, 0, 0, 0, 0xEE, 2, 0, 4, 0, 16, 0,
+    ]);
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&[0x42; 16]);
+
+    let cam_entry = VPKRespawnCamEntry::from_wav_bytes(&wav, 1234)?;
+    assert_eq!(cam_entry.magic, RESPAWN_CAM_ENTRY_MAGIC);
+    assert_eq!(cam_entry.original_size, wav.len() as u32);
+    assert_eq!(cam_entry.compressed_size, 0);
+    assert_eq!(cam_entry.sample_rate, 48000);
+    assert_eq!(cam_entry.channels, 2);
+    assert_eq!(cam_entry.sample_count, 4);
+    assert_eq!(cam_entry.header_size, 44);
+    assert_eq!(cam_entry.vpk_content_offset, 1234);
+
+    // The generated header describes the same audio
+    let header = create_wav_header(&cam_entry)?;
+    assert_eq!(&header[22..28], &wav[34..40], "Channels and sample rate");
+    assert_eq!(&header[40..44], &16u32.to_le_bytes(), "Data size");
+
+    assert!(VPKRespawnCamEntry::from_wav_bytes(b"RIFF\0\0\0\0AVI ", 0).is_err());
+    assert!(VPKRespawnCamEntry::from_wav_bytes(&wav[..40], 0).is_err());
+
+    // 8-bit audio can't be described by the generated header
+    let mut wav_8bit = wav.clone();
+    wav_8bit[46] = 8;
+    assert!(VPKRespawnCamEntry::from_wav_bytes(&wav_8bit, 0).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn open_respawn() -> Result<()> {
+    let vpk = vpk_plumber::pak::open_respawn(std::path::Path::new(common::PAK_REVPK_TITANFALL))?;
+    assert_eq!(
+        vpk.tree.files.len(),
+        common::TITANFALL_TREE_COUNT,
+        "Tree size does not match"
+    );
+    Ok(())
+}
+
+#[test]
+fn archive_indices() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_TITANFALL)?;
+    let vpk = VPKRespawn::try_from(&mut file)?;
+
+    assert_eq!(
+        vpk.tree.archive_indices(),
+        (0..=4).collect(),
+        "Archives 000 to 004 should be referenced"
+    );
+    Ok(())
+}
+
+#[test]
+fn read_all_cams_uppercase_extension() -> Result<()> {
+    let archive_path = format!("{}titanfall", common::DIR_REVPK);
+
+    let mut entry = VPKDirectoryEntryRespawn::new();
+    entry.file_parts.push(VPKFilePartEntryRespawn {
+        entry_length: 44,
+        entry_length_uncompressed: 44,
+        ..Default::default()
+    });
+
+    let mut vpk = VPKRespawn::new();
+    vpk.tree
+        .files
+        .insert("sound/MP_COLONY.WAV".to_string(), entry);
+    vpk.read_all_cams(&archive_path, "client_mp_common.bsp.pak000")?;
+
+    assert!(
+        vpk.archive_cams.contains_key(&0),
+        "CAMs should be read for WAV files with an uppercase extension"
+    );
+    Ok(())
+}
+
+#[test]
+fn read_all_cams_shared_cache() -> Result<()> {
+    let cam_cache = Arc::new(CamCache::new());
+    let archive_path = format!("{}titanfall", common::DIR_REVPK);
+    let vpk_name = "client_mp_common.bsp.pak000".to_string();
+
+    // Two directory files with sounds in the same shared archive
+    let read_dir = |wav_path: &str| -> Result<VPKRespawn> {
+        let mut entry = VPKDirectoryEntryRespawn::new();
+        entry.file_parts.push(VPKFilePartEntryRespawn {
+            entry_length: 44,
+            entry_length_uncompressed: 44,
+            ..Default::default()
+        });
+
+        let mut vpk = VPKRespawn::new();
+        vpk.tree.files.insert(wav_path.to_string(), entry);
+        vpk.set_cam_cache(Some(Arc::clone(&cam_cache)));
+        vpk.read_all_cams(&archive_path, &vpk_name)?;
+        Ok(vpk)
+    };
+
+    let colony = read_dir("sound/mp_colony.wav")?;
+    let lagoon = read_dir("sound/mp_lagoon.wav")?;
+
+    assert_eq!(cam_cache.parse_count(), 1, "The CAM should be parsed once");
+    assert!(colony.archive_cams.contains_key(&0), "CAM should be read");
+    assert!(
+        Arc::ptr_eq(&colony.archive_cams[&0], &lagoon.archive_cams[&0]),
+        "The CAM should be shared instead of copied"
+    );
+
+    cam_cache.invalidate(Path::new(common::CAM_REVPK_TITANFALL));
+    let _ = read_dir("sound/mp_colony.wav")?;
+    assert_eq!(
+        cam_cache.parse_count(),
+        2,
+        "An invalidated CAM should be parsed again"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn cam_cache_concurrent() -> Result<()> {
+    let cam_cache = CamCache::new();
+    let copy_dir = tempfile::tempdir()?;
+    let copy = copy_dir.path().join("copy.vpk.cam");
+    std::fs::copy(common::CAM_REVPK_TITANFALL, &copy)?;
+
+    // Threads requesting the same CAM share one parse, different CAMs are parsed separately
+    let cams = std::thread::scope(|scope| {
+        let handles: Vec<_> = [Path::new(common::CAM_REVPK_TITANFALL), copy.as_path()]
+            .into_iter()
+            .cycle()
+            .take(8)
+            .map(|cam_path| scope.spawn(|| cam_cache.get(cam_path)))
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<std::result::Result<Vec<_>, _>>()
+    })?;
+
+    assert_eq!(
+        cam_cache.parse_count(),
+        2,
+        "Every CAM should be parsed once"
+    );
+    assert!(Arc::ptr_eq(&cams[0], &cams[2]));
+    assert!(Arc::ptr_eq(&cams[1], &cams[3]));
+    assert_eq!(cams[0], cams[1], "Both CAMs should have the same entries");
+
+    Ok(())
+}
+
+#[test]
+fn total_sizes() -> Result<()> {
+    let vpk = VPKRespawn::new();
+    assert_eq!(vpk.tree.total_uncompressed_size(), 0);
+    assert_eq!(vpk.tree.total_compressed_size(), 0);
+    assert!((vpk.tree.compression_ratio() - 1.0).abs() < f64::EPSILON);
+
+    let mut file = File::open(common::PAK_REVPK_SINGLE_FILE)?;
+    let vpk = VPKRespawn::try_from(&mut file)?;
+    assert_eq!(
+        vpk.tree.total_uncompressed_size(),
+        common::SINGLE_FILE_CONTENT.len() as u64
+    );
+    assert_eq!(
+        vpk.tree.total_compressed_size(),
+        std::fs::metadata(common::PAK_REVPK_ARCHIVE)?.len(),
+        "The only file fills the whole archive"
+    );
+
+    // The colony VPK stores no sounds or preload data, so the sizes of its files add up to the uncompressed size
+    let mut file = File::open(common::PAK_REVPK_TITANFALL)?;
+    let vpk = VPKRespawn::try_from(&mut file)?;
+    let uncompressed = vpk.tree.total_uncompressed_size();
+    let compressed = vpk.tree.total_compressed_size();
+    assert_eq!(
+        uncompressed,
+        vpk.file_paths()
+            .iter()
+            .filter_map(|path| vpk.file_size(path))
+            .sum::<u64>()
+    );
+    assert!(compressed < uncompressed, "The VPK should be compressed");
+    assert!((vpk.tree.compression_ratio() - uncompressed as f64 / compressed as f64).abs() < 1e-9);
+
+    Ok(())
+}
+
+#[test]
+fn tree_iter_large() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_TITANFALL)?;
+    let vpk = VPKRespawn::try_from(&mut file)?;
+
+    let entries = VPKTreeIter::<_, VPKDirectoryEntryRespawn>::new(
+        &mut file,
+        std::mem::size_of::<VPKHeaderRespawn>() as u64,
+        vpk.header.tree_size.into(),
+    )?
+    .collect::<vpk_plumber::pak::Result<HashMap<_, _>>>()?;
+
+    assert!(
+        entries == vpk.tree.files.into_iter().collect::<HashMap<_, _>>(),
+        "Iterated entries should match the parsed tree"
+    );
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn vpk_serde() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_TITANFALL)?;
+    let mut vpk = VPKRespawn::try_from(&mut file)?;
+    vpk.read_cam(0, &common::CAM_REVPK_TITANFALL.to_string())?;
+
+    let json = serde_json::to_string(&vpk)?;
+    let parsed: VPKRespawn = serde_json::from_str(&json)?;
+    assert!(parsed == vpk, "VPK does not round-trip");
+
+    Ok(())
+}
+
+#[test]
+fn undersized_tree_size() -> Result<()> {
+    // Older versions of the fixture claimed a tree size of 39 bytes, ending the tree in the middle of the entry of the file
+    let vpk = VPKRespawn::from_file(&mut File::open(common::PAK_REVPK_SHORT_TREE)?);
+    assert!(
+        matches!(
+            &vpk,
+            Err(vpk_plumber::pak::Error::BadData(msg))
+                if msg == "Entry for test/file.txt extends past the end of the tree"
+        ),
+        "Entries past the tree size in the header should be rejected: {:?}",
+        vpk.err()
+    );
+
+    Ok(())
+}
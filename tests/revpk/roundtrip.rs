@@ -1,6 +1,13 @@
-use std::{fs::File, path::Path};
+use std::{fs::File, io::Read, path::Path, sync::Arc};
 
-use vpk_plumber::pak::{PakWorker, PakWriter, revpk::VPKRespawn};
+use vpk_plumber::pak::{
+    Error, PakReader, PakWorker, PakWriter, WriteOptions,
+    revpk::{
+        LzhamError, LzhamLevel, LzhamParams, RESPAWN_MAX_PART_SIZE, RespawnWriteReport,
+        VPKFilePartEntryRespawn, VPKRespawn, VPKRespawnCam, VPKRespawnCamEntry,
+        decompress_with_adler32,
+    },
+};
 
 use crate::common::{self, Result};
 
@@ -14,6 +21,77 @@ fn large() -> Result<()> {
     roundtrip(common::PAK_REVPK_TITANFALL)
 }
 
+#[test]
+fn cam() -> Result<()> {
+    // Read a cam file
+    let mut file = File::open(common::CAM_REVPK_TITANFALL)?;
+    let cam = VPKRespawnCam::from_file(&mut file)?;
+
+    // Write it to a file
+    let out = tempfile::NamedTempFile::new()?;
+    cam.write_to_file(out.path().to_str().unwrap())?;
+
+    // The written file should be identical to the original
+    assert_eq!(
+        std::fs::read(common::CAM_REVPK_TITANFALL)?,
+        std::fs::read(&out)?,
+        "CAM files do not match"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn write_all_cams() -> Result<()> {
+    let mut vpk = VPKRespawn::from_file(&mut File::open(common::PAK_REVPK_TITANFALL)?)?;
+    vpk.read_cam(0, &common::CAM_REVPK_TITANFALL.to_string())?;
+    vpk.read_cam(3, &common::CAM_REVPK_TITANFALL.to_string())?;
+
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().join("cams");
+    vpk.write_all_cams(
+        archive_path.to_str().unwrap(),
+        "client_mp_common.bsp.pak000",
+    )?;
+
+    for archive_index in [0, 3] {
+        let cam_path = archive_path.join(format!(
+            "client_mp_common.bsp.pak000_{archive_index:0>3}.vpk.cam"
+        ));
+        let cam = VPKRespawnCam::from_file(&mut File::open(&cam_path)?)?;
+        assert_eq!(
+            cam.entries, vpk.archive_cams[&archive_index].entries,
+            "Entries of the CAM for archive {archive_index} should match"
+        );
+    }
+    assert_eq!(
+        std::fs::read_dir(&archive_path)?.count(),
+        2,
+        "A CAM file should be written for every parsed CAM"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn cam_invalid_magic() -> Result<()> {
+    let mut entry = VPKRespawnCamEntry::new();
+    entry.magic = 0;
+
+    let cam = VPKRespawnCam {
+        entries: [(entry.vpk_content_offset, entry)].into(),
+    };
+
+    let mut out = tempfile::tempfile()?;
+    assert!(
+        cam.write(&mut out)
+            .is_err_and(|x| matches!(x, vpk_plumber::pak::Error::BadData(_))),
+        "CAM entries with an invalid magic should not be written"
+    );
+
+    Ok(())
+}
+
 fn roundtrip<P>(path: P) -> Result<()>
 where
     P: AsRef<Path>,
@@ -70,3 +148,635 @@ where
 
     Ok(())
 }
+
+#[test]
+fn archive() -> Result<()> {
+    let compressible = vec![b'a'; RESPAWN_MAX_PART_SIZE * 2 + 512];
+    let files: [(&str, &[u8]); 3] = [
+        ("test/compressible.txt", &compressible),
+        (
+            common::SINGLE_FILE_NAME,
+            common::SINGLE_FILE_CONTENT.as_bytes(),
+        ),
+        ("test/empty.txt", &[]),
+    ];
+
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    let mut vpk = VPKRespawn::new();
+    vpk.write_archive(archive_path, common::SINGLE_FILE_ARCHIVE, 0, &files)?;
+
+    let parts = &vpk.tree.files["test/compressible.txt"].file_parts;
+    assert_eq!(parts.len(), 3, "File should be split into parts");
+    assert!(
+        parts
+            .iter()
+            .all(|part| part.entry_length < part.entry_length_uncompressed),
+        "Compressible parts should be stored compressed",
+    );
+
+    let part = &vpk.tree.files[common::SINGLE_FILE_NAME].file_parts[0];
+    assert_eq!(
+        part.entry_length, part.entry_length_uncompressed,
+        "Incompressible parts should be stored uncompressed",
+    );
+
+    for (file_path, data) in files {
+        let result = vpk
+            .read_file(archive_path, common::SINGLE_FILE_ARCHIVE, file_path)
+            .expect("File should be readable");
+
+        assert_eq!(result, data, "File contents should match");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn archive_normalizes_paths() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    let mut vpk = VPKRespawn::new();
+    vpk.write_archive(
+        archive_path,
+        common::SINGLE_FILE_ARCHIVE,
+        0,
+        &[(
+            "\\Scripts\\VScripts//test.nut",
+            common::SINGLE_FILE_CONTENT.as_bytes(),
+        )],
+    )?;
+
+    assert!(
+        vpk.tree.files.contains_key("Scripts/VScripts/test.nut"),
+        "The entry should be stored under the normalized path"
+    );
+    assert_eq!(vpk.tree.files.len(), 1);
+
+    let result = vpk.read_file(
+        archive_path,
+        common::SINGLE_FILE_ARCHIVE,
+        "Scripts/VScripts/test.nut",
+    );
+    assert_eq!(
+        result.as_deref(),
+        Some(common::SINGLE_FILE_CONTENT.as_bytes()),
+        "File should be readable by its normalized path"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn archive_custom_naming() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+    let archives_dir = out_dir.path().join("archives");
+
+    let mut vpk = VPKRespawn::new();
+    let naming_dir = archives_dir.clone();
+    vpk.set_archive_naming(Some(Arc::new(move |archive_index: u16| {
+        naming_dir.join(format!("archive_{archive_index}.bin"))
+    })));
+    vpk.write_archive(
+        archive_path,
+        common::SINGLE_FILE_ARCHIVE,
+        2,
+        &[(
+            common::SINGLE_FILE_NAME,
+            common::SINGLE_FILE_CONTENT.as_bytes(),
+        )],
+    )?;
+
+    assert!(
+        archives_dir.join("archive_2.bin").is_file(),
+        "Archive should be written at the path of the naming"
+    );
+
+    let result = vpk.read_file(
+        archive_path,
+        common::SINGLE_FILE_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+    );
+    assert_eq!(
+        result.as_deref(),
+        Some(common::SINGLE_FILE_CONTENT.as_bytes()),
+        "File should be readable through the same naming"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn archive_compression_levels() -> Result<()> {
+    let compressible = vec![b'a'; RESPAWN_MAX_PART_SIZE + 512];
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    for level in LzhamLevel::ALL {
+        let params = LzhamParams {
+            level,
+            ..Default::default()
+        };
+
+        let mut vpk = VPKRespawn::new();
+        vpk.write_archive_with(
+            archive_path,
+            common::SINGLE_FILE_ARCHIVE,
+            0,
+            &[("test/compressible.txt", &compressible)],
+            &params,
+        )?;
+
+        assert!(
+            vpk.tree.files["test/compressible.txt"]
+                .file_parts
+                .iter()
+                .all(|part| part.entry_length < part.entry_length_uncompressed),
+            "Parts should be stored compressed at level {level:?}",
+        );
+
+        let result = vpk.read_file(
+            archive_path,
+            common::SINGLE_FILE_ARCHIVE,
+            "test/compressible.txt",
+        );
+        assert_eq!(
+            result.as_deref(),
+            Some(compressible.as_slice()),
+            "File contents should match at level {level:?}"
+        );
+    }
+
+    let mut vpk = VPKRespawn::new();
+    let result = vpk.write_archive_with(
+        archive_path,
+        common::SINGLE_FILE_ARCHIVE,
+        0,
+        &[],
+        &LzhamParams {
+            dict_size_log2: 40,
+            ..Default::default()
+        },
+    );
+    assert!(
+        matches!(result, Err(Error::BadData(_))),
+        "Unsupported dictionary sizes should be rejected"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn archive_part_adler32() -> Result<()> {
+    let compressible = vec![b'a'; 4096];
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    let mut vpk = VPKRespawn::new();
+    vpk.write_archive(
+        archive_path,
+        common::SINGLE_FILE_ARCHIVE,
+        0,
+        &[("test/compressible.txt", &compressible)],
+    )?;
+
+    let part = &vpk.tree.files["test/compressible.txt"].file_parts[0];
+    let archive = std::fs::read_dir(out_dir.path())?
+        .next()
+        .expect("Archive should be written")?;
+    let data = std::fs::read(archive.path())?;
+    let start = usize::try_from(part.entry_offset)?;
+    let compressed = &data[start..start + usize::try_from(part.entry_length)?];
+
+    let (decompressed, adler32) =
+        decompress_with_adler32(compressed, usize::try_from(part.entry_length_uncompressed)?)?;
+    assert_eq!(decompressed, compressible);
+
+    // Adler-32 as defined in RFC 1950
+    let (a, b) = compressible.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + u32::from(byte)) % 65521;
+        (a, (b + a) % 65521)
+    });
+    assert_eq!(adler32, (b << 16) | a, "Checksum should match the data");
+
+    Ok(())
+}
+
+#[test]
+fn decompress_garbage() {
+    let result = decompress_with_adler32(b"definitely not lzham data", 1024);
+    assert!(
+        matches!(result, Err(e) if LzhamError::from_decompress_status(e.status()) == e),
+        "Garbage should fail to decompress instead of returning a buffer: {result:?}"
+    );
+}
+
+#[test]
+fn decompress_too_small() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    let mut vpk = VPKRespawn::new();
+    vpk.write_archive(
+        archive_path,
+        common::SINGLE_FILE_ARCHIVE,
+        0,
+        &[("test/compressible.txt", &vec![b'a'; 4096])],
+    )?;
+
+    let part = &vpk.tree.files["test/compressible.txt"].file_parts[0];
+    let data = std::fs::read(out_dir.path().join("single_file_000.vpk"))?;
+    let start = usize::try_from(part.entry_offset)?;
+    let compressed = &data[start..start + usize::try_from(part.entry_length)?];
+
+    assert_eq!(
+        decompress_with_adler32(compressed, 1024),
+        Err(LzhamError::DestBufferTooSmall),
+        "Data larger than the buffer should fail to decompress"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn from_scratch() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    let data = common::SINGLE_FILE_CONTENT.as_bytes();
+    std::fs::write(out_dir.path().join("scratch_000.vpk"), data)?;
+
+    let part = VPKFilePartEntryRespawn {
+        entry_length: data.len() as u64,
+        entry_length_uncompressed: data.len() as u64,
+        ..VPKFilePartEntryRespawn::new()
+    };
+
+    let mut vpk = VPKRespawn::new();
+    vpk.upsert_entry(
+        common::SINGLE_FILE_NAME,
+        data,
+        std::slice::from_ref(&part),
+        None,
+    )?;
+    vpk.upsert_entry("test/removed.txt", data, &[part], Some(vec![1]))?;
+
+    assert!(
+        vpk.remove_entry("test/removed.txt").is_some(),
+        "Removed entry should be returned"
+    );
+    assert!(
+        vpk.tree.preload.is_empty(),
+        "Preload data should be removed with the entry"
+    );
+
+    vpk.write_dir(out_dir.path().join("scratch_dir.vpk").to_str().unwrap())?;
+
+    let mut file = File::open(out_dir.path().join("scratch_dir.vpk"))?;
+    let vpk_result = VPKRespawn::from_file(&mut file)?;
+
+    assert_eq!(vpk_result.tree.files.len(), 1, "VPK sizes don't match");
+    assert_eq!(
+        vpk_result.read_file(archive_path, "scratch", common::SINGLE_FILE_NAME),
+        Some(data.to_vec()),
+        "File contents should match"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn modified_tree_size() -> Result<()> {
+    let mut file = File::open(common::PAK_REVPK_SINGLE_FILE)?;
+    let source = VPKRespawn::from_file(&mut file)?;
+    let entry = &source.tree.files[common::SINGLE_FILE_NAME];
+
+    let mut vpk = VPKRespawn::new();
+    vpk.tree
+        .files
+        .insert("materials/first.vmt".to_string(), entry.clone());
+    vpk.tree
+        .files
+        .insert("materials/models/second.vmt".to_string(), entry.clone());
+
+    let out = tempfile::NamedTempFile::new()?;
+    vpk.write_dir(out.path().to_str().unwrap())?;
+
+    let vpk_result = VPKRespawn::from_file(&mut File::open(&out)?)?;
+    assert_ne!(
+        vpk_result.header.tree_size, 0,
+        "Tree size should be written"
+    );
+
+    vpk.rebuild_header_tree_size()?;
+    assert_eq!(
+        vpk.header.tree_size, vpk_result.header.tree_size,
+        "Rebuilt tree size should match the written one"
+    );
+    assert!(
+        vpk_result.tree.files == vpk.tree.files,
+        "VPK file data doesn't match"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn read_file_streaming() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    let first: &[u8] = b"first part, ";
+    let second: &[u8] = &[b'x'; 4096];
+    let third: &[u8] = b"third part";
+
+    std::fs::write(
+        out_dir.path().join("scratch_000.vpk"),
+        [first, third].concat(),
+    )?;
+
+    // Let the archive writer compress the second part
+    let mut vpk = VPKRespawn::new();
+    vpk.write_archive(archive_path, "scratch", 1, &[("compressed", second)])?;
+    let compressed_part = vpk.remove_entry("compressed").unwrap().file_parts.remove(0);
+    assert!(compressed_part.entry_length < compressed_part.entry_length_uncompressed);
+
+    let parts = [
+        VPKFilePartEntryRespawn {
+            entry_length: first.len() as u64,
+            entry_length_uncompressed: first.len() as u64,
+            ..VPKFilePartEntryRespawn::new()
+        },
+        compressed_part,
+        VPKFilePartEntryRespawn {
+            entry_offset: first.len() as u64,
+            entry_length: third.len() as u64,
+            entry_length_uncompressed: third.len() as u64,
+            ..VPKFilePartEntryRespawn::new()
+        },
+    ];
+
+    vpk.upsert_entry(
+        "scripts/parts.txt",
+        &[first, second, third].concat(),
+        &parts,
+        Some(b"preload, ".to_vec()),
+    )?;
+
+    let mut result = Vec::new();
+    vpk.read_file_streaming(archive_path, "scratch", "scripts/parts.txt")?
+        .read_to_end(&mut result)?;
+
+    assert_eq!(
+        result,
+        [b"preload, ", first, second, third].concat(),
+        "Parts should be stitched together"
+    );
+    assert_eq!(
+        vpk.read_file(archive_path, "scratch", "scripts/parts.txt"),
+        Some(result)
+    );
+
+    vpk.tree.files.get_mut("scripts/parts.txt").unwrap().crc ^= 1;
+    let result = vpk
+        .read_file_streaming(archive_path, "scratch", "scripts/parts.txt")?
+        .read_to_end(&mut Vec::new());
+    assert!(
+        result.is_err_and(|e| e.kind() == std::io::ErrorKind::InvalidData),
+        "A CRC mismatch should fail the final read"
+    );
+
+    assert!(
+        vpk.read_file_streaming(archive_path, "missing", "scripts/parts.txt")
+            .is_err_and(|e| matches!(e, vpk_plumber::pak::Error::ArchiveOpen { .. })),
+        "Missing archives should be reported when opening the file"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn write_compressed_archive() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    let files = vec![
+        ("scripts/compressible.txt".to_string(), vec![b'x'; 4096]),
+        (
+            "scripts/incompressible.bin".to_string(),
+            (0..=255).collect(),
+        ),
+        ("scripts/empty.txt".to_string(), Vec::new()),
+        (
+            "scripts/large.bin".to_string(),
+            (0..2 * RESPAWN_MAX_PART_SIZE + 10)
+                .map(|i| (i % 251) as u8)
+                .collect(),
+        ),
+    ];
+
+    let parts = VPKRespawn::write_compressed_archive(
+        &files,
+        out_dir.path().join("scratch_002.vpk").to_str().unwrap(),
+    )?;
+    assert_eq!(parts.len(), files.len(), "Every file should have parts");
+
+    assert!(
+        parts[0][0].entry_length < parts[0][0].entry_length_uncompressed,
+        "Compressible data should be compressed"
+    );
+    assert_eq!(
+        parts[1][0].entry_length, parts[1][0].entry_length_uncompressed,
+        "Incompressible data should be stored uncompressed"
+    );
+    assert!(
+        matches!(
+            parts[2].as_slice(),
+            [part] if part.entry_length == 0 && part.entry_length_uncompressed == 0
+        ),
+        "Empty files should have a single empty part"
+    );
+    assert_eq!(
+        parts[3]
+            .iter()
+            .map(|part| part.entry_length_uncompressed)
+            .collect::<Vec<_>>(),
+        [
+            RESPAWN_MAX_PART_SIZE as u64,
+            RESPAWN_MAX_PART_SIZE as u64,
+            10
+        ],
+        "Large files should be split into parts"
+    );
+
+    let mut offset = 0;
+    for (file_parts, (_, data)) in parts.iter().zip(&files) {
+        let mut uncompressed = 0;
+        for part in file_parts {
+            assert_eq!(part.entry_offset, offset, "Parts should be sequential");
+            offset += part.entry_length;
+            uncompressed += part.entry_length_uncompressed;
+        }
+        assert_eq!(uncompressed, data.len() as u64);
+    }
+    assert_eq!(
+        std::fs::metadata(out_dir.path().join("scratch_002.vpk"))?.len(),
+        offset,
+        "Archive size should match the parts"
+    );
+
+    let mut vpk = VPKRespawn::new();
+    for (file_parts, (file_path, data)) in parts.into_iter().zip(&files) {
+        let file_parts: Vec<_> = file_parts
+            .into_iter()
+            .map(|part| VPKFilePartEntryRespawn {
+                archive_index: 2,
+                ..part
+            })
+            .collect();
+        vpk.upsert_entry(file_path, data, &file_parts, None)?;
+    }
+
+    for (file_path, data) in &files {
+        assert_eq!(
+            vpk.read_file(archive_path, "scratch", file_path).as_ref(),
+            Some(data),
+            "Contents of {file_path} should match"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn write_dir_drops_empty_parts() -> Result<()> {
+    let part = |entry_offset: u64, entry_length: u64| VPKFilePartEntryRespawn {
+        entry_offset,
+        entry_length,
+        entry_length_uncompressed: entry_length,
+        ..VPKFilePartEntryRespawn::new()
+    };
+
+    let mut vpk = VPKRespawn::new();
+    vpk.upsert_entry(
+        "scripts/edited.txt",
+        b"first second",
+        &[part(0, 6), part(6, 0), part(6, 6)],
+        None,
+    )?;
+    vpk.upsert_entry("scripts/empty.txt", b"", &[part(12, 0)], None)?;
+
+    let out = tempfile::NamedTempFile::new()?;
+    let out_path = out.path().to_str().unwrap();
+
+    assert!(
+        matches!(
+            vpk.write_dir_with(out_path, &WriteOptions { strict: true }),
+            Err(Error::BadData(_))
+        ),
+        "Strict writes should reject empty parts"
+    );
+
+    let report = vpk.write_dir_with(out_path, &WriteOptions::default())?;
+    assert_eq!(
+        report,
+        RespawnWriteReport {
+            dropped_empty_parts: vec!["scripts/edited.txt".to_string()],
+        },
+        "Dropped parts should be reported"
+    );
+
+    let vpk_result = VPKRespawn::from_file(&mut File::open(&out)?)?;
+    assert_eq!(
+        vpk_result.tree.files["scripts/edited.txt"].file_parts,
+        vec![part(0, 6), part(6, 6)],
+        "Empty part should be dropped without moving the other parts"
+    );
+    assert_eq!(
+        vpk_result.tree.files["scripts/empty.txt"].file_parts,
+        vec![part(12, 0)],
+        "Empty files should keep their part"
+    );
+    assert_eq!(
+        vpk_result.write_dir_with(out_path, &WriteOptions { strict: true })?,
+        RespawnWriteReport::default(),
+        "Rewriting should not drop any more parts"
+    );
+
+    Ok(())
+}
+
+#[ignore = "slow, reads and compresses more than 4 GiB of data"]
+#[test]
+fn write_archive_from_paths_large() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    // Sparse, so it doesn't take up 4 GiB on disk
+    let size = u64::from(u32::MAX) + 2;
+    let source_path = out_dir.path().join("large.bin");
+    File::create(&source_path)?.set_len(size)?;
+
+    let mut vpk = VPKRespawn::new();
+    vpk.write_archive_from_paths(
+        archive_path,
+        "client_mp_large.bsp.pak000",
+        0,
+        &[("maps/large.bin", &source_path)],
+    )?;
+
+    let entry = vpk.tree.get("maps/large.bin").unwrap();
+    assert_eq!(
+        entry
+            .file_parts
+            .iter()
+            .map(|part| part.entry_length_uncompressed)
+            .sum::<u64>(),
+        size,
+        "Parts should add up to the size of the file"
+    );
+    assert!(
+        entry.file_parts.len() > 4096,
+        "File should be split into parts"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn write_archive_from_paths() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    let data: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let source_path = out_dir.path().join("source.bin");
+    std::fs::write(&source_path, &data)?;
+
+    let mut vpk = VPKRespawn::new();
+    vpk.write_archive_from_paths(
+        archive_path,
+        "client_mp_scratch.bsp.pak000",
+        0,
+        &[("maps/source.bin", &source_path)],
+    )?;
+
+    assert_eq!(
+        vpk.tree.get("maps/source.bin").unwrap().file_parts.len(),
+        3,
+        "File should be split into parts"
+    );
+    assert_eq!(
+        vpk.read_file(
+            archive_path,
+            "client_mp_scratch.bsp.pak000",
+            "maps/source.bin"
+        ),
+        Some(data),
+        "File should be read back unchanged"
+    );
+
+    Ok(())
+}
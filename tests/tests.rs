@@ -7,3 +7,12 @@ mod v2;
 
 #[cfg(feature = "detect")]
 mod detect;
+
+#[cfg(feature = "compat")]
+mod compat;
+
+#[cfg(feature = "tokio")]
+mod async_reader;
+
+#[cfg(feature = "detect")]
+mod examples;
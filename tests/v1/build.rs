@@ -0,0 +1,295 @@
+use std::fs::File;
+
+use vpk_plumber::pak::{
+    Dedup, Error, PackOrdering, PakBuilder, PakReader, PakWorker,
+    v1::{VPK_DIR_ARCHIVE_INDEX, VPKVersion1},
+};
+
+use crate::common::Result;
+
+const FILES: [(&str, &[u8]); 5] = [
+    ("models/props/crate.mdl", b"model"),
+    ("materials/props/crate.vtf", b"texture"),
+    ("materials/props/crate.vmt", b"material"),
+    ("materials/props/barrel.VMT", b"material 2"),
+    ("sound\\ambient\\wind.wav", b"sound"),
+];
+
+fn builder() -> PakBuilder {
+    let mut builder = PakBuilder::new();
+    for (file_path, data) in FILES {
+        builder.add_file(file_path, data.to_vec());
+    }
+    builder
+}
+
+#[test]
+fn build() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let out_path = out_dir.path().to_str().unwrap();
+
+    let report = builder().build(out_path, "pak01")?;
+    assert_eq!(report.archive_count, 1, "One archive should be written");
+    assert_eq!(report.bytes_written, 35, "Bytes written should match");
+
+    let vpk = VPKVersion1::from_file(&mut File::open(out_dir.path().join("pak01_dir.vpk"))?)?;
+    assert_eq!(vpk.tree.files.len(), 5, "Tree size does not match");
+    assert_eq!(
+        vpk.read_file(out_path, "pak01", "sound/ambient/wind.wav"),
+        Some(b"sound".to_vec()),
+        "Content does not match expected"
+    );
+    assert_eq!(
+        vpk.read_file(out_path, "pak01", "materials/props/crate.vmt"),
+        Some(b"material".to_vec()),
+        "Content does not match expected"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn build_extension_priority() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let out_path = out_dir.path().to_str().unwrap();
+
+    let report = builder()
+        .ordering(PackOrdering::ByExtensionPriority(vec![
+            "vmt".to_string(),
+            "vtf".to_string(),
+        ]))
+        .build(out_path, "pak01")?;
+
+    let expected = [
+        "materials/props/barrel.VMT",
+        "materials/props/crate.vmt",
+        "materials/props/crate.vtf",
+        "models/props/crate.mdl",
+        "sound/ambient/wind.wav",
+    ];
+    assert_eq!(report.order, expected, "Build order does not match");
+
+    let vpk = VPKVersion1::from_file(&mut File::open(out_dir.path().join("pak01_dir.vpk"))?)?;
+    let offsets: Vec<u32> = expected
+        .iter()
+        .map(|file_path| vpk.tree.files[*file_path].entry_offset)
+        .collect();
+
+    assert!(
+        offsets.is_sorted(),
+        "Entry offsets should follow the requested ordering"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn build_custom_ordering() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+
+    let report = builder()
+        .ordering(PackOrdering::Custom(Box::new(|a, b| b.cmp(a))))
+        .build(out_dir.path().to_str().unwrap(), "pak01")?;
+
+    assert_eq!(
+        report.order.first().map(String::as_str),
+        Some("sound/ambient/wind.wav"),
+        "Build order does not match"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn build_max_archive_size() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let out_path = out_dir.path().to_str().unwrap();
+
+    let report = builder().max_archive_size(16).build(out_path, "pak01")?;
+    assert!(
+        report.archive_count > 1,
+        "Multiple archives should be written"
+    );
+
+    let vpk = VPKVersion1::from_file(&mut File::open(out_dir.path().join("pak01_dir.vpk"))?)?;
+    for file_path in &report.order {
+        assert!(
+            vpk.read_file(out_path, "pak01", file_path).is_some(),
+            "File should be readable"
+        );
+    }
+    assert!(
+        out_dir
+            .path()
+            .join(format!("pak01_{:0>3}.vpk", report.archive_count - 1))
+            .exists(),
+        "Last archive should exist"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn build_archive_indices() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let out_path = out_dir.path().to_str().unwrap();
+
+    let report = builder().max_archive_size(16).build(out_path, "pak01")?;
+    let mut vpk = VPKVersion1::from_file(&mut File::open(out_dir.path().join("pak01_dir.vpk"))?)?;
+    assert_eq!(
+        vpk.tree.archive_indices(),
+        (0..report.archive_count).collect(),
+        "Every written archive should be referenced"
+    );
+
+    vpk.tree
+        .insert_file("preload.txt", b"preload", 3, 0, 16, false)?;
+    vpk.tree.insert_file(
+        "embedded.txt",
+        b"embedded",
+        VPK_DIR_ARCHIVE_INDEX,
+        0,
+        0,
+        false,
+    )?;
+    assert_eq!(
+        vpk.tree.archive_indices(),
+        (0..report.archive_count).collect(),
+        "Preload-only and embedded entries should not reference an archive"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn build_from_path() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let out_path = out_dir.path().to_str().unwrap();
+
+    let source_path = out_dir.path().join("source.bin");
+    std::fs::write(&source_path, b"from disk")?;
+
+    let mut builder = builder();
+    builder.add_file_from_path("data/source.bin", &source_path);
+    builder.build(out_path, "pak01")?;
+
+    let vpk = VPKVersion1::from_file(&mut File::open(out_dir.path().join("pak01_dir.vpk"))?)?;
+    assert_eq!(
+        vpk.read_file(out_path, "pak01", "data/source.bin"),
+        Some(b"from disk".to_vec()),
+        "File should be read from its source path"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn build_dedup() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let out_path = out_dir.path().to_str().unwrap();
+
+    let source_path = out_dir.path().join("source.vmt");
+    std::fs::write(&source_path, b"shared material")?;
+
+    let mut builder = PakBuilder::new().dedup(Dedup::On);
+    builder.add_file("materials/a.vmt", b"shared material".to_vec());
+    builder.add_file("materials/b.vmt", b"shared material".to_vec());
+    builder.add_file_from_path("materials/c.vmt", &source_path);
+    // Same size, different contents
+    builder.add_file("materials/d.vmt", b"unique material".to_vec());
+
+    let report = builder.build(out_path, "pak01")?;
+    assert_eq!(
+        report.bytes_written, 30,
+        "Shared contents should be written once"
+    );
+    assert_eq!(report.bytes_deduplicated, 30);
+    assert_eq!(report.order.len(), 4);
+    assert_eq!(
+        std::fs::metadata(out_dir.path().join("pak01_000.vpk"))?.len(),
+        30,
+        "Archive should only hold one copy of the shared contents"
+    );
+
+    let vpk = VPKVersion1::from_file(&mut File::open(out_dir.path().join("pak01_dir.vpk"))?)?;
+    for file_path in ["materials/a.vmt", "materials/b.vmt", "materials/c.vmt"] {
+        assert_eq!(
+            vpk.read_file(out_path, "pak01", file_path),
+            Some(b"shared material".to_vec()),
+            "Content of {file_path} does not match expected"
+        );
+    }
+    assert_eq!(
+        vpk.read_file(out_path, "pak01", "materials/d.vmt"),
+        Some(b"unique material".to_vec())
+    );
+
+    // Every copy is written without deduplication
+    let report = builder.dedup(Dedup::Off).build(out_path, "pak02")?;
+    assert_eq!(report.bytes_written, 60);
+    assert_eq!(report.bytes_deduplicated, 0);
+
+    Ok(())
+}
+
+#[test]
+fn build_entry_too_large() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let out_path = out_dir.path().to_str().unwrap();
+
+    // Sparse, so it doesn't take up 4 GiB on disk
+    let size = u64::from(u32::MAX) + 2;
+    let source_path = out_dir.path().join("large.bin");
+    File::create(&source_path)?.set_len(size)?;
+
+    let mut builder = builder();
+    builder.add_file_from_path("data/large.bin", &source_path);
+
+    let result = builder.build(out_path, "pak01");
+    assert!(
+        matches!(
+            &result,
+            Err(Error::EntryTooLarge { path, size: found }) if path == "data/large.bin" && *found == size
+        ),
+        "Files of 4 GiB and more should be rejected"
+    );
+    assert!(
+        !out_dir.path().join("pak01_000.vpk").exists(),
+        "Nothing should be written"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn build_deterministic() -> Result<()> {
+    let out_a = tempfile::tempdir()?;
+    let out_b = tempfile::tempdir()?;
+
+    builder()
+        .max_archive_size(16)
+        .build(out_a.path().to_str().unwrap(), "pak01")?;
+
+    // The order files are added in should not matter
+    let mut reversed = PakBuilder::new().max_archive_size(16);
+    for (file_path, data) in FILES.iter().rev() {
+        reversed.add_file(file_path, data.to_vec());
+    }
+    reversed.build(out_b.path().to_str().unwrap(), "pak01")?;
+
+    let mut names: Vec<_> = std::fs::read_dir(out_a.path())?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect::<std::io::Result<_>>()?;
+    names.sort();
+
+    assert!(names.len() > 2, "Multiple archives should be written");
+    for name in names {
+        assert_eq!(
+            std::fs::read(out_a.path().join(&name))?,
+            std::fs::read(out_b.path().join(&name))?,
+            "{name:?} should be byte-identical"
+        );
+    }
+
+    Ok(())
+}
@@ -1,6 +1,9 @@
-use std::fs::File;
+use std::{fs::File, path::Path};
 
-use vpk_plumber::pak::{PakReader, v1::VPKVersion1};
+use vpk_plumber::pak::{
+    ArchiveConsistency, CaseSensitivity, ExtensionFilter, ExtractOptions, Manifest, ManifestEntry,
+    PakReader, PakWorker, VPKDirectoryEntry, ValidationIssueKind, v1::VPKVersion1,
+};
 
 use crate::common::{self, Result};
 
@@ -41,6 +44,174 @@ fn vpk_single_file() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn vpk_single_file_skip_crc() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let mut vpk = VPKVersion1::try_from(&mut file)?;
+
+    vpk.tree
+        .files
+        .get_mut(common::SINGLE_FILE_NAME)
+        .unwrap()
+        .crc ^= 1;
+
+    let result = vpk.read_file(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+    );
+    assert!(result.is_none(), "Mismatching CRC should fail the read");
+
+    let options = ExtractOptions {
+        verify_crc: false,
+        ..Default::default()
+    };
+    let result = vpk
+        .read_file_with(
+            common::DIR_V1,
+            common::SINGLE_FILE_ARCHIVE,
+            common::SINGLE_FILE_NAME,
+            &options,
+        )
+        .unwrap();
+
+    assert_eq!(
+        result,
+        common::SINGLE_FILE_CONTENT.as_bytes(),
+        "Content does not match expected"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_verify_all_crcs() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let mut vpk = VPKVersion1::try_from(&mut file)?;
+
+    let results = vpk.verify_all_crcs(common::DIR_V1, common::SINGLE_FILE_ARCHIVE);
+    assert!(
+        matches!(results.as_slice(), [(file_path, Ok(()))] if file_path == common::SINGLE_FILE_NAME),
+        "All CRCs should match"
+    );
+
+    vpk.tree
+        .files
+        .get_mut(common::SINGLE_FILE_NAME)
+        .unwrap()
+        .crc ^= 1;
+
+    let results = vpk.verify_all_crcs(common::DIR_V1, common::SINGLE_FILE_ARCHIVE);
+    assert!(
+        matches!(
+            results.as_slice(),
+            [(file_path, Err(vpk_plumber::pak::Error::CrcMismatch { .. }))] if file_path == common::SINGLE_FILE_NAME
+        ),
+        "Mismatching CRC should be reported"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_verify_filtered_crcs() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let results = vpk.verify_filtered_crcs(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        &ExtensionFilter::new(&["TXT"]),
+    );
+    assert!(
+        matches!(results.as_slice(), [(file_path, Ok(()))] if file_path == common::SINGLE_FILE_NAME),
+        "The matching file should be verified"
+    );
+
+    let results = vpk.verify_filtered_crcs(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        &ExtensionFilter::new(&["vmt"]),
+    );
+    assert!(results.is_empty(), "No file should be verified");
+
+    Ok(())
+}
+
+#[test]
+fn vpk_archive_consistency() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let dir = tempfile::tempdir()?;
+    let archive_path = dir.path().to_str().unwrap();
+    let archive = dir.path().join("single_file_000.vpk");
+    std::fs::copy(common::PAK_V1_ARCHIVE, &archive)?;
+
+    let results = vpk.check_archive_consistency(archive_path, common::SINGLE_FILE_ARCHIVE, 4);
+    assert!(
+        matches!(results.as_slice(), [ArchiveConsistency::Consistent(0)]),
+        "Archive should be consistent"
+    );
+
+    // Simulate an archive left over from a different version of the directory file
+    let mut data = std::fs::read(&archive)?;
+    for byte in &mut data {
+        *byte = byte.wrapping_add(1);
+    }
+    std::fs::write(&archive, data)?;
+
+    let results = vpk.check_archive_consistency(archive_path, common::SINGLE_FILE_ARCHIVE, 4);
+    assert!(
+        matches!(results.as_slice(), [ArchiveConsistency::ArchiveStale(0)]),
+        "Modified archive should be stale"
+    );
+
+    std::fs::remove_file(&archive)?;
+
+    let results = vpk.check_archive_consistency(archive_path, common::SINGLE_FILE_ARCHIVE, 4);
+    assert!(
+        matches!(results.as_slice(), [ArchiveConsistency::Unreadable(0, _)]),
+        "Missing archive should be unreadable"
+    );
+
+    // Reading fails without the data being wrong, which should not be reported as a stale archive
+    std::fs::create_dir(&archive)?;
+
+    let results = vpk.check_archive_consistency(archive_path, common::SINGLE_FILE_ARCHIVE, 4);
+    assert!(
+        matches!(
+            results.as_slice(),
+            [ArchiveConsistency::Unreadable(0, e)] if !matches!(e, vpk_plumber::pak::Error::ArchiveOpen { .. })
+        ),
+        "Archive that can't be read should be unreadable with the read error, got {results:?}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_single_file_eof() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE_EOF)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let result = vpk
+        .read_file(
+            common::DIR_V1,
+            common::SINGLE_FILE_EOF_ARCHIVE,
+            common::SINGLE_FILE_NAME,
+        )
+        .unwrap();
+
+    assert_eq!(
+        result,
+        common::SINGLE_FILE_CONTENT.as_bytes(),
+        "Content does not match expected"
+    );
+
+    Ok(())
+}
+
 #[test]
 fn vpk_large() -> Result<()> {
     let mut file = File::open(common::PAK_V1_PORTAL2)?;
@@ -54,3 +225,476 @@ fn vpk_large() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn vpk_filter() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_PORTAL2)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    assert_eq!(
+        vpk.tree.filter_by_extension("vmt").count(),
+        common::PORTAL2_VMT_COUNT
+    );
+    assert_eq!(
+        vpk.tree.filter_by_extension("VMT").count(),
+        common::PORTAL2_VMT_COUNT
+    );
+    assert_eq!(
+        vpk.tree
+            .filter_by_directory("materials/models/props")
+            .count(),
+        262
+    );
+    assert_eq!(
+        vpk.tree
+            .filter_by_directory("materials/models/props/")
+            .count(),
+        262
+    );
+    assert_eq!(
+        vpk.tree.filter_by_path_prefix("materials/models/").count(),
+        2307
+    );
+    Ok(())
+}
+
+#[test]
+fn vpk_glob() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_PORTAL2)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    assert_eq!(
+        vpk.tree.glob("materials/models/props/*").count(),
+        vpk.tree
+            .filter_by_directory("materials/models/props")
+            .count()
+    );
+    assert_eq!(
+        vpk.tree.glob("materials/models/**").count(),
+        vpk.tree.filter_by_path_prefix("materials/models/").count()
+    );
+    assert_eq!(vpk.tree.glob("**/*.vmt").count(), common::PORTAL2_VMT_COUNT);
+    assert_eq!(
+        vpk.tree.glob("materials/models/props/*.vmt").count(),
+        vpk.tree
+            .filter_by_directory("materials/models/props")
+            .filter(|(file_path, _)| file_path.ends_with(".vmt"))
+            .count()
+    );
+
+    assert!(
+        vpk.tree.try_glob("materials/**.vmt").is_err(),
+        "Invalid pattern should be an error"
+    );
+    assert_eq!(
+        vpk.tree.glob("materials/**.vmt").count(),
+        0,
+        "Invalid pattern should match no files"
+    );
+    Ok(())
+}
+
+#[test]
+fn vpk_extension_filter() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_PORTAL2)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let filter = ExtensionFilter::new(&["vtf", ".VMT", "res"]);
+
+    let mut selected = filter.select(vpk.file_paths());
+    selected.sort_unstable();
+
+    let mut expected: Vec<&str> = vpk
+        .file_paths()
+        .into_iter()
+        .filter(|file_path| {
+            let file_path = file_path.to_ascii_lowercase();
+            file_path.ends_with(".vtf")
+                || file_path.ends_with(".vmt")
+                || file_path.ends_with(".res")
+        })
+        .collect();
+    expected.sort_unstable();
+
+    assert_eq!(selected, expected, "Selected files do not match");
+
+    let index = vpk.extension_index();
+    assert_eq!(
+        filter.select_from(&index),
+        selected,
+        "Selecting from a reused index should match"
+    );
+    assert_eq!(index.get("VMT").len(), common::PORTAL2_VMT_COUNT);
+    assert_eq!(
+        index.counts().map(|(_, count)| count).sum::<usize>(),
+        common::PORTAL2_TREE_COUNT
+    );
+    assert!(
+        selected.iter().all(|file_path| filter.matches(file_path)),
+        "Every selected file should match the filter"
+    );
+    assert_eq!(
+        selected
+            .iter()
+            .filter(|file_path| file_path.ends_with(".vmt"))
+            .count(),
+        common::PORTAL2_VMT_COUNT
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_single_file_rel() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let result = vpk.read_file_rel(
+        Path::new(common::PAK_V1_SINGLE_FILE),
+        common::SINGLE_FILE_NAME,
+    )?;
+
+    assert_eq!(
+        result,
+        common::SINGLE_FILE_CONTENT.as_bytes(),
+        "Content does not match expected"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_single_file_rel_missing_archive() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    // Copy only the directory file, so the archive cannot be found
+    let dir = tempfile::tempdir()?;
+    let dir_file_path = dir.path().join("single_file_dir.vpk");
+    std::fs::copy(common::PAK_V1_SINGLE_FILE, &dir_file_path)?;
+
+    let result = vpk.read_file_rel(&dir_file_path, common::SINGLE_FILE_NAME);
+
+    let expected_path = dir.path().join("single_file_000.vpk");
+    assert!(
+        result.is_err_and(|x| matches!(
+            x,
+            vpk_plumber::pak::Error::ArchiveOpen { ref path, .. } if Path::new(path) == expected_path
+        )),
+        "Error should contain the attempted archive path",
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn vpk_serde() -> Result<()> {
+    use vpk_plumber::pak::{VPKDirectoryEntry, VPKTree, v1::VPKHeaderV1};
+
+    let mut file = File::open(common::PAK_V1_PORTAL2)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let json = serde_json::to_string(&vpk.tree)?;
+    let tree: VPKTree<VPKDirectoryEntry> = serde_json::from_str(&json)?;
+
+    assert_eq!(tree.files.len(), common::PORTAL2_TREE_COUNT);
+    assert_eq!(tree.preload.len(), vpk.tree.preload.len());
+    for (file_path, entry) in &vpk.tree.files {
+        assert_eq!(
+            tree.files[file_path].crc, entry.crc,
+            "CRC of {file_path} does not match"
+        );
+    }
+    assert!(tree == vpk.tree, "Tree does not round-trip");
+
+    let header: VPKHeaderV1 = serde_json::from_str(&serde_json::to_string(&vpk.header)?)?;
+    assert_eq!(header, vpk.header);
+
+    let parsed: VPKVersion1 = serde_json::from_str(&serde_json::to_string(&vpk)?)?;
+    assert!(parsed == vpk, "VPK does not round-trip");
+
+    Ok(())
+}
+
+#[test]
+fn vpk_validate() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_PORTAL2)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+    assert!(vpk.validate().is_empty(), "Loaded VPK should be valid");
+
+    let mut vpk = VPKVersion1::new();
+    let files = [
+        "valid.txt",
+        "terminator.txt",
+        "missing_preload.txt",
+        "preload_mismatch.txt",
+        "Case.txt",
+        "case.txt",
+        "dir\\backslash.txt",
+        "dir/../parent.txt",
+    ];
+    for file_path in files {
+        vpk.tree
+            .files
+            .insert(file_path.to_string(), VPKDirectoryEntry::new());
+    }
+
+    vpk.tree.files.get_mut("terminator.txt").unwrap().terminator = 0;
+    vpk.tree
+        .files
+        .get_mut("missing_preload.txt")
+        .unwrap()
+        .preload_length = 4;
+    vpk.tree
+        .files
+        .get_mut("preload_mismatch.txt")
+        .unwrap()
+        .preload_length = 4;
+    vpk.tree
+        .preload
+        .insert("preload_mismatch.txt".to_string(), vec![0; 2]);
+    vpk.tree.preload.insert("valid.txt".to_string(), vec![0; 2]);
+
+    let issues = vpk.validate();
+    let mut kinds: Vec<(Option<&str>, ValidationIssueKind)> = issues
+        .iter()
+        .map(|issue| (issue.path.as_deref(), issue.kind))
+        .collect();
+    kinds.sort();
+
+    let mut expected = vec![
+        (Some("Case.txt"), ValidationIssueKind::CaseCollision),
+        (Some("case.txt"), ValidationIssueKind::CaseCollision),
+        (
+            Some("dir/../parent.txt"),
+            ValidationIssueKind::ParentComponent,
+        ),
+        (Some("dir\\backslash.txt"), ValidationIssueKind::Backslash),
+        (
+            Some("missing_preload.txt"),
+            ValidationIssueKind::MissingPreload,
+        ),
+        (
+            Some("preload_mismatch.txt"),
+            ValidationIssueKind::PreloadLengthMismatch,
+        ),
+        (Some("terminator.txt"), ValidationIssueKind::BadTerminator),
+        (Some("valid.txt"), ValidationIssueKind::UnexpectedPreload),
+    ];
+    expected.sort();
+
+    assert_eq!(kinds, expected);
+    Ok(())
+}
+
+#[test]
+fn vpk_mixed_separators() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_PORTAL2)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    // Only the directory file is included in the test data, so read a file stored entirely in the preload data
+    let expected = vpk
+        .read_file(
+            common::DIR_V1,
+            "pak01",
+            "materials/models/props/faith_plate.vmt",
+        )
+        .unwrap();
+
+    for file_path in [
+        "materials\\models\\props\\faith_plate.vmt",
+        "materials/models\\props/faith_plate.vmt",
+        "/materials/models/props/faith_plate.vmt",
+    ] {
+        assert!(vpk.tree.get(file_path).is_some(), "{file_path} not found");
+        assert_eq!(
+            vpk.read_file(common::DIR_V1, "pak01", file_path).as_ref(),
+            Some(&expected),
+            "Content of {file_path} does not match"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn vpk_case_insensitive() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_PORTAL2)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let stored_path = "materials/models/props/faith_plate.vmt";
+    let expected = vpk.read_file(common::DIR_V1, "pak01", stored_path).unwrap();
+
+    let options = ExtractOptions {
+        case_sensitivity: CaseSensitivity::Insensitive,
+        ..Default::default()
+    };
+
+    for file_path in [
+        "Materials/Models/Props/Faith_Plate.VMT",
+        "MATERIALS\\models//PROPS\\faith_plate.vmt",
+        "./materials/models/props/FAITH_PLATE.vmt",
+    ] {
+        assert!(
+            vpk.read_file(common::DIR_V1, "pak01", file_path).is_none(),
+            "Lookups should be case-sensitive by default"
+        );
+        assert_eq!(
+            vpk.tree.find(file_path, CaseSensitivity::Insensitive),
+            Some((stored_path, &vpk.tree.files[stored_path])),
+            "{file_path} should be found under its stored path"
+        );
+        assert_eq!(
+            vpk.read_file_with(common::DIR_V1, "pak01", file_path, &options)
+                .as_ref(),
+            Some(&expected),
+            "Content of {file_path} does not match"
+        );
+    }
+
+    assert_eq!(
+        vpk.tree.find(
+            "materials/models/props/faith_plate.vmt.bak",
+            CaseSensitivity::Insensitive
+        ),
+        None
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_read_file_ci() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_PORTAL2)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let expected = vpk.read_file(
+        common::DIR_V1,
+        "pak01",
+        "materials/models/props/faith_plate.vmt",
+    );
+    assert!(expected.is_some());
+
+    let file_path = "Materials/Models/Props/FAITH_PLATE.VMT";
+    assert!(vpk.tree.get(file_path).is_none());
+    assert!(vpk.tree.get_ci(file_path).is_some());
+    assert_eq!(
+        vpk.read_file_ci(common::DIR_V1, "pak01", file_path),
+        expected
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_manifest() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_PORTAL2)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let manifest = vpk.manifest();
+    assert_eq!(manifest.files.len(), common::PORTAL2_TREE_COUNT);
+    assert!(
+        manifest.files.is_sorted_by(|a, b| a.path < b.path),
+        "Files should be sorted by path"
+    );
+
+    let file_path = "materials/dev/dev_tvmonitor1a.vmt";
+    let entry = manifest.get(file_path).unwrap();
+    assert_eq!(entry.crc, vpk.file_crc(file_path).unwrap());
+    assert_eq!(entry.size, vpk.file_size(file_path).unwrap());
+    assert_eq!(entry.archives, [vpk.tree.files[file_path].archive_index]);
+    assert!(manifest.diff(&vpk.manifest()).is_empty());
+
+    let filtered = vpk.manifest_filtered(&ExtensionFilter::new(&["vmt"]));
+    assert_eq!(filtered.files.len(), common::PORTAL2_VMT_COUNT);
+    assert_eq!(filtered.get(file_path), Some(entry));
+
+    Ok(())
+}
+
+#[test]
+fn vpk_manifest_diff() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_PORTAL2)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+    let mut modified = VPKVersion1::try_from(&mut File::open(common::PAK_V1_PORTAL2)?)?;
+
+    let crc_path = "materials/dev/dev_tvmonitor1a.vmt";
+    let size_path = "materials/effects/projected_wall.vmt";
+    let moved_path = "materials/effects/tractor_beam.vmt";
+    let removed_path = "materials/models/props/faith_plate.vmt";
+
+    modified.tree.files.get_mut(crc_path).unwrap().crc ^= 1;
+    modified.tree.files.get_mut(size_path).unwrap().entry_length += 1;
+    modified
+        .tree
+        .files
+        .get_mut(moved_path)
+        .unwrap()
+        .archive_index += 1;
+    modified.tree.remove_file(removed_path).unwrap();
+    modified
+        .tree
+        .insert_file("scripts/added.txt", b"added", 0, 0, 0, false)?;
+
+    let diff = vpk.manifest().diff(&modified.manifest());
+    assert_eq!(diff.added, ["scripts/added.txt"]);
+    assert_eq!(diff.removed, [removed_path]);
+    assert_eq!(
+        diff.changed,
+        [crc_path, size_path],
+        "Only CRC and size changes should be listed, in path order"
+    );
+
+    let reverse = modified.manifest().diff(&vpk.manifest());
+    assert_eq!(reverse.added, diff.removed);
+    assert_eq!(reverse.removed, diff.added);
+    assert_eq!(reverse.changed, diff.changed);
+
+    Ok(())
+}
+
+#[test]
+fn vpk_manifest_export() -> Result<()> {
+    let manifest: Manifest = [
+        ManifestEntry {
+            path: "b/\"quoted\", path.txt".to_string(),
+            crc: 0xAB,
+            size: 10,
+            archives: vec![1, 3],
+        },
+        ManifestEntry {
+            path: "a/file.txt".to_string(),
+            crc: 0x1234_5678,
+            size: 0,
+            archives: Vec::new(),
+        },
+    ]
+    .into_iter()
+    .collect();
+
+    let mut csv = Vec::new();
+    manifest.write_csv(&mut csv)?;
+    assert_eq!(
+        String::from_utf8(csv)?,
+        "path,crc32,size,archives\n\
+         a/file.txt,12345678,0,\n\
+         \"b/\"\"quoted\"\", path.txt\",000000AB,10,1;3\n"
+    );
+
+    let mut json = Vec::new();
+    manifest.write_json(&mut json)?;
+    let value: serde_json::Value = serde_json::from_slice(&json)?;
+    assert_eq!(
+        value,
+        serde_json::json!({"files": [
+            {"path": "a/file.txt", "crc": 0x1234_5678, "size": 0, "archives": []},
+            {"path": "b/\"quoted\", path.txt", "crc": 0xAB, "size": 10, "archives": [1, 3]},
+        ]})
+    );
+
+    #[cfg(feature = "serde")]
+    assert_eq!(serde_json::from_slice::<Manifest>(&json)?, manifest);
+
+    Ok(())
+}
@@ -1,10 +1,17 @@
-use std::{fs::File, io::Read};
+use std::{fs::File, io::Read, mem};
 
-use vpk_plumber::pak::{PakReader, v1::VPKVersion1};
+#[cfg(feature = "mem-map")]
+use vpk_plumber::pak::v1::VPK_DIR_ARCHIVE_INDEX;
+use vpk_plumber::pak::{
+    Error, ExtensionFilter, ExtractOptions, FileLocation, PakReader,
+    v1::{VPKHeaderV1, VPKVersion1},
+};
 
 use crate::common::{self, Result};
 
+#[cfg(feature = "mem-map")]
 use filebuffer::FileBuffer;
+#[cfg(feature = "mem-map")]
 use std::collections::HashMap;
 
 #[test]
@@ -32,6 +39,76 @@ fn vpk_single_file() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn vpk_file_size() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let mut vpk = VPKVersion1::try_from(&mut file)?;
+
+    // Stored entirely as preload data
+    vpk.tree
+        .insert_file("test/preload.txt", b"preload", 0, 0, 16, false)?;
+
+    for file_path in [common::SINGLE_FILE_NAME, "test/preload.txt"] {
+        let data = vpk
+            .read_file(common::DIR_V1, common::SINGLE_FILE_ARCHIVE, file_path)
+            .expect("File should be readable");
+
+        assert_eq!(
+            vpk.file_size(file_path),
+            Some(data.len() as u64),
+            "Size of {file_path} should match its contents"
+        );
+    }
+
+    assert_eq!(vpk.file_size("test/missing.txt"), None);
+    Ok(())
+}
+
+#[test]
+fn vpk_file_metadata() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let mut vpk = VPKVersion1::try_from(&mut file)?;
+
+    assert_eq!(vpk.file_size(common::SINGLE_FILE_NAME), Some(9));
+    assert_eq!(vpk.file_crc(common::SINGLE_FILE_NAME), Some(0x4570_FA16));
+    assert_eq!(
+        vpk.file_location(common::SINGLE_FILE_NAME),
+        Some(FileLocation::Archive {
+            index: 0,
+            offset: 9,
+            length: 9
+        })
+    );
+
+    vpk.tree
+        .insert_file("test/preload.txt", b"preload", 0, 0, 16, false)?;
+    vpk.tree.insert_file(
+        "test/dir.txt",
+        b"in directory",
+        vpk_plumber::pak::v1::VPK_DIR_ARCHIVE_INDEX,
+        4,
+        0,
+        false,
+    )?;
+
+    assert_eq!(
+        vpk.file_location("test/preload.txt"),
+        Some(FileLocation::Preload)
+    );
+    assert_eq!(
+        vpk.file_location("test/dir.txt"),
+        Some(FileLocation::InDirectory {
+            offset: mem::size_of::<VPKHeaderV1>() as u64 + u64::from(vpk.header.tree_size) + 4,
+            length: 12
+        }),
+        "Offsets of data in the directory file should be absolute"
+    );
+
+    assert_eq!(vpk.file_crc("test/missing.txt"), None);
+    assert_eq!(vpk.file_location("test/missing.txt"), None);
+    Ok(())
+}
+
 #[cfg(feature = "mem-map")]
 #[test]
 fn vpk_single_file_mem_map() -> Result<()> {
@@ -62,3 +139,956 @@ fn vpk_single_file_mem_map() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(feature = "mem-map")]
+#[test]
+fn vpk_forged_entry_mem_map() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let mut vpk = VPKVersion1::try_from(&mut file)?;
+
+    let out_dir = tempfile::tempdir()?;
+    std::fs::write(out_dir.path().join("archive.vpk"), [b'a'; 16])?;
+    let mut archive_mmaps = HashMap::new();
+    archive_mmaps.insert(
+        0,
+        FileBuffer::open(out_dir.path().join("archive.vpk")).unwrap(),
+    );
+
+    // An entry declaring data past the end of the tiny archive
+    let entry = vpk.tree.files.get_mut(common::SINGLE_FILE_NAME).unwrap();
+    entry.archive_index = 0;
+    entry.entry_offset = u32::MAX - 4;
+    entry.entry_length = u32::MAX;
+
+    let result = vpk.extract_file_mem_map(
+        common::DIR_V1,
+        &archive_mmaps,
+        common::SINGLE_FILE_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+        out_dir.path().join("out").to_str().unwrap(),
+    );
+    assert!(
+        matches!(result, Err(Error::UnexpectedEof { .. })),
+        "Data past the end of the archive should be reported: {result:?}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_single_file_eof() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE_EOF)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let out_path = tempfile::NamedTempFile::new()?;
+
+    vpk.extract_file(
+        common::DIR_V1,
+        common::SINGLE_FILE_EOF_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+        out_path.path().to_str().unwrap(),
+    )?;
+
+    let mut result = String::new();
+    File::open(&out_path)?.read_to_string(&mut result)?;
+
+    assert_eq!(
+        result,
+        common::SINGLE_FILE_CONTENT,
+        "File contents should match",
+    );
+    Ok(())
+}
+
+#[cfg(feature = "mem-map")]
+#[test]
+fn vpk_single_file_eof_mem_map() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE_EOF)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let mut archive_mmaps = HashMap::new();
+    archive_mmaps.insert(
+        VPK_DIR_ARCHIVE_INDEX,
+        FileBuffer::open(common::PAK_V1_SINGLE_FILE_EOF).unwrap(),
+    );
+
+    let out_path = tempfile::NamedTempFile::new()?;
+
+    vpk.extract_file_mem_map(
+        common::DIR_V1,
+        &archive_mmaps,
+        common::SINGLE_FILE_EOF_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+        out_path.path().to_str().unwrap(),
+    )?;
+
+    let mut result = String::new();
+    File::open(&out_path)?.read_to_string(&mut result)?;
+
+    assert_eq!(
+        result,
+        common::SINGLE_FILE_CONTENT,
+        "File contents should match",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_extract_all() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let out_dir = tempfile::tempdir()?;
+    let mut calls = Vec::new();
+    let mut progress = |file_path: &str, done: usize, total: usize| {
+        calls.push((file_path.to_string(), done, total));
+    };
+
+    let summary = vpk.extract_all(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        out_dir.path().to_str().unwrap(),
+        Some(&mut progress),
+    )?;
+
+    assert_eq!(summary.files_extracted, 1, "One file should be extracted");
+    assert_eq!(
+        summary.bytes_written,
+        common::SINGLE_FILE_CONTENT.len() as u64,
+        "Bytes written should match the file size",
+    );
+    assert!(summary.errors.is_empty(), "No errors should be reported");
+    assert_eq!(
+        calls,
+        vec![(common::SINGLE_FILE_NAME.to_string(), 1, 1)],
+        "Progress should be reported once per file",
+    );
+
+    let mut result = String::new();
+    File::open(out_dir.path().join(common::SINGLE_FILE_NAME))?.read_to_string(&mut result)?;
+
+    assert_eq!(
+        result,
+        common::SINGLE_FILE_CONTENT,
+        "File contents should match",
+    );
+    Ok(())
+}
+
+#[test]
+fn vpk_extract_to_dir() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let out_dir = tempfile::tempdir()?;
+    let out_path = out_dir.path().to_str().unwrap();
+    let file_path = out_dir.path().join(common::SINGLE_FILE_NAME);
+
+    std::fs::create_dir_all(file_path.parent().unwrap())?;
+    std::fs::write(&file_path, "existing")?;
+
+    let summary =
+        vpk.extract_to_dir(common::DIR_V1, common::SINGLE_FILE_ARCHIVE, out_path, false)?;
+    assert_eq!(summary.files_skipped, 1, "Existing files should be skipped");
+    assert_eq!(
+        summary.files_extracted, 0,
+        "Skipped files should not count as extracted"
+    );
+    assert!(summary.errors.is_empty(), "No errors should be reported");
+    assert_eq!(
+        std::fs::read_to_string(&file_path)?,
+        "existing",
+        "Existing files should not be overwritten"
+    );
+
+    let summary =
+        vpk.extract_to_dir(common::DIR_V1, common::SINGLE_FILE_ARCHIVE, out_path, true)?;
+    assert_eq!(summary.files_extracted, 1, "File should be extracted");
+    assert_eq!(summary.files_skipped, 0);
+    assert_eq!(
+        summary.bytes_written,
+        common::SINGLE_FILE_CONTENT.len() as u64
+    );
+    assert_eq!(
+        std::fs::read_to_string(&file_path)?,
+        common::SINGLE_FILE_CONTENT,
+        "Existing files should be overwritten"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_extract_filtered() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let out_dir = tempfile::tempdir()?;
+    let summary = vpk.extract_filtered(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        out_dir.path().to_str().unwrap(),
+        &ExtensionFilter::new(&["vmt"]),
+        None,
+    )?;
+
+    assert_eq!(summary.files_extracted, 0, "No file should be extracted");
+    assert!(
+        !out_dir.path().join(common::SINGLE_FILE_NAME).exists(),
+        "Filtered out files should not be extracted"
+    );
+
+    let summary = vpk.extract_filtered(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        out_dir.path().to_str().unwrap(),
+        &ExtensionFilter::new(&["vmt", "txt"]),
+        None,
+    )?;
+
+    assert_eq!(summary.files_extracted, 1, "One file should be extracted");
+    assert!(summary.errors.is_empty(), "No errors should be reported");
+
+    let mut result = String::new();
+    File::open(out_dir.path().join(common::SINGLE_FILE_NAME))?.read_to_string(&mut result)?;
+
+    assert_eq!(
+        result,
+        common::SINGLE_FILE_CONTENT,
+        "File contents should match",
+    );
+    Ok(())
+}
+
+#[test]
+fn vpk_extract_files_missing() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let out_dir = tempfile::tempdir()?;
+    let summary = vpk.extract_files(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        &[common::SINGLE_FILE_NAME, "test/missing.txt"],
+        out_dir.path().to_str().unwrap(),
+        None,
+    )?;
+
+    assert_eq!(summary.files_extracted, 1, "One file should be extracted");
+    assert!(
+        matches!(
+            summary.errors.as_slice(),
+            [(file_path, vpk_plumber::pak::Error::FileNotFound(_))] if file_path == "test/missing.txt"
+        ),
+        "Missing files should be reported"
+    );
+    Ok(())
+}
+
+#[test]
+fn vpk_extract_all_path_escape() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let mut vpk = VPKVersion1::try_from(&mut file)?;
+
+    let entry = vpk.tree.files[common::SINGLE_FILE_NAME].clone();
+    vpk.tree.files.insert("../evil.txt".to_string(), entry);
+
+    let root = tempfile::tempdir()?;
+    let out_dir = root.path().join("out");
+
+    let summary = vpk.extract_all(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        out_dir.to_str().unwrap(),
+        None,
+    )?;
+
+    assert_eq!(
+        summary.files_extracted, 1,
+        "The valid file should be extracted"
+    );
+    assert_eq!(
+        summary.errors.len(),
+        1,
+        "The escaping file should be rejected"
+    );
+    assert_eq!(summary.errors[0].0, "../evil.txt");
+    assert!(
+        !root.path().join("evil.txt").exists(),
+        "Nothing should be written outside the output directory",
+    );
+    Ok(())
+}
+
+#[test]
+fn vpk_single_file_progress() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let out_path = tempfile::NamedTempFile::new()?;
+    let mut calls = Vec::new();
+
+    vpk.extract_file_with_progress(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+        out_path.path().to_str().unwrap(),
+        &mut |written, total| calls.push((written, total)),
+    )?;
+
+    let len = common::SINGLE_FILE_CONTENT.len() as u64;
+    assert_eq!(
+        calls.last(),
+        Some(&(len, len)),
+        "Progress should end at the file size",
+    );
+    assert!(
+        calls.windows(2).all(|w| w[0].0 <= w[1].0),
+        "Progress should never go backwards",
+    );
+    Ok(())
+}
+
+#[test]
+fn vpk_single_file_chunk_size() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let len = common::SINGLE_FILE_CONTENT.len();
+
+    // A chunk size of 0 is clamped to 1
+    for (chunk_size, expected_chunks) in [(0, len), (1, len), (2, len.div_ceil(2)), (1024, 1)] {
+        let out_path = tempfile::NamedTempFile::new()?;
+        let options = ExtractOptions {
+            chunk_size,
+            ..Default::default()
+        };
+
+        let mut chunks = 0;
+        vpk.extract_file_with(
+            common::DIR_V1,
+            common::SINGLE_FILE_ARCHIVE,
+            common::SINGLE_FILE_NAME,
+            out_path.path().to_str().unwrap(),
+            &options,
+            &mut |_, _| chunks += 1,
+        )?;
+
+        assert_eq!(
+            chunks, expected_chunks,
+            "Chunk size of {chunk_size} should read {expected_chunks} chunks",
+        );
+
+        let mut result = String::new();
+        File::open(&out_path)?.read_to_string(&mut result)?;
+        assert_eq!(
+            result,
+            common::SINGLE_FILE_CONTENT,
+            "File contents should match",
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn vpk_single_file_verify_crc() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let mut vpk = VPKVersion1::try_from(&mut file)?;
+
+    vpk.tree
+        .files
+        .get_mut(common::SINGLE_FILE_NAME)
+        .unwrap()
+        .crc ^= 1;
+
+    let out_path = tempfile::NamedTempFile::new()?;
+    let out_path = out_path.path().to_str().unwrap();
+
+    let result = vpk.extract_file(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+        out_path,
+    );
+    assert!(
+        result.is_err_and(|x| matches!(
+            x,
+            vpk_plumber::pak::Error::CrcMismatch { expected, found, ref file }
+                if expected == found ^ 1 && file == common::SINGLE_FILE_NAME
+        )),
+        "Mismatching CRC should be an error",
+    );
+    assert!(
+        !std::path::Path::new(out_path).exists(),
+        "Output with a mismatching CRC should be removed"
+    );
+
+    let options = ExtractOptions {
+        verify_crc: false,
+        ..Default::default()
+    };
+    vpk.extract_file_with(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+        out_path,
+        &options,
+        &mut |_, _| {},
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn vpk_read_file_to() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let mut out = Vec::new();
+    let written = vpk.read_file_to(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+        &mut out,
+    )?;
+
+    assert_eq!(written, out.len() as u64);
+    assert_eq!(out, common::SINGLE_FILE_CONTENT.as_bytes());
+
+    let mut hasher = md5::Context::new();
+    vpk.read_file_to(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+        &mut hasher,
+    )?;
+    assert_eq!(
+        hasher.finalize(),
+        md5::compute(common::SINGLE_FILE_CONTENT),
+        "Data should be streamed into the hasher"
+    );
+
+    let result = vpk.read_file_to(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        "test/missing.txt",
+        &mut Vec::new(),
+    );
+    assert!(matches!(
+        result,
+        Err(vpk_plumber::pak::Error::FileNotFound(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn vpk_extract_missing_file() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let out_dir = tempfile::tempdir()?;
+    let out_path = out_dir.path().join("missing").join("missing.txt");
+
+    let result = vpk.extract_file(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        "test/missing.txt",
+        out_path.to_str().unwrap(),
+    );
+
+    assert!(result.is_err(), "Missing files should fail to extract");
+    assert!(
+        !out_path.parent().unwrap().exists(),
+        "No output should be created for missing files"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_read_file_streaming() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let mut vpk = VPKVersion1::try_from(&mut file)?;
+
+    let mut result = String::new();
+    vpk.read_file_streaming(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+    )?
+    .read_to_string(&mut result)?;
+    assert_eq!(result, common::SINGLE_FILE_CONTENT);
+
+    vpk.tree
+        .files
+        .get_mut(common::SINGLE_FILE_NAME)
+        .unwrap()
+        .crc ^= 1;
+
+    let mut reader = vpk.read_file_streaming(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+    )?;
+    let error = reader.read_to_end(&mut Vec::new()).unwrap_err();
+    assert!(
+        matches!(
+            error
+                .get_ref()
+                .and_then(|e| e.downcast_ref::<vpk_plumber::pak::Error>()),
+            Some(vpk_plumber::pak::Error::CrcMismatch { .. })
+        ),
+        "A CRC mismatch should fail the final read"
+    );
+
+    let error = reader.read(&mut [0; 16]).unwrap_err();
+    assert_eq!(
+        error.kind(),
+        std::io::ErrorKind::InvalidData,
+        "Reads after a CRC mismatch should keep failing"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_read_file_streaming_preload() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_PORTAL2)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    // Only the directory file is included in the test data, so read a file stored entirely in the preload data
+    let file_path = "materials/models/props/faith_plate.vmt";
+    let mut result = Vec::new();
+    vpk.read_file_streaming(common::DIR_V1, "pak01", file_path)?
+        .read_to_end(&mut result)?;
+
+    assert_eq!(
+        Some(result),
+        vpk.read_file(common::DIR_V1, "pak01", file_path)
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn vpk_extract_files_parallel() -> Result<()> {
+    use vpk_plumber::pak::{PakBuilder, PakWorker};
+
+    let vpk_dir = tempfile::tempdir()?;
+    let vpk_path = vpk_dir.path().to_str().unwrap();
+
+    let mut builder = PakBuilder::new().max_archive_size(1024);
+    let files: Vec<(String, Vec<u8>)> = (0..100)
+        .map(|i| (format!("files/file_{i:0>3}.txt"), vec![i as u8; 100 + i]))
+        .collect();
+    for (file_path, data) in &files {
+        builder.add_file(file_path, data.clone());
+    }
+    let report = builder.build(vpk_path, "pak01")?;
+    assert!(
+        report.archive_count > 1,
+        "Files should span several archives"
+    );
+
+    let vpk = VPKVersion1::from_file(&mut File::open(vpk_dir.path().join("pak01_dir.vpk"))?)?;
+
+    let mut file_paths: Vec<&str> = files.iter().map(|(p, _)| p.as_str()).collect();
+    file_paths.push("files/missing.txt");
+
+    let out_dir = tempfile::tempdir()?;
+    let results = vpk.extract_files_parallel(
+        vpk_path,
+        "pak01",
+        &file_paths,
+        out_dir.path().to_str().unwrap(),
+    );
+
+    assert_eq!(results.len(), 101, "Every file should have a result");
+    let failed: Vec<&str> = results
+        .iter()
+        .filter(|(_, result)| result.is_err())
+        .map(|(file_path, _)| file_path.as_str())
+        .collect();
+    assert_eq!(
+        failed,
+        ["files/missing.txt"],
+        "Only the missing file should fail"
+    );
+
+    for (file_path, data) in &files {
+        assert_eq!(
+            &std::fs::read(out_dir.path().join(file_path))?,
+            data,
+            "Contents of {file_path} should match"
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn vpk_extract_all_parallel() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let out_dir = tempfile::tempdir()?;
+    let results = vpk.extract_all_parallel(
+        common::DIR_V1,
+        common::SINGLE_FILE_ARCHIVE,
+        out_dir.path().to_str().unwrap(),
+    );
+
+    assert_eq!(results.len(), 1, "File should be extracted");
+    assert_eq!(results[0].0, common::SINGLE_FILE_NAME, "Path should match");
+    assert!(results[0].1.is_ok(), "No errors should be reported");
+    assert_eq!(
+        std::fs::read(out_dir.path().join(common::SINGLE_FILE_NAME))?,
+        common::SINGLE_FILE_CONTENT.as_bytes(),
+        "Content does not match expected"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_try_read_file() -> Result<()> {
+    let vpk_dir = tempfile::tempdir()?;
+    let vpk_path = vpk_dir.path().to_str().unwrap();
+    let archive = vpk_dir.path().join("single_file_000.vpk");
+    std::fs::copy(common::PAK_V1_ARCHIVE, &archive)?;
+
+    let vpk = VPKVersion1::try_from(&mut File::open(common::PAK_V1_SINGLE_FILE)?)?;
+
+    assert_eq!(
+        vpk.try_read_file(
+            vpk_path,
+            common::SINGLE_FILE_ARCHIVE,
+            common::SINGLE_FILE_NAME
+        )?,
+        common::SINGLE_FILE_CONTENT.as_bytes(),
+        "Content does not match expected"
+    );
+
+    assert!(
+        matches!(
+            vpk.try_read_file(vpk_path, common::SINGLE_FILE_ARCHIVE, "test/missing.txt"),
+            Err(Error::FileNotFound(_))
+        ),
+        "Nonexistent files should not be found"
+    );
+
+    let mut data = std::fs::read(&archive)?;
+    data[vpk.tree.files[common::SINGLE_FILE_NAME].entry_offset as usize] ^= 0xFF;
+    std::fs::write(&archive, data)?;
+    assert!(
+        matches!(
+            vpk.try_read_file(
+                vpk_path,
+                common::SINGLE_FILE_ARCHIVE,
+                common::SINGLE_FILE_NAME
+            ),
+            Err(Error::CrcMismatch { .. })
+        ),
+        "Corrupted data should not match its CRC"
+    );
+
+    std::fs::remove_file(&archive)?;
+    assert!(
+        matches!(
+            vpk.try_read_file(
+                vpk_path,
+                common::SINGLE_FILE_ARCHIVE,
+                common::SINGLE_FILE_NAME
+            ),
+            Err(Error::ArchiveOpen { .. })
+        ),
+        "Deleted archives should not open"
+    );
+    assert_eq!(
+        vpk.read_file(
+            vpk_path,
+            common::SINGLE_FILE_ARCHIVE,
+            common::SINGLE_FILE_NAME
+        ),
+        None,
+        "read_file should return None on errors"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_extract_files_observed() -> Result<()> {
+    use std::sync::Arc;
+    use vpk_plumber::pak::{PakBuilder, PakWorker};
+
+    let vpk_dir = tempfile::tempdir()?;
+    let vpk_path = vpk_dir.path().to_str().unwrap();
+
+    let mut builder = PakBuilder::new().max_archive_size(1024);
+    for i in 0..50 {
+        builder.add_file(&format!("files/file_{i:0>3}.txt"), vec![i as u8; 100]);
+    }
+    let report = builder.build(vpk_path, "pak01")?;
+
+    let observer = Arc::new(common::CountingObserver::default());
+    let mut vpk = VPKVersion1::from_file(&mut File::open(vpk_dir.path().join("pak01_dir.vpk"))?)?;
+    vpk.set_observer(Some(observer.clone()));
+
+    let out_dir = tempfile::tempdir()?;
+    let summary = vpk.extract_all(vpk_path, "pak01", out_dir.path().to_str().unwrap(), None)?;
+    assert_eq!(summary.files_extracted, 50, "All files should be extracted");
+
+    let opened = observer.opened();
+    assert_eq!(
+        opened.len(),
+        usize::from(report.archive_count),
+        "Every archive should be opened"
+    );
+    assert!(
+        opened.values().all(|&count| count == 1),
+        "Every archive should be opened once: {opened:?}"
+    );
+    assert_eq!(
+        observer.bytes_read().values().sum::<u64>(),
+        report.bytes_written,
+        "All file data should be read once"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_extract_files_custom_naming() -> Result<()> {
+    use std::sync::Arc;
+    use vpk_plumber::pak::{PakBuilder, PakWorker};
+
+    let vpk_dir = tempfile::tempdir()?;
+    let vpk_path = vpk_dir.path().to_str().unwrap();
+
+    let mut builder = PakBuilder::new().max_archive_size(1024);
+    for i in 0..20 {
+        builder.add_file(&format!("files/file_{i:0>3}.txt"), vec![i as u8; 100]);
+    }
+    let report = builder.build(vpk_path, "pak01")?;
+
+    // Move the archives to a layout without padding or the VPK name
+    let archive_dir = vpk_dir.path().join("archives");
+    std::fs::create_dir(&archive_dir)?;
+    for i in 0..report.archive_count {
+        std::fs::rename(
+            vpk_dir.path().join(format!("pak01_{i:0>3}.vpk")),
+            archive_dir.join(format!("{i}.bin")),
+        )?;
+    }
+
+    let mut vpk = VPKVersion1::from_file(&mut File::open(vpk_dir.path().join("pak01_dir.vpk"))?)?;
+    assert!(
+        vpk.read_file(vpk_path, "pak01", "files/file_000.txt")
+            .is_none(),
+        "Archives should not be found with the default naming"
+    );
+
+    vpk.set_archive_naming(Some(Arc::new(move |archive_index: u16| {
+        archive_dir.join(format!("{archive_index}.bin"))
+    })));
+
+    for i in 0..20 {
+        assert_eq!(
+            vpk.read_file(vpk_path, "pak01", &format!("files/file_{i:0>3}.txt")),
+            Some(vec![i as u8; 100]),
+            "Files should be read from the renamed archives"
+        );
+    }
+
+    vpk.set_archive_naming(None);
+    assert!(
+        vpk.read_file(vpk_path, "pak01", "files/file_000.txt")
+            .is_none(),
+        "Removing the naming should restore the default naming"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_extract_truncated_archive() -> Result<()> {
+    use vpk_plumber::pak::{PakBuilder, PakWorker};
+
+    let vpk_dir = tempfile::tempdir()?;
+    let vpk_path = vpk_dir.path().to_str().unwrap();
+
+    let mut builder = PakBuilder::new();
+    builder.add_file("files/file.txt", vec![7; 100]);
+    builder.build(vpk_path, "pak01")?;
+
+    File::options()
+        .write(true)
+        .open(vpk_dir.path().join("pak01_000.vpk"))?
+        .set_len(60)?;
+
+    let vpk = VPKVersion1::from_file(&mut File::open(vpk_dir.path().join("pak01_dir.vpk"))?)?;
+    let out_dir = tempfile::tempdir()?;
+    let result = vpk.extract_file(
+        vpk_path,
+        "pak01",
+        "files/file.txt",
+        out_dir.path().join("file.txt").to_str().unwrap(),
+    );
+
+    assert!(
+        matches!(
+            &result,
+            Err(Error::UnexpectedEof { file, missing: 40 }) if file == "files/file.txt"
+        ),
+        "Truncated archive should be reported, got {result:?}"
+    );
+
+    Ok(())
+}
+
+// Writing to /dev/full always fails with ENOSPC, so output paths linked to it simulate a full file system
+#[cfg(target_os = "linux")]
+#[test]
+fn vpk_extract_out_of_space() -> Result<()> {
+    use std::os::unix::fs::symlink;
+    use vpk_plumber::pak::{PakBuilder, PakWorker};
+
+    let vpk_dir = tempfile::tempdir()?;
+    let vpk_path = vpk_dir.path().to_str().unwrap();
+
+    let mut builder = PakBuilder::new();
+    for file_path in ["files/a.txt", "files/b.txt", "files/c.txt"] {
+        builder.add_file(file_path, file_path.repeat(16).into_bytes());
+    }
+    builder.build(vpk_path, "pak01")?;
+    let vpk = VPKVersion1::from_file(&mut File::open(vpk_dir.path().join("pak01_dir.vpk"))?)?;
+
+    let out_dir = tempfile::tempdir()?;
+    let output_dir = out_dir.path().to_str().unwrap();
+    std::fs::create_dir(out_dir.path().join("files"))?;
+    symlink("/dev/full", out_dir.path().join("files/b.txt"))?;
+
+    let summary = vpk.extract_all(vpk_path, "pak01", output_dir, None)?;
+    assert_eq!(
+        summary.files_extracted, 1,
+        "Only files/a.txt should be extracted"
+    );
+    assert!(
+        matches!(
+            summary.errors.as_slice(),
+            [(file_path, Error::OutOfSpace { .. })] if file_path == "files/b.txt"
+        ),
+        "Running out of space should be reported"
+    );
+    assert_eq!(
+        summary.not_attempted,
+        vec!["files/b.txt", "files/c.txt"],
+        "The failed and remaining files should not be attempted"
+    );
+    assert!(
+        !out_dir.path().join("files/b.txt").exists(),
+        "Partial output should be removed"
+    );
+
+    // Resume once there is space again
+    let not_attempted: Vec<&str> = summary.not_attempted.iter().map(String::as_str).collect();
+    let summary = vpk.extract_files(vpk_path, "pak01", &not_attempted, output_dir, None)?;
+    assert_eq!(
+        summary.files_extracted, 2,
+        "Remaining files should be extracted"
+    );
+    assert_eq!(
+        std::fs::read(out_dir.path().join("files/c.txt"))?,
+        "files/c.txt".repeat(16).as_bytes()
+    );
+
+    // Keep the partial output on request
+    let partial_path = out_dir.path().join("d.txt");
+    symlink("/dev/full", &partial_path)?;
+    let result = vpk.extract_file_with(
+        vpk_path,
+        "pak01",
+        "files/a.txt",
+        partial_path.to_str().unwrap(),
+        &ExtractOptions {
+            keep_partial: true,
+            ..Default::default()
+        },
+        &mut |_, _| {},
+    );
+    assert!(
+        matches!(result, Err(Error::OutOfSpace { written: 0, .. })),
+        "Running out of space should be reported"
+    );
+    assert!(
+        out_dir
+            .path()
+            .join("d.txt.partial")
+            .symlink_metadata()
+            .is_ok(),
+        "Partial output should be kept"
+    );
+
+    Ok(())
+}
+
+#[cfg(all(target_os = "linux", feature = "mem-map"))]
+#[test]
+fn vpk_extract_mem_map_cleanup() -> Result<()> {
+    use std::os::unix::fs::symlink;
+
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let mut vpk = VPKVersion1::try_from(&mut file)?;
+    let archive_mmaps = HashMap::from([(0, FileBuffer::open(common::PAK_V1_ARCHIVE)?)]);
+
+    let out_dir = tempfile::tempdir()?;
+    let full_path = out_dir.path().join("full.txt");
+    symlink("/dev/full", &full_path)?;
+
+    let result = vpk.extract_file_mem_map(
+        common::DIR_V1,
+        &archive_mmaps,
+        common::SINGLE_FILE_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+        full_path.to_str().unwrap(),
+    );
+    assert!(
+        matches!(result, Err(Error::OutOfSpace { written: 0, .. })),
+        "Running out of space should be reported: {result:?}"
+    );
+    assert!(
+        full_path.symlink_metadata().is_err(),
+        "Partial output should be removed"
+    );
+
+    vpk.tree
+        .files
+        .get_mut(common::SINGLE_FILE_NAME)
+        .unwrap()
+        .crc ^= 1;
+
+    let out_path = out_dir.path().join("files/crc.txt");
+    let result = vpk.extract_file_mem_map(
+        common::DIR_V1,
+        &archive_mmaps,
+        common::SINGLE_FILE_ARCHIVE,
+        common::SINGLE_FILE_NAME,
+        out_path.to_str().unwrap(),
+    );
+    assert!(
+        matches!(result, Err(Error::CrcMismatch { .. })),
+        "Mismatching CRC should be an error: {result:?}"
+    );
+    assert!(
+        !out_path.exists(),
+        "Output with a mismatching CRC should be removed"
+    );
+
+    Ok(())
+}
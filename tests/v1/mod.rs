@@ -1,3 +1,4 @@
+mod build;
 mod data;
 mod extract;
 mod read;
@@ -1,6 +1,11 @@
-use std::fs::File;
+use std::{fs::File, io::Cursor, mem};
 
-use vpk_plumber::pak::v1::VPKVersion1;
+use vpk_plumber::pak::{
+    DirEntry, DuplicatePolicy, Error, IncrementalTreeParser, PakBuilder, PakReader, PakWorker,
+    ParseOptions, ParseWarningKind, StepResult, VPKDirectoryEntry, VPKTree, VPKTreeIter,
+    ValidationIssueKind,
+    v1::{VPKHeaderV1, VPKVersion1},
+};
 
 use crate::common::{self, Result};
 
@@ -25,6 +30,17 @@ fn valid_vpk_eof() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn valid_vpk_from_bytes() -> Result<()> {
+    let vpk = VPKVersion1::from_bytes(&std::fs::read(common::PAK_V1_SINGLE_FILE)?)?;
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    assert!(
+        vpk == VPKVersion1::try_from(&mut file)?,
+        "VPK from bytes should match VPK from file"
+    );
+    Ok(())
+}
+
 #[test]
 fn valid_vpk_large() -> Result<()> {
     let mut file = File::open(common::PAK_V1_PORTAL2)?;
@@ -32,6 +48,312 @@ fn valid_vpk_large() -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "ordered-tree")]
+#[test]
+fn ordered_tree() -> Result<()> {
+    use vpk_plumber::pak::TreeEntries;
+
+    let mut file = File::open(common::PAK_V1_PORTAL2)?;
+    let mut vpk = VPKVersion1::try_from(&mut file)?;
+
+    let mut directory_order = Vec::new();
+    for parsed in TreeEntries::<_, VPKDirectoryEntry>::new(
+        &mut file,
+        mem::size_of::<VPKHeaderV1>() as u64,
+        vpk.header.tree_size.into(),
+    )? {
+        directory_order.push(parsed?.file_path);
+    }
+
+    assert!(
+        vpk.tree.files.keys().eq(directory_order.iter()),
+        "Files should iterate in the order of the directory file"
+    );
+
+    let removed = directory_order.remove(directory_order.len() / 2);
+    vpk.tree.remove_file(&removed);
+    assert!(
+        vpk.tree.files.keys().eq(directory_order.iter()),
+        "Removing a file should keep the order of the other files"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn tree_iter_large() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_PORTAL2)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    let mut count = 0;
+    for parsed in VPKTreeIter::<_, VPKDirectoryEntry>::new(
+        &mut file,
+        mem::size_of::<VPKHeaderV1>() as u64,
+        vpk.header.tree_size.into(),
+    )? {
+        let (file_path, entry) = parsed?;
+        assert_eq!(
+            vpk.tree.files.get(&file_path),
+            Some(&entry),
+            "Entry for {file_path} does not match"
+        );
+        count += 1;
+    }
+
+    assert_eq!(count, vpk.tree.files.len(), "Tree size does not match");
+    Ok(())
+}
+
+#[test]
+fn tree_iter_stop_early() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_PORTAL2)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+    let wanted = vpk.tree.files.keys().next().unwrap().clone();
+
+    let mut iter = VPKTreeIter::<_, VPKDirectoryEntry>::new(
+        &mut file,
+        mem::size_of::<VPKHeaderV1>() as u64,
+        vpk.header.tree_size.into(),
+    )?;
+    let found = iter.find(|parsed| matches!(parsed, Ok((file_path, _)) if *file_path == wanted));
+
+    assert!(found.is_some(), "{wanted} should be found");
+    Ok(())
+}
+
+#[test]
+fn incremental_vpk_large() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_PORTAL2)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+
+    for budget in [1, 50, 10_000] {
+        let mut parser = IncrementalTreeParser::<_, VPKDirectoryEntry>::new(
+            &mut file,
+            mem::size_of::<VPKHeaderV1>() as u64,
+            vpk.header.tree_size.into(),
+        )?;
+
+        while let StepResult::Parsed(count) = parser.step(budget)? {
+            assert!(count <= budget, "A step should not exceed its budget");
+        }
+
+        assert!(parser.is_done(), "Parser should be done");
+
+        let tree = parser.into_tree();
+        assert_eq!(tree.files.len(), common::PORTAL2_TREE_COUNT);
+        assert!(
+            tree == vpk.tree,
+            "Incremental tree should match the eager parse with a budget of {budget}",
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn invalid_tree_size_too_large() -> Result<()> {
+    let mut data = std::fs::read(common::PAK_V1_SINGLE_FILE)?;
+    data[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    let vpk = VPKVersion1::from_reader(&mut Cursor::new(&data));
+    assert!(
+        vpk.is_err_and(|x| matches!(x, vpk_plumber::pak::Error::InvalidTreeSize(_))),
+        "Tree size past the end of the file should be invalid",
+    );
+
+    // Deliberately truncated files can still be parsed
+    let options = ParseOptions {
+        allow_truncated_tree: true,
+        ..Default::default()
+    };
+    let vpk = VPKVersion1::from_reader_with_options(&mut Cursor::new(&data), &options)?;
+    assert_eq!(vpk.tree.files.len(), 1, "Tree should still be parsed");
+
+    Ok(())
+}
+
+#[test]
+fn invalid_tree_size_zero() -> Result<()> {
+    let mut data = std::fs::read(common::PAK_V1_SINGLE_FILE)?;
+    data[8..12].copy_from_slice(&0u32.to_le_bytes());
+
+    let vpk = VPKVersion1::from_reader(&mut Cursor::new(&data));
+    assert!(
+        vpk.is_err_and(|x| matches!(x, vpk_plumber::pak::Error::InvalidTreeSize(_))),
+        "Empty tree followed by data should be invalid",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn truncated_tree() -> Result<()> {
+    let data = std::fs::read(common::PAK_V1_SINGLE_FILE)?;
+    let tree_start = mem::size_of::<VPKHeaderV1>();
+    let tree_size = (data.len() - tree_start) as u64;
+
+    for len in tree_start..data.len() {
+        let tree = VPKTree::<VPKDirectoryEntry>::from(
+            &mut Cursor::new(&data[..len]),
+            tree_start as u64,
+            tree_size,
+        );
+
+        assert!(
+            tree.is_err_and(|x| matches!(x, vpk_plumber::pak::Error::BadData(_))),
+            "Tree truncated to {len} bytes should be invalid",
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn garbage_tree() -> Result<()> {
+    let mut data = std::fs::read(common::PAK_V1_SINGLE_FILE)?;
+    let tree_start = mem::size_of::<VPKHeaderV1>();
+    data[tree_start..].fill(0xAB);
+
+    let vpk = VPKVersion1::from_reader(&mut Cursor::new(&data));
+    assert!(
+        vpk.is_err_and(|x| matches!(x, vpk_plumber::pak::Error::BadData(_))),
+        "Garbage tree should be invalid",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn overlong_tree_string() -> Result<()> {
+    let mut data = std::fs::read(common::PAK_V1_SINGLE_FILE)?;
+    let tree_start = mem::size_of::<VPKHeaderV1>();
+    data.truncate(tree_start);
+    data.resize(tree_start + 10 * 1024 * 1024, b'a');
+    let tree_size = u32::try_from(data.len() - tree_start)?;
+    data[8..12].copy_from_slice(&tree_size.to_le_bytes());
+
+    let vpk = VPKVersion1::from_reader(&mut Cursor::new(&data));
+    assert!(
+        vpk.is_err_and(
+            |x| matches!(x, vpk_plumber::pak::Error::Util { ref source, .. }
+            if source.to_string().starts_with("string longer than"))
+        ),
+        "Extension without a terminator within the length limit should be invalid",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn bad_terminator() -> Result<()> {
+    let options = ParseOptions {
+        record_entry_spans: true,
+        ..Default::default()
+    };
+    let mut data = std::fs::read(common::PAK_V1_SINGLE_FILE)?;
+    let vpk = VPKVersion1::from_reader_with_options(&mut Cursor::new(&data), &options)?;
+
+    // Written by some old tools
+    let span = vpk.tree.entry_span(common::SINGLE_FILE_NAME).unwrap();
+    let terminator_offset = usize::try_from(span.end)? - 2;
+    data[terminator_offset..terminator_offset + 2].copy_from_slice(&0u16.to_le_bytes());
+
+    let vpk = VPKVersion1::from_reader(&mut Cursor::new(&data));
+    assert!(
+        vpk.is_err_and(|x| matches!(x, Error::InvalidEntryTerminator(_))),
+        "Bad terminators should be rejected by default",
+    );
+
+    let options = ParseOptions {
+        allow_bad_terminators: true,
+        collect_warnings: true,
+        ..Default::default()
+    };
+    let vpk = VPKVersion1::from_reader_with_options(&mut Cursor::new(&data), &options)?;
+    assert_eq!(
+        vpk.read_file(
+            common::DIR_V1,
+            common::SINGLE_FILE_ARCHIVE,
+            common::SINGLE_FILE_NAME
+        ),
+        Some(common::SINGLE_FILE_CONTENT.as_bytes().to_vec()),
+        "File should still be readable"
+    );
+
+    let warnings = vpk.tree.parse_warnings();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].kind, ParseWarningKind::BadTerminator);
+    assert_eq!(warnings[0].offset, terminator_offset as u64);
+    assert_eq!(warnings[0].path.as_deref(), Some(common::SINGLE_FILE_NAME));
+    assert!(
+        vpk.validate()
+            .iter()
+            .any(|issue| issue.kind == ValidationIssueKind::BadTerminator),
+        "Validation should still report the terminator"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn duplicate_paths() -> Result<()> {
+    // Two entries for test/file.txt, followed by bytes the tree size covers
+    let mut data = b"txt\0test\0".to_vec();
+    let mut entry_starts = Vec::new();
+    for crc in [1, 2] {
+        data.extend_from_slice(b"file\0");
+        entry_starts.push(data.len() as u64);
+        VPKDirectoryEntry {
+            crc,
+            ..Default::default()
+        }
+        .write(&mut data)?;
+    }
+    data.extend_from_slice(b"\0\0\0");
+    let trailing_start = data.len() as u64;
+    data.extend_from_slice(&[0xAB; 4]);
+    let size = data.len() as u64;
+
+    let parse = |duplicates| {
+        let options = ParseOptions {
+            duplicates,
+            collect_warnings: true,
+            ..Default::default()
+        };
+        VPKTree::<VPKDirectoryEntry>::from_with_options(&mut Cursor::new(&data), 0, size, &options)
+    };
+
+    let tree = parse(DuplicatePolicy::LastWins)?;
+    assert_eq!(tree.get(common::SINGLE_FILE_NAME).unwrap().crc, 2);
+
+    let warnings: Vec<_> = tree
+        .parse_warnings()
+        .iter()
+        .map(|warning| (warning.kind, warning.offset))
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            (ParseWarningKind::DuplicatePath, entry_starts[1]),
+            (ParseWarningKind::TrailingData, trailing_start)
+        ]
+    );
+
+    let tree = parse(DuplicatePolicy::FirstWins)?;
+    assert_eq!(tree.get(common::SINGLE_FILE_NAME).unwrap().crc, 1);
+
+    assert!(
+        parse(DuplicatePolicy::Reject).is_err_and(|x| matches!(x, Error::BadData(_))),
+        "Duplicate paths should be rejected",
+    );
+
+    // Warnings are only recorded when asked for
+    let tree = VPKTree::<VPKDirectoryEntry>::from(&mut Cursor::new(&data), 0, size)?;
+    assert!(tree.parse_warnings().is_empty());
+
+    Ok(())
+}
+
 #[test]
 fn invalid_vpk() -> Result<()> {
     let mut file = File::open(common::PAK_V1_ARCHIVE)?;
@@ -83,3 +405,169 @@ fn vpk_revpk_empty() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn archive_reader() -> Result<()> {
+    let vpk_dir = tempfile::tempdir()?;
+    let vpk_path = vpk_dir.path().to_str().unwrap();
+
+    let mut builder = PakBuilder::new().max_archive_size(64 * 1024);
+    let files: Vec<(String, Vec<u8>)> = (0..500)
+        .map(|i: u32| {
+            (
+                format!("files/file_{i:0>3}.txt"),
+                i.to_le_bytes().repeat(64),
+            )
+        })
+        .collect();
+    for (file_path, data) in &files {
+        builder.add_file(file_path, data.clone());
+    }
+    let report = builder.build(vpk_path, "pak01")?;
+
+    let vpk = VPKVersion1::from_file(&mut File::open(vpk_dir.path().join("pak01_dir.vpk"))?)?;
+    let mut reader = vpk.reader(vpk_path, "pak01");
+
+    for (file_path, data) in &files {
+        assert_eq!(
+            &reader.read_file(file_path)?,
+            data,
+            "Contents of {file_path} should match"
+        );
+    }
+    assert_eq!(
+        reader.open_archive_count(),
+        usize::from(report.archive_count),
+        "Every archive should only be opened once"
+    );
+
+    let mut out = Vec::new();
+    let written = reader.read_file_to(&files[0].0, &mut out)?;
+    assert_eq!(written, out.len() as u64);
+    assert_eq!(out, files[0].1, "Contents written to a writer should match");
+
+    assert!(
+        matches!(
+            reader.read_file("files/missing.txt"),
+            Err(Error::FileNotFound(_))
+        ),
+        "Missing files should not be found"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn open_v1() -> Result<()> {
+    let vpk = vpk_plumber::pak::open_v1(std::path::Path::new(common::PAK_V1_SINGLE_FILE))?;
+    assert_eq!(vpk.tree.files.len(), 1, "Tree size does not match");
+
+    assert!(
+        matches!(
+            vpk_plumber::pak::open_v1(std::path::Path::new("missing_dir.vpk")),
+            Err(Error::Io(_))
+        ),
+        "Missing files should not open"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn total_entry_size() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_EMPTY)?;
+    assert_eq!(VPKVersion1::try_from(&mut file)?.tree.total_entry_size(), 0);
+
+    // The single file fixtures store the file in an archive and after the tree respectively
+    for path in [common::PAK_V1_SINGLE_FILE, common::PAK_V1_SINGLE_FILE_EOF] {
+        let mut file = File::open(path)?;
+        let vpk = VPKVersion1::try_from(&mut file)?;
+        assert_eq!(
+            vpk.tree.total_entry_size(),
+            common::SINGLE_FILE_CONTENT.len() as u64,
+            "Total size of {path} should be the size of its file"
+        );
+    }
+
+    let mut file = File::open(common::PAK_V1_PORTAL2)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+    assert_eq!(
+        vpk.tree.total_entry_size(),
+        vpk.file_paths()
+            .iter()
+            .filter_map(|path| vpk.file_size(path))
+            .sum::<u64>()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn tree_len_contains() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_EMPTY)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+    assert_eq!(vpk.tree.len(), 0);
+    assert!(vpk.tree.is_empty(), "Empty tree should be empty");
+    assert!(!vpk.tree.contains(common::SINGLE_FILE_NAME));
+
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+    assert_eq!(vpk.tree.len(), 1);
+    assert!(!vpk.tree.is_empty(), "Single file tree should not be empty");
+    assert!(vpk.tree.contains(common::SINGLE_FILE_NAME));
+    assert!(
+        vpk.tree
+            .contains(&common::SINGLE_FILE_NAME.replace('/', "\\")),
+        "Backslashes should be treated as separators"
+    );
+    assert!(
+        vpk.tree.contains(&format!("/{}", common::SINGLE_FILE_NAME)),
+        "Leading separators should be ignored"
+    );
+    assert!(!vpk.tree.contains("test/missing.txt"));
+
+    let mut file = File::open(common::PAK_V1_PORTAL2)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+    assert_eq!(vpk.tree.len(), vpk.tree.files.len());
+    assert!(
+        vpk.tree.len() > 1,
+        "Large tree should contain several files"
+    );
+    assert!(
+        vpk.tree.files.keys().all(|path| vpk.tree.contains(path)),
+        "Tree should contain all of its files"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn error_display() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let vpk = VPKVersion1::try_from(&mut file)?;
+    let error = vpk
+        .try_read_file(common::DIR_V1, common::SINGLE_FILE_ARCHIVE, "missing.txt")
+        .unwrap_err();
+    assert_eq!(error.to_string(), "file not found in VPK: missing.txt");
+
+    let mut file = File::open(common::PAK_V1_ARCHIVE)?;
+    let Err(error) = VPKVersion1::try_from(&mut file) else {
+        panic!("VPK file should be invalid");
+    };
+    assert!(
+        error.to_string().starts_with("invalid VPK signature: "),
+        "Unexpected message: {error}"
+    );
+
+    let error = Error::CrcMismatch {
+        expected: 0x1234,
+        found: 0xABCD,
+        file: common::SINGLE_FILE_NAME.to_string(),
+    };
+    assert_eq!(
+        error.to_string(),
+        "CRC mismatch in test/file.txt: expected 0x00001234, found 0x0000ABCD"
+    );
+
+    Ok(())
+}
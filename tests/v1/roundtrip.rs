@@ -1,6 +1,14 @@
-use std::{fs::File, path::Path};
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter, Cursor, Read, Seek, Write},
+    path::Path,
+};
 
-use vpk_plumber::pak::{PakWorker, PakWriter, v1::VPKVersion1};
+use vpk_plumber::pak::{
+    DirEntry, PakReader, PakWorker, PakWriter, ParseOptions, VPKDirectoryEntry, VPKTree,
+    patch_entry_in_place,
+    v1::{VPKHeaderV1, VPKVersion1},
+};
 
 use crate::common::{self, Result};
 
@@ -80,3 +88,721 @@ where
 
     Ok(())
 }
+
+#[test]
+fn tree_preload() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let vpk = VPKVersion1::from_file(&mut file)?;
+
+    // Move the whole file into preload data
+    let mut entry = vpk.tree.files[common::SINGLE_FILE_NAME].clone();
+    entry.entry_length = 0;
+
+    let mut tree = VPKTree::new();
+    tree.insert_file_with_preload(
+        common::SINGLE_FILE_NAME,
+        entry,
+        common::SINGLE_FILE_CONTENT.as_bytes().to_vec(),
+    )?;
+
+    assert_eq!(
+        tree.files[common::SINGLE_FILE_NAME].preload_length as usize,
+        common::SINGLE_FILE_CONTENT.len(),
+        "Preload length should match the preload data",
+    );
+
+    let mut out = tempfile::tempfile()?;
+    tree.write(&mut out)?;
+    let size = out.stream_position()?;
+
+    let tree_result = VPKTree::<VPKDirectoryEntry>::from(&mut out, 0, size)?;
+    assert!(tree == tree_result, "Trees should match");
+
+    Ok(())
+}
+
+#[test]
+fn tree_root_files() -> Result<()> {
+    // Files in the root directory and files without an extension are stored with a " " component,
+    // an empty one would end the directory list of the extension
+    let mut tree = VPKTree::new();
+    for file_path in ["root.txt", "README", "bin/LICENSE", "test/file.txt"] {
+        tree.insert_file(file_path, file_path.as_bytes(), 0, 0, 0, false)?;
+    }
+
+    let mut out = tempfile::tempfile()?;
+    tree.write(&mut out)?;
+    let size = out.stream_position()?;
+
+    let tree_result = VPKTree::<VPKDirectoryEntry>::from(&mut out, 0, size)?;
+    assert_eq!(
+        tree_result.sorted_paths(),
+        ["README", "bin/LICENSE", "root.txt", "test/file.txt"],
+        "Paths should survive the round trip"
+    );
+    assert!(tree == tree_result, "Trees should match");
+
+    Ok(())
+}
+
+#[test]
+fn buffered_io() -> Result<()> {
+    let mut file = BufReader::new(File::open(common::PAK_V1_PORTAL2)?);
+    let vpk = VPKVersion1::from_reader(&mut file)?;
+
+    let mut out = BufWriter::new(Cursor::new(Vec::new()));
+    vpk.header.write(&mut out)?;
+    vpk.tree.write(&mut out)?;
+
+    let mut out = out.into_inner()?;
+    out.rewind()?;
+    let header = VPKHeaderV1::from(&mut out)?;
+    assert_eq!(vpk.header, header, "Headers should match");
+
+    let tree_start = out.stream_position()?;
+    let tree = VPKTree::<VPKDirectoryEntry>::from(&mut out, tree_start, header.tree_size.into())?;
+    assert!(vpk.tree == tree, "Trees should match");
+
+    Ok(())
+}
+
+#[test]
+fn tree_preload_unsupported() -> Result<()> {
+    /// An entry format without preload data, relying on the default of `set_preload_length`.
+    struct NoPreloadEntry;
+
+    impl DirEntry for NoPreloadEntry {
+        fn from<R: Read + Seek>(_file: &mut R) -> vpk_plumber::pak::Result<Self> {
+            Ok(Self)
+        }
+
+        fn write<W: Write>(&self, _file: &mut W) -> vpk_plumber::pak::Result<()> {
+            Ok(())
+        }
+
+        fn get_preload_length(&self) -> usize {
+            0
+        }
+    }
+
+    let mut tree = VPKTree::new();
+    tree.insert_file_with_preload(common::SINGLE_FILE_NAME, NoPreloadEntry, Vec::new())?;
+
+    let result = tree.insert_file_with_preload(
+        common::SINGLE_FILE_NAME,
+        NoPreloadEntry,
+        common::SINGLE_FILE_CONTENT.as_bytes().to_vec(),
+    );
+    assert!(
+        result.is_err_and(|x| matches!(x, vpk_plumber::pak::Error::BadData(_))),
+        "Preload data should be rejected for entries without preload data",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn tree_preload_mismatch() -> Result<()> {
+    let mut entry = VPKDirectoryEntry::new();
+    entry.preload_length = 4;
+
+    let mut tree = VPKTree::new();
+    tree.files
+        .insert(common::SINGLE_FILE_NAME.to_string(), entry);
+
+    let result = tree.write(&mut tempfile::tempfile()?);
+
+    assert!(
+        result.is_err_and(|x| matches!(x, vpk_plumber::pak::Error::BadData(_))),
+        "Writing an entry without its preload data should fail",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn tree_preload_bytes() -> Result<()> {
+    let mut tree = VPKTree::new();
+    tree.insert_file(
+        common::SINGLE_FILE_NAME,
+        common::SINGLE_FILE_CONTENT.as_bytes(),
+        0,
+        0,
+        4,
+        false,
+    )?;
+    tree.insert_file("test/other.txt", b"other data", 0, 5, 0, false)?;
+
+    // Matching preload data survives a round trip byte for byte
+    let out = tempfile::NamedTempFile::new()?;
+    tree.write(&mut File::create(&out)?)?;
+    let data = std::fs::read(&out)?;
+
+    let tree_result =
+        VPKTree::<VPKDirectoryEntry>::from(&mut File::open(&out)?, 0, data.len() as u64)?;
+    let rewritten = tempfile::NamedTempFile::new()?;
+    tree_result.write(&mut File::create(&rewritten)?)?;
+    assert_eq!(
+        std::fs::read(&rewritten)?,
+        data,
+        "Rewritten tree should be identical"
+    );
+
+    // Preload data of another length than the entry describes names the offending path
+    tree.preload
+        .get_mut(common::SINGLE_FILE_NAME)
+        .unwrap()
+        .push(0);
+    let result = tree.write(&mut tempfile::tempfile()?);
+    assert!(
+        result.is_err_and(
+            |x| matches!(x, vpk_plumber::pak::Error::BadData(ref message)
+            if message.contains(common::SINGLE_FILE_NAME))
+        ),
+        "Writing mismatched preload data should fail",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn from_scratch() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    let material = b"material data";
+    let texture = b"texture data";
+    std::fs::write(
+        out_dir.path().join("scratch_000.vpk"),
+        [material.as_slice(), texture.as_slice()].concat(),
+    )?;
+
+    let mut vpk = VPKVersion1::new();
+    vpk.upsert_entry("materials/test.vmt", material, 0, 0, None)?;
+    vpk.upsert_entry(
+        "materials/test.vtf",
+        texture,
+        0,
+        material.len() as u32,
+        Some(b"header ".to_vec()),
+    )?;
+    vpk.upsert_entry("materials/removed.vmt", material, 0, 0, Some(vec![1]))?;
+
+    assert!(
+        vpk.remove_entry("materials/removed.vmt").is_some(),
+        "Removed entry should be returned"
+    );
+    assert!(
+        !vpk.tree.preload.contains_key("materials/removed.vmt"),
+        "Preload data should be removed with the entry"
+    );
+
+    vpk.write_dir(out_dir.path().join("scratch_dir.vpk").to_str().unwrap())?;
+
+    let mut file = File::open(out_dir.path().join("scratch_dir.vpk"))?;
+    let vpk_result = VPKVersion1::from_file(&mut file)?;
+
+    assert_eq!(vpk_result.tree.files.len(), 2, "VPK sizes don't match");
+    assert_eq!(
+        vpk_result.read_file(archive_path, "scratch", "materials/test.vmt"),
+        Some(material.to_vec()),
+        "File contents should match"
+    );
+    assert_eq!(
+        vpk_result.read_file(archive_path, "scratch", "materials/test.vtf"),
+        Some(b"header texture data".to_vec()),
+        "File contents should include the preload data"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn add_file() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    let mut vpk = VPKVersion1::new();
+    let mut archive = Vec::new();
+
+    let files: [(&str, &[u8]); 2] = [
+        ("materials/test.vmt", b"material data"),
+        ("materials/models/test.vtf", b"texture data"),
+    ];
+
+    for (file_path, data) in files {
+        let offset = vpk.add_file(file_path, data, 0)?;
+        assert_eq!(
+            offset as usize,
+            archive.len(),
+            "Files should be appended to the archive"
+        );
+        archive.extend_from_slice(data);
+    }
+
+    assert_eq!(vpk.tree.files.len(), 2, "VPK sizes don't match");
+    assert!(
+        vpk.tree.preload.is_empty(),
+        "No preload data should be added"
+    );
+
+    let entry = &vpk.tree.files["materials/models/test.vtf"];
+    assert_eq!(entry.archive_index, 0);
+    assert_eq!(entry.entry_offset, 13);
+    assert_eq!(entry.entry_length, 12);
+
+    std::fs::write(out_dir.path().join("scratch_000.vpk"), archive)?;
+
+    for (file_path, data) in files {
+        assert_eq!(
+            vpk.read_file(archive_path, "scratch", file_path),
+            Some(data.to_vec()),
+            "File contents should match"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn add_file_archive_ends() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let mut vpk = VPKVersion1::try_from(&mut file)?;
+    let entry = &vpk.tree.files[common::SINGLE_FILE_NAME];
+    let parsed_end = entry.entry_offset + entry.entry_length;
+
+    assert_eq!(
+        vpk.add_file("test/a.txt", b"aaaa", 0)?,
+        parsed_end,
+        "Files should be appended after the parsed entries"
+    );
+    assert_eq!(
+        vpk.add_file("test/other.txt", b"other", 1)?,
+        0,
+        "Every archive should be tracked separately"
+    );
+
+    vpk.upsert_entry("test/b.txt", b"bb", 0, 100, None)?;
+    assert_eq!(
+        vpk.add_file("test/c.txt", b"c", 0)?,
+        102,
+        "Entries upserted after the end should move it"
+    );
+
+    assert!(vpk.remove_entry("test/c.txt").is_some());
+    assert_eq!(
+        vpk.add_file("test/d.txt", b"d", 0)?,
+        103,
+        "Removed files should not free up their space"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn tree_insert_file() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+
+    let mut vpk = VPKVersion1::new();
+    let mut archive = Vec::new();
+
+    let files: [(&str, &[u8]); 3] = [
+        ("materials/test.vmt", b"material data"),
+        ("materials\\models\\test.vtf", b"texture data"),
+        ("scripts/small.txt", b"tiny"),
+    ];
+
+    for (file_path, data) in files {
+        let entry_offset = u32::try_from(archive.len())?;
+        vpk.tree
+            .insert_file(file_path, data, 0, entry_offset, 8, false)?;
+        archive.extend_from_slice(&data[data.len().min(8)..]);
+    }
+
+    let entry = &vpk.tree.files["materials/models/test.vtf"];
+    assert_eq!(entry.preload_length, 8);
+    assert_eq!(entry.entry_offset, 5);
+    assert_eq!(entry.entry_length, 4);
+    assert_eq!(vpk.tree.preload["materials/models/test.vtf"], b"texture ");
+
+    let entry = &vpk.tree.files["scripts/small.txt"];
+    assert_eq!(entry.preload_length, 4);
+    assert_eq!(
+        entry.entry_length, 0,
+        "Small files should be stored entirely in the preload data"
+    );
+
+    assert!(
+        vpk.tree
+            .insert_file("materials/test.vmt", b"other", 0, 0, 0, false)
+            .is_err(),
+        "Existing files should not be overwritten"
+    );
+    assert_eq!(vpk.tree.files["materials/test.vmt"].preload_length, 8);
+
+    std::fs::write(out_dir.path().join("scratch_000.vpk"), archive)?;
+
+    for (file_path, data) in files {
+        assert_eq!(
+            vpk.read_file(archive_path, "scratch", file_path),
+            Some(data.to_vec()),
+            "File contents should match"
+        );
+    }
+
+    vpk.tree
+        .insert_file("materials/test.vmt", b"other", 1, 0, 0, true)?;
+    let entry = &vpk.tree.files["materials/test.vmt"];
+    assert_eq!(
+        (
+            entry.archive_index,
+            entry.preload_length,
+            entry.entry_length
+        ),
+        (1, 0, 5)
+    );
+    assert!(
+        !vpk.tree.preload.contains_key("materials/test.vmt"),
+        "Old preload data should be removed"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn tree_rename() -> Result<()> {
+    let mut tree = VPKTree::new();
+    tree.insert_file_with_preload("test/preload.txt", VPKDirectoryEntry::new(), vec![1, 2, 3])?;
+    tree.insert_file_with_preload("test/other.txt", VPKDirectoryEntry::new(), Vec::new())?;
+
+    tree.rename_file("test/preload.txt", "test/renamed.txt")?;
+
+    assert!(!tree.files.contains_key("test/preload.txt"));
+    assert!(!tree.preload.contains_key("test/preload.txt"));
+    assert_eq!(
+        tree.preload.get("test/renamed.txt"),
+        Some(&vec![1, 2, 3]),
+        "Preload data should move with its file"
+    );
+    assert_eq!(tree.files["test/renamed.txt"].preload_length, 3);
+    tree.validate_preload()?;
+
+    assert!(
+        tree.rename_file("test/renamed.txt", "test/other.txt")
+            .is_err(),
+        "Renaming onto an existing file should fail"
+    );
+    assert!(
+        tree.rename_file("test/missing.txt", "test/new.txt")
+            .is_err_and(|x| matches!(x, vpk_plumber::pak::Error::FileNotFound(_))),
+        "Renaming a missing file should fail"
+    );
+    assert_eq!(
+        tree.files.len(),
+        2,
+        "Failed renames should not modify the tree"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn tree_remove() -> Result<()> {
+    let mut tree = VPKTree::new();
+    tree.insert_file_with_preload("test/preload.txt", VPKDirectoryEntry::new(), vec![1, 2, 3])?;
+
+    assert!(tree.remove_file("test/preload.txt").is_some());
+    assert!(tree.files.is_empty());
+    assert!(tree.preload.is_empty(), "Preload data should be removed");
+    assert!(tree.remove_file("test/preload.txt").is_none());
+
+    Ok(())
+}
+
+#[test]
+fn tree_mixed_separators() -> Result<()> {
+    let mut tree = VPKTree::new();
+    tree.insert_file_with_preload("test\\preload.txt", VPKDirectoryEntry::new(), vec![1, 2, 3])?;
+    tree.insert_file_with_preload("/test//other.txt", VPKDirectoryEntry::new(), Vec::new())?;
+
+    assert!(tree.files.contains_key("test/preload.txt"));
+    assert!(tree.preload.contains_key("test/preload.txt"));
+    assert!(tree.files.contains_key("test/other.txt"));
+
+    tree.rename_file("./test\\preload.txt", "test//renamed.txt")?;
+    assert!(!tree.files.contains_key("test/preload.txt"));
+    assert_eq!(tree.preload.get("test/renamed.txt"), Some(&vec![1, 2, 3]));
+    assert!(
+        tree.rename_file("test/renamed.txt", "test\\other.txt")
+            .is_err(),
+        "Renaming onto an existing file with different separators should fail"
+    );
+
+    assert!(tree.remove_file("test\\renamed.txt").is_some());
+    assert!(tree.remove_file("/test/other.txt").is_some());
+    assert!(tree.files.is_empty());
+    assert!(tree.preload.is_empty(), "Preload data should be removed");
+
+    Ok(())
+}
+
+#[test]
+fn tree_edit_roundtrip() -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let archive_path = out_dir.path().to_str().unwrap();
+    std::fs::copy(
+        common::PAK_V1_ARCHIVE,
+        out_dir.path().join("edited_000.vpk"),
+    )?;
+
+    let mut vpk = VPKVersion1::from_file(&mut File::open(common::PAK_V1_SINGLE_FILE)?)?;
+    vpk.tree.insert_file_with_preload(
+        "test/preload.txt",
+        VPKDirectoryEntry::new(),
+        b"preload".to_vec(),
+    )?;
+    vpk.tree.insert_file_with_preload(
+        "test/removed.txt",
+        VPKDirectoryEntry::new(),
+        b"removed".to_vec(),
+    )?;
+
+    vpk.tree
+        .rename_file(common::SINGLE_FILE_NAME, "test/renamed.txt")?;
+    vpk.tree
+        .rename_file("test/preload.txt", "other/preload.txt")?;
+    assert!(vpk.tree.remove_file("test/removed.txt").is_some());
+
+    vpk.write_dir(out_dir.path().join("edited_dir.vpk").to_str().unwrap())?;
+    let vpk = VPKVersion1::from_file(&mut File::open(out_dir.path().join("edited_dir.vpk"))?)?;
+
+    assert_eq!(
+        vpk.tree.sorted_paths(),
+        ["other/preload.txt", "test/renamed.txt"],
+        "Edits should survive writing and reading the directory"
+    );
+    assert!(!vpk.tree.preload.contains_key("test/removed.txt"));
+    assert_eq!(
+        vpk.read_file(archive_path, "edited", "test/renamed.txt"),
+        Some(common::SINGLE_FILE_CONTENT.as_bytes().to_vec()),
+        "Renamed file should still point at its archive data"
+    );
+    assert_eq!(
+        vpk.tree.get_preload("other/preload.txt"),
+        Some(&b"preload".to_vec()),
+        "Preload data should move with its file"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn patch_entry() -> Result<()> {
+    let out = tempfile::NamedTempFile::new()?;
+    std::fs::copy(common::PAK_V1_SINGLE_FILE, out.path())?;
+    let original = std::fs::read(out.path())?;
+
+    let options = ParseOptions {
+        record_entry_spans: true,
+        ..Default::default()
+    };
+    let vpk = VPKVersion1::from_file_with_options(&mut File::open(out.path())?, &options)?;
+    let span = vpk
+        .tree
+        .entry_span(common::SINGLE_FILE_NAME)
+        .expect("Span should be recorded");
+    assert_eq!(
+        span.end - span.start,
+        18,
+        "Span should cover the whole entry"
+    );
+
+    let mut entry = vpk.tree.files[common::SINGLE_FILE_NAME].clone();
+    entry.crc = 0xDEAD_BEEF;
+    patch_entry_in_place(
+        &mut OpenOptions::new().write(true).open(out.path())?,
+        span.clone(),
+        &entry,
+    )?;
+
+    let vpk = VPKVersion1::from_file(&mut File::open(out.path())?)?;
+    assert_eq!(vpk.tree.files[common::SINGLE_FILE_NAME].crc, 0xDEAD_BEEF);
+    assert!(
+        vpk.tree.entry_span(common::SINGLE_FILE_NAME).is_none(),
+        "Spans should only be recorded when requested"
+    );
+
+    let patched = std::fs::read(out.path())?;
+    let start = usize::try_from(span.start)?;
+    assert_eq!(patched.len(), original.len());
+    assert_eq!(patched[..start], original[..start]);
+    assert_eq!(patched[start..start + 4], 0xDEAD_BEEF_u32.to_le_bytes());
+    assert_eq!(
+        patched[start + 4..],
+        original[start + 4..],
+        "Only the CRC should change"
+    );
+
+    assert!(
+        patch_entry_in_place(
+            &mut OpenOptions::new().write(true).open(out.path())?,
+            span.start..span.end + 1,
+            &entry,
+        )
+        .is_err_and(|x| matches!(x, vpk_plumber::pak::Error::BadData(_))),
+        "Entries that don't fit the span should be rejected"
+    );
+    assert_eq!(
+        std::fs::read(out.path())?,
+        patched,
+        "Nothing should be written"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn rebuild_header_tree_size() -> Result<()> {
+    let mut vpk = VPKVersion1::from_file(&mut File::open(common::PAK_V1_SINGLE_FILE)?)?;
+    let original_size = vpk.header.tree_size;
+
+    vpk.rebuild_header_tree_size()?;
+    assert_eq!(
+        vpk.header.tree_size, original_size,
+        "Unchanged trees should keep their size"
+    );
+
+    vpk.tree
+        .insert_file("materials/added.vmt", b"added", 0, 0, 0, false)?;
+    vpk.rebuild_header_tree_size()?;
+    assert!(vpk.header.tree_size > original_size);
+
+    let out = tempfile::NamedTempFile::new()?;
+    vpk.write_dir(out.path().to_str().unwrap())?;
+
+    let written = VPKVersion1::from_file(&mut File::open(&out)?)?;
+    assert_eq!(written.header.tree_size, vpk.header.tree_size);
+    assert_eq!(written.tree.files.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn modified_tree_size() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_SINGLE_FILE)?;
+    let mut vpk = VPKVersion1::from_file(&mut file)?;
+    let original_size = vpk.header.tree_size;
+
+    let entry = vpk.tree.files[common::SINGLE_FILE_NAME].clone();
+    vpk.tree
+        .files
+        .insert("materials/added/first.vmt".to_string(), entry.clone());
+    vpk.tree
+        .files
+        .insert("materials/added/second.vmt".to_string(), entry);
+
+    let out = tempfile::NamedTempFile::new()?;
+    vpk.write_dir(out.path().to_str().unwrap())?;
+
+    let vpk_result = VPKVersion1::from_file(&mut File::open(&out)?)?;
+    assert!(
+        vpk_result.header.tree_size > original_size,
+        "Tree size should grow with the added entries"
+    );
+    assert_eq!(vpk_result.tree.files.len(), 3, "VPK sizes don't match");
+    assert!(
+        vpk_result.tree.files == vpk.tree.files,
+        "VPK file data doesn't match"
+    );
+
+    let mut vpk = VPKVersion1::new();
+    vpk.tree
+        .files
+        .insert("materials/new.vmt".to_string(), VPKDirectoryEntry::new());
+
+    vpk.write_dir(out.path().to_str().unwrap())?;
+
+    let vpk_result = VPKVersion1::from_file(&mut File::open(&out)?)?;
+    assert!(
+        vpk_result.tree.files == vpk.tree.files,
+        "VPK file data doesn't match"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn write_dir_deterministic() -> Result<()> {
+    let mut file = File::open(common::PAK_V1_PORTAL2)?;
+    let vpk = VPKVersion1::from_file(&mut file)?;
+
+    let write = |vpk: &VPKVersion1| -> Result<Vec<u8>> {
+        let out = tempfile::NamedTempFile::new()?;
+        vpk.write_dir(out.path().to_str().unwrap())?;
+        Ok(std::fs::read(&out)?)
+    };
+
+    // Rewriting the written file must not move anything either
+    let bytes = write(&vpk)?;
+    assert!(write(&vpk)? == bytes, "Writes should be identical");
+    let vpk_result = VPKVersion1::from_bytes(&bytes)?;
+    assert!(
+        write(&vpk_result)? == bytes,
+        "Rewriting the written file should be identical"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn tree_sorted_paths() -> Result<()> {
+    let file_paths = [
+        "sound/b.wav",
+        "materials/z.vmt",
+        "materials/a.b.vmt",
+        "materials/a.vmt",
+        "README",
+        "materials/models/a.vmt",
+    ];
+
+    let tree_from = |file_paths: &mut dyn Iterator<Item = &&str>| {
+        let mut tree = VPKTree::<VPKDirectoryEntry>::new();
+        for file_path in file_paths {
+            tree.files
+                .insert((*file_path).to_string(), VPKDirectoryEntry::new());
+        }
+        tree
+    };
+
+    let tree = tree_from(&mut file_paths.iter());
+    assert_eq!(
+        tree.sorted_paths(),
+        [
+            "README",
+            "materials/a.b.vmt",
+            "materials/a.vmt",
+            "materials/z.vmt",
+            "materials/models/a.vmt",
+            "sound/b.wav",
+        ],
+        "Paths should be sorted by extension, directory and path"
+    );
+
+    // Writing the same tree twice, or a tree built in another order, results in the same bytes
+    let write = |tree: &VPKTree<VPKDirectoryEntry>| -> Result<Vec<u8>> {
+        let out = tempfile::NamedTempFile::new()?;
+        tree.write(&mut File::create(&out)?)?;
+        Ok(std::fs::read(&out)?)
+    };
+
+    let bytes = write(&tree)?;
+    assert_eq!(write(&tree)?, bytes, "Writes should be identical");
+    assert_eq!(
+        write(&tree_from(&mut file_paths.iter().rev()))?,
+        bytes,
+        "Insertion order should not matter"
+    );
+
+    Ok(())
+}
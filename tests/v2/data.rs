@@ -1,10 +1,13 @@
 use std::fs::File;
+use std::panic::{self, AssertUnwindSafe};
 
-use vpk_plumber::pak::{PakReader, v2::VPKVersion2};
+use vpk_plumber::pak::{
+    FileLocation, PakReader, PakWriter, ValidationIssueKind,
+    v2::{VPK_CAPS_V2, VPKVersion2},
+};
 
 use crate::common::{self, Result};
 
-#[ignore = "not yet implemented"]
 #[test]
 fn vpk_empty() -> Result<()> {
     let mut file = File::open(common::PAK_V2_EMPTY)?;
@@ -20,7 +23,6 @@ fn vpk_empty() -> Result<()> {
     Ok(())
 }
 
-#[ignore = "not yet implemented"]
 #[test]
 fn vpk_single_file() -> Result<()> {
     let mut file = File::open(common::PAK_V2_SINGLE_FILE)?;
@@ -30,7 +32,7 @@ fn vpk_single_file() -> Result<()> {
         .read_file(
             &String::from(common::DIR_V2),
             &String::from(common::SINGLE_FILE_ARCHIVE),
-            &String::from(common::SINGLE_FILE_NAME),
+            &String::from(common::SINGLE_FILE_NAME_V2),
         )
         .unwrap();
 
@@ -56,3 +58,292 @@ fn vpk_large() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn vpk_file_size() -> Result<()> {
+    let mut file = File::open(common::PAK_V2_SINGLE_FILE)?;
+    let vpk = VPKVersion2::try_from(&mut file)?;
+
+    assert_eq!(
+        vpk.file_size(common::SINGLE_FILE_NAME_V2),
+        Some(common::SINGLE_FILE_CONTENT.len() as u64)
+    );
+    assert_eq!(vpk.file_size("test/missing.txt"), None);
+    Ok(())
+}
+
+#[test]
+fn vpk_file_metadata() -> Result<()> {
+    let mut file = File::open(common::PAK_V2_SINGLE_FILE)?;
+    let vpk = VPKVersion2::try_from(&mut file)?;
+
+    assert_eq!(vpk.file_crc(common::SINGLE_FILE_NAME_V2), Some(0x4570_FA16));
+    assert_eq!(
+        vpk.file_location(common::SINGLE_FILE_NAME_V2),
+        Some(FileLocation::Archive {
+            index: 0,
+            offset: 0,
+            length: 9
+        })
+    );
+    assert_eq!(vpk.file_location("test/missing.txt"), None);
+    Ok(())
+}
+
+#[test]
+fn vpk_rebuild_header_tree_size() -> Result<()> {
+    let mut file = File::open(common::PAK_V2_SINGLE_FILE)?;
+    let mut vpk = VPKVersion2::try_from(&mut file)?;
+    let original_size = vpk.header.tree_size;
+
+    vpk.rebuild_header_tree_size()?;
+    assert_eq!(
+        vpk.header.tree_size, original_size,
+        "Unchanged trees should keep their size"
+    );
+
+    let removed = vpk.tree.sorted_paths()[0].to_string();
+    vpk.tree.remove_file(&removed);
+    vpk.rebuild_header_tree_size()?;
+    assert!(vpk.header.tree_size < original_size);
+
+    Ok(())
+}
+
+#[cfg(feature = "md5")]
+#[test]
+fn vpk_verify_archives() -> Result<()> {
+    let mut file = File::open(common::PAK_V2_SINGLE_FILE)?;
+    let vpk = VPKVersion2::try_from(&mut file)?;
+
+    let mismatches = vpk.verify_archives(common::DIR_V2, common::SINGLE_FILE_ARCHIVE)?;
+
+    assert!(mismatches.is_empty(), "Checksums should match");
+    Ok(())
+}
+
+#[cfg(feature = "md5")]
+#[test]
+fn vpk_verify_archives_corrupted() -> Result<()> {
+    use vpk_plumber::pak::v2::{ArchiveMD5Mismatch, MD5Target, VPKArchiveMD5SectionEntry};
+
+    let mut file = File::open(common::PAK_V2_SINGLE_FILE)?;
+    let mut vpk = VPKVersion2::try_from(&mut file)?;
+
+    // The fixture has no archive MD5 entries, so add one covering the file
+    let expected: [u8; 16] = md5::compute(common::SINGLE_FILE_CONTENT).into();
+    vpk.archive_md5_section_entries
+        .push(VPKArchiveMD5SectionEntry {
+            archive_index: 0,
+            starting_offset: 0,
+            count: common::SINGLE_FILE_CONTENT.len() as u32,
+            md5_checksum: expected,
+        });
+
+    let dir = tempfile::tempdir()?;
+    let archive_path = dir.path().to_str().unwrap();
+    std::fs::copy(
+        common::PAK_V2_SINGLE_FILE,
+        dir.path().join("single_file_dir.vpk"),
+    )?;
+
+    let mut archive = common::SINGLE_FILE_CONTENT.as_bytes().to_vec();
+    archive[0] ^= 0xFF;
+    std::fs::write(dir.path().join("single_file_000.vpk"), &archive)?;
+
+    let mismatches = vpk.verify_archives(archive_path, common::SINGLE_FILE_ARCHIVE)?;
+
+    assert_eq!(
+        mismatches,
+        vec![ArchiveMD5Mismatch {
+            target: MD5Target::Archive {
+                archive_index: 0,
+                starting_offset: 0,
+                count: common::SINGLE_FILE_CONTENT.len() as u32,
+            },
+            expected,
+            actual: md5::compute(&archive).into(),
+        }],
+        "Corrupted archive should be detected",
+    );
+
+    // Corrupt the tree in the directory file
+    let mut dir_file = std::fs::read(common::PAK_V2_SINGLE_FILE)?;
+    dir_file[30] ^= 0xFF;
+    std::fs::write(dir.path().join("single_file_dir.vpk"), &dir_file)?;
+
+    let targets: Vec<MD5Target> = vpk
+        .verify_archives(archive_path, common::SINGLE_FILE_ARCHIVE)?
+        .into_iter()
+        .map(|mismatch| mismatch.target)
+        .collect();
+
+    assert!(
+        targets.contains(&MD5Target::Tree),
+        "Corrupted tree should be detected",
+    );
+    assert!(
+        targets.contains(&MD5Target::File),
+        "Corrupted file should be detected",
+    );
+    assert!(
+        !targets.contains(&MD5Target::ArchiveMD5Section),
+        "Archive MD5 section is intact",
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn vpk_serde() -> Result<()> {
+    use vpk_plumber::pak::v2::{VPKArchiveMD5SectionEntry, VPKHeaderV2, VPKOtherMD5Section};
+
+    let mut file = File::open(common::PAK_V2_PORTAL)?;
+    let vpk = VPKVersion2::try_from(&mut file)?;
+
+    let json = serde_json::to_value(&vpk.other_md5_section)?;
+    let tree_checksum = json["tree_checksum"].as_str().unwrap();
+    assert_eq!(tree_checksum.len(), 32, "Checksum should be a hex string");
+    assert!(tree_checksum.chars().all(|c| c.is_ascii_hexdigit()));
+
+    let other_md5_section: VPKOtherMD5Section = serde_json::from_value(json)?;
+    assert_eq!(other_md5_section, vpk.other_md5_section);
+
+    let entries: Vec<VPKArchiveMD5SectionEntry> =
+        serde_json::from_str(&serde_json::to_string(&vpk.archive_md5_section_entries)?)?;
+    assert_eq!(entries, vpk.archive_md5_section_entries);
+
+    let header: VPKHeaderV2 = serde_json::from_str(&serde_json::to_string(&vpk.header)?)?;
+    assert_eq!(header, vpk.header);
+
+    let parsed: VPKVersion2 = serde_json::from_str(&serde_json::to_string(&vpk)?)?;
+    assert_eq!(parsed.header, vpk.header);
+    assert!(parsed.tree == vpk.tree, "Tree does not round-trip");
+    assert_eq!(parsed.file_data, vpk.file_data);
+    assert_eq!(parsed.other_md5_section, vpk.other_md5_section);
+
+    assert!(
+        serde_json::from_str::<VPKOtherMD5Section>(
+            r#"{"tree_checksum":"00","archive_md5_section_checksum":"","unknown":""}"#
+        )
+        .is_err(),
+        "Checksums of the wrong length should be rejected"
+    );
+
+    let zeros = "0".repeat(32);
+    let signed = format!("+f{}", &zeros[2..]);
+    assert!(
+        serde_json::from_str::<VPKOtherMD5Section>(&format!(
+            r#"{{"tree_checksum":"{signed}","archive_md5_section_checksum":"{zeros}","unknown":"{zeros}"}}"#
+        ))
+        .is_err(),
+        "Checksums with characters other than hex digits should be rejected"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_validate() -> Result<()> {
+    let mut file = File::open(common::PAK_V2_PORTAL)?;
+    let mut vpk = VPKVersion2::try_from(&mut file)?;
+    assert!(vpk.validate().is_empty(), "Loaded VPK should be valid");
+
+    vpk.header.archive_md5_section_size += 1;
+    vpk.header.signature_section_size = 0;
+    vpk.file_data.push(0);
+
+    let issues = vpk.validate();
+    assert!(
+        issues
+            .iter()
+            .all(|issue| issue.path.is_none()
+                && issue.kind == ValidationIssueKind::SectionSizeMismatch),
+        "Only the header should be invalid"
+    );
+    assert_eq!(
+        issues.len(),
+        if vpk.signature_section.is_some() {
+            3
+        } else {
+            2
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn vpk_capabilities() -> Result<()> {
+    let mut file = File::open(common::PAK_V2_SINGLE_FILE)?;
+    let vpk = VPKVersion2::try_from(&mut file)?;
+
+    // Unimplemented operations panic, so this fails once they are implemented without updating the capabilities
+    let read = panic::catch_unwind(AssertUnwindSafe(|| {
+        vpk.read_file(
+            common::DIR_V2,
+            common::SINGLE_FILE_ARCHIVE,
+            common::SINGLE_FILE_NAME_V2,
+        )
+    }))
+    .is_ok_and(|result| result.is_some_and(|data| data == common::SINGLE_FILE_CONTENT.as_bytes()));
+    assert_eq!(VPK_CAPS_V2.read, read, "Read capability is out of date");
+
+    let output = tempfile::NamedTempFile::new()?;
+    let write_dir = panic::catch_unwind(AssertUnwindSafe(|| {
+        vpk.write_dir(output.path().to_str().unwrap())
+    }))
+    .is_ok_and(|result| result.is_ok());
+    assert_eq!(
+        VPK_CAPS_V2.write_dir, write_dir,
+        "Write capability is out of date"
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "signature-verify")]
+#[test]
+fn vpk_verify_signature() -> Result<()> {
+    let mut file = File::open(common::PAK_V2_PORTAL)?;
+    let vpk = VPKVersion2::try_from(&mut file)?;
+    assert!(
+        vpk.verify_signature(
+            const_format::concatcp!(common::DIR_V2, "portal/"),
+            "portal_pak"
+        )?,
+        "Signature should be valid"
+    );
+    assert!(
+        vpk.verify_signature_from(&mut file)?,
+        "Signature should be valid when read from an open file"
+    );
+
+    // Corrupt the tree checksum, which is covered by the signature
+    let mut data = std::fs::read(common::PAK_V2_PORTAL)?;
+    let checksum_offset = 28
+        + vpk.header.tree_size
+        + vpk.header.file_data_section_size
+        + vpk.header.archive_md5_section_size;
+    data[checksum_offset as usize] ^= 0xFF;
+
+    let tampered = tempfile::NamedTempFile::new()?;
+    std::fs::write(tampered.path(), &data)?;
+    let mut file = File::open(tampered.path())?;
+    let vpk = VPKVersion2::try_from(&mut file)?;
+    assert!(
+        !vpk.verify_signature_from(&mut file)?,
+        "Signature of tampered file should be invalid"
+    );
+
+    let mut file = File::open(common::PAK_V2_SINGLE_FILE)?;
+    let vpk = VPKVersion2::try_from(&mut file)?;
+    assert!(vpk.signature_section.is_none());
+    assert!(
+        !vpk.verify_signature(common::DIR_V2, "missing")?,
+        "Unsigned file should not be verified"
+    );
+
+    Ok(())
+}
@@ -1,13 +1,17 @@
 use std::{fs::File, io::Read};
 
-use vpk_plumber::pak::{PakReader, v2::VPKVersion2};
+use vpk_plumber::pak::{
+    FileDataMode, PakReader, PakWorker, ParseOptions,
+    v2::{VPK_DIR_ARCHIVE_INDEX_V2, VPKVersion2},
+};
 
 use crate::common::{self, Result};
 
+#[cfg(feature = "mem-map")]
 use filebuffer::FileBuffer;
+#[cfg(feature = "mem-map")]
 use std::collections::HashMap;
 
-#[ignore = "not yet implemented"]
 #[test]
 fn vpk_single_file() -> Result<()> {
     let mut file = File::open(common::PAK_V2_SINGLE_FILE)?;
@@ -18,7 +22,7 @@ fn vpk_single_file() -> Result<()> {
     vpk.extract_file(
         common::DIR_V2,
         common::SINGLE_FILE_ARCHIVE,
-        common::SINGLE_FILE_NAME,
+        common::SINGLE_FILE_NAME_V2,
         out_path.path().to_str().unwrap(),
     )?;
 
@@ -34,7 +38,6 @@ fn vpk_single_file() -> Result<()> {
 }
 
 #[cfg(feature = "mem-map")]
-#[ignore = "not yet implemented"]
 #[test]
 fn vpk_single_file_mem_map() -> Result<()> {
     let mut file = File::open(common::PAK_V2_SINGLE_FILE)?;
@@ -49,7 +52,7 @@ fn vpk_single_file_mem_map() -> Result<()> {
         common::DIR_V2,
         &archive_mmaps,
         common::SINGLE_FILE_ARCHIVE,
-        common::SINGLE_FILE_NAME,
+        common::SINGLE_FILE_NAME_V2,
         out_path.path().to_str().unwrap(),
     )?;
 
@@ -64,3 +67,80 @@ fn vpk_single_file_mem_map() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn vpk_inline_file_data() -> Result<()> {
+    let files: [(&str, &[u8], usize); 2] = [
+        (
+            common::SINGLE_FILE_NAME,
+            common::SINGLE_FILE_CONTENT.as_bytes(),
+            0,
+        ),
+        ("test/preload.txt", b"preloaded and inline data", 9),
+    ];
+
+    // A directory file storing the data of its files in the file data section
+    let mut vpk = VPKVersion2::new();
+    for (file_path, data, max_preload_bytes) in files {
+        let entry_offset = u32::try_from(vpk.file_data.len())?;
+        vpk.tree.insert_file(
+            file_path,
+            data,
+            u16::try_from(VPK_DIR_ARCHIVE_INDEX_V2)?,
+            entry_offset,
+            max_preload_bytes,
+            false,
+        )?;
+        vpk.file_data.extend_from_slice(&data[max_preload_bytes..]);
+    }
+    vpk.rebuild_header_tree_size()?;
+    vpk.header.file_data_section_size = u32::try_from(vpk.file_data.len())?;
+
+    let out_dir = tempfile::tempdir()?;
+    let dir_path = out_dir.path().join("inline_dir.vpk");
+    let mut dir_file = File::create(&dir_path)?;
+    vpk.header.write(&mut dir_file)?;
+    vpk.tree.write(&mut dir_file)?;
+    std::io::Write::write_all(&mut dir_file, &vpk.file_data)?;
+    vpk.other_md5_section.write(&mut dir_file)?;
+    drop(dir_file);
+
+    let archive_path = out_dir.path().to_str().unwrap();
+    let eager = VPKVersion2::from_file(&mut File::open(&dir_path)?)?;
+    let lazy = VPKVersion2::from_file_with_options(
+        &mut File::open(&dir_path)?,
+        &ParseOptions {
+            file_data: FileDataMode::Lazy,
+            ..Default::default()
+        },
+    )?;
+
+    assert_eq!(
+        eager.file_data, vpk.file_data,
+        "Eager mode should load the section"
+    );
+    assert!(
+        lazy.file_data.is_empty(),
+        "Lazy mode should not load the section"
+    );
+
+    for (file_path, data, _) in files {
+        for (mode, vpk) in [("eager", &eager), ("lazy", &lazy)] {
+            assert_eq!(
+                vpk.try_read_file(archive_path, "inline", file_path)?,
+                data,
+                "Data of {file_path} read in {mode} mode should match"
+            );
+
+            let mut streamed = Vec::new();
+            vpk.read_file_streaming(archive_path, "inline", file_path)?
+                .read_to_end(&mut streamed)?;
+            assert_eq!(
+                streamed, data,
+                "Data of {file_path} streamed in {mode} mode should match"
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use std::fs::File;
 
-use vpk_plumber::pak::v2::VPKVersion2;
+use vpk_plumber::pak::{PakWorker, v2::VPKVersion2};
 
 use crate::common::{self, Result};
 
@@ -25,6 +25,22 @@ fn valid_vpk_large() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn valid_vpk_from_bytes() -> Result<()> {
+    let vpk = VPKVersion2::from_bytes(&std::fs::read(common::PAK_V2_PORTAL)?)?;
+    let mut file = File::open(common::PAK_V2_PORTAL)?;
+    let from_file = VPKVersion2::try_from(&mut file)?;
+    assert!(
+        vpk.tree == from_file.tree,
+        "Tree from bytes should match tree from file"
+    );
+    assert_eq!(
+        vpk.header.tree_size, from_file.header.tree_size,
+        "Header from bytes should match header from file"
+    );
+    Ok(())
+}
+
 #[test]
 fn invalid_vpk() -> Result<()> {
     let mut file = File::open(common::PAK_V2_ARCHIVE)?;
@@ -76,3 +92,14 @@ fn vpk_revpk_empty() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn open_v2() -> Result<()> {
+    let vpk = vpk_plumber::pak::open_v2(std::path::Path::new(common::PAK_V2_PORTAL))?;
+    assert_eq!(
+        vpk.tree.files.len(),
+        common::PORTAL_TREE_COUNT,
+        "Tree size does not match"
+    );
+    Ok(())
+}
@@ -1,22 +1,29 @@
-use std::{fs::File, path::Path};
-
-use vpk_plumber::pak::{PakWorker, PakWriter, v2::VPKVersion2};
+use std::{
+    fs::File,
+    io::{Seek, SeekFrom},
+    path::Path,
+};
+
+use vpk_plumber::pak::{
+    Error, PakWorker, PakWriter,
+    v2::{
+        VPKArchiveMD5SectionEntry, VPKHeaderV2, VPKOtherMD5Section, VPKSignatureSection,
+        VPKVersion2,
+    },
+};
 
 use crate::common::{self, Result};
 
-#[ignore = "not yet implemented"]
 #[test]
 fn empty() -> Result<()> {
     roundtrip(common::PAK_V2_EMPTY)
 }
 
-#[ignore = "not yet implemented"]
 #[test]
 fn single_file() -> Result<()> {
     roundtrip(common::PAK_V2_SINGLE_FILE)
 }
 
-#[ignore = "not yet implemented"]
 #[test]
 fn large() -> Result<()> {
     roundtrip(common::PAK_V2_PORTAL)
@@ -26,9 +33,10 @@ fn roundtrip<P>(path: P) -> Result<()>
 where
     P: AsRef<Path>,
 {
-    // Read a vpk file
+    // Read a vpk file, Valve's tools write empty directory blocks which are not kept in the tree
     let mut file = File::open(path)?;
-    let vpk = VPKVersion2::from_file(&mut file)?;
+    let mut vpk = VPKVersion2::from_file(&mut file)?;
+    vpk.rebuild_header_tree_size()?;
 
     // Write it to a directory
     let out = tempfile::NamedTempFile::new()?;
@@ -78,3 +86,136 @@ where
 
     Ok(())
 }
+
+#[test]
+fn header() -> Result<()> {
+    let vpk = VPKVersion2::from_file(&mut File::open(common::PAK_V2_PORTAL)?)?;
+
+    let mut file = tempfile::tempfile()?;
+    vpk.header.write(&mut file)?;
+    assert_eq!(file.stream_position()?, 28, "Header should be 28 bytes");
+
+    file.seek(SeekFrom::Start(0))?;
+    assert_eq!(
+        VPKHeaderV2::from(&mut file)?,
+        vpk.header,
+        "Headers do not match"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn header_invalid() -> Result<()> {
+    let vpk = VPKVersion2::from_file(&mut File::open(common::PAK_V2_PORTAL)?)?;
+    let mut file = tempfile::tempfile()?;
+
+    let header = VPKHeaderV2 {
+        archive_md5_section_size: 27,
+        ..vpk.header
+    };
+    assert!(
+        matches!(header.write(&mut file), Err(Error::BadData(_))),
+        "Archive MD5 section size should be a multiple of 28"
+    );
+
+    let header = VPKHeaderV2 {
+        archive_md5_section_size: 28,
+        other_md5_section_size: 0,
+        ..header
+    };
+    assert!(
+        matches!(header.write(&mut file), Err(Error::BadData(_))),
+        "Other MD5 section size should be 48"
+    );
+
+    let header = VPKHeaderV2 {
+        other_md5_section_size: 48,
+        signature_section_size: 4,
+        ..header
+    };
+    assert!(
+        matches!(header.write(&mut file), Err(Error::BadData(_))),
+        "Signature section size should be 0 or 296"
+    );
+
+    let header = VPKHeaderV2 {
+        signature_section_size: 0,
+        version: 1,
+        ..header
+    };
+    assert!(
+        matches!(header.write(&mut file), Err(Error::BadVersion(_))),
+        "Version should be 2"
+    );
+
+    assert_eq!(file.stream_position()?, 0, "Nothing should be written");
+
+    Ok(())
+}
+
+#[test]
+fn sections() -> Result<()> {
+    let vpk = VPKVersion2::from_file(&mut File::open(common::PAK_V2_PORTAL)?)?;
+
+    let signature_section = VPKSignatureSection {
+        public_key_size: 160,
+        public_key: [0xAB; 160],
+        signature_size: 128,
+        signature: [0xCD; 128],
+    };
+
+    let mut file = tempfile::tempfile()?;
+    for entry in &vpk.archive_md5_section_entries {
+        entry.write(&mut file)?;
+    }
+    vpk.other_md5_section.write(&mut file)?;
+    signature_section.write(&mut file)?;
+
+    assert_eq!(
+        file.stream_position()?,
+        u64::from(vpk.header.archive_md5_section_size) + 48 + 296,
+        "Section sizes do not match"
+    );
+
+    file.seek(SeekFrom::Start(0))?;
+    for entry in &vpk.archive_md5_section_entries {
+        assert_eq!(
+            &VPKArchiveMD5SectionEntry::from(&mut file)?,
+            entry,
+            "Archive MD5 entries do not match"
+        );
+    }
+    assert_eq!(
+        VPKOtherMD5Section::from(&mut file)?,
+        vpk.other_md5_section,
+        "Other MD5 sections do not match"
+    );
+    assert_eq!(
+        VPKSignatureSection::from(&mut file)?,
+        signature_section,
+        "Signature sections do not match"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn signature_section_invalid() -> Result<()> {
+    let signature_section = VPKSignatureSection {
+        public_key_size: 128,
+        public_key: [0; 160],
+        signature_size: 128,
+        signature: [0; 128],
+    };
+
+    assert!(
+        matches!(
+            signature_section.write(&mut tempfile::tempfile()?),
+            Err(Error::BadData(_))
+        ),
+        "Public key size should match the public key"
+    );
+
+    Ok(())
+}